@@ -0,0 +1,141 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use actix_web::error::Error;
+use bytes::{Bytes, BytesMut};
+use futures_core::{ready, Stream};
+use rand::{distributions::Alphanumeric, Rng as _};
+
+use crate::{named::Content, range::HttpRange, ChunkedReadFile};
+
+/// Body of a `multipart/byteranges` response, reading each range from the file contents in
+/// turn.
+pub(crate) struct ByteRangesBody {
+    boundary: String,
+    content_type: String,
+    file_length: u64,
+    content: Content,
+    ranges: VecDeque<HttpRange>,
+    current: Option<ChunkedReadFile>,
+    first: bool,
+    done: bool,
+}
+
+impl ByteRangesBody {
+    pub(crate) fn new(
+        ranges: Vec<HttpRange>,
+        content_type: String,
+        file_length: u64,
+        content: Content,
+    ) -> Self {
+        let boundary = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect();
+
+        Self {
+            boundary,
+            content_type,
+            file_length,
+            content,
+            ranges: ranges.into(),
+            current: None,
+            first: true,
+            done: false,
+        }
+    }
+
+    /// Returns the `Content-Type` header value for the response.
+    pub(crate) fn content_type(&self) -> String {
+        format!("multipart/byteranges; boundary={}", self.boundary)
+    }
+
+    /// Returns the exact length of the encoded body.
+    pub(crate) fn size(&self) -> u64 {
+        let parts = self
+            .ranges
+            .iter()
+            .enumerate()
+            .fold(0, |size, (idx, range)| {
+                size + self.part_head(range, idx == 0).len() as u64 + range.length
+            });
+
+        parts + self.tail().len() as u64
+    }
+
+    fn part_head(&self, range: &HttpRange, first: bool) -> String {
+        format!(
+            "{}--{}\r\ncontent-type: {}\r\ncontent-range: bytes {}-{}/{}\r\n\r\n",
+            if first { "" } else { "\r\n" },
+            self.boundary,
+            self.content_type,
+            range.start,
+            range.start + range.length - 1,
+            self.file_length,
+        )
+    }
+
+    fn tail(&self) -> String {
+        format!("\r\n--{}--\r\n", self.boundary)
+    }
+}
+
+impl fmt::Debug for ByteRangesBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ByteRangesBody")
+            .field("boundary", &self.boundary)
+            .field("ranges", &self.ranges)
+            .finish()
+    }
+}
+
+impl Stream for ByteRangesBody {
+    type Item = Result<Bytes, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        if let Some(reader) = this.current.as_mut() {
+            match ready!(Pin::new(reader).poll_next(cx)) {
+                Some(res) => return Poll::Ready(Some(res)),
+                None => this.current = None,
+            }
+        }
+
+        if let Some(range) = this.ranges.pop_front() {
+            let head = this.part_head(&range, this.first);
+            this.first = false;
+
+            let file = match this.content {
+                Content::File(ref file) => match file.try_clone() {
+                    Ok(file) => file,
+                    Err(err) => return Poll::Ready(Some(Err(err.into()))),
+                },
+
+                // in-memory ranges are sent along with their head
+                Content::Bytes(ref bytes) => {
+                    let end = range.start + range.length;
+                    let mut part = BytesMut::from(head.as_bytes());
+                    part.extend_from_slice(&bytes[range.start as usize..end as usize]);
+                    return Poll::Ready(Some(Ok(part.freeze())));
+                }
+            };
+
+            this.current = Some(ChunkedReadFile::new(range.length, range.start, file));
+
+            return Poll::Ready(Some(Ok(Bytes::from(head))));
+        }
+
+        if this.done {
+            Poll::Ready(None)
+        } else {
+            this.done = true;
+            Poll::Ready(Some(Ok(Bytes::from(this.tail()))))
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use std::{
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use actix_web::{
+    http::header::{CacheControl, CacheDirective, Expires},
+    HttpResponseBuilder,
+};
+
+/// Caching headers sent with files.
+///
+/// A policy sets the `Cache-Control` header and, optionally, an `Expires` header relative to the
+/// time of the response. Policies are applied with [`NamedFile::cache_policy`] or per mount and
+/// per file with [`Files::cache_policy`], [`Files::path_cache_policy`] and
+/// [`Files::mime_cache_policy`].
+///
+/// # Examples
+/// ```
+/// use std::time::Duration;
+/// use actix_files::{CachePolicy, Files};
+///
+/// let files = Files::new("/", "./static")
+///     // revalidate anything not matched below
+///     .cache_policy(CachePolicy::no_cache())
+///     // hashed bundles never change
+///     .path_cache_policy("assets/**", CachePolicy::immutable(Duration::from_secs(31_536_000)))
+///     .mime_cache_policy(mime::IMAGE_STAR, CachePolicy::max_age(Duration::from_secs(3600)));
+/// ```
+///
+/// [`NamedFile::cache_policy`]: crate::NamedFile::cache_policy
+/// [`Files::cache_policy`]: crate::Files::cache_policy
+/// [`Files::path_cache_policy`]: crate::Files::path_cache_policy
+/// [`Files::mime_cache_policy`]: crate::Files::mime_cache_policy
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachePolicy {
+    cache_control: CacheControl,
+    expires: Option<Duration>,
+}
+
+impl CachePolicy {
+    /// Constructs a policy sending the given `Cache-Control` header.
+    pub fn new(cache_control: CacheControl) -> Self {
+        Self {
+            cache_control,
+            expires: None,
+        }
+    }
+
+    /// Allows public caching for the given time (`public, max-age=N`).
+    pub fn max_age(max_age: Duration) -> Self {
+        Self::new(CacheControl(vec![
+            CacheDirective::Public,
+            CacheDirective::MaxAge(secs(max_age)),
+        ]))
+    }
+
+    /// Allows public caching for the given time without revalidation
+    /// (`public, max-age=N, immutable`).
+    ///
+    /// Suitable for files whose names change with their contents, e.g. hashed bundles.
+    pub fn immutable(max_age: Duration) -> Self {
+        Self::new(CacheControl(vec![
+            CacheDirective::Public,
+            CacheDirective::MaxAge(secs(max_age)),
+            CacheDirective::Extension("immutable".to_owned(), None),
+        ]))
+    }
+
+    /// Requires caches to revalidate before each use (`no-cache`).
+    ///
+    /// Suitable for entry points such as `index.html`.
+    pub fn no_cache() -> Self {
+        Self::new(CacheControl(vec![CacheDirective::NoCache]))
+    }
+
+    /// Forbids storing the response (`no-store`).
+    pub fn no_store() -> Self {
+        Self::new(CacheControl(vec![CacheDirective::NoStore]))
+    }
+
+    /// Also sends an `Expires` header for the time of the response plus `after`.
+    ///
+    /// Durations over one year are capped at one year, the longest expiry HTTP/1.1 recommends.
+    pub fn expires(mut self, after: Duration) -> Self {
+        self.expires = Some(after.min(MAX_EXPIRES));
+        self
+    }
+
+    pub(crate) fn insert_headers(&self, res: &mut HttpResponseBuilder) {
+        res.insert_header(self.cache_control.clone());
+
+        if let Some(after) = self.expires {
+            res.insert_header(Expires((SystemTime::now() + after).into()));
+        }
+    }
+}
+
+/// One year, the longest time an `Expires` header should be in the future.
+const MAX_EXPIRES: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+fn secs(duration: Duration) -> u32 {
+    duration.as_secs().min(u32::MAX as u64) as u32
+}
+
+/// Selects which files a cache policy applies to.
+#[derive(Debug, Clone)]
+enum Matcher {
+    Path(String),
+    Mime(mime::Mime),
+}
+
+impl Matcher {
+    fn matches(&self, path: &str, content_type: &mime::Mime) -> bool {
+        match self {
+            Matcher::Path(pattern) if pattern.contains('/') => glob_match(pattern, path),
+            Matcher::Path(pattern) => {
+                let file_name = path.rsplit('/').next().unwrap_or(path);
+                glob_match(pattern, file_name)
+            }
+            Matcher::Mime(mime) => {
+                mime.type_() == content_type.type_()
+                    && (mime.subtype() == mime::STAR
+                        || mime.subtype() == content_type.subtype())
+            }
+        }
+    }
+}
+
+/// Cache policies of a `Files` service.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CachePolicies {
+    default: Option<CachePolicy>,
+    rules: Vec<(Matcher, CachePolicy)>,
+}
+
+impl CachePolicies {
+    pub(crate) fn set_default(&mut self, policy: CachePolicy) {
+        self.default = Some(policy);
+    }
+
+    pub(crate) fn add_path(&mut self, pattern: &str, policy: CachePolicy) {
+        let pattern = pattern.trim_start_matches('/').to_owned();
+        self.rules.push((Matcher::Path(pattern), policy));
+    }
+
+    pub(crate) fn add_mime(&mut self, mime: mime::Mime, policy: CachePolicy) {
+        self.rules.push((Matcher::Mime(mime), policy));
+    }
+
+    /// Returns the policy of the first matching rule, or the default policy.
+    ///
+    /// `path` is relative to the served directory.
+    pub(crate) fn resolve(
+        &self,
+        path: &Path,
+        content_type: &mime::Mime,
+    ) -> Option<CachePolicy> {
+        let path = path
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        self.rules
+            .iter()
+            .find(|(matcher, _)| matcher.matches(&path, content_type))
+            .map(|(_, policy)| policy)
+            .or(self.default.as_ref())
+            .cloned()
+    }
+}
+
+/// Matches a `/` separated path against a glob pattern.
+///
+/// `?` matches one character and `*` any characters other than `/`. A `**` matches any
+/// characters including `/`.
+fn glob_match(pattern: &str, path: &str) -> bool {
+    fn matches(pattern: &[u8], path: &[u8]) -> bool {
+        match pattern.first() {
+            None => path.is_empty(),
+
+            Some(b'*') if pattern.get(1) == Some(&b'*') => {
+                let rest = &pattern[2..];
+                // `**/` also matches no directories at all
+                let rest_no_slash = if rest.first() == Some(&b'/') {
+                    &rest[1..]
+                } else {
+                    rest
+                };
+
+                matches(rest_no_slash, path)
+                    || (0..=path.len()).any(|idx| matches(rest, &path[idx..]))
+            }
+
+            Some(b'*') => {
+                let rest = &pattern[1..];
+                let max = path.iter().position(|&b| b == b'/').unwrap_or(path.len());
+                (0..=max).any(|idx| matches(rest, &path[idx..]))
+            }
+
+            Some(b'?') => {
+                !path.is_empty() && path[0] != b'/' && matches(&pattern[1..], &path[1..])
+            }
+
+            Some(&b) => path.first() == Some(&b) && matches(&pattern[1..], &path[1..]),
+        }
+    }
+
+    matches(pattern.as_bytes(), path.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use actix_web::http::header::EXPIRES;
+
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.html", "index.html"));
+        assert!(!glob_match("*.html", "dir/index.html"));
+        assert!(glob_match("assets/*", "assets/app.js"));
+        assert!(!glob_match("assets/*", "assets/js/app.js"));
+        assert!(glob_match("assets/**", "assets/js/app.js"));
+        assert!(glob_match("**/*.js", "app.js"));
+        assert!(glob_match("**/*.js", "a/b/app.js"));
+        assert!(!glob_match("**/app.js", "xapp.js"));
+        assert!(glob_match("app.????????.js", "app.1a2b3c4d.js"));
+        assert!(!glob_match("app.????????.js", "app.js"));
+    }
+
+    #[test]
+    fn test_resolve() {
+        let mut policies = CachePolicies::default();
+        assert_eq!(
+            policies.resolve(Path::new("a.txt"), &mime::TEXT_PLAIN),
+            None
+        );
+
+        policies.set_default(CachePolicy::no_cache());
+        policies.add_path("index.html", CachePolicy::no_store());
+        policies.add_path(
+            "/static/**",
+            CachePolicy::immutable(Duration::from_secs(60)),
+        );
+        policies.add_mime(
+            mime::IMAGE_STAR,
+            CachePolicy::max_age(Duration::from_secs(10)),
+        );
+
+        let resolve =
+            |path: &str, mime: mime::Mime| policies.resolve(&PathBuf::from(path), &mime);
+
+        // file name patterns match in any directory
+        assert_eq!(
+            resolve("docs/index.html", mime::TEXT_HTML),
+            Some(CachePolicy::no_store())
+        );
+        assert_eq!(
+            resolve("static/img/logo.png", mime::IMAGE_PNG),
+            Some(CachePolicy::immutable(Duration::from_secs(60)))
+        );
+        assert_eq!(
+            resolve("logo.png", mime::IMAGE_PNG),
+            Some(CachePolicy::max_age(Duration::from_secs(10)))
+        );
+        assert_eq!(
+            resolve("about.html", mime::TEXT_HTML),
+            Some(CachePolicy::no_cache())
+        );
+    }
+
+    #[test]
+    fn test_expires_capped() {
+        let policy = CachePolicy::no_cache().expires(Duration::from_secs(u64::MAX));
+        assert_eq!(policy.expires, Some(MAX_EXPIRES));
+
+        let mut res = actix_web::HttpResponse::Ok();
+        policy.insert_headers(&mut res);
+        assert!(res.finish().headers().contains_key(EXPIRES));
+    }
+}
@@ -8,6 +8,7 @@ use std::{
 };
 
 use actix_web::{
+    dev::{BodySize, FileRegion, MessageBody},
     error::{BlockingError, Error},
     rt::task::{spawn_blocking, JoinHandle},
 };
@@ -80,7 +81,7 @@ impl Stream for ChunkedReadFile {
                         Ok((file, Bytes::from(buf)))
                     });
                     this.state = ChunkedReadFileState::Future(fut);
-                    self.poll_next(cx)
+                    Stream::poll_next(self, cx)
                 }
             }
             ChunkedReadFileState::Future(ref mut fut) => {
@@ -96,3 +97,32 @@ impl Stream for ChunkedReadFile {
         }
     }
 }
+
+impl MessageBody for ChunkedReadFile {
+    type Error = Error;
+
+    fn size(&self) -> BodySize {
+        BodySize::Sized(self.size)
+    }
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        Stream::poll_next(self, cx)
+    }
+
+    fn take_file_region(self: Pin<&mut Self>) -> Option<FileRegion> {
+        let this = self.get_mut();
+
+        match this.state {
+            ChunkedReadFileState::File(ref mut file) if this.counter < this.size => {
+                let region =
+                    FileRegion::new(file.take()?, this.offset, this.size - this.counter);
+                this.counter = this.size;
+                Some(region)
+            }
+            _ => None,
+        }
+    }
+}
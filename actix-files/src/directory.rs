@@ -1,8 +1,20 @@
-use std::{fmt::Write, fs::DirEntry, io, path::Path, path::PathBuf};
+use std::{
+    cmp::Ordering,
+    fmt::Write,
+    fs::DirEntry,
+    io,
+    path::Path,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-use actix_web::{dev::ServiceResponse, HttpRequest, HttpResponse};
+use actix_web::{
+    dev::ServiceResponse,
+    http::header::{self, Accept, Header as _, HttpDate},
+    HttpRequest, HttpResponse,
+};
 use askama_escape::{escape as escape_html_entity, Html};
-use percent_encoding::{utf8_percent_encode, CONTROLS};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
 
 /// A directory; responds with the generated directory listing.
 #[derive(Debug)]
@@ -110,3 +122,487 @@ pub(crate) fn directory_listing(
             .body(html),
     ))
 }
+
+/// Characters percent-encoded in file names of listing links.
+const FILE_NAME_SET: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'\'')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'\\')
+    .add(b'`');
+
+const DEFAULT_PAGE_TEMPLATE: &str = "<!DOCTYPE html>\n\
+    <html>\n\
+    <head><meta charset=\"utf-8\"><title>{{title}}</title></head>\n\
+    <body>\n\
+    <h1>{{title}}</h1>\n\
+    <table>\n\
+    {{header}}\n\
+    {{entries}}\
+    </table>\n\
+    </body>\n\
+    </html>\n";
+
+/// Order of the entries of a directory listing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListingSort {
+    /// Sort by file name.
+    Name,
+
+    /// Sort by file size. Directories have a size of zero.
+    Size,
+
+    /// Sort by modification time.
+    Modified,
+}
+
+/// Built-in renderer of directory listings, used with [`Files::directory_listing`].
+///
+/// Lists the visible entries of a directory as an HTML table with file sizes and modification
+/// times. Entries are sorted by name, with directories first, by default.
+///
+/// With [`json`](Self::json) enabled, a JSON listing is sent to clients preferring
+/// `application/json` in their `Accept` header:
+///
+/// ```json
+/// {"path":"/static/","entries":[{"name":"app.js","url":"/static/app.js","is_dir":false,"size":1024,"modified":1625097600}]}
+/// ```
+///
+/// # Templates
+/// The HTML page can be replaced with [`template`](Self::template), where `{{title}}`,
+/// `{{path}}`, `{{header}}` and `{{entries}}` are substituted. Each entry is rendered with
+/// [`entry_template`](Self::entry_template), where `{{name}}`, `{{url}}`, `{{size}}` and
+/// `{{modified}}` are substituted. All substituted values are HTML escaped.
+///
+/// # Examples
+/// ```
+/// use actix_files::{DirectoryListing, Files, ListingSort};
+///
+/// let files = Files::new("/static", ".")
+///     .show_files_listing()
+///     .directory_listing(
+///         DirectoryListing::new()
+///             .sort_by(ListingSort::Modified)
+///             .descending(true)
+///             .json(true)
+///             .entry_template("<li><a href=\"{{url}}\">{{name}}</a> {{size}}</li>"),
+///     );
+/// ```
+///
+/// [`Files::directory_listing`]: crate::Files::directory_listing
+#[derive(Debug, Clone)]
+pub struct DirectoryListing {
+    sort: ListingSort,
+    descending: bool,
+    directories_first: bool,
+    show_size: bool,
+    show_modified: bool,
+    json: bool,
+    template: Option<String>,
+    entry_template: Option<String>,
+}
+
+impl DirectoryListing {
+    /// Constructs a renderer with the default settings.
+    pub fn new() -> Self {
+        Self {
+            sort: ListingSort::Name,
+            descending: false,
+            directories_first: true,
+            show_size: true,
+            show_modified: true,
+            json: false,
+            template: None,
+            entry_template: None,
+        }
+    }
+
+    /// Sets the order of entries.
+    ///
+    /// By default entries are sorted by name.
+    pub fn sort_by(mut self, sort: ListingSort) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    /// Sorts entries in descending order.
+    ///
+    /// By default entries are sorted in ascending order.
+    pub fn descending(mut self, descending: bool) -> Self {
+        self.descending = descending;
+        self
+    }
+
+    /// Lists directories before files, regardless of the sort order.
+    ///
+    /// Enabled by default.
+    pub fn directories_first(mut self, directories_first: bool) -> Self {
+        self.directories_first = directories_first;
+        self
+    }
+
+    /// Shows file sizes.
+    ///
+    /// Enabled by default.
+    pub fn show_size(mut self, show_size: bool) -> Self {
+        self.show_size = show_size;
+        self
+    }
+
+    /// Shows modification times.
+    ///
+    /// Enabled by default.
+    pub fn show_modified(mut self, show_modified: bool) -> Self {
+        self.show_modified = show_modified;
+        self
+    }
+
+    /// Sends a JSON listing to clients preferring `application/json`.
+    ///
+    /// Disabled by default.
+    pub fn json(mut self, json: bool) -> Self {
+        self.json = json;
+        self
+    }
+
+    /// Sets the template of the HTML page.
+    pub fn template<T: Into<String>>(mut self, template: T) -> Self {
+        self.template = Some(template.into());
+        self
+    }
+
+    /// Sets the template of each entry of the HTML page.
+    pub fn entry_template<T: Into<String>>(mut self, template: T) -> Self {
+        self.entry_template = Some(template.into());
+        self
+    }
+
+    /// Renders the listing of `dir` for `req`.
+    pub fn render(
+        &self,
+        dir: &Directory,
+        req: &HttpRequest,
+    ) -> Result<ServiceResponse, io::Error> {
+        let mut entries = Vec::new();
+
+        for entry in dir.path.read_dir()? {
+            if dir.is_visible(&entry) {
+                let entry = entry.unwrap();
+
+                let metadata = match entry.metadata() {
+                    Ok(metadata) => metadata,
+                    Err(_) => continue,
+                };
+
+                entries.push(ListingEntry {
+                    name: entry.file_name().to_string_lossy().into_owned(),
+                    is_dir: metadata.is_dir(),
+                    size: if metadata.is_dir() { 0 } else { metadata.len() },
+                    modified: metadata.modified().ok(),
+                });
+            }
+        }
+
+        self.sort(&mut entries);
+
+        let mut res = HttpResponse::Ok();
+
+        let body = if self.json {
+            res.insert_header((header::VARY, "Accept"));
+
+            if prefers_json(req) {
+                res.content_type("application/json");
+                self.render_json(&entries, req)
+            } else {
+                res.content_type("text/html; charset=utf-8");
+                self.render_html(&entries, req)
+            }
+        } else {
+            res.content_type("text/html; charset=utf-8");
+            self.render_html(&entries, req)
+        };
+
+        Ok(ServiceResponse::new(req.clone(), res.body(body)))
+    }
+
+    fn sort(&self, entries: &mut [ListingEntry]) {
+        entries.sort_by(|a, b| {
+            let ord = match self.sort {
+                ListingSort::Name => Ordering::Equal,
+                ListingSort::Size => a.size.cmp(&b.size),
+                ListingSort::Modified => a.modified.cmp(&b.modified),
+            }
+            .then_with(|| a.name.cmp(&b.name));
+
+            let ord = if self.descending { ord.reverse() } else { ord };
+
+            if self.directories_first {
+                b.is_dir.cmp(&a.is_dir).then(ord)
+            } else {
+                ord
+            }
+        });
+    }
+
+    fn render_html(&self, entries: &[ListingEntry], req: &HttpRequest) -> String {
+        let default_entry_template;
+        let entry_template = match self.entry_template {
+            Some(ref template) => template.as_str(),
+            None => {
+                default_entry_template = self.default_template(
+                    "<tr><td><a href=\"{{url}}\">{{name}}</a></td>",
+                    "<td>{{size}}</td>",
+                    "<td>{{modified}}</td>",
+                    "</tr>\n",
+                );
+                &default_entry_template
+            }
+        };
+
+        let mut rows = String::new();
+
+        for entry in entries {
+            rows.push_str(&render_template(entry_template, |key| {
+                let value = match key {
+                    "name" if entry.is_dir => format!("{}/", entry.name),
+                    "name" => entry.name.clone(),
+                    "url" => entry.url(req.path()),
+                    "size" if self.show_size && !entry.is_dir => format_size(entry.size),
+                    "modified" if self.show_modified => entry
+                        .modified
+                        .map(|modified| HttpDate::from(modified).to_string())
+                        .unwrap_or_default(),
+                    "size" | "modified" => String::new(),
+                    _ => return None,
+                };
+
+                Some(escape_html(&value))
+            }));
+        }
+
+        let header = self.default_template(
+            "<tr><th>Name</th>",
+            "<th>Size</th>",
+            "<th>Last modified</th>",
+            "</tr>",
+        );
+
+        let template = self.template.as_deref().unwrap_or(DEFAULT_PAGE_TEMPLATE);
+
+        render_template(template, |key| match key {
+            "title" => Some(format!("Index of {}", escape_html(req.path()))),
+            "path" => Some(escape_html(req.path())),
+            "header" => Some(header.clone()),
+            "entries" => Some(rows.clone()),
+            _ => None,
+        })
+    }
+
+    /// Joins the parts of a default table row, leaving out hidden columns.
+    fn default_template(&self, name: &str, size: &str, modified: &str, end: &str) -> String {
+        let mut row = name.to_owned();
+
+        if self.show_size {
+            row.push_str(size);
+        }
+
+        if self.show_modified {
+            row.push_str(modified);
+        }
+
+        row.push_str(end);
+        row
+    }
+
+    fn render_json(&self, entries: &[ListingEntry], req: &HttpRequest) -> String {
+        let entries = entries
+            .iter()
+            .map(|entry| {
+                let mut obj = serde_json::Map::new();
+                obj.insert("name".to_owned(), entry.name.clone().into());
+                obj.insert("url".to_owned(), entry.url(req.path()).into());
+                obj.insert("is_dir".to_owned(), entry.is_dir.into());
+
+                if self.show_size {
+                    obj.insert("size".to_owned(), entry.size.into());
+                }
+
+                if self.show_modified {
+                    let modified = entry
+                        .modified
+                        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                        .map(|modified| modified.as_secs());
+                    obj.insert("modified".to_owned(), modified.into());
+                }
+
+                serde_json::Value::Object(obj)
+            })
+            .collect::<Vec<_>>();
+
+        serde_json::json!({
+            "path": req.path(),
+            "entries": entries,
+        })
+        .to_string()
+    }
+}
+
+impl Default for DirectoryListing {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct ListingEntry {
+    name: String,
+    is_dir: bool,
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+impl ListingEntry {
+    /// Returns the link to the entry from the listing at `base`.
+    fn url(&self, base: &str) -> String {
+        let name = utf8_percent_encode(&self.name, FILE_NAME_SET);
+        let sep = if base.ends_with('/') { "" } else { "/" };
+        let slash = if self.is_dir { "/" } else { "" };
+
+        format!("{}{}{}{}", base, sep, name, slash)
+    }
+}
+
+/// Returns true if the client prefers a JSON listing over an HTML one.
+fn prefers_json(req: &HttpRequest) -> bool {
+    let accept = match Accept::parse(req) {
+        Ok(accept) => accept,
+        Err(_) => return false,
+    };
+
+    for mime in accept.mime_precedence() {
+        if mime.type_() == mime::APPLICATION && mime.subtype() == mime::JSON {
+            return true;
+        }
+
+        // HTML is preferred for wildcards
+        if (mime.type_() == mime::TEXT || mime.type_() == mime::STAR)
+            && (mime.subtype() == mime::HTML || mime.subtype() == mime::STAR)
+        {
+            return false;
+        }
+    }
+
+    false
+}
+
+fn escape_html(value: &str) -> String {
+    escape_html_entity(value, Html).to_string()
+}
+
+/// Substitutes `{{key}}` placeholders in `template` in a single pass, so placeholders in values
+/// are not substituted again.
+///
+/// Unknown placeholders are kept as is.
+fn render_template<F>(template: &str, mut value: F) -> String
+where
+    F: FnMut(&str) -> Option<String>,
+{
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let end = match rest.find("}}") {
+            Some(end) => end,
+            None => break,
+        };
+
+        let key = rest[2..end].trim();
+
+        match value(key) {
+            Some(val) => out.push_str(&val),
+            None => out.push_str(&rest[..end + 2]),
+        }
+
+        rest = &rest[end + 2..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
+/// Formats a file size with binary prefixes, e.g. `1.5 KiB`.
+fn format_size(size: u64) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+
+    if size < 1024 {
+        return format!("{} B", size);
+    }
+
+    let mut size = size as f64 / 1024.0;
+    let mut unit = 0;
+
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.0 MiB");
+    }
+
+    #[test]
+    fn test_render_template() {
+        let render = |template| {
+            render_template(template, |key| match key {
+                "name" => Some("a {{url}}".to_owned()),
+                "url" => Some("/a%20b".to_owned()),
+                _ => None,
+            })
+        };
+
+        // placeholders in values are not substituted again
+        assert_eq!(
+            render("<b>{{ name }}</b> {{url}}"),
+            "<b>a {{url}}</b> /a%20b"
+        );
+        assert_eq!(render("{{other}} {{name"), "{{other}} {{name");
+    }
+
+    #[test]
+    fn test_entry_url() {
+        let entry = ListingEntry {
+            name: "a \"b\"#?.txt".to_owned(),
+            is_dir: false,
+            size: 0,
+            modified: None,
+        };
+        assert_eq!(entry.url("/files"), "/files/a%20%22b%22%23%3F.txt");
+
+        let entry = ListingEntry {
+            name: "dir".to_owned(),
+            is_dir: true,
+            size: 0,
+            modified: None,
+        };
+        assert_eq!(entry.url("/files/"), "/files/dir/");
+    }
+}
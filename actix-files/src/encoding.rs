@@ -1,5 +1,69 @@
+use actix_web::http::ContentEncoding;
 use mime::Mime;
 
+/// Encodings of precompressed variants and their file extensions, in order of preference.
+pub(crate) const PRECOMPRESSED_VARIANTS: &[(ContentEncoding, &str)] =
+    &[(ContentEncoding::Br, "br"), (ContentEncoding::Gzip, "gz")];
+
+/// Returns the precompressed variant encodings accepted by an `Accept-Encoding` header value,
+/// best first.
+///
+/// Encodings are ordered by quality value, then by [`PRECOMPRESSED_VARIANTS`] order.
+pub(crate) fn accepted_variants(accept_encoding: &str) -> Vec<(ContentEncoding, &'static str)> {
+    let mut wildcard = None;
+    let mut qualities = Vec::new();
+
+    for item in accept_encoding.split(',') {
+        let mut params = item.split(';');
+        let coding = params
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+
+        let quality = params
+            .filter_map(|param| {
+                let param = param.trim();
+                param
+                    .strip_prefix("q=")
+                    .or_else(|| param.strip_prefix("Q="))
+            })
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+
+        if coding == "*" {
+            wildcard = Some(quality);
+        } else {
+            qualities.push((coding, quality));
+        }
+    }
+
+    let mut variants = PRECOMPRESSED_VARIANTS
+        .iter()
+        .filter_map(|&(encoding, ext)| {
+            let quality = qualities
+                .iter()
+                .find(|(coding, _)| coding == encoding.as_str())
+                .map(|(_, quality)| *quality)
+                .or(wildcard)?;
+
+            if quality > 0.0 {
+                Some((encoding, ext, quality))
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // stable sort keeps preference order for equal qualities
+    variants.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(std::cmp::Ordering::Equal));
+
+    variants
+        .into_iter()
+        .map(|(encoding, ext, _)| (encoding, ext))
+        .collect()
+}
+
 /// Transforms MIME `text/*` types into their UTF-8 equivalent, if supported.
 ///
 /// MIME types that are converted
@@ -49,4 +113,26 @@ mod tests {
         assert_eq!(equiv_utf8_text(mime::TEXT_XML), mime::TEXT_XML);
         assert_eq!(equiv_utf8_text(mime::IMAGE_PNG), mime::IMAGE_PNG);
     }
+
+    #[test]
+    fn test_accepted_variants() {
+        let encodings = |header| {
+            accepted_variants(header)
+                .into_iter()
+                .map(|(encoding, _)| encoding)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            encodings("gzip, deflate, br"),
+            vec![ContentEncoding::Br, ContentEncoding::Gzip]
+        );
+        assert_eq!(
+            encodings("br;q=0.5, gzip"),
+            vec![ContentEncoding::Gzip, ContentEncoding::Br]
+        );
+        assert_eq!(encodings("gzip;q=0, *"), vec![ContentEncoding::Br]);
+        assert_eq!(encodings("deflate, identity"), vec![]);
+        assert_eq!(encodings(""), vec![]);
+    }
 }
@@ -20,8 +20,9 @@ use actix_web::{
 use futures_core::future::LocalBoxFuture;
 
 use crate::{
-    directory_listing, named, Directory, DirectoryRenderer, FilesService, HttpNewService,
-    MimeOverride, PathFilter,
+    cache::CachePolicies, directory_listing, named, CachePolicy, Directory, DirectoryListing,
+    DirectoryRenderer, DiskStorage, FilesService, HttpNewService, MimeOverride, PathFilter,
+    Storage,
 };
 
 /// Static files handling service.
@@ -37,7 +38,7 @@ use crate::{
 /// ```
 pub struct Files {
     path: String,
-    directory: PathBuf,
+    storage: Rc<dyn Storage>,
     index: Option<String>,
     show_index: bool,
     redirect_to_slash: bool,
@@ -46,11 +47,22 @@ pub struct Files {
     mime_override: Option<Rc<MimeOverride>>,
     path_filter: Option<Rc<PathFilter>>,
     file_flags: named::Flags,
+    max_ranges: usize,
+    cache_policies: Rc<CachePolicies>,
+    spa_index: Option<String>,
+    spa_asset_extensions: Rc<Vec<String>>,
     use_guards: Option<Rc<dyn Guard>>,
     guards: Vec<Rc<dyn Guard>>,
     hidden_files: bool,
 }
 
+/// Extensions of paths not served with the SPA index file by default.
+const DEFAULT_SPA_ASSET_EXTENSIONS: &[&str] = &[
+    "js", "mjs", "css", "map", "json", "wasm", "txt", "xml", "pdf", "png", "jpg", "jpeg",
+    "gif", "svg", "ico", "webp", "avif", "woff", "woff2", "ttf", "otf", "eot", "mp3", "mp4",
+    "webm",
+];
+
 impl fmt::Debug for Files {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.write_str("Files")
@@ -60,13 +72,17 @@ impl fmt::Debug for Files {
 impl Clone for Files {
     fn clone(&self) -> Self {
         Self {
-            directory: self.directory.clone(),
+            storage: self.storage.clone(),
             index: self.index.clone(),
             show_index: self.show_index,
             redirect_to_slash: self.redirect_to_slash,
             default: self.default.clone(),
             renderer: self.renderer.clone(),
             file_flags: self.file_flags,
+            max_ranges: self.max_ranges,
+            cache_policies: self.cache_policies.clone(),
+            spa_index: self.spa_index.clone(),
+            spa_asset_extensions: self.spa_asset_extensions.clone(),
             path: self.path.clone(),
             mime_override: self.mime_override.clone(),
             path_filter: self.path_filter.clone(),
@@ -105,9 +121,27 @@ impl Files {
             }
         };
 
+        Files::with_storage(mount_path, DiskStorage::new(dir))
+    }
+
+    /// Create new `Files` instance serving files from the given storage.
+    ///
+    /// See [`Files::new`] for the meaning of `mount_path`. Files listings are only shown for
+    /// storage on disk.
+    ///
+    /// # Examples
+    /// ```
+    /// use actix_files::{Files, MemoryStorage};
+    ///
+    /// let mut storage = MemoryStorage::new();
+    /// storage.insert("index.html", "<h1>Hello</h1>");
+    ///
+    /// let files = Files::with_storage("/", storage).index_file("index.html");
+    /// ```
+    pub fn with_storage<S: Storage>(mount_path: &str, storage: S) -> Files {
         Files {
             path: mount_path.to_owned(),
-            directory: dir,
+            storage: Rc::new(storage),
             index: None,
             show_index: false,
             redirect_to_slash: false,
@@ -116,6 +150,15 @@ impl Files {
             mime_override: None,
             path_filter: None,
             file_flags: named::Flags::default(),
+            max_ranges: named::DEFAULT_MAX_RANGES,
+            cache_policies: Rc::new(CachePolicies::default()),
+            spa_index: None,
+            spa_asset_extensions: Rc::new(
+                DEFAULT_SPA_ASSET_EXTENSIONS
+                    .iter()
+                    .map(|ext| (*ext).to_owned())
+                    .collect(),
+            ),
             use_guards: None,
             guards: Vec::new(),
             hidden_files: false,
@@ -141,6 +184,15 @@ impl Files {
         self
     }
 
+    /// Set the built-in directory renderer to use, configured with sorting, columns, templates
+    /// and a JSON listing.
+    ///
+    /// Files listings must still be enabled with [`Files::show_files_listing()`].
+    pub fn directory_listing(mut self, listing: DirectoryListing) -> Self {
+        self.renderer = Rc::new(move |dir, req| listing.render(dir, req));
+        self
+    }
+
     /// Set custom directory renderer
     pub fn files_listing_renderer<F>(mut self, f: F) -> Self
     where
@@ -228,6 +280,96 @@ impl Files {
         self
     }
 
+    /// Specifies whether to serve precompressed variants of files.
+    ///
+    /// When enabled, a `.br` or `.gz` file next to the requested file is served instead if the
+    /// client accepts that encoding, with the `Content-Encoding` header set. Brotli is preferred
+    /// when both are equally acceptable. Responses include `Vary: accept-encoding`.
+    ///
+    /// Default is false.
+    pub fn precompressed(mut self, value: bool) -> Self {
+        self.file_flags.set(named::Flags::PRECOMPRESSED, value);
+        self
+    }
+
+    /// Sets the maximum number of ranges served for a multi-range request.
+    ///
+    /// See [`NamedFile::max_ranges`]. Default is 16.
+    pub fn max_ranges(mut self, max_ranges: usize) -> Self {
+        self.max_ranges = max_ranges;
+        self
+    }
+
+    /// Sets the cache policy for files not matched by a path or MIME cache policy.
+    ///
+    /// By default no caching headers are sent.
+    pub fn cache_policy(mut self, policy: CachePolicy) -> Self {
+        Rc::make_mut(&mut self.cache_policies).set_default(policy);
+        self
+    }
+
+    /// Sets the cache policy for files whose path matches a glob pattern.
+    ///
+    /// Paths are relative to the served directory and separated by `/`. In patterns, `?` matches
+    /// one character, `*` matches any characters except `/` and `**` matches any characters.
+    /// Patterns without a `/`, e.g. `*.html`, are matched against the file name only.
+    ///
+    /// Path and MIME cache policies are checked in the order they are added and the first match
+    /// is used.
+    pub fn path_cache_policy(mut self, pattern: &str, policy: CachePolicy) -> Self {
+        Rc::make_mut(&mut self.cache_policies).add_path(pattern, policy);
+        self
+    }
+
+    /// Sets the cache policy for files of a MIME type, e.g. `text/html` or `image/*`.
+    ///
+    /// See [`Files::path_cache_policy`] for the order in which policies are matched.
+    pub fn mime_cache_policy(mut self, mime: mime::Mime, policy: CachePolicy) -> Self {
+        Rc::make_mut(&mut self.cache_policies).add_mime(mime, policy);
+        self
+    }
+
+    /// Enables single-page application mode, serving `index` for missing files.
+    ///
+    /// `GET` and `HEAD` requests for paths that do not exist on disk are answered with the file
+    /// at `index`, relative to the served directory, so that a client-side router can handle
+    /// them. Paths with an asset-like extension (see [`Files::spa_asset_extensions`]) and other
+    /// methods still get a 404 response, or are passed to the [default handler].
+    ///
+    /// # Examples
+    /// ```
+    /// use actix_files::Files;
+    ///
+    /// let files = Files::new("/", "./dist")
+    ///     .index_file("index.html")
+    ///     .spa_fallback("index.html");
+    /// ```
+    ///
+    /// [default handler]: Files::default_handler
+    pub fn spa_fallback<T: Into<String>>(mut self, index: T) -> Self {
+        self.spa_index = Some(index.into());
+        self
+    }
+
+    /// Sets the file extensions of paths that are never answered with the SPA index file.
+    ///
+    /// Extensions are matched case-insensitively and given without a leading dot. Defaults to
+    /// common script, style, image, font and media extensions such as `js`, `css`, `map` and
+    /// `png`.
+    pub fn spa_asset_extensions<I, S>(mut self, extensions: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.spa_asset_extensions = Rc::new(
+            extensions
+                .into_iter()
+                .map(|ext| ext.as_ref().trim_start_matches('.').to_ascii_lowercase())
+                .collect(),
+        );
+        self
+    }
+
     /// Adds a routing guard.
     ///
     /// Use this to allow multiple chained file services that respond to strictly different
@@ -354,7 +496,7 @@ impl ServiceFactory<ServiceRequest> for Files {
 
     fn new_service(&self, _: ()) -> Self::Future {
         let mut srv = FilesService {
-            directory: self.directory.clone(),
+            storage: self.storage.clone(),
             index: self.index.clone(),
             show_index: self.show_index,
             redirect_to_slash: self.redirect_to_slash,
@@ -363,6 +505,10 @@ impl ServiceFactory<ServiceRequest> for Files {
             mime_override: self.mime_override.clone(),
             path_filter: self.path_filter.clone(),
             file_flags: self.file_flags,
+            max_ranges: self.max_ranges,
+            cache_policies: self.cache_policies.clone(),
+            spa_index: self.spa_index.clone(),
+            spa_asset_extensions: self.spa_asset_extensions.clone(),
             guards: self.use_guards.clone(),
             hidden_files: self.hidden_files,
         };
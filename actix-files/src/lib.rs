@@ -1,6 +1,6 @@
 //! Static file serving for Actix Web.
 //!
-//! Provides a non-blocking service for serving static files from disk.
+//! Provides a non-blocking service for serving static files from disk or other [`Storage`].
 //!
 //! # Example
 //! ```
@@ -23,6 +23,8 @@ use actix_web::{
 use mime_guess::from_ext;
 use std::path::Path;
 
+mod byteranges;
+mod cache;
 mod chunked;
 mod directory;
 mod encoding;
@@ -32,13 +34,16 @@ mod named;
 mod path_buf;
 mod range;
 mod service;
+mod storage;
 
+pub use crate::cache::CachePolicy;
 pub use crate::chunked::ChunkedReadFile;
-pub use crate::directory::Directory;
+pub use crate::directory::{Directory, DirectoryListing, ListingSort};
 pub use crate::files::Files;
 pub use crate::named::NamedFile;
 pub use crate::range::HttpRange;
 pub use crate::service::FilesService;
+pub use crate::storage::{DiskStorage, MemoryStorage, Storage};
 
 use self::directory::{directory_listing, DirectoryRenderer};
 use self::error::FilesError;
@@ -164,11 +169,10 @@ mod tests {
         assert!(NamedFile::open("test--").is_err());
         let mut file = NamedFile::open("Cargo.toml").unwrap();
         {
-            file.file();
-            let _f: &File = &file;
+            let _f: &File = file.file().unwrap();
         }
         {
-            let _f: &mut File = &mut file;
+            let _f: &mut File = file.file_mut().unwrap();
         }
 
         let req = TestRequest::default().to_http_request();
@@ -188,11 +192,10 @@ mod tests {
         assert!(NamedFile::open("test--").is_err());
         let mut file = NamedFile::open("Cargo.toml").unwrap();
         {
-            file.file();
-            let _f: &File = &file;
+            let _f: &File = file.file().unwrap();
         }
         {
-            let _f: &mut File = &mut file;
+            let _f: &mut File = file.file_mut().unwrap();
         }
 
         let req = TestRequest::default().to_http_request();
@@ -215,11 +218,10 @@ mod tests {
         let mut file =
             NamedFile::from_file(File::open("Cargo.toml").unwrap(), "貨物.toml").unwrap();
         {
-            file.file();
-            let _f: &File = &file;
+            let _f: &File = file.file().unwrap();
         }
         {
-            let _f: &mut File = &mut file;
+            let _f: &mut File = file.file_mut().unwrap();
         }
 
         let req = TestRequest::default().to_http_request();
@@ -240,11 +242,10 @@ mod tests {
             .unwrap()
             .set_content_type(mime::TEXT_XML);
         {
-            file.file();
-            let _f: &File = &file;
+            let _f: &File = file.file().unwrap();
         }
         {
-            let _f: &mut File = &mut file;
+            let _f: &mut File = file.file_mut().unwrap();
         }
 
         let req = TestRequest::default().to_http_request();
@@ -263,11 +264,10 @@ mod tests {
     async fn test_named_file_image() {
         let mut file = NamedFile::open("tests/test.png").unwrap();
         {
-            file.file();
-            let _f: &File = &file;
+            let _f: &File = file.file().unwrap();
         }
         {
-            let _f: &mut File = &mut file;
+            let _f: &mut File = file.file_mut().unwrap();
         }
 
         let req = TestRequest::default().to_http_request();
@@ -308,11 +308,10 @@ mod tests {
             .unwrap()
             .set_content_disposition(cd);
         {
-            file.file();
-            let _f: &File = &file;
+            let _f: &File = file.file().unwrap();
         }
         {
-            let _f: &mut File = &mut file;
+            let _f: &mut File = file.file_mut().unwrap();
         }
 
         let req = TestRequest::default().to_http_request();
@@ -331,11 +330,10 @@ mod tests {
     async fn test_named_file_binary() {
         let mut file = NamedFile::open("tests/test.binary").unwrap();
         {
-            file.file();
-            let _f: &File = &file;
+            let _f: &File = file.file().unwrap();
         }
         {
-            let _f: &mut File = &mut file;
+            let _f: &mut File = file.file_mut().unwrap();
         }
 
         let req = TestRequest::default().to_http_request();
@@ -356,11 +354,10 @@ mod tests {
             .unwrap()
             .set_status_code(StatusCode::NOT_FOUND);
         {
-            file.file();
-            let _f: &File = &file;
+            let _f: &File = file.file().unwrap();
         }
         {
-            let _f: &mut File = &mut file;
+            let _f: &mut File = file.file_mut().unwrap();
         }
 
         let req = TestRequest::default().to_http_request();
@@ -494,6 +491,257 @@ mod tests {
         assert_eq!(bytes, data);
     }
 
+    #[actix_rt::test]
+    async fn test_named_file_multiple_ranges() {
+        let srv = actix_test::start(|| {
+            App::new()
+                .service(Files::new("/capped", ".").max_ranges(2))
+                .service(Files::new("/", "."))
+        });
+        let data = fs::read("tests/test.binary").unwrap();
+
+        let mut response = srv
+            .get("/tests/test.binary")
+            .insert_header((header::RANGE, "bytes=50-59,0-9,5-14"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert!(response.headers().get(header::CONTENT_RANGE).is_none());
+
+        let content_type = response.headers().get(header::CONTENT_TYPE).unwrap();
+        let content_type = content_type.to_str().unwrap().to_owned();
+        let boundary = content_type
+            .strip_prefix("multipart/byteranges; boundary=")
+            .unwrap();
+
+        let content_length = response.headers().get(header::CONTENT_LENGTH).unwrap();
+        let content_length: usize = content_length.to_str().unwrap().parse().unwrap();
+
+        let bytes = response.body().await.unwrap();
+        assert_eq!(bytes.len(), content_length);
+
+        // overlapping ranges are coalesced and sorted
+        let mut expected = Vec::new();
+        for (idx, (start, end)) in [(0, 14), (50, 59)].iter().enumerate() {
+            if idx > 0 {
+                expected.extend_from_slice(b"\r\n");
+            }
+            expected.extend_from_slice(
+                format!(
+                    "--{}\r\ncontent-type: application/octet-stream\r\n\
+                     content-range: bytes {}-{}/100\r\n\r\n",
+                    boundary, start, end
+                )
+                .as_bytes(),
+            );
+            expected.extend_from_slice(&data[*start..=*end]);
+        }
+        expected.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+        assert_eq!(bytes, expected);
+
+        // ranges coalesced into one are served as a single part
+        let response = srv
+            .get("/tests/test.binary")
+            .insert_header((header::RANGE, "bytes=0-9,10-19"))
+            .send()
+            .await
+            .unwrap();
+        let content_range = response.headers().get(header::CONTENT_RANGE).unwrap();
+        assert_eq!(content_range.to_str().unwrap(), "bytes 0-19/100");
+
+        // the entire file is served when there are too many ranges
+        let mut response = srv
+            .get("/capped/tests/test.binary")
+            .insert_header((header::RANGE, "bytes=0-1,10-11,20-21"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.body().await.unwrap(), data);
+    }
+
+    #[actix_rt::test]
+    async fn test_cache_policies() {
+        let srv = test::init_service(
+            App::new().service(
+                Files::new("/", ".")
+                    .index_file("index.txt")
+                    .cache_policy(CachePolicy::no_cache())
+                    .path_cache_policy(
+                        "tests/*.binary",
+                        CachePolicy::immutable(Duration::from_secs(600)),
+                    )
+                    .mime_cache_policy(
+                        mime::IMAGE_STAR,
+                        CachePolicy::max_age(Duration::from_secs(60))
+                            .expires(Duration::from_secs(60)),
+                    )
+                    .path_cache_policy("**/first/index.txt", CachePolicy::no_store()),
+            ),
+        )
+        .await;
+
+        let cache_control = |uri: &str| {
+            let req = TestRequest::with_uri(uri).to_request();
+            let srv = &srv;
+
+            async move {
+                let res = test::call_service(srv, req).await;
+                assert_eq!(res.status(), StatusCode::OK);
+                let expires = res.headers().contains_key(header::EXPIRES);
+                let cache_control = res.headers().get(header::CACHE_CONTROL).unwrap();
+                (cache_control.to_str().unwrap().to_owned(), expires)
+            }
+        };
+
+        assert_eq!(
+            cache_control("/tests/test.binary").await,
+            ("public, max-age=600, immutable".to_owned(), false)
+        );
+        assert_eq!(
+            cache_control("/tests/test.png").await,
+            ("public, max-age=60".to_owned(), true)
+        );
+        assert_eq!(
+            cache_control("/Cargo.toml").await,
+            ("no-cache".to_owned(), false)
+        );
+        assert_eq!(
+            cache_control("/tests/fixtures/guards/first/").await,
+            ("no-store".to_owned(), false)
+        );
+        assert_eq!(
+            cache_control("/tests/fixtures/guards/second/").await,
+            ("no-cache".to_owned(), false)
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_spa_fallback() {
+        let srv = test::init_service(
+            App::new().service(
+                Files::new("/", "tests/fixtures/spa")
+                    .index_file("index.html")
+                    .spa_fallback("index.html")
+                    .spa_asset_extensions(&["js", "CSS"])
+                    .method_guard(guard::Any(guard::Get()).or(guard::Post()))
+                    .path_cache_policy("index.html", CachePolicy::no_cache()),
+            ),
+        )
+        .await;
+
+        let index = fs::read("tests/fixtures/spa/index.html").unwrap();
+
+        for uri in &["/", "/users/42", "/settings/profile.old"] {
+            let req = TestRequest::with_uri(uri).to_request();
+            let res = test::call_service(&srv, req).await;
+            assert_eq!(res.status(), StatusCode::OK, "{}", uri);
+            assert_eq!(
+                res.headers().get(header::CONTENT_TYPE).unwrap(),
+                "text/html"
+            );
+            assert_eq!(
+                res.headers().get(header::CACHE_CONTROL).unwrap(),
+                "no-cache"
+            );
+            assert_eq!(test::read_body(res).await, index);
+        }
+
+        // existing assets are served as usual
+        let req = TestRequest::with_uri("/app.js").to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(res.headers().get(header::CACHE_CONTROL).is_none());
+
+        // missing assets and other methods are not found
+        for uri in &["/missing.js", "/static/missing.css"] {
+            let req = TestRequest::with_uri(uri).to_request();
+            let res = test::call_service(&srv, req).await;
+            assert_eq!(res.status(), StatusCode::NOT_FOUND, "{}", uri);
+        }
+
+        let req = TestRequest::post().uri("/users/42").to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_rt::test]
+    async fn test_memory_storage() {
+        let mut storage = MemoryStorage::new();
+        storage.insert("index.html", "<h1>Hello</h1>");
+        storage.insert("docs/report.pdf", &b"%PDF-1.4 report"[..]);
+
+        let srv = test::init_service(
+            App::new().service(
+                Files::with_storage("/", storage)
+                    .index_file("index.html")
+                    .show_files_listing(),
+            ),
+        )
+        .await;
+
+        let req = TestRequest::with_uri("/").to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/html"
+        );
+        let etag = res.headers().get(header::ETAG).unwrap().clone();
+        assert!(res.headers().contains_key(header::LAST_MODIFIED));
+        assert_eq!(test::read_body(res).await, Bytes::from("<h1>Hello</h1>"));
+
+        let req = TestRequest::with_uri("/")
+            .insert_header((header::IF_NONE_MATCH, etag))
+            .to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+
+        let req = TestRequest::with_uri("/docs/report.pdf")
+            .insert_header((header::RANGE, "bytes=9-14"))
+            .to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            res.headers().get(header::CONTENT_DISPOSITION).unwrap(),
+            "attachment; filename=\"report.pdf\""
+        );
+        assert_eq!(
+            res.headers().get(header::CONTENT_RANGE).unwrap(),
+            "bytes 9-14/15"
+        );
+        assert_eq!(test::read_body(res).await, Bytes::from("report"));
+
+        // no files listing for storage in memory
+        let req = TestRequest::with_uri("/docs/").to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        let req = TestRequest::with_uri("/missing.txt").to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_cache_policy() {
+        let file = NamedFile::open("Cargo.toml")
+            .unwrap()
+            .cache_policy(CachePolicy::no_cache());
+        let etag = file.etag().unwrap();
+
+        // also sent with not modified responses
+        let req = TestRequest::default()
+            .insert_header((header::IF_NONE_MATCH, etag))
+            .to_http_request();
+        let resp = file.respond_to(&req);
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(
+            resp.headers().get(header::CACHE_CONTROL).unwrap(),
+            "no-cache"
+        );
+    }
+
     #[actix_rt::test]
     async fn test_head_content_length_headers() {
         let srv = actix_test::start(|| App::new().service(Files::new("/", ".")));
@@ -649,6 +897,77 @@ mod tests {
         assert!(format!("{:?}", bytes).contains("/tests/test.png"));
     }
 
+    #[actix_rt::test]
+    async fn test_directory_listing() {
+        let srv = test::init_service(
+            App::new().service(
+                Files::new("/", "tests")
+                    .show_files_listing()
+                    .directory_listing(
+                        DirectoryListing::new()
+                            .sort_by(ListingSort::Size)
+                            .descending(true)
+                            .show_modified(false)
+                            .json(true)
+                            .template("<ul>{{entries}}</ul>")
+                            .entry_template("<li>{{name}} {{size}}</li>"),
+                    ),
+            ),
+        )
+        .await;
+
+        let req = TestRequest::with_uri("/fixtures/precompressed").to_request();
+        let resp = test::call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get(header::VARY).unwrap(), "Accept");
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/html; charset=utf-8"
+        );
+
+        let bytes = test::read_body(resp).await;
+        assert_eq!(
+            bytes,
+            "<ul>\
+             <li>app.js 22 B</li>\
+             <li>app.js.br 14 B</li>\
+             <li>app.js.gz 12 B</li>\
+             <li>style.css.gz 10 B</li>\
+             <li>style.css 8 B</li>\
+             </ul>"
+        );
+
+        // directories are listed first
+        let req = TestRequest::with_uri("/fixtures/")
+            .insert_header((header::ACCEPT, "application/json, text/html;q=0.9"))
+            .to_request();
+        let resp = test::call_service(&srv, req).await;
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/json"
+        );
+
+        let bytes = test::read_body(resp).await;
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["path"], "/fixtures/");
+
+        let entries = json["entries"].as_array().unwrap();
+        assert_eq!(entries.len(), 3);
+        assert!(entries.iter().all(|entry| entry["is_dir"] == true));
+        assert_eq!(entries[0]["url"], "/fixtures/spa/");
+        assert!(entries[0].get("modified").is_none());
+
+        // HTML is preferred for wildcards
+        let req = TestRequest::with_uri("/fixtures/")
+            .insert_header((header::ACCEPT, "*/*"))
+            .to_request();
+        let resp = test::call_service(&srv, req).await;
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/html; charset=utf-8"
+        );
+    }
+
     #[actix_rt::test]
     async fn test_redirect_to_slash_directory() {
         // should not redirect if no index and files listing is disabled
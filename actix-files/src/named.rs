@@ -1,9 +1,8 @@
 use actix_service::{Service, ServiceFactory};
 use actix_utils::future::{ok, ready, Ready};
 use actix_web::dev::{AppService, HttpServiceFactory, ResourceDef};
-use std::fs::{File, Metadata};
+use std::fs::File;
 use std::io;
-use std::ops::{Deref, DerefMut};
 use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -11,20 +10,23 @@ use std::time::{SystemTime, UNIX_EPOCH};
 use std::os::unix::fs::MetadataExt;
 
 use actix_web::{
-    dev::{BodyEncoding, ServiceRequest, ServiceResponse, SizedStream},
+    dev::{AnyBody, BodyEncoding, ServiceRequest, ServiceResponse, SizedStream},
     http::{
         header::{
             self, Charset, ContentDisposition, DispositionParam, DispositionType, ExtendedValue,
         },
         ContentEncoding, StatusCode,
     },
-    Error, HttpMessage, HttpRequest, HttpResponse, Responder,
+    Error, HttpMessage, HttpRequest, HttpResponse, HttpResponseBuilder, Responder,
 };
 use bitflags::bitflags;
+use bytes::Bytes;
 use mime_guess::from_path;
 
 use crate::ChunkedReadFile;
-use crate::{encoding::equiv_utf8_text, range::HttpRange};
+use crate::{
+    byteranges::ByteRangesBody, cache::CachePolicy, encoding::equiv_utf8_text, range::HttpRange,
+};
 
 bitflags! {
     pub(crate) struct Flags: u8 {
@@ -32,6 +34,7 @@ bitflags! {
         const LAST_MD =             0b0000_0010;
         const CONTENT_DISPOSITION = 0b0000_0100;
         const PREFER_UTF8 =         0b0000_1000;
+        const PRECOMPRESSED =       0b0001_0000;
     }
 }
 
@@ -67,15 +70,29 @@ impl Default for Flags {
 /// ```
 #[derive(Debug)]
 pub struct NamedFile {
-    path: PathBuf,
-    file: File,
+    pub(crate) path: PathBuf,
+    pub(crate) content: Content,
     modified: Option<SystemTime>,
-    pub(crate) md: Metadata,
+    len: u64,
+    ino: u64,
     pub(crate) flags: Flags,
     pub(crate) status_code: StatusCode,
     pub(crate) content_type: mime::Mime,
     pub(crate) content_disposition: header::ContentDisposition,
     pub(crate) encoding: Option<ContentEncoding>,
+    pub(crate) max_ranges: usize,
+    pub(crate) precompressed: Option<ContentEncoding>,
+    pub(crate) cache_policy: Option<CachePolicy>,
+}
+
+/// Default cap on the number of ranges served in a `multipart/byteranges` response.
+pub(crate) const DEFAULT_MAX_RANGES: usize = 16;
+
+/// Contents of a [`NamedFile`].
+#[derive(Debug)]
+pub(crate) enum Content {
+    File(File),
+    Bytes(Bytes),
 }
 
 impl NamedFile {
@@ -101,6 +118,52 @@ impl NamedFile {
     /// }
     /// ```
     pub fn from_file<P: AsRef<Path>>(file: File, path: P) -> io::Result<NamedFile> {
+        let md = file.metadata()?;
+
+        let ino = {
+            #[cfg(unix)]
+            {
+                md.ino()
+            }
+            #[cfg(not(unix))]
+            {
+                0
+            }
+        };
+
+        Self::from_content(Content::File(file), path, md.len(), ino, md.modified().ok())
+    }
+
+    /// Creates an instance serving `bytes` from memory.
+    ///
+    /// The given `path` is only used to determine the `ContentType` and `ContentDisposition`
+    /// headers. No `ETag` or `Last-Modified` headers are sent unless a modification time is set
+    /// with [`NamedFile::set_last_modified`].
+    ///
+    /// # Examples
+    /// ```
+    /// use actix_files::NamedFile;
+    ///
+    /// let named_file = NamedFile::from_bytes("<h1>Hello</h1>", "index.html").unwrap();
+    /// assert_eq!(named_file.content_type(), &mime::TEXT_HTML);
+    /// ```
+    pub fn from_bytes<B, P>(bytes: B, path: P) -> io::Result<NamedFile>
+    where
+        B: Into<Bytes>,
+        P: AsRef<Path>,
+    {
+        let bytes = bytes.into();
+        let len = bytes.len() as u64;
+        Self::from_content(Content::Bytes(bytes), path, len, 0, None)
+    }
+
+    fn from_content<P: AsRef<Path>>(
+        content: Content,
+        path: P,
+        len: u64,
+        ino: u64,
+        modified: Option<SystemTime>,
+    ) -> io::Result<NamedFile> {
         let path = path.as_ref().to_path_buf();
 
         // Get the name of the file and use it to construct default Content-Type
@@ -147,20 +210,22 @@ impl NamedFile {
             (ct, cd)
         };
 
-        let md = file.metadata()?;
-        let modified = md.modified().ok();
         let encoding = None;
 
         Ok(NamedFile {
             path,
-            file,
+            content,
             content_type,
             content_disposition,
-            md,
             modified,
+            len,
+            ino,
             encoding,
             status_code: StatusCode::OK,
             flags: Flags::default(),
+            max_ranges: DEFAULT_MAX_RANGES,
+            precompressed: None,
+            cache_policy: None,
         })
     }
 
@@ -178,9 +243,31 @@ impl NamedFile {
     }
 
     /// Returns reference to the underlying `File` object.
+    ///
+    /// Returns `None` if the file is served from memory, see [`NamedFile::from_bytes`].
+    #[inline]
+    pub fn file(&self) -> Option<&File> {
+        match self.content {
+            Content::File(ref file) => Some(file),
+            Content::Bytes(_) => None,
+        }
+    }
+
+    /// Returns mutable reference to the underlying `File` object.
+    ///
+    /// Returns `None` if the file is served from memory, see [`NamedFile::from_bytes`].
+    #[inline]
+    pub fn file_mut(&mut self) -> Option<&mut File> {
+        match self.content {
+            Content::File(ref mut file) => Some(file),
+            Content::Bytes(_) => None,
+        }
+    }
+
+    /// Returns true if the file is served from memory rather than read from a `File`.
     #[inline]
-    pub fn file(&self) -> &File {
-        &self.file
+    pub fn is_in_memory(&self) -> bool {
+        matches!(self.content, Content::Bytes(_))
     }
 
     /// Retrieve the path of this file.
@@ -202,6 +289,12 @@ impl NamedFile {
         self.path.as_path()
     }
 
+    /// Returns the MIME Content-Type the file is served with.
+    #[inline]
+    pub fn content_type(&self) -> &mime::Mime {
+        &self.content_type
+    }
+
     /// Set response **Status Code**
     pub fn set_status_code(mut self, status: StatusCode) -> Self {
         self.status_code = status;
@@ -277,31 +370,58 @@ impl NamedFile {
         self
     }
 
+    /// Sets the modification time used for the `ETag` and `Last-Modified` headers.
+    ///
+    /// By default the modification time is read from the file system.
+    #[inline]
+    pub fn set_last_modified(mut self, modified: SystemTime) -> Self {
+        self.modified = Some(modified);
+        self
+    }
+
+    /// Sets the `Cache-Control` and `Expires` headers sent with this file.
+    ///
+    /// By default no caching headers are sent.
+    #[inline]
+    pub fn cache_policy(mut self, policy: CachePolicy) -> Self {
+        self.cache_policy = Some(policy);
+        self
+    }
+
+    /// Sets the maximum number of ranges served for a multi-range request.
+    ///
+    /// Overlapping and adjacent ranges are merged first. If more ranges remain, the `Range`
+    /// header is ignored and the entire file is served.
+    ///
+    /// Default is 16.
+    #[inline]
+    pub fn max_ranges(mut self, max_ranges: usize) -> Self {
+        self.max_ranges = max_ranges;
+        self
+    }
+
     pub(crate) fn etag(&self) -> Option<header::EntityTag> {
         // This etag format is similar to Apache's.
         self.modified.as_ref().map(|mtime| {
-            let ino = {
-                #[cfg(unix)]
-                {
-                    self.md.ino()
-                }
-                #[cfg(not(unix))]
-                {
-                    0
-                }
-            };
-
             let dur = mtime
                 .duration_since(UNIX_EPOCH)
                 .expect("modification time must be after epoch");
 
-            header::EntityTag::strong(format!(
+            let mut tag = format!(
                 "{:x}:{:x}:{:x}:{:x}",
-                ino,
-                self.md.len(),
+                self.ino,
+                self.len,
                 dur.as_secs(),
                 dur.subsec_nanos()
-            ))
+            );
+
+            // keep tags of precompressed variants distinct from the original file
+            if let Some(encoding) = self.precompressed {
+                tag.push(':');
+                tag.push_str(encoding.as_str());
+            }
+
+            header::EntityTag::strong(tag)
         })
     }
 
@@ -309,6 +429,18 @@ impl NamedFile {
         self.modified.map(|mtime| mtime.into())
     }
 
+    /// Sets `Content-Encoding` when serving a precompressed variant and `Vary` when variants are
+    /// negotiated.
+    fn insert_precompressed_headers(&self, res: &mut HttpResponseBuilder) {
+        if let Some(encoding) = self.precompressed {
+            res.insert_header((header::CONTENT_ENCODING, encoding.as_str()));
+        }
+
+        if self.flags.contains(Flags::PRECOMPRESSED) {
+            res.insert_header((header::VARY, "accept-encoding"));
+        }
+    }
+
     /// Creates an `HttpResponse` with file as a streaming body.
     pub fn into_response(self, req: &HttpRequest) -> HttpResponse {
         if self.status_code != StatusCode::OK {
@@ -332,9 +464,16 @@ impl NamedFile {
                 res.encoding(current_encoding);
             }
 
-            let reader = ChunkedReadFile::new(self.md.len(), 0, self.file);
+            self.insert_precompressed_headers(&mut res);
+
+            if let Some(ref policy) = self.cache_policy {
+                policy.insert_headers(&mut res);
+            }
 
-            return res.streaming(reader);
+            return match self.content {
+                Content::File(file) => res.streaming(ChunkedReadFile::new(self.len, 0, file)),
+                Content::Bytes(bytes) => res.body(bytes),
+            };
         }
 
         let etag = if self.flags.contains(Flags::ETAG) {
@@ -387,12 +526,12 @@ impl NamedFile {
 
         let mut resp = HttpResponse::build(self.status_code);
 
-        if self.flags.contains(Flags::PREFER_UTF8) {
-            let ct = equiv_utf8_text(self.content_type.clone());
-            resp.insert_header((header::CONTENT_TYPE, ct.to_string()));
+        let content_type = if self.flags.contains(Flags::PREFER_UTF8) {
+            equiv_utf8_text(self.content_type.clone()).to_string()
         } else {
-            resp.insert_header((header::CONTENT_TYPE, self.content_type.to_string()));
-        }
+            self.content_type.to_string()
+        };
+        resp.insert_header((header::CONTENT_TYPE, content_type.clone()));
 
         if self.flags.contains(Flags::CONTENT_DISPOSITION) {
             resp.insert_header((
@@ -406,6 +545,12 @@ impl NamedFile {
             resp.encoding(current_encoding);
         }
 
+        self.insert_precompressed_headers(&mut resp);
+
+        if let Some(ref policy) = self.cache_policy {
+            policy.insert_headers(&mut resp);
+        }
+
         if let Some(lm) = last_modified {
             resp.insert_header((header::LAST_MODIFIED, lm.to_string()));
         }
@@ -416,21 +561,31 @@ impl NamedFile {
 
         resp.insert_header((header::ACCEPT_RANGES, "bytes"));
 
-        let mut length = self.md.len();
+        let mut length = self.len;
         let mut offset = 0;
+        let mut multiple_ranges = None;
 
         // check for range header
         if let Some(ranges) = req.headers().get(header::RANGE) {
             if let Ok(ranges_header) = ranges.to_str() {
                 if let Ok(ranges) = HttpRange::parse(ranges_header, length) {
-                    length = ranges[0].length;
-                    offset = ranges[0].start;
+                    let ranges = HttpRange::coalesce(ranges);
+
+                    if ranges.len() == 1 {
+                        length = ranges[0].length;
+                        offset = ranges[0].start;
+
+                        resp.encoding(ContentEncoding::Identity);
+                        resp.insert_header((
+                            header::CONTENT_RANGE,
+                            format!("bytes {}-{}/{}", offset, offset + length - 1, self.len),
+                        ));
+                    } else if ranges.len() <= self.max_ranges {
+                        resp.encoding(ContentEncoding::Identity);
+                        multiple_ranges = Some(ranges);
+                    }
 
-                    resp.encoding(ContentEncoding::Identity);
-                    resp.insert_header((
-                        header::CONTENT_RANGE,
-                        format!("bytes {}-{}/{}", offset, offset + length - 1, self.md.len()),
-                    ));
+                    // with too many ranges the entire file is served
                 } else {
                     resp.insert_header((header::CONTENT_RANGE, format!("bytes */{}", length)));
                     return resp.status(StatusCode::RANGE_NOT_SATISFIABLE).finish();
@@ -446,27 +601,27 @@ impl NamedFile {
             return resp.status(StatusCode::NOT_MODIFIED).finish();
         }
 
-        let reader = ChunkedReadFile::new(length, offset, self.file);
+        if let Some(ranges) = multiple_ranges {
+            let body = ByteRangesBody::new(ranges, content_type, self.len, self.content);
 
-        if offset != 0 || length != self.md.len() {
             resp.status(StatusCode::PARTIAL_CONTENT);
-        }
+            resp.insert_header((header::CONTENT_TYPE, body.content_type()));
 
-        resp.body(SizedStream::new(length, reader))
-    }
-}
-
-impl Deref for NamedFile {
-    type Target = File;
+            return resp.body(SizedStream::new(body.size(), body));
+        }
 
-    fn deref(&self) -> &File {
-        &self.file
-    }
-}
+        if offset != 0 || length != self.len {
+            resp.status(StatusCode::PARTIAL_CONTENT);
+        }
 
-impl DerefMut for NamedFile {
-    fn deref_mut(&mut self) -> &mut File {
-        &mut self.file
+        match self.content {
+            Content::File(file) => resp.body(AnyBody::from_message(ChunkedReadFile::new(
+                length, offset, file,
+            ))),
+            Content::Bytes(bytes) => {
+                resp.body(bytes.slice(offset as usize..(offset + length) as usize))
+            }
+        }
     }
 }
 
@@ -525,8 +680,14 @@ impl ServiceFactory<ServiceRequest> for NamedFile {
     type Future = Ready<Result<Self::Service, ()>>;
 
     fn new_service(&self, _: ()) -> Self::Future {
+        let bytes = match self.content {
+            Content::File(_) => None,
+            Content::Bytes(ref bytes) => Some((bytes.clone(), self.modified)),
+        };
+
         ok(NamedFileService {
             path: self.path.clone(),
+            bytes,
         })
     }
 }
@@ -535,6 +696,7 @@ impl ServiceFactory<ServiceRequest> for NamedFile {
 #[derive(Debug)]
 pub struct NamedFileService {
     path: PathBuf,
+    bytes: Option<(Bytes, Option<SystemTime>)>,
 }
 
 impl Service<ServiceRequest> for NamedFileService {
@@ -546,8 +708,18 @@ impl Service<ServiceRequest> for NamedFileService {
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
         let (req, _) = req.into_parts();
+
+        let named_file = match self.bytes {
+            Some((ref bytes, modified)) => NamedFile::from_bytes(bytes.clone(), &self.path)
+                .map(|mut named_file| {
+                    named_file.modified = modified;
+                    named_file
+                }),
+            None => NamedFile::open(&self.path),
+        };
+
         ready(
-            NamedFile::open(&self.path)
+            named_file
                 .map_err(|e| e.into())
                 .map(|f| f.into_response(&req))
                 .map(|res| ServiceResponse::new(req, res)),
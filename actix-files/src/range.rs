@@ -1,3 +1,5 @@
+use std::cmp;
+
 use derive_more::{Display, Error};
 
 /// HTTP Range header representation.
@@ -31,6 +33,25 @@ impl HttpRange {
             Err(_) => Err(ParseRangeErr(())),
         }
     }
+
+    /// Sorts ranges and merges those that overlap or are adjacent.
+    pub(crate) fn coalesce(mut ranges: Vec<HttpRange>) -> Vec<HttpRange> {
+        ranges.sort_by_key(|range| range.start);
+
+        let mut merged: Vec<HttpRange> = Vec::with_capacity(ranges.len());
+
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.start + last.length => {
+                    let end = cmp::max(last.start + last.length, range.start + range.length);
+                    last.length = end - last.start;
+                }
+                _ => merged.push(range),
+            }
+        }
+
+        merged
+    }
 }
 
 #[cfg(test)]
@@ -39,6 +60,28 @@ mod tests {
 
     struct T(&'static str, u64, Vec<HttpRange>);
 
+    #[test]
+    fn test_coalesce() {
+        let range = |start, length| HttpRange { start, length };
+        let bounds = |ranges: Vec<HttpRange>| {
+            ranges
+                .iter()
+                .map(|r| (r.start, r.length))
+                .collect::<Vec<_>>()
+        };
+
+        // overlapping, adjacent and out of order
+        let ranges = vec![range(50, 10), range(0, 10), range(5, 10), range(15, 5)];
+        assert_eq!(bounds(HttpRange::coalesce(ranges)), vec![(0, 20), (50, 10)]);
+
+        // contained
+        let ranges = vec![range(0, 100), range(10, 10)];
+        assert_eq!(bounds(HttpRange::coalesce(ranges)), vec![(0, 100)]);
+
+        let ranges = vec![range(0, 10), range(11, 10)];
+        assert_eq!(bounds(HttpRange::coalesce(ranges)), vec![(0, 10), (11, 10)]);
+    }
+
     #[test]
     fn test_parse() {
         let tests = vec![
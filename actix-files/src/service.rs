@@ -1,4 +1,4 @@
-use std::{fmt, io, path::PathBuf, rc::Rc};
+use std::{fmt, io, path::Path, rc::Rc};
 
 use actix_service::Service;
 use actix_utils::future::ok;
@@ -12,13 +12,13 @@ use actix_web::{
 use futures_core::future::LocalBoxFuture;
 
 use crate::{
-    named, Directory, DirectoryRenderer, FilesError, HttpService, MimeOverride, NamedFile,
-    PathBufWrap, PathFilter,
+    cache::CachePolicies, encoding::accepted_variants, named, Directory, DirectoryRenderer,
+    FilesError, HttpService, MimeOverride, NamedFile, PathBufWrap, PathFilter, Storage,
 };
 
 /// Assembled file serving service.
 pub struct FilesService {
-    pub(crate) directory: PathBuf,
+    pub(crate) storage: Rc<dyn Storage>,
     pub(crate) index: Option<String>,
     pub(crate) show_index: bool,
     pub(crate) redirect_to_slash: bool,
@@ -27,6 +27,10 @@ pub struct FilesService {
     pub(crate) mime_override: Option<Rc<MimeOverride>>,
     pub(crate) path_filter: Option<Rc<PathFilter>>,
     pub(crate) file_flags: named::Flags,
+    pub(crate) max_ranges: usize,
+    pub(crate) cache_policies: Rc<CachePolicies>,
+    pub(crate) spa_index: Option<String>,
+    pub(crate) spa_asset_extensions: Rc<Vec<String>>,
     pub(crate) guards: Option<Rc<dyn Guard>>,
     pub(crate) hidden_files: bool,
 }
@@ -45,6 +49,104 @@ impl FilesService {
             Box::pin(ok(req.error_response(err)))
         }
     }
+
+    /// Serves the SPA index file in place of a file that does not exist, if enabled.
+    ///
+    /// `path` is the requested path, relative to the served directory.
+    fn handle_not_found(
+        &self,
+        path: &Path,
+        err: io::Error,
+        req: ServiceRequest,
+    ) -> LocalBoxFuture<'static, Result<ServiceResponse, Error>> {
+        let index = match self.spa_index {
+            Some(ref index) if err.kind() == io::ErrorKind::NotFound => index,
+            _ => return self.handle_err(err, req),
+        };
+
+        let is_asset = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map_or(false, |ext| {
+                self.spa_asset_extensions
+                    .iter()
+                    .any(|asset_ext| asset_ext.eq_ignore_ascii_case(ext))
+            });
+
+        if is_asset || !matches!(*req.method(), Method::HEAD | Method::GET) {
+            return self.handle_err(err, req);
+        }
+
+        match self.open_file(Path::new(index), &req) {
+            Ok(named_file) => self.serve_named_file(req, named_file, Path::new(index)),
+            Err(err) => self.handle_err(err, req),
+        }
+    }
+
+    /// Applies the service's file settings to `named_file` and responds with it.
+    ///
+    /// `path` is the path of the file, relative to the served directory.
+    fn serve_named_file(
+        &self,
+        req: ServiceRequest,
+        mut named_file: NamedFile,
+        path: &Path,
+    ) -> LocalBoxFuture<'static, Result<ServiceResponse, Error>> {
+        if let Some(ref mime_override) = self.mime_override {
+            let new_disposition = mime_override(&named_file.content_type.type_());
+            named_file.content_disposition.disposition = new_disposition;
+        }
+        named_file.flags = self.file_flags;
+        named_file.max_ranges = self.max_ranges;
+        named_file.cache_policy = self.cache_policies.resolve(path, &named_file.content_type);
+
+        let (req, _) = req.into_parts();
+        let res = named_file.into_response(&req);
+        Box::pin(ok(ServiceResponse::new(req, res)))
+    }
+}
+
+impl FilesService {
+    /// Opens the file at `path`, or its best precompressed variant accepted by the client.
+    ///
+    /// `path` is relative to the served directory.
+    fn open_file(&self, path: &Path, req: &ServiceRequest) -> io::Result<NamedFile> {
+        let named_file = self.storage.open(path)?;
+
+        if !self.file_flags.contains(named::Flags::PRECOMPRESSED) {
+            return Ok(named_file);
+        }
+
+        let accept_encoding = match req
+            .headers()
+            .get(header::ACCEPT_ENCODING)
+            .and_then(|val| val.to_str().ok())
+        {
+            Some(accept_encoding) => accept_encoding,
+            None => return Ok(named_file),
+        };
+
+        for (encoding, ext) in accepted_variants(accept_encoding) {
+            let mut variant_path = path.as_os_str().to_owned();
+            variant_path.push(".");
+            variant_path.push(ext);
+
+            let mut variant = match self.storage.open(Path::new(&variant_path)) {
+                Ok(variant) => variant,
+                Err(_) => continue,
+            };
+
+            // path, content type and disposition still describe the original file
+            variant.path = named_file.path.clone();
+            variant.content_type = named_file.content_type;
+            variant.content_disposition = named_file.content_disposition;
+            variant.precompressed = Some(encoding);
+
+            return Ok(variant);
+        }
+
+        Ok(named_file)
+    }
 }
 
 impl fmt::Debug for FilesService {
@@ -95,13 +197,9 @@ impl Service<ServiceRequest> for FilesService {
             }
         }
 
-        // full file path
-        let path = self.directory.join(&real_path);
-        if let Err(err) = path.canonicalize() {
-            return Box::pin(self.handle_err(err, req));
-        }
+        let path = real_path.as_ref();
 
-        if path.is_dir() {
+        if self.storage.is_dir(path) {
             if self.redirect_to_slash
                 && !req.path().ends_with('/')
                 && (self.index.is_some() || self.show_index)
@@ -115,21 +213,15 @@ impl Service<ServiceRequest> for FilesService {
                 )));
             }
 
-            let serve_named_file = |req: ServiceRequest, mut named_file: NamedFile| {
-                if let Some(ref mime_override) = self.mime_override {
-                    let new_disposition = mime_override(&named_file.content_type.type_());
-                    named_file.content_disposition.disposition = new_disposition;
+            // files listings are only shown for storage on disk
+            let dir = match self.storage.root_dir() {
+                Some(root_dir) if self.show_index => {
+                    Some(Directory::new(root_dir.to_owned(), root_dir.join(path)))
                 }
-                named_file.flags = self.file_flags;
-
-                let (req, _) = req.into_parts();
-                let res = named_file.into_response(&req);
-                Box::pin(ok(ServiceResponse::new(req, res)))
+                _ => None,
             };
 
-            let show_index = |req: ServiceRequest| {
-                let dir = Directory::new(self.directory.clone(), path.clone());
-
+            let show_index = |req: ServiceRequest, dir: Directory| {
                 let (req, _) = req.into_parts();
                 let x = (self.renderer)(&dir, &req);
 
@@ -140,31 +232,29 @@ impl Service<ServiceRequest> for FilesService {
             };
 
             match self.index {
-                Some(ref index) => match NamedFile::open(path.join(index)) {
-                    Ok(named_file) => serve_named_file(req, named_file),
-                    Err(_) if self.show_index => show_index(req),
-                    Err(err) => self.handle_err(err, req),
+                Some(ref index) => {
+                    let index_path = path.join(index);
+
+                    match self.open_file(&index_path, &req) {
+                        Ok(named_file) => self.serve_named_file(req, named_file, &index_path),
+                        Err(err) => match dir {
+                            Some(dir) => show_index(req, dir),
+                            None => self.handle_err(err, req),
+                        },
+                    }
+                }
+                None => match dir {
+                    Some(dir) => show_index(req, dir),
+                    None => Box::pin(ok(ServiceResponse::from_err(
+                        FilesError::IsDirectory,
+                        req.into_parts().0,
+                    ))),
                 },
-                None if self.show_index => show_index(req),
-                _ => Box::pin(ok(ServiceResponse::from_err(
-                    FilesError::IsDirectory,
-                    req.into_parts().0,
-                ))),
             }
         } else {
-            match NamedFile::open(path) {
-                Ok(mut named_file) => {
-                    if let Some(ref mime_override) = self.mime_override {
-                        let new_disposition = mime_override(&named_file.content_type.type_());
-                        named_file.content_disposition.disposition = new_disposition;
-                    }
-                    named_file.flags = self.file_flags;
-
-                    let (req, _) = req.into_parts();
-                    let res = named_file.into_response(&req);
-                    Box::pin(ok(ServiceResponse::new(req, res)))
-                }
-                Err(err) => self.handle_err(err, req),
+            match self.open_file(path, &req) {
+                Ok(named_file) => self.serve_named_file(req, named_file, path),
+                Err(err) => self.handle_not_found(path, err, req),
             }
         }
     }
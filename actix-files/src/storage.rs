@@ -0,0 +1,188 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io,
+    path::{Component, Path, PathBuf},
+    time::SystemTime,
+};
+
+use bytes::Bytes;
+
+use crate::NamedFile;
+
+/// A source of the files served by [`Files`](crate::Files).
+///
+/// Paths given to a storage are relative to its root and have already been sanitized, i.e. they
+/// contain no `..` or root components. Files opened from a storage are served with the same
+/// `ETag`, range, conditional request and `Content-Disposition` handling as files on disk.
+///
+/// [`DiskStorage`] is used by [`Files::new`](crate::Files::new). [`MemoryStorage`] serves files
+/// from memory, e.g. assets embedded into the binary.
+pub trait Storage: 'static {
+    /// Opens the file at `path`.
+    ///
+    /// Should return an error of kind [`io::ErrorKind::NotFound`] if there is no such file.
+    fn open(&self, path: &Path) -> io::Result<NamedFile>;
+
+    /// Returns true if `path` is a directory.
+    fn is_dir(&self, path: &Path) -> bool;
+
+    /// Returns the directory on disk the storage serves files from, if any.
+    ///
+    /// Files listings are only shown for storage on disk. Returns `None` by default.
+    fn root_dir(&self) -> Option<&Path> {
+        None
+    }
+}
+
+/// Storage serving files from a directory on disk.
+#[derive(Debug, Clone)]
+pub struct DiskStorage {
+    root: PathBuf,
+}
+
+impl DiskStorage {
+    /// Constructs a storage serving files from the `root` directory.
+    pub fn new<T: Into<PathBuf>>(root: T) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl Storage for DiskStorage {
+    fn open(&self, path: &Path) -> io::Result<NamedFile> {
+        let path = self.root.join(path);
+        let file = File::open(&path)?;
+
+        if file.metadata()?.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "Provided path is a directory",
+            ));
+        }
+
+        NamedFile::from_file(file, path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.root.join(path).is_dir()
+    }
+
+    fn root_dir(&self) -> Option<&Path> {
+        Some(&self.root)
+    }
+}
+
+/// Storage serving files from memory.
+///
+/// All files share a modification time, used for the `ETag` and `Last-Modified` headers, which
+/// defaults to the time the storage was created.
+///
+/// # Examples
+/// ```
+/// use actix_web::App;
+/// use actix_files::{Files, MemoryStorage};
+///
+/// let mut storage = MemoryStorage::new();
+/// storage.insert("index.html", "<script src=\"js/app.js\"></script>");
+/// storage.insert("js/app.js", &include_bytes!("../tests/test.js")[..]);
+///
+/// let app = App::new()
+///     .service(Files::with_storage("/", storage).index_file("index.html"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct MemoryStorage {
+    files: HashMap<PathBuf, Bytes>,
+    modified: SystemTime,
+}
+
+impl MemoryStorage {
+    /// Constructs an empty storage.
+    pub fn new() -> Self {
+        Self {
+            files: HashMap::new(),
+            modified: SystemTime::now(),
+        }
+    }
+
+    /// Adds a file at `path`, relative to the storage root, replacing any file at that path.
+    pub fn insert<P, B>(&mut self, path: P, contents: B)
+    where
+        P: AsRef<Path>,
+        B: Into<Bytes>,
+    {
+        self.files.insert(normalize(path.as_ref()), contents.into());
+    }
+
+    /// Sets the modification time of the files.
+    pub fn last_modified(mut self, modified: SystemTime) -> Self {
+        self.modified = modified;
+        self
+    }
+}
+
+impl Default for MemoryStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn open(&self, path: &Path) -> io::Result<NamedFile> {
+        match self.files.get(path) {
+            Some(bytes) => Ok(
+                NamedFile::from_bytes(bytes.clone(), path)?.set_last_modified(self.modified)
+            ),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.files
+            .keys()
+            .any(|file| file != path && file.starts_with(path))
+    }
+}
+
+/// Strips root and `.` components from `path`.
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| matches!(component, Component::Normal(_) | Component::ParentDir))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_storage() {
+        let mut storage = MemoryStorage::new();
+        storage.insert("/index.html", "index");
+        storage.insert("./css/site.css", "body {}");
+
+        assert!(storage.is_dir(Path::new("")));
+        assert!(storage.is_dir(Path::new("css")));
+        assert!(!storage.is_dir(Path::new("css/site.css")));
+        assert!(!storage.is_dir(Path::new("cs")));
+
+        let named_file = storage.open(Path::new("css/site.css")).unwrap();
+        assert_eq!(named_file.content_type(), &mime::TEXT_CSS);
+        assert!(named_file.is_in_memory());
+        assert!(named_file.file().is_none());
+
+        let err = storage.open(Path::new("site.css")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_disk_storage() {
+        let storage = DiskStorage::new(".");
+
+        assert!(storage.is_dir(Path::new("tests")));
+        assert!(storage.open(Path::new("tests")).is_err());
+        assert!(storage.open(Path::new("Cargo.toml")).is_ok());
+
+        let err = storage.open(Path::new("missing.txt")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+}
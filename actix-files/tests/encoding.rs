@@ -36,3 +36,85 @@ async fn test_utf8_file_contents() {
         Some(&HeaderValue::from_static("text/plain; charset=utf-8")),
     );
 }
+
+#[actix_rt::test]
+async fn test_precompressed_variants() {
+    let srv = test::init_service(
+        App::new()
+            .service(Files::new("/", "./tests/fixtures/precompressed").precompressed(true)),
+    )
+    .await;
+
+    let get = |uri: &str, accept_encoding: Option<&str>| {
+        let mut req = TestRequest::with_uri(uri);
+        if let Some(accept_encoding) = accept_encoding {
+            req = req.insert_header((header::ACCEPT_ENCODING, accept_encoding));
+        }
+        req.to_request()
+    };
+
+    let res = test::call_service(&srv, get("/app.js", Some("gzip, br"))).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.headers().get(header::CONTENT_ENCODING),
+        Some(&HeaderValue::from_static("br")),
+    );
+    assert_eq!(
+        res.headers().get(header::VARY),
+        Some(&HeaderValue::from_static("accept-encoding")),
+    );
+    let br_etag = res.headers().get(header::ETAG).unwrap().clone();
+    assert_eq!(test::read_body(res).await, "brotli-variant");
+
+    let res = test::call_service(&srv, get("/app.js", Some("gzip;q=1, br;q=0.5"))).await;
+    assert_eq!(
+        res.headers().get(header::CONTENT_ENCODING),
+        Some(&HeaderValue::from_static("gzip")),
+    );
+    let gzip_etag = res.headers().get(header::ETAG).unwrap().clone();
+    assert_ne!(br_etag, gzip_etag);
+    assert_eq!(test::read_body(res).await, "gzip-variant");
+
+    // only a gzip variant exists, content type is that of the original file
+    let res = test::call_service(&srv, get("/style.css", Some("br, gzip"))).await;
+    assert_eq!(
+        res.headers().get(header::CONTENT_ENCODING),
+        Some(&HeaderValue::from_static("gzip")),
+    );
+    assert_eq!(
+        res.headers().get(header::CONTENT_TYPE),
+        Some(&HeaderValue::from_static("text/css")),
+    );
+
+    // no acceptable variant
+    let res = test::call_service(&srv, get("/app.js", None)).await;
+    assert!(res.headers().get(header::CONTENT_ENCODING).is_none());
+    assert_eq!(
+        res.headers().get(header::VARY),
+        Some(&HeaderValue::from_static("accept-encoding")),
+    );
+    assert_ne!(res.headers().get(header::ETAG).unwrap(), br_etag);
+    assert_eq!(test::read_body(res).await, "console.log(\"plain\");\n");
+
+    // ranges apply to the chosen variant
+    let req = TestRequest::with_uri("/app.js")
+        .insert_header((header::ACCEPT_ENCODING, "br"))
+        .insert_header((header::RANGE, "bytes=0-5"))
+        .to_request();
+    let res = test::call_service(&srv, req).await;
+    assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(
+        res.headers().get(header::CONTENT_RANGE),
+        Some(&HeaderValue::from_static("bytes 0-5/14")),
+    );
+    assert_eq!(test::read_body(res).await, "brotli");
+
+    // disabled by default
+    let srv = test::init_service(
+        App::new().service(Files::new("/", "./tests/fixtures/precompressed")),
+    )
+    .await;
+    let res = test::call_service(&srv, get("/app.js", Some("br"))).await;
+    assert!(res.headers().get(header::CONTENT_ENCODING).is_none());
+    assert!(res.headers().get(header::VARY).is_none());
+}
@@ -9,9 +9,11 @@ use std::{
 use bytes::{Bytes, BytesMut};
 use futures_core::{ready, Stream};
 
-use crate::error::Error;
+use crate::{error::Error, header::HeaderMap};
 
-use super::{BodySize, BodyStream, MessageBody, MessageBodyMapErr, SizedStream};
+use super::{
+    BodySize, BodyStream, FileRegion, MessageBody, MessageBodyMapErr, SizedStream,
+};
 
 pub type Body = AnyBody;
 
@@ -84,6 +86,23 @@ impl MessageBody for AnyBody {
             },
         }
     }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        match self.get_mut() {
+            AnyBody::Message(body) => Pin::new(body).poll_trailers(cx),
+            _ => Poll::Ready(Ok(None)),
+        }
+    }
+
+    fn take_file_region(self: Pin<&mut Self>) -> Option<FileRegion> {
+        match self.get_mut() {
+            AnyBody::Message(body) => body.as_pin_mut().take_file_region(),
+            _ => None,
+        }
+    }
 }
 
 impl PartialEq for AnyBody {
@@ -230,4 +249,18 @@ impl MessageBody for BoxAnyBody {
             None => Poll::Ready(None),
         }
     }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        self.0
+            .as_mut()
+            .poll_trailers(cx)
+            .map_err(|err| Error::new_body().with_cause(err))
+    }
+
+    fn take_file_region(mut self: Pin<&mut Self>) -> Option<FileRegion> {
+        self.0.as_mut().take_file_region()
+    }
 }
@@ -0,0 +1,50 @@
+use std::fs::File;
+
+/// A region of a file making up the rest of a message body.
+///
+/// Bodies return a region from [`MessageBody::take_file_region`] so that plaintext HTTP/1.1
+/// connections can send it with `sendfile(2)`, without copying it through user space, when
+/// enabled with [`HttpServiceBuilder::sendfile`].
+///
+/// [`MessageBody::take_file_region`]: super::MessageBody::take_file_region
+/// [`HttpServiceBuilder::sendfile`]: crate::HttpServiceBuilder::sendfile
+#[derive(Debug)]
+pub struct FileRegion {
+    file: File,
+    offset: u64,
+    len: u64,
+}
+
+impl FileRegion {
+    /// Constructs a region of `len` bytes of `file`, starting at `offset`.
+    pub fn new(file: File, offset: u64, len: u64) -> Self {
+        Self { file, offset, len }
+    }
+
+    /// Returns the file the region is part of.
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    /// Returns the offset of the region in the file.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns the length of the region.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns true if the region is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Removes the first `n` bytes from the region, after they have been sent.
+    pub(crate) fn advance(&mut self, n: u64) {
+        let n = n.min(self.len);
+        self.offset += n;
+        self.len -= n;
+    }
+}
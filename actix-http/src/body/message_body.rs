@@ -11,9 +11,9 @@ use bytes::{Bytes, BytesMut};
 use futures_core::ready;
 use pin_project_lite::pin_project;
 
-use crate::error::Error;
+use crate::{error::Error, header::HeaderMap};
 
-use super::BodySize;
+use super::{BodySize, FileRegion};
 
 /// An interface for response bodies.
 pub trait MessageBody {
@@ -27,6 +27,31 @@ pub trait MessageBody {
         self: Pin<&mut Self>,
         cx: &mut Context<'_>,
     ) -> Poll<Option<Result<Bytes, Self::Error>>>;
+
+    /// Attempt to pull out the trailing headers once the body stream is exhausted.
+    ///
+    /// Only called after `poll_next` has returned `Poll::Ready(None)`. Trailers are sent by
+    /// the HTTP/2 dispatcher as a final HEADERS frame and by the HTTP/1.1 dispatcher after the
+    /// last chunk of a chunked body; they are dropped for other transfer encodings.
+    ///
+    /// The default implementation yields no trailers.
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Poll::Ready(Ok(None))
+    }
+
+    /// Takes the rest of the body as a region of a file, if it consists of one.
+    ///
+    /// Called by the HTTP/1.1 dispatcher, when `sendfile` is enabled, before the first call to
+    /// `poll_next` for bodies with a known size. If a region is returned, its length must equal
+    /// the body size and `poll_next` is not called again.
+    ///
+    /// The default implementation yields no region.
+    fn take_file_region(self: Pin<&mut Self>) -> Option<FileRegion> {
+        None
+    }
 }
 
 impl MessageBody for () {
@@ -61,6 +86,17 @@ where
     ) -> Poll<Option<Result<Bytes, Self::Error>>> {
         Pin::new(self.get_mut().as_mut()).poll_next(cx)
     }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Pin::new(self.get_mut().as_mut()).poll_trailers(cx)
+    }
+
+    fn take_file_region(self: Pin<&mut Self>) -> Option<FileRegion> {
+        Pin::new(self.get_mut().as_mut()).take_file_region()
+    }
 }
 
 impl<B> MessageBody for Pin<Box<B>>
@@ -80,6 +116,17 @@ where
     ) -> Poll<Option<Result<Bytes, Self::Error>>> {
         self.as_mut().poll_next(cx)
     }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        self.get_mut().as_mut().poll_trailers(cx)
+    }
+
+    fn take_file_region(self: Pin<&mut Self>) -> Option<FileRegion> {
+        self.get_mut().as_mut().take_file_region()
+    }
 }
 
 impl MessageBody for Bytes {
@@ -229,4 +276,23 @@ where
             None => Poll::Ready(None),
         }
     }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        let this = self.as_mut().project();
+
+        match ready!(this.body.poll_trailers(cx)) {
+            Ok(trailers) => Poll::Ready(Ok(trailers)),
+            Err(err) => {
+                let f = self.as_mut().project().mapper.take().unwrap();
+                Poll::Ready(Err((f)(err)))
+            }
+        }
+    }
+
+    fn take_file_region(self: Pin<&mut Self>) -> Option<FileRegion> {
+        self.project().body.take_file_region()
+    }
 }
@@ -10,6 +10,7 @@ use futures_core::ready;
 #[allow(clippy::module_inception)]
 mod body;
 mod body_stream;
+mod file_region;
 mod message_body;
 mod response_body;
 mod size;
@@ -17,6 +18,7 @@ mod sized_stream;
 
 pub use self::body::{AnyBody, Body, BoxAnyBody};
 pub use self::body_stream::BodyStream;
+pub use self::file_region::FileRegion;
 pub use self::message_body::MessageBody;
 pub(crate) use self::message_body::MessageBodyMapErr;
 pub use self::response_body::ResponseBody;
@@ -8,9 +8,9 @@ use bytes::Bytes;
 use futures_core::{ready, Stream};
 use pin_project::pin_project;
 
-use crate::error::Error;
+use crate::{error::Error, header::HeaderMap};
 
-use super::{Body, BodySize, MessageBody};
+use super::{Body, BodySize, FileRegion, MessageBody};
 
 #[pin_project(project = ResponseBodyProj)]
 pub enum ResponseBody<B> {
@@ -63,6 +63,23 @@ where
     ) -> Poll<Option<Result<Bytes, Self::Error>>> {
         Stream::poll_next(self, cx)
     }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        match self.project() {
+            ResponseBodyProj::Body(body) => body.poll_trailers(cx).map_err(Into::into),
+            ResponseBodyProj::Other(body) => Pin::new(body).poll_trailers(cx),
+        }
+    }
+
+    fn take_file_region(self: Pin<&mut Self>) -> Option<FileRegion> {
+        match self.project() {
+            ResponseBodyProj::Body(body) => body.take_file_region(),
+            ResponseBodyProj::Other(body) => Pin::new(body).take_file_region(),
+        }
+    }
 }
 
 impl<B> Stream for ResponseBody<B>
@@ -5,10 +5,12 @@ use actix_service::{IntoServiceFactory, Service, ServiceFactory};
 
 use crate::{
     body::{AnyBody, MessageBody},
-    config::{KeepAlive, ServiceConfig},
+    config::{H2Settings, HeadLimits, KeepAlive, ServiceConfig},
     h1::{self, ExpectHandler, H1Service, UpgradeHandler},
     h2::H2Service,
     service::HttpService,
+    shutdown::ShutdownSignal,
+    stats::ConnectionStats,
     ConnectCallback, Extensions, Request, Response,
 };
 
@@ -25,6 +27,12 @@ pub struct HttpServiceBuilder<T, S, X = ExpectHandler, U = UpgradeHandler> {
     expect: X,
     upgrade: Option<U>,
     on_connect_ext: Option<Rc<ConnectCallback<T>>>,
+    connection_stats: Option<ConnectionStats>,
+    sendfile: bool,
+    head_limits: HeadLimits,
+    h2_settings: H2Settings,
+    shutdown_signal: Option<ShutdownSignal>,
+    h2c_upgrade: bool,
     _phantom: PhantomData<S>,
 }
 
@@ -46,6 +54,12 @@ where
             expect: ExpectHandler,
             upgrade: None,
             on_connect_ext: None,
+            connection_stats: None,
+            sendfile: false,
+            head_limits: HeadLimits::default(),
+            h2_settings: H2Settings::default(),
+            shutdown_signal: None,
+            h2c_upgrade: false,
             _phantom: PhantomData,
         }
     }
@@ -132,6 +146,12 @@ where
             expect: expect.into_factory(),
             upgrade: self.upgrade,
             on_connect_ext: self.on_connect_ext,
+            connection_stats: self.connection_stats,
+            sendfile: self.sendfile,
+            head_limits: self.head_limits,
+            h2_settings: self.h2_settings,
+            shutdown_signal: self.shutdown_signal,
+            h2c_upgrade: self.h2c_upgrade,
             _phantom: PhantomData,
         }
     }
@@ -156,6 +176,12 @@ where
             expect: self.expect,
             upgrade: Some(upgrade.into_factory()),
             on_connect_ext: self.on_connect_ext,
+            connection_stats: self.connection_stats,
+            sendfile: self.sendfile,
+            head_limits: self.head_limits,
+            h2_settings: self.h2_settings,
+            shutdown_signal: self.shutdown_signal,
+            h2c_upgrade: self.h2c_upgrade,
             _phantom: PhantomData,
         }
     }
@@ -173,6 +199,164 @@ where
         self
     }
 
+    /// Sets counters to be updated as connections are opened, closed and reused.
+    ///
+    /// The same [`ConnectionStats`] can be given to many services to aggregate their counters.
+    pub fn connection_stats(mut self, stats: ConnectionStats) -> Self {
+        self.connection_stats = Some(stats);
+        self
+    }
+
+    /// Enables sending file bodies with `sendfile(2)` on plaintext HTTP/1.1 connections.
+    ///
+    /// Bodies which consist of a file region, such as files served by `actix-files`, are then
+    /// written to the socket by the kernel without copying them through user space. This only
+    /// applies to services created with `tcp()` on Linux; TLS and HTTP/2 connections, compressed
+    /// bodies and other platforms fall back to reading the file in chunks.
+    ///
+    /// By default sendfile is disabled.
+    pub fn sendfile(mut self, enabled: bool) -> Self {
+        self.sendfile = enabled;
+        self
+    }
+
+    /// Set the maximum number of headers accepted in a request head.
+    ///
+    /// Requests with more headers are answered with 431 (Request Header Fields Too Large).
+    ///
+    /// By default up to 96 headers are accepted.
+    pub fn max_headers(mut self, num: usize) -> Self {
+        self.head_limits = self.head_limits.max_headers(num);
+        self
+    }
+
+    /// Set the maximum size in bytes of a request head, including the request line.
+    ///
+    /// Requests with a larger head are answered with 431 (Request Header Fields Too Large).
+    ///
+    /// By default the limit is 128KiB.
+    pub fn max_header_size(mut self, bytes: usize) -> Self {
+        self.head_limits = self.head_limits.max_header_size(bytes);
+        self
+    }
+
+    /// Set the maximum length in bytes of a request URI.
+    ///
+    /// Requests with a longer URI are answered with 414 (URI Too Long).
+    ///
+    /// By default the limit is 128KiB.
+    pub fn max_uri_length(mut self, bytes: usize) -> Self {
+        self.head_limits = self.head_limits.max_uri_length(bytes);
+        self
+    }
+
+    /// Set all limits of HTTP/1 request heads, replacing those set with
+    /// [`max_headers`](Self::max_headers), [`max_header_size`](Self::max_header_size) and
+    /// [`max_uri_length`](Self::max_uri_length).
+    pub fn head_limits(mut self, limits: HeadLimits) -> Self {
+        self.head_limits = limits;
+        self
+    }
+
+    /// Set all settings of HTTP/2 connections, replacing those set with the `h2_*` methods.
+    pub fn h2_settings(mut self, settings: H2Settings) -> Self {
+        self.h2_settings = settings;
+        self
+    }
+
+    /// Set the maximum number of concurrent streams a client may open on an HTTP/2 connection.
+    ///
+    /// Streams over the limit are refused with `REFUSED_STREAM`.
+    ///
+    /// By default the number of streams is not limited.
+    pub fn h2_max_concurrent_streams(mut self, max: u32) -> Self {
+        self.h2_settings = self.h2_settings.max_concurrent_streams(max);
+        self
+    }
+
+    /// Set the initial window size (in octets) for HTTP/2 stream-level flow control for received
+    /// data.
+    ///
+    /// The default value is 65,535 and is good for APIs, but not for big uploads.
+    ///
+    /// # Panics
+    /// Panics if `size` is larger than 2,147,483,647.
+    pub fn h2_initial_window_size(mut self, size: u32) -> Self {
+        self.h2_settings = self.h2_settings.initial_window_size(size);
+        self
+    }
+
+    /// Set the initial window size (in octets) for HTTP/2 connection-level flow control for
+    /// received data.
+    ///
+    /// The default value is 65,535 and is good for APIs, but not for big uploads.
+    ///
+    /// # Panics
+    /// Panics if `size` is larger than 2,147,483,647.
+    pub fn h2_initial_connection_window_size(mut self, size: u32) -> Self {
+        self.h2_settings = self.h2_settings.initial_connection_window_size(size);
+        self
+    }
+
+    /// Set the largest HTTP/2 frame payload (in octets) the server is willing to receive.
+    ///
+    /// By default the max frame size is 16,384.
+    ///
+    /// # Panics
+    /// Panics if `size` is not between 16,384 and 16,777,215.
+    pub fn h2_max_frame_size(mut self, size: u32) -> Self {
+        self.h2_settings = self.h2_settings.max_frame_size(size);
+        self
+    }
+
+    /// Set the maximum size (in octets) of the header list of a received HTTP/2 request.
+    ///
+    /// By default the limit is 16MiB.
+    pub fn h2_max_header_list_size(mut self, size: u32) -> Self {
+        self.h2_settings = self.h2_settings.max_header_list_size(size);
+        self
+    }
+
+    /// Set the interval in milliseconds of HTTP/2 keep-alive pings.
+    ///
+    /// A PING frame is sent on every connection at this interval. Connections which have not
+    /// answered a ping by the time the next one is due are closed.
+    ///
+    /// To disable pings set value to 0.
+    ///
+    /// By default keep-alive pings are disabled.
+    pub fn h2_keep_alive_interval(mut self, val: u64) -> Self {
+        self.h2_settings = self.h2_settings.keep_alive_interval(val);
+        self
+    }
+
+    /// Sets a signal for shutting down HTTP/2 connections gracefully.
+    ///
+    /// Once triggered, connections send GOAWAY and close after finishing their in-flight streams.
+    /// See [`ShutdownSignal`] for details.
+    pub fn shutdown_signal(mut self, signal: ShutdownSignal) -> Self {
+        self.shutdown_signal = Some(signal);
+        self
+    }
+
+    /// Enables switching HTTP/1.1 connections to cleartext HTTP/2 with `Upgrade: h2c`.
+    ///
+    /// The upgrade request is then answered as the first HTTP/2 stream. Requests with a body are
+    /// still served over HTTP/1.1. Takes precedence over the [`upgrade`](Self::upgrade) handler
+    /// and only applies to services created with [`finish`](Self::finish). Cleartext HTTP/2 with
+    /// prior knowledge is always accepted by those services.
+    ///
+    /// After the `101 Switching Protocols` response, connections which do not send the HTTP/2
+    /// connection preface within the [client timeout](Self::client_timeout) are closed.
+    ///
+    /// The `h2c` token is not allowed over TLS, so leave this disabled for TLS services.
+    ///
+    /// By default h2c upgrades are disabled.
+    pub fn h2c_upgrade(mut self, enabled: bool) -> Self {
+        self.h2c_upgrade = enabled;
+        self
+    }
+
     /// Finish service configuration and create a HTTP Service for HTTP/1 protocol.
     pub fn h1<F, B>(self, service: F) -> H1Service<T, S, B, X, U>
     where
@@ -188,7 +372,12 @@ where
             self.client_disconnect,
             self.secure,
             self.local_addr,
-        );
+        )
+        .with_connection_stats(self.connection_stats)
+        .with_sendfile(self.sendfile)
+        .with_head_limits(self.head_limits)
+        .with_h2_settings(self.h2_settings)
+        .with_shutdown_signal(self.shutdown_signal);
 
         H1Service::with_config(cfg, service.into_factory())
             .expect(self.expect)
@@ -213,7 +402,12 @@ where
             self.client_disconnect,
             self.secure,
             self.local_addr,
-        );
+        )
+        .with_connection_stats(self.connection_stats)
+        .with_sendfile(self.sendfile)
+        .with_head_limits(self.head_limits)
+        .with_h2_settings(self.h2_settings)
+        .with_shutdown_signal(self.shutdown_signal);
 
         H2Service::with_config(cfg, service.into_factory())
             .on_connect_ext(self.on_connect_ext)
@@ -236,7 +430,13 @@ where
             self.client_disconnect,
             self.secure,
             self.local_addr,
-        );
+        )
+        .with_connection_stats(self.connection_stats)
+        .with_sendfile(self.sendfile)
+        .with_head_limits(self.head_limits)
+        .with_h2_settings(self.h2_settings)
+        .with_shutdown_signal(self.shutdown_signal)
+        .with_h2c_upgrade(self.h2c_upgrade);
 
         HttpService::with_config(cfg, service.into_factory())
             .expect(self.expect)
@@ -1,6 +1,8 @@
 use std::net::IpAddr;
 use std::time::Duration;
 
+use super::proxy::Proxy;
+
 const DEFAULT_H2_CONN_WINDOW: u32 = 1024 * 1024 * 2; // 2MB
 const DEFAULT_H2_STREAM_WINDOW: u32 = 1024 * 1024; // 1MB
 
@@ -16,6 +18,8 @@ pub(crate) struct ConnectorConfig {
     pub(crate) conn_window_size: u32,
     pub(crate) stream_window_size: u32,
     pub(crate) local_address: Option<IpAddr>,
+    pub(crate) proxies: Vec<Proxy>,
+    pub(crate) h2c: bool,
 }
 
 impl Default for ConnectorConfig {
@@ -30,6 +34,8 @@ impl Default for ConnectorConfig {
             conn_window_size: DEFAULT_H2_CONN_WINDOW,
             stream_window_size: DEFAULT_H2_STREAM_WINDOW,
             local_address: None,
+            proxies: Vec::new(),
+            h2c: false,
         }
     }
 }
@@ -19,6 +19,7 @@ use crate::{body::MessageBody, Error};
 
 use super::error::SendRequestError;
 use super::pool::Acquired;
+use super::proxy::Forward;
 use super::{h1proto, h2proto};
 
 /// Trait alias for types impl [tokio::io::AsyncRead] and [tokio::io::AsyncWrite].
@@ -31,6 +32,8 @@ pub struct H1Connection<Io: ConnectionIo> {
     io: Option<Io>,
     created: time::Instant,
     acquired: Acquired<Io>,
+    /// Set when requests are sent to a forward proxy.
+    pub(super) forward: Option<Forward>,
 }
 
 impl<Io: ConnectionIo> H1Connection<Io> {
@@ -228,6 +231,7 @@ impl<Io: ConnectionIo> ConnectionType<Io> {
             io: Some(io),
             created,
             acquired,
+            forward: None,
         })
     }
 
@@ -268,12 +272,12 @@ where
                 Connection::Tls(ConnectionType::H1(conn)) => {
                     h1proto::send_request(conn, head.into(), body).await
                 }
+                Connection::Tcp(ConnectionType::H2(conn)) => {
+                    h2proto::send_request(conn, head.into(), body).await
+                }
                 Connection::Tls(ConnectionType::H2(conn)) => {
                     h2proto::send_request(conn, head.into(), body).await
                 }
-                _ => unreachable!(
-                    "Plain Tcp connection can be used only in Http1 protocol"
-                ),
             }
         })
     }
@@ -296,14 +300,13 @@ where
                     let (head, framed) = h1proto::open_tunnel(self, head.into()).await?;
                     Ok((head, framed))
                 }
-                Connection::Tls(ConnectionType::H2(mut conn)) => {
+                Connection::Tcp(ConnectionType::H2(mut conn)) => {
                     conn.release();
                     Err(SendRequestError::TunnelNotSupported)
                 }
-                Connection::Tcp(ConnectionType::H2(_)) => {
-                    unreachable!(
-                        "Plain Tcp connection can be used only in Http1 protocol"
-                    )
+                Connection::Tls(ConnectionType::H2(mut conn)) => {
+                    conn.release();
+                    Err(SendRequestError::TunnelNotSupported)
                 }
             }
         })
@@ -22,9 +22,10 @@ use http::Uri;
 use pin_project::pin_project;
 
 use super::config::ConnectorConfig;
-use super::connection::{Connection, ConnectionIo};
+use super::connection::{Connection, ConnectionIo, ConnectionType};
 use super::error::ConnectError;
 use super::pool::ConnectionPool;
+use super::proxy::{self, Forward, Proxy};
 use super::Connect;
 use super::Protocol;
 
@@ -212,6 +213,18 @@ where
         self
     }
 
+    /// Use cleartext HTTP/2 (h2c) with prior knowledge for `http://` requests.
+    ///
+    /// Plaintext connections then start with the HTTP/2 connection preface instead of HTTP/1.1,
+    /// so the server must support h2c, as is common for service meshes and gRPC backends. Not
+    /// used for `ws://` WebSocket connections or requests forwarded by an HTTP proxy.
+    ///
+    /// By default h2c is disabled.
+    pub fn h2c(mut self, enabled: bool) -> Self {
+        self.config.h2c = enabled;
+        self
+    }
+
     /// Set total number of simultaneous connections per type of scheme.
     ///
     /// If limit is 0, the connector has no limit.
@@ -261,19 +274,44 @@ where
         self
     }
 
+    /// Route connections through a forward proxy.
+    ///
+    /// May be called multiple times; connections use the first proxy matching their origin.
+    /// The connection timeout includes the proxy handshake.
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.config.proxies.push(proxy);
+        self
+    }
+
+    /// Route connections through the proxies set in the `HTTP_PROXY`, `HTTPS_PROXY` and
+    /// `NO_PROXY` environment variables, or their lowercase variants.
+    ///
+    /// Invalid proxy URIs are logged and ignored.
+    pub fn proxy_from_env(mut self) -> Self {
+        self.config.proxies.extend(proxy::from_env());
+        self
+    }
+
     /// Finish configuration process and create connector service.
     /// The Connector builder always concludes by calling `finish()` last in
     /// its combinator chain.
     pub fn finish(self) -> ConnectorService<S, Io> {
         let local_address = self.config.local_address;
         let timeout = self.config.timeout;
+        let proxies: Rc<[Proxy]> = self.config.proxies.clone().into();
 
-        let tcp_service_inner =
-            TcpConnectorInnerService::new(self.connector, timeout, local_address);
+        let tcp_service_inner = TcpConnectorInnerService::new(
+            self.connector,
+            timeout,
+            local_address,
+            proxies.clone(),
+        );
 
         #[allow(clippy::redundant_clone)]
         let tcp_service = TcpConnectorService {
             service: tcp_service_inner.clone(),
+            h2c: self.config.h2c,
+            proxies: proxies.clone(),
         };
 
         let tls_service = match self.ssl {
@@ -353,7 +391,11 @@ where
         let tls_pool = tls_service
             .map(move |tls_service| ConnectionPool::new(tls_service, tls_config));
 
-        ConnectorServicePriv { tcp_pool, tls_pool }
+        ConnectorServicePriv {
+            tcp_pool,
+            tls_pool,
+            proxies,
+        }
     }
 }
 
@@ -361,6 +403,8 @@ where
 #[derive(Clone)]
 pub struct TcpConnectorService<S: Clone> {
     service: S,
+    h2c: bool,
+    proxies: Rc<[Proxy]>,
 }
 
 impl<S, Io> Service<Connect> for TcpConnectorService<S>
@@ -376,8 +420,20 @@ where
     actix_service::forward_ready!(service);
 
     fn call(&self, req: Connect) -> Self::Future {
+        let h2c = self.h2c
+            && req.uri.scheme_str() == Some("http")
+            && !proxy::find(&self.proxies, &req.uri)
+                .map_or(false, |proxy| proxy.forwards(&req.uri));
+
+        let protocol = if h2c {
+            Protocol::Http2
+        } else {
+            Protocol::Http1
+        };
+
         TcpConnectorFuture {
             fut: self.service.call(req),
+            protocol,
         }
     }
 }
@@ -386,6 +442,7 @@ where
 pub struct TcpConnectorFuture<Fut> {
     #[pin]
     fut: Fut,
+    protocol: Protocol,
 }
 
 impl<Fut, Io> Future for TcpConnectorFuture<Fut>
@@ -395,10 +452,11 @@ where
     type Output = Result<(Io, Protocol), ConnectError>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        self.project()
-            .fut
+        let this = self.project();
+        let protocol = *this.protocol;
+        this.fut
             .poll(cx)
-            .map_ok(|res| (res.into_parts().0, Protocol::Http1))
+            .map_ok(|res| (res.into_parts().0, protocol))
     }
 }
 
@@ -505,13 +563,14 @@ where
     }
 }
 
-/// service for establish tcp connection.
+/// service for establish tcp connection, through a proxy if one matches the origin.
 /// operation is canceled when timeout limit reached.
 #[derive(Clone)]
 pub struct TcpConnectorInnerService<S: Clone> {
     service: S,
     timeout: Duration,
     local_address: Option<std::net::IpAddr>,
+    proxies: Rc<[Proxy]>,
 }
 
 impl<S: Clone> TcpConnectorInnerService<S> {
@@ -519,11 +578,13 @@ impl<S: Clone> TcpConnectorInnerService<S> {
         service: S,
         timeout: Duration,
         local_address: Option<std::net::IpAddr>,
+        proxies: Rc<[Proxy]>,
     ) -> Self {
         Self {
             service,
             timeout,
             local_address,
+            proxies,
         }
     }
 }
@@ -536,45 +597,83 @@ where
             Error = TcpConnectError,
         > + Clone
         + 'static,
+    Io: ConnectionIo,
 {
     type Response = S::Response;
     type Error = ConnectError;
-    type Future = TcpConnectorInnerFuture<S::Future>;
+    type Future = TcpConnectorInnerFuture<S::Future, Io>;
 
     actix_service::forward_ready!(service);
 
     fn call(&self, req: Connect) -> Self::Future {
-        let mut req = TcpConnect::new(req.uri).set_addr(req.addr);
+        let (mut connect, proxy) = match proxy::find(&self.proxies, &req.uri) {
+            Some(proxy) => (
+                TcpConnect::new(proxy.uri().clone()),
+                Some((proxy.clone(), req.uri)),
+            ),
+            None => (TcpConnect::new(req.uri).set_addr(req.addr), None),
+        };
 
         if let Some(local_addr) = self.local_address {
-            req = req.set_local_addr(local_addr);
+            connect = connect.set_local_addr(local_addr);
         }
 
         TcpConnectorInnerFuture {
-            fut: self.service.call(req),
+            fut: self.service.call(connect),
+            proxy,
+            handshake: None,
             timeout: sleep(self.timeout),
         }
     }
 }
 
 #[pin_project]
-pub struct TcpConnectorInnerFuture<Fut> {
+pub struct TcpConnectorInnerFuture<Fut, Io> {
     #[pin]
     fut: Fut,
+    /// Proxy connected to and the origin it connects to.
+    proxy: Option<(Proxy, Uri)>,
+    handshake:
+        Option<LocalBoxFuture<'static, Result<TcpConnection<Uri, Io>, ConnectError>>>,
     #[pin]
     timeout: Sleep,
 }
 
-impl<Fut, Io> Future for TcpConnectorInnerFuture<Fut>
+impl<Fut, Io> Future for TcpConnectorInnerFuture<Fut, Io>
 where
     Fut: Future<Output = Result<TcpConnection<Uri, Io>, TcpConnectError>>,
+    Io: ConnectionIo,
 {
     type Output = Result<TcpConnection<Uri, Io>, ConnectError>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self.project();
-        match this.fut.poll(cx) {
-            Poll::Ready(res) => Poll::Ready(res.map_err(ConnectError::from)),
+
+        if this.handshake.is_none() {
+            match this.fut.poll(cx) {
+                Poll::Ready(Ok(conn)) => match this.proxy.take() {
+                    None => return Poll::Ready(Ok(conn)),
+                    Some((proxy, uri)) => {
+                        let io = conn.into_parts().0;
+                        *this.handshake = Some(Box::pin(async move {
+                            let io = if proxy.forwards(&uri) {
+                                io
+                            } else {
+                                proxy.tunnel(io, &uri).await?
+                            };
+                            Ok(TcpConnection::new(io, uri))
+                        }));
+                    }
+                },
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err.into())),
+                Poll::Pending => {
+                    return this.timeout.poll(cx).map(|_| Err(ConnectError::Timeout))
+                }
+            }
+        }
+
+        match this.handshake.as_mut().unwrap().as_mut().poll(cx) {
+            Poll::Ready(res) => Poll::Ready(res),
             Poll::Pending => this.timeout.poll(cx).map(|_| Err(ConnectError::Timeout)),
         }
     }
@@ -607,6 +706,7 @@ where
 {
     tcp_pool: ConnectionPool<S1, Io1>,
     tls_pool: Option<ConnectionPool<S2, Io2>>,
+    proxies: Rc<[Proxy]>,
 }
 
 impl<S1, S2, Io1, Io2> Service<Connect> for ConnectorServicePriv<S1, S2, Io1, Io2>
@@ -638,7 +738,15 @@ where
                 None => ConnectorServiceFuture::SslIsNotSupported,
                 Some(ref pool) => ConnectorServiceFuture::Tls(pool.call(req)),
             },
-            _ => ConnectorServiceFuture::Tcp(self.tcp_pool.call(req)),
+            _ => {
+                let forward = proxy::find(&self.proxies, &req.uri)
+                    .filter(|proxy| proxy.forwards(&req.uri))
+                    .map(|proxy| Forward {
+                        authorization: proxy.authorization(),
+                    });
+
+                ConnectorServiceFuture::Tcp(self.tcp_pool.call(req), forward)
+            }
         }
     }
 }
@@ -655,7 +763,10 @@ where
     Io1: ConnectionIo,
     Io2: ConnectionIo,
 {
-    Tcp(#[pin] <ConnectionPool<S1, Io1> as Service<Connect>>::Future),
+    Tcp(
+        #[pin] <ConnectionPool<S1, Io1> as Service<Connect>>::Future,
+        Option<Forward>,
+    ),
     Tls(#[pin] <ConnectionPool<S2, Io2> as Service<Connect>>::Future),
     SslIsNotSupported,
 }
@@ -675,7 +786,14 @@ where
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         match self.project() {
-            ConnectorServiceProj::Tcp(fut) => fut.poll(cx).map_ok(Connection::Tcp),
+            ConnectorServiceProj::Tcp(fut, forward) => {
+                fut.poll(cx).map_ok(|mut conn| {
+                    if let ConnectionType::H1(ref mut conn) = conn {
+                        conn.forward = forward.take();
+                    }
+                    Connection::Tcp(conn)
+                })
+            }
             ConnectorServiceProj::Tls(fut) => fut.poll(cx).map_ok(Connection::Tls),
             ConnectorServiceProj::SslIsNotSupported => {
                 Poll::Ready(Err(ConnectError::SslIsNotSupported))
@@ -48,6 +48,11 @@ pub enum ConnectError {
     /// Connection io error
     #[display(fmt = "{}", _0)]
     Io(io::Error),
+
+    /// Proxy refused or failed to open a connection
+    #[display(fmt = "Proxy error: {}", _0)]
+    #[from(ignore)]
+    Proxy(String),
 }
 
 impl std::error::Error for ConnectError {}
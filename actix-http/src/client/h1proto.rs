@@ -13,7 +13,7 @@ use futures_util::SinkExt as _;
 
 use crate::h1;
 use crate::http::{
-    header::{HeaderMap, IntoHeaderValue, EXPECT, HOST},
+    header::{HeaderMap, IntoHeaderValue, EXPECT, HOST, PROXY_AUTHORIZATION},
     StatusCode,
 };
 use crate::message::{RequestHeadType, ResponseHead};
@@ -25,7 +25,7 @@ use super::error::{ConnectError, SendRequestError};
 use crate::body::{BodySize, MessageBody};
 
 pub(crate) async fn send_request<Io, B>(
-    io: H1Connection<Io>,
+    mut io: H1Connection<Io>,
     mut head: RequestHeadType,
     body: B,
 ) -> Result<(ResponseHead, Payload), SendRequestError>
@@ -61,6 +61,16 @@ where
         }
     }
 
+    // forward proxies take the whole URI and their credentials with each request
+    if let Some(forward) = io.forward.take() {
+        let mut owned = head.into_owned();
+        owned.set_full_target();
+        if let Some(value) = forward.authorization {
+            owned.headers.insert(PROXY_AUTHORIZATION, value);
+        }
+        head = RequestHeadType::Owned(owned);
+    }
+
     // create Framed and prepare sending request
     let mut framed = Framed::new(io, h1::ClientCodec::default());
 
@@ -11,6 +11,10 @@ use actix_rt::{
 use bytes::BytesMut;
 use time::OffsetDateTime;
 
+use crate::h1::decoder::{MAX_BUFFER_SIZE, MAX_HEADERS};
+use crate::shutdown::ShutdownSignal;
+use crate::stats::ConnectionStats;
+
 /// "Sun, 06 Nov 1994 08:49:37 GMT".len()
 const DATE_VALUE_LENGTH: usize = 29;
 
@@ -41,6 +45,140 @@ impl From<Option<usize>> for KeepAlive {
     }
 }
 
+/// Limits of HTTP/1 request heads.
+///
+/// Set with [`HttpServiceBuilder::head_limits`](crate::HttpServiceBuilder::head_limits).
+#[derive(Debug, Clone, Copy)]
+pub struct HeadLimits {
+    pub(crate) max_headers: usize,
+    pub(crate) max_header_size: usize,
+    pub(crate) max_uri_length: usize,
+}
+
+impl HeadLimits {
+    /// Sets the maximum number of headers accepted in a request head. Defaults to 96.
+    pub fn max_headers(mut self, num: usize) -> Self {
+        self.max_headers = num;
+        self
+    }
+
+    /// Sets the maximum size in bytes of a request head. Defaults to 128KiB.
+    pub fn max_header_size(mut self, bytes: usize) -> Self {
+        self.max_header_size = bytes;
+        self
+    }
+
+    /// Sets the maximum length in bytes of a request URI. Defaults to 128KiB.
+    pub fn max_uri_length(mut self, bytes: usize) -> Self {
+        self.max_uri_length = bytes;
+        self
+    }
+}
+
+impl Default for HeadLimits {
+    fn default() -> Self {
+        Self {
+            max_headers: MAX_HEADERS,
+            max_header_size: MAX_BUFFER_SIZE,
+            // only bounded by the head size
+            max_uri_length: MAX_BUFFER_SIZE,
+        }
+    }
+}
+
+/// Largest HTTP/2 flow control window, 2^31-1 (RFC 7540 §6.9.1).
+const MAX_WINDOW_SIZE: u32 = (1 << 31) - 1;
+
+/// Settings of HTTP/2 connections.
+///
+/// Set with [`HttpServiceBuilder::h2_settings`](crate::HttpServiceBuilder::h2_settings).
+#[derive(Debug, Clone, Copy)]
+pub struct H2Settings {
+    pub(crate) max_concurrent_streams: u32,
+    pub(crate) initial_window_size: u32,
+    pub(crate) initial_connection_window_size: u32,
+    pub(crate) max_frame_size: u32,
+    pub(crate) max_header_list_size: u32,
+    /// Interval of keep-alive pings in milliseconds, or 0 when disabled.
+    pub(crate) keep_alive_interval: u64,
+}
+
+impl H2Settings {
+    /// Sets the maximum number of concurrent streams per connection. Not limited by default.
+    pub fn max_concurrent_streams(mut self, max: u32) -> Self {
+        self.max_concurrent_streams = max;
+        self
+    }
+
+    /// Sets the initial stream-level flow control window size. Defaults to 65,535.
+    ///
+    /// # Panics
+    /// Panics if `size` is larger than 2,147,483,647.
+    pub fn initial_window_size(mut self, size: u32) -> Self {
+        assert!(
+            size <= MAX_WINDOW_SIZE,
+            "HTTP/2 window size must be at most 2,147,483,647"
+        );
+
+        self.initial_window_size = size;
+        self
+    }
+
+    /// Sets the initial connection-level flow control window size. Defaults to 65,535.
+    ///
+    /// # Panics
+    /// Panics if `size` is larger than 2,147,483,647.
+    pub fn initial_connection_window_size(mut self, size: u32) -> Self {
+        assert!(
+            size <= MAX_WINDOW_SIZE,
+            "HTTP/2 window size must be at most 2,147,483,647"
+        );
+
+        self.initial_connection_window_size = size;
+        self
+    }
+
+    /// Sets the largest frame payload the server is willing to receive. Defaults to 16,384.
+    ///
+    /// # Panics
+    /// Panics if `size` is not between 16,384 and 16,777,215.
+    pub fn max_frame_size(mut self, size: u32) -> Self {
+        assert!(
+            (16_384..=16_777_215).contains(&size),
+            "HTTP/2 max frame size must be between 16,384 and 16,777,215"
+        );
+
+        self.max_frame_size = size;
+        self
+    }
+
+    /// Sets the maximum size of the header list of a request. Defaults to 16MiB.
+    pub fn max_header_list_size(mut self, size: u32) -> Self {
+        self.max_header_list_size = size;
+        self
+    }
+
+    /// Sets the interval in milliseconds of keep-alive pings, or 0 to disable them, which is
+    /// the default.
+    pub fn keep_alive_interval(mut self, val: u64) -> Self {
+        self.keep_alive_interval = val;
+        self
+    }
+}
+
+impl Default for H2Settings {
+    fn default() -> Self {
+        Self {
+            max_concurrent_streams: u32::MAX,
+            initial_window_size: 65_535,
+            initial_connection_window_size: 65_535,
+            max_frame_size: 16_384,
+            max_header_list_size: 16 << 20,
+            keep_alive_interval: 0,
+        }
+    }
+}
+
 /// Http service configuration
 pub struct ServiceConfig(Rc<Inner>);
 
@@ -52,6 +190,12 @@ struct Inner {
     secure: bool,
     local_addr: Option<std::net::SocketAddr>,
     date_service: DateService,
+    connection_stats: Option<ConnectionStats>,
+    sendfile: bool,
+    head_limits: HeadLimits,
+    h2_settings: H2Settings,
+    shutdown_signal: Option<ShutdownSignal>,
+    h2c_upgrade: bool,
 }
 
 impl Clone for ServiceConfig {
@@ -94,9 +238,69 @@ impl ServiceConfig {
             secure,
             local_addr,
             date_service: DateService::new(),
+            connection_stats: None,
+            sendfile: false,
+            head_limits: HeadLimits::default(),
+            h2_settings: H2Settings::default(),
+            shutdown_signal: None,
+            h2c_upgrade: false,
         }))
     }
 
+    /// Sets the counters to update as connections are opened and reused.
+    pub(crate) fn with_connection_stats(
+        mut self,
+        stats: Option<ConnectionStats>,
+    ) -> Self {
+        Rc::get_mut(&mut self.0)
+            .expect("ServiceConfig is already shared")
+            .connection_stats = stats;
+        self
+    }
+
+    /// Sets whether plaintext HTTP/1.1 connections may send file bodies with `sendfile(2)`.
+    pub(crate) fn with_sendfile(mut self, sendfile: bool) -> Self {
+        Rc::get_mut(&mut self.0)
+            .expect("ServiceConfig is already shared")
+            .sendfile = sendfile;
+        self
+    }
+
+    /// Sets the limits of HTTP/1 request heads.
+    pub(crate) fn with_head_limits(mut self, head_limits: HeadLimits) -> Self {
+        Rc::get_mut(&mut self.0)
+            .expect("ServiceConfig is already shared")
+            .head_limits = head_limits;
+        self
+    }
+
+    /// Sets the settings of HTTP/2 connections.
+    pub(crate) fn with_h2_settings(mut self, h2_settings: H2Settings) -> Self {
+        Rc::get_mut(&mut self.0)
+            .expect("ServiceConfig is already shared")
+            .h2_settings = h2_settings;
+        self
+    }
+
+    /// Sets the signal which makes HTTP/2 connections shut down gracefully.
+    pub(crate) fn with_shutdown_signal(
+        mut self,
+        signal: Option<ShutdownSignal>,
+    ) -> Self {
+        Rc::get_mut(&mut self.0)
+            .expect("ServiceConfig is already shared")
+            .shutdown_signal = signal;
+        self
+    }
+
+    /// Sets whether HTTP/1.1 requests may switch the connection to HTTP/2 with `Upgrade: h2c`.
+    pub(crate) fn with_h2c_upgrade(mut self, enabled: bool) -> Self {
+        Rc::get_mut(&mut self.0)
+            .expect("ServiceConfig is already shared")
+            .h2c_upgrade = enabled;
+        self
+    }
+
     /// Returns true if connection is secure (HTTPS)
     #[inline]
     pub fn secure(&self) -> bool {
@@ -111,6 +315,58 @@ impl ServiceConfig {
         self.0.local_addr
     }
 
+    /// Returns the connection counters, if configured.
+    #[inline]
+    pub fn connection_stats(&self) -> Option<&ConnectionStats> {
+        self.0.connection_stats.as_ref()
+    }
+
+    /// Returns true if plaintext HTTP/1.1 connections may send file bodies with `sendfile(2)`.
+    #[inline]
+    pub fn sendfile(&self) -> bool {
+        self.0.sendfile
+    }
+
+    /// Returns the maximum number of headers of an HTTP/1 request.
+    #[inline]
+    pub fn max_headers(&self) -> usize {
+        self.0.head_limits.max_headers
+    }
+
+    /// Returns the maximum size in bytes of an HTTP/1 request line and headers.
+    #[inline]
+    pub fn max_header_size(&self) -> usize {
+        self.0.head_limits.max_header_size
+    }
+
+    /// Returns the maximum length in bytes of an HTTP/1 request target.
+    #[inline]
+    pub fn max_uri_length(&self) -> usize {
+        self.0.head_limits.max_uri_length
+    }
+
+    #[inline]
+    pub(crate) fn head_limits(&self) -> &HeadLimits {
+        &self.0.head_limits
+    }
+
+    #[inline]
+    pub(crate) fn h2_settings(&self) -> &H2Settings {
+        &self.0.h2_settings
+    }
+
+    /// Returns the graceful shutdown signal, if configured.
+    #[inline]
+    pub fn shutdown_signal(&self) -> Option<&ShutdownSignal> {
+        self.0.shutdown_signal.as_ref()
+    }
+
+    /// Returns true if HTTP/1.1 requests may switch the connection to HTTP/2 with `Upgrade: h2c`.
+    #[inline]
+    pub fn h2c_upgrade(&self) -> bool {
+        self.0.h2c_upgrade
+    }
+
     /// Keep alive duration if configured.
     #[inline]
     pub fn keep_alive(&self) -> Option<Duration> {
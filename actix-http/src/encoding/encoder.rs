@@ -24,9 +24,9 @@ use flate2::write::{GzEncoder, ZlibEncoder};
 use zstd::stream::write::Encoder as ZstdEncoder;
 
 use crate::{
-    body::{Body, BodySize, BoxAnyBody, MessageBody, ResponseBody},
+    body::{Body, BodySize, BoxAnyBody, FileRegion, MessageBody, ResponseBody},
     http::{
-        header::{ContentEncoding, CONTENT_ENCODING},
+        header::{ContentEncoding, HeaderMap, CONTENT_ENCODING},
         HeaderValue, StatusCode,
     },
     Error, ResponseHead,
@@ -37,6 +37,56 @@ use crate::error::BlockingError;
 
 const MAX_CHUNK_SIZE_ENCODE_IN_PLACE: usize = 1024;
 
+/// Compression levels used by an [`Encoder`] for each algorithm.
+///
+/// Levels are clamped to the range supported by the algorithm: 0–9 for gzip and deflate, 0–11
+/// for brotli and 1–22 for zstd. By default gzip and deflate use level 1 and brotli and zstd use
+/// level 3, favouring speed over size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionLevels {
+    gzip: u32,
+    deflate: u32,
+    brotli: u32,
+    zstd: i32,
+}
+
+impl CompressionLevels {
+    /// Sets the gzip compression level.
+    pub fn gzip(mut self, level: u32) -> Self {
+        self.gzip = level.min(9);
+        self
+    }
+
+    /// Sets the deflate compression level.
+    pub fn deflate(mut self, level: u32) -> Self {
+        self.deflate = level.min(9);
+        self
+    }
+
+    /// Sets the brotli compression level.
+    pub fn brotli(mut self, level: u32) -> Self {
+        self.brotli = level.min(11);
+        self
+    }
+
+    /// Sets the zstd compression level.
+    pub fn zstd(mut self, level: i32) -> Self {
+        self.zstd = level.max(1).min(22);
+        self
+    }
+}
+
+impl Default for CompressionLevels {
+    fn default() -> Self {
+        Self {
+            gzip: 1,
+            deflate: 1,
+            brotli: 3,
+            zstd: 3,
+        }
+    }
+}
+
 #[pin_project]
 pub struct Encoder<B> {
     eof: bool,
@@ -51,6 +101,16 @@ impl<B: MessageBody> Encoder<B> {
         encoding: ContentEncoding,
         head: &mut ResponseHead,
         body: ResponseBody<B>,
+    ) -> ResponseBody<Encoder<B>> {
+        Self::response_with_levels(encoding, head, body, CompressionLevels::default())
+    }
+
+    /// Wraps a response body with an encoder using the given compression levels.
+    pub fn response_with_levels(
+        encoding: ContentEncoding,
+        head: &mut ResponseHead,
+        body: ResponseBody<B>,
+        levels: CompressionLevels,
     ) -> ResponseBody<Encoder<B>> {
         let can_encode = !(head.headers().contains_key(&CONTENT_ENCODING)
             || head.status == StatusCode::SWITCHING_PROTOCOLS
@@ -76,7 +136,7 @@ impl<B: MessageBody> Encoder<B> {
 
         if can_encode {
             // Modify response body only if encoder is not None
-            if let Some(enc) = ContentEncoder::encoder(encoding) {
+            if let Some(enc) = ContentEncoder::encoder(encoding, levels) {
                 update_head(encoding, head);
                 head.no_chunking(false);
                 return ResponseBody::Body(Encoder {
@@ -146,6 +206,30 @@ where
             }
         }
     }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        match self.project() {
+            EncoderBodyProj::Bytes(_) => Poll::Ready(Ok(None)),
+            EncoderBodyProj::Stream(b) => {
+                b.poll_trailers(cx).map_err(EncoderError::Body)
+            }
+            EncoderBodyProj::BoxedStream(ref mut b) => b
+                .as_pin_mut()
+                .poll_trailers(cx)
+                .map_err(EncoderError::Boxed),
+        }
+    }
+
+    fn take_file_region(self: Pin<&mut Self>) -> Option<FileRegion> {
+        match self.project() {
+            EncoderBodyProj::Bytes(_) => None,
+            EncoderBodyProj::Stream(b) => b.take_file_region(),
+            EncoderBodyProj::BoxedStream(ref mut b) => b.as_pin_mut().take_file_region(),
+        }
+    }
 }
 
 impl<B> MessageBody for Encoder<B>
@@ -229,6 +313,24 @@ where
             }
         }
     }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        self.project().body.poll_trailers(cx)
+    }
+
+    fn take_file_region(self: Pin<&mut Self>) -> Option<FileRegion> {
+        let this = self.project();
+
+        // encoded bodies are not sent as they are stored
+        if this.encoder.is_none() && this.fut.is_none() {
+            this.body.take_file_region()
+        } else {
+            None
+        }
+    }
 }
 
 fn update_head(encoding: ContentEncoding, head: &mut ResponseHead) {
@@ -252,25 +354,27 @@ enum ContentEncoder {
 }
 
 impl ContentEncoder {
-    fn encoder(encoding: ContentEncoding) -> Option<Self> {
+    #[allow(unused_variables)]
+    fn encoder(encoding: ContentEncoding, levels: CompressionLevels) -> Option<Self> {
         match encoding {
             #[cfg(feature = "compress-gzip")]
             ContentEncoding::Deflate => Some(ContentEncoder::Deflate(ZlibEncoder::new(
                 Writer::new(),
-                flate2::Compression::fast(),
+                flate2::Compression::new(levels.deflate),
             ))),
             #[cfg(feature = "compress-gzip")]
             ContentEncoding::Gzip => Some(ContentEncoder::Gzip(GzEncoder::new(
                 Writer::new(),
-                flate2::Compression::fast(),
+                flate2::Compression::new(levels.gzip),
             ))),
             #[cfg(feature = "compress-brotli")]
-            ContentEncoding::Br => {
-                Some(ContentEncoder::Br(BrotliEncoder::new(Writer::new(), 3)))
-            }
+            ContentEncoding::Br => Some(ContentEncoder::Br(BrotliEncoder::new(
+                Writer::new(),
+                levels.brotli,
+            ))),
             #[cfg(feature = "compress-zstd")]
             ContentEncoding::Zstd => {
-                let encoder = ZstdEncoder::new(Writer::new(), 3).ok()?;
+                let encoder = ZstdEncoder::new(Writer::new(), levels.zstd).ok()?;
                 Some(ContentEncoder::Zstd(encoder))
             }
             _ => None,
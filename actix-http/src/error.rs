@@ -179,6 +179,10 @@ pub enum ParseError {
     #[display(fmt = "Message head is too large")]
     TooLarge,
 
+    /// A request target is longer than the configured limit.
+    #[display(fmt = "Request URI is too long")]
+    UriTooLong,
+
     /// A message reached EOF, but is not complete.
     #[display(fmt = "Message is incomplete")]
     Incomplete,
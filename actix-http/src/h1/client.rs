@@ -59,10 +59,12 @@ impl ClientCodec {
         } else {
             Flags::empty()
         };
+        let decoder = decoder::MessageDecoder::new(*config.head_limits());
+
         ClientCodec {
             inner: ClientCodecInner {
                 config,
-                decoder: decoder::MessageDecoder::default(),
+                decoder,
                 payload: None,
                 version: Version::HTTP_11,
                 ctype: ConnectionType::Close,
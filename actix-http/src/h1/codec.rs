@@ -11,6 +11,7 @@ use super::{Message, MessageType};
 use crate::body::BodySize;
 use crate::config::ServiceConfig;
 use crate::error::ParseError;
+use crate::header::HeaderMap;
 use crate::message::ConnectionType;
 use crate::request::Request;
 use crate::response::Response;
@@ -28,6 +29,7 @@ pub struct Codec {
     config: ServiceConfig,
     decoder: decoder::MessageDecoder<Request>,
     payload: Option<PayloadDecoder>,
+    trailers: Option<HeaderMap>,
     version: Version,
     ctype: ConnectionType,
 
@@ -59,11 +61,14 @@ impl Codec {
             Flags::empty()
         };
 
+        let decoder = decoder::MessageDecoder::new(*config.head_limits());
+
         Codec {
             config,
             flags,
-            decoder: decoder::MessageDecoder::default(),
+            decoder,
             payload: None,
+            trailers: None,
             version: Version::HTTP_11,
             ctype: ConnectionType::Close,
             encoder: encoder::MessageEncoder::default(),
@@ -104,6 +109,34 @@ impl Codec {
     pub fn config(&self) -> &ServiceConfig {
         &self.config
     }
+
+    /// Returns the number of response body bytes left to write when a `Content-Length` is set.
+    ///
+    /// Returns `Some(0)` for responses to `HEAD` requests.
+    pub(crate) fn payload_remaining(&self) -> Option<u64> {
+        self.encoder.te.remaining()
+    }
+
+    /// Records that `len` response body bytes were written directly to the IO stream.
+    pub(crate) fn payload_written(&mut self, len: u64) {
+        self.encoder.te.advance(len)
+    }
+
+    /// Take trailer fields of the last fully decoded chunked request payload.
+    pub(crate) fn take_trailers(&mut self) -> Option<HeaderMap> {
+        self.trailers.take()
+    }
+
+    /// Encode end of the response payload followed by trailer fields.
+    ///
+    /// Trailers are only written for chunked responses.
+    pub(crate) fn encode_trailers(
+        &mut self,
+        trailers: &HeaderMap,
+        dst: &mut BytesMut,
+    ) -> io::Result<()> {
+        self.encoder.encode_trailers(trailers, dst)
+    }
 }
 
 impl Decoder for Codec {
@@ -115,7 +148,8 @@ impl Decoder for Codec {
             Ok(match payload.decode(src)? {
                 Some(PayloadItem::Chunk(chunk)) => Some(Message::Chunk(Some(chunk))),
                 Some(PayloadItem::Eof) => {
-                    self.payload.take();
+                    self.trailers =
+                        self.payload.take().and_then(|mut pl| pl.take_trailers());
                     Some(Message::Chunk(None))
                 }
                 None => None,
@@ -8,17 +8,22 @@ use bytes::{Buf, Bytes, BytesMut};
 use http::header::{HeaderName, HeaderValue};
 use http::{header, Method, StatusCode, Uri, Version};
 use log::{debug, error, trace};
+use smallvec::SmallVec;
 
+use crate::config::HeadLimits;
 use crate::error::ParseError;
 use crate::header::HeaderMap;
 use crate::message::{ConnectionType, ResponseHead};
 use crate::request::Request;
 
 pub(crate) const MAX_BUFFER_SIZE: usize = 131_072;
-const MAX_HEADERS: usize = 96;
+pub(crate) const MAX_HEADERS: usize = 96;
 
 /// Incoming message decoder
-pub(crate) struct MessageDecoder<T: MessageType>(PhantomData<T>);
+pub(crate) struct MessageDecoder<T: MessageType> {
+    limits: HeadLimits,
+    _phantom: PhantomData<T>,
+}
 
 #[derive(Debug)]
 /// Incoming request type
@@ -30,7 +35,16 @@ pub(crate) enum PayloadType {
 
 impl<T: MessageType> Default for MessageDecoder<T> {
     fn default() -> Self {
-        MessageDecoder(PhantomData)
+        MessageDecoder::new(HeadLimits::default())
+    }
+}
+
+impl<T: MessageType> MessageDecoder<T> {
+    pub(crate) fn new(limits: HeadLimits) -> Self {
+        MessageDecoder {
+            limits,
+            _phantom: PhantomData,
+        }
     }
 }
 
@@ -39,7 +53,7 @@ impl<T: MessageType> Decoder for MessageDecoder<T> {
     type Error = ParseError;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        T::decode(src)
+        T::decode(src, &self.limits)
     }
 }
 
@@ -56,7 +70,10 @@ pub(crate) trait MessageType: Sized {
 
     fn headers_mut(&mut self) -> &mut HeaderMap;
 
-    fn decode(src: &mut BytesMut) -> Result<Option<(Self, PayloadType)>, ParseError>;
+    fn decode(
+        src: &mut BytesMut,
+        limits: &HeadLimits,
+    ) -> Result<Option<(Self, PayloadType)>, ParseError>;
 
     fn set_headers(
         &mut self,
@@ -182,18 +199,33 @@ impl MessageType for Request {
         &mut self.head_mut().headers
     }
 
-    fn decode(src: &mut BytesMut) -> Result<Option<(Self, PayloadType)>, ParseError> {
-        let mut headers: [HeaderIndex; MAX_HEADERS] = EMPTY_HEADER_INDEX_ARRAY;
+    fn decode(
+        src: &mut BytesMut,
+        limits: &HeadLimits,
+    ) -> Result<Option<(Self, PayloadType)>, ParseError> {
+        let mut headers = header_indices(limits);
 
         let (len, method, uri, ver, h_len) = {
-            let mut parsed: [httparse::Header<'_>; MAX_HEADERS] = EMPTY_HEADER_ARRAY;
+            let mut parsed = parsed_headers(limits);
 
             let mut req = httparse::Request::new(&mut parsed);
             match req.parse(src)? {
                 httparse::Status::Complete(len) => {
+                    if len > limits.max_header_size {
+                        trace!("request head is over the size limit");
+                        return Err(ParseError::TooLarge);
+                    }
+
                     let method = Method::from_bytes(req.method.unwrap().as_bytes())
                         .map_err(|_| ParseError::Method)?;
-                    let uri = Uri::try_from(req.path.unwrap())?;
+
+                    let path = req.path.unwrap();
+                    if path.len() > limits.max_uri_length {
+                        trace!("request target is over the length limit");
+                        return Err(ParseError::UriTooLong);
+                    }
+
+                    let uri = Uri::try_from(path)?;
                     let version = if req.version.unwrap() == 1 {
                         Version::HTTP_11
                     } else {
@@ -204,8 +236,13 @@ impl MessageType for Request {
                     (len, method, uri, version, req.headers.len())
                 }
                 httparse::Status::Partial => {
-                    return if src.len() >= MAX_BUFFER_SIZE {
-                        trace!("MAX_BUFFER_SIZE unprocessed data reached, closing");
+                    return if src.len() > limits.max_uri_length
+                        && partial_uri_length(src) > limits.max_uri_length
+                    {
+                        trace!("request target is over the length limit, closing");
+                        Err(ParseError::UriTooLong)
+                    } else if src.len() >= limits.max_header_size {
+                        trace!("request head is over the size limit, closing");
                         Err(ParseError::TooLarge)
                     } else {
                         // Return None to notify more read are needed for parsing request
@@ -258,15 +295,23 @@ impl MessageType for ResponseHead {
         &mut self.headers
     }
 
-    fn decode(src: &mut BytesMut) -> Result<Option<(Self, PayloadType)>, ParseError> {
-        let mut headers: [HeaderIndex; MAX_HEADERS] = EMPTY_HEADER_INDEX_ARRAY;
+    fn decode(
+        src: &mut BytesMut,
+        limits: &HeadLimits,
+    ) -> Result<Option<(Self, PayloadType)>, ParseError> {
+        let mut headers = header_indices(limits);
 
         let (len, ver, status, h_len) = {
-            let mut parsed: [httparse::Header<'_>; MAX_HEADERS] = EMPTY_HEADER_ARRAY;
+            let mut parsed = parsed_headers(limits);
 
             let mut res = httparse::Response::new(&mut parsed);
             match res.parse(src)? {
                 httparse::Status::Complete(len) => {
+                    if len > limits.max_header_size {
+                        error!("response head is over the size limit");
+                        return Err(ParseError::TooLarge);
+                    }
+
                     let version = if res.version.unwrap() == 1 {
                         Version::HTTP_11
                     } else {
@@ -279,8 +324,8 @@ impl MessageType for ResponseHead {
                     (len, version, status, res.headers.len())
                 }
                 httparse::Status::Partial => {
-                    return if src.len() >= MAX_BUFFER_SIZE {
-                        error!("MAX_BUFFER_SIZE unprocessed data reached, closing");
+                    return if src.len() >= limits.max_header_size {
+                        error!("response head is over the size limit, closing");
                         Err(ParseError::TooLarge)
                     } else {
                         Ok(None)
@@ -326,12 +371,33 @@ pub(crate) const EMPTY_HEADER_INDEX: HeaderIndex = HeaderIndex {
     value: (0, 0),
 };
 
-pub(crate) const EMPTY_HEADER_INDEX_ARRAY: [HeaderIndex; MAX_HEADERS] =
-    [EMPTY_HEADER_INDEX; MAX_HEADERS];
-
 pub(crate) const EMPTY_HEADER_ARRAY: [httparse::Header<'static>; MAX_HEADERS] =
     [httparse::EMPTY_HEADER; MAX_HEADERS];
 
+/// Header index buffer for up to the configured number of headers, on the stack by default.
+fn header_indices(limits: &HeadLimits) -> SmallVec<[HeaderIndex; MAX_HEADERS]> {
+    SmallVec::from_elem(EMPTY_HEADER_INDEX, limits.max_headers)
+}
+
+/// Parser header buffer for up to the configured number of headers, on the stack by default.
+fn parsed_headers<'a>(
+    limits: &HeadLimits,
+) -> SmallVec<[httparse::Header<'a>; MAX_HEADERS]> {
+    SmallVec::from_elem(httparse::EMPTY_HEADER, limits.max_headers)
+}
+
+/// Returns the length of the request target in an incomplete request line.
+///
+/// Returns 0 when the request line is complete or the target has not started yet.
+fn partial_uri_length(src: &[u8]) -> usize {
+    let line_end = src.iter().position(|&b| b == b'\n').unwrap_or(src.len());
+
+    match src[..line_end].iter().position(|&b| b == b' ') {
+        Some(target_start) if line_end == src.len() => line_end - target_start - 1,
+        _ => 0,
+    }
+}
+
 impl HeaderIndex {
     pub(crate) fn record(
         bytes: &[u8],
@@ -361,26 +427,39 @@ pub enum PayloadItem {
 ///
 /// If a message body does not include a Transfer-Encoding, it *should*
 /// include a Content-Length header.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct PayloadDecoder {
     kind: Kind,
+    trailers: Option<HeaderMap>,
 }
 
 impl PayloadDecoder {
     pub fn length(x: u64) -> PayloadDecoder {
         PayloadDecoder {
             kind: Kind::Length(x),
+            trailers: None,
         }
     }
 
     pub fn chunked() -> PayloadDecoder {
         PayloadDecoder {
             kind: Kind::Chunked(ChunkedState::Size, 0),
+            trailers: None,
         }
     }
 
     pub fn eof() -> PayloadDecoder {
-        PayloadDecoder { kind: Kind::Eof }
+        PayloadDecoder {
+            kind: Kind::Eof,
+            trailers: None,
+        }
+    }
+
+    /// Take trailer fields received at the end of a chunked payload.
+    ///
+    /// Only available after the decoder has yielded `PayloadItem::Eof`.
+    pub fn take_trailers(&mut self) -> Option<HeaderMap> {
+        self.trailers.take()
     }
 }
 
@@ -417,8 +496,7 @@ enum ChunkedState {
     Body,
     BodyCr,
     BodyLf,
-    EndCr,
-    EndLf,
+    Trailers,
     End,
 }
 
@@ -452,7 +530,7 @@ impl Decoder for PayloadDecoder {
                 loop {
                     let mut buf = None;
                     // advances the chunked state
-                    *state = match state.step(src, size, &mut buf) {
+                    *state = match state.step(src, size, &mut buf, &mut self.trailers) {
                         Poll::Pending => return Ok(None),
                         Poll::Ready(Ok(state)) => state,
                         Poll::Ready(Err(e)) => return Err(e),
@@ -498,6 +576,7 @@ impl ChunkedState {
         body: &mut BytesMut,
         size: &mut u64,
         buf: &mut Option<Bytes>,
+        trailers: &mut Option<HeaderMap>,
     ) -> Poll<Result<ChunkedState, io::Error>> {
         use self::ChunkedState::*;
         match *self {
@@ -508,8 +587,7 @@ impl ChunkedState {
             Body => ChunkedState::read_body(body, size, buf),
             BodyCr => ChunkedState::read_body_cr(body),
             BodyLf => ChunkedState::read_body_lf(body),
-            EndCr => ChunkedState::read_end_cr(body),
-            EndLf => ChunkedState::read_end_lf(body),
+            Trailers => ChunkedState::read_trailers(body, trailers),
             End => Poll::Ready(Ok(ChunkedState::End)),
         }
     }
@@ -570,7 +648,7 @@ impl ChunkedState {
     ) -> Poll<Result<ChunkedState, io::Error>> {
         match byte!(rdr) {
             b'\n' if *size > 0 => Poll::Ready(Ok(ChunkedState::Body)),
-            b'\n' if *size == 0 => Poll::Ready(Ok(ChunkedState::EndCr)),
+            b'\n' if *size == 0 => Poll::Ready(Ok(ChunkedState::Trailers)),
             _ => Poll::Ready(Err(io::Error::new(
                 io::ErrorKind::InvalidInput,
                 "Invalid chunk size LF",
@@ -624,23 +702,63 @@ impl ChunkedState {
             ))),
         }
     }
-    fn read_end_cr(rdr: &mut BytesMut) -> Poll<Result<ChunkedState, io::Error>> {
-        match byte!(rdr) {
-            b'\r' => Poll::Ready(Ok(ChunkedState::EndLf)),
-            _ => Poll::Ready(Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                "Invalid chunk end CR",
-            ))),
+
+    fn read_trailers(
+        rdr: &mut BytesMut,
+        trailers: &mut Option<HeaderMap>,
+    ) -> Poll<Result<ChunkedState, io::Error>> {
+        if rdr.len() < 2 {
+            return Poll::Pending;
         }
-    }
-    fn read_end_lf(rdr: &mut BytesMut) -> Poll<Result<ChunkedState, io::Error>> {
-        match byte!(rdr) {
-            b'\n' => Poll::Ready(Ok(ChunkedState::End)),
-            _ => Poll::Ready(Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                "Invalid chunk end LF",
-            ))),
+
+        // no trailer fields, just the final CRLF
+        if rdr.starts_with(b"\r\n") {
+            rdr.advance(2);
+            return Poll::Ready(Ok(ChunkedState::End));
+        }
+
+        let len = match rdr.windows(4).position(|w| w == b"\r\n\r\n") {
+            Some(pos) => pos + 4,
+            None if rdr.len() >= MAX_BUFFER_SIZE => {
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Chunked trailer section is too large",
+                )));
+            }
+            None => return Poll::Pending,
+        };
+
+        let section = rdr.split_to(len);
+        let mut parsed = EMPTY_HEADER_ARRAY;
+
+        let headers = match httparse::parse_headers(&section, &mut parsed) {
+            Ok(httparse::Status::Complete((_, headers))) => headers,
+            _ => {
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Invalid chunked trailer section",
+                )));
+            }
+        };
+
+        let mut map = HeaderMap::with_capacity(headers.len());
+        for header in headers {
+            let name = HeaderName::from_bytes(header.name.as_bytes());
+            let value = HeaderValue::from_bytes(header.value);
+
+            match (name, value) {
+                (Ok(name), Ok(value)) => map.append(name, value),
+                _ => {
+                    return Poll::Ready(Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "Invalid chunked trailer field",
+                    )));
+                }
+            }
         }
+
+        *trailers = Some(map);
+        Poll::Ready(Ok(ChunkedState::End))
     }
 }
 
@@ -741,6 +859,47 @@ mod tests {
         assert_eq!(req.path(), "/test2");
     }
 
+    #[test]
+    fn test_parse_head_limits() {
+        let limits = HeadLimits {
+            max_headers: 2,
+            max_header_size: 64,
+            max_uri_length: 16,
+        };
+
+        let mut buf = BytesMut::from("GET /test HTTP/1.1\r\na: 1\r\nb: 2\r\n\r\n");
+        let mut reader = MessageDecoder::<Request>::new(limits);
+        assert!(reader.decode(&mut buf).unwrap().is_some());
+
+        let mut buf =
+            BytesMut::from("GET /test HTTP/1.1\r\na: 1\r\nb: 2\r\nc: 3\r\n\r\n");
+        let mut reader = MessageDecoder::<Request>::new(limits);
+        assert!(matches!(reader.decode(&mut buf), Err(ParseError::TooLarge)));
+
+        let mut buf = BytesMut::from("GET /test HTTP/1.1\r\nlong: 0123456789012345678901234567890123456789\r\n\r\n");
+        let mut reader = MessageDecoder::<Request>::new(limits);
+        assert!(matches!(reader.decode(&mut buf), Err(ParseError::TooLarge)));
+
+        let mut buf = BytesMut::from("GET /01234567890123456789 HTTP/1.1\r\n\r\n");
+        let mut reader = MessageDecoder::<Request>::new(limits);
+        assert!(matches!(
+            reader.decode(&mut buf),
+            Err(ParseError::UriTooLong)
+        ));
+
+        // request line is not complete yet but the target is already too long
+        let mut buf = BytesMut::from("GET /01234567890123456789");
+        let mut reader = MessageDecoder::<Request>::new(limits);
+        assert!(matches!(
+            reader.decode(&mut buf),
+            Err(ParseError::UriTooLong)
+        ));
+
+        let mut buf = BytesMut::from("GET /0123");
+        let mut reader = MessageDecoder::<Request>::new(limits);
+        assert!(reader.decode(&mut buf).unwrap().is_none());
+    }
+
     #[test]
     fn test_parse_body() {
         let mut buf =
@@ -1232,6 +1391,50 @@ mod tests {
         assert!(msg.eof());
     }
 
+    #[test]
+    fn test_parse_chunked_payload_trailers() {
+        let mut buf = BytesMut::from(
+            "GET /test HTTP/1.1\r\n\
+            transfer-encoding: chunked\r\n\
+            \r\n",
+        );
+
+        let mut reader = MessageDecoder::<Request>::default();
+        let (_msg, pl) = reader.decode(&mut buf).unwrap().unwrap();
+        let mut pl = pl.unwrap();
+
+        buf.extend(b"4\r\ndata\r\n0\r\nx-checksum: abc\r\n");
+        let chunk = pl.decode(&mut buf).unwrap().unwrap().chunk();
+        assert_eq!(chunk, Bytes::from_static(b"data"));
+        assert!(pl.decode(&mut buf).unwrap().is_none());
+
+        buf.extend(b"x-status: 0\r\n\r\nGET");
+        assert!(pl.decode(&mut buf).unwrap().unwrap().eof());
+        assert_eq!(&buf[..], b"GET");
+
+        let trailers = pl.take_trailers().unwrap();
+        assert_eq!(trailers.len(), 2);
+        assert_eq!(trailers.get("x-checksum").unwrap(), "abc");
+        assert_eq!(trailers.get("x-status").unwrap(), "0");
+        assert!(pl.take_trailers().is_none());
+    }
+
+    #[test]
+    fn test_parse_chunked_payload_invalid_trailers() {
+        let mut buf = BytesMut::from(
+            "GET /test HTTP/1.1\r\n\
+            transfer-encoding: chunked\r\n\
+            \r\n",
+        );
+
+        let mut reader = MessageDecoder::<Request>::default();
+        let (_msg, pl) = reader.decode(&mut buf).unwrap().unwrap();
+        let mut pl = pl.unwrap();
+
+        buf.extend(b"0\r\nbad header\r\n\r\n");
+        assert!(pl.decode(&mut buf).is_err());
+    }
+
     #[test]
     fn test_response_http10_read_until_eof() {
         let mut buf = BytesMut::from("HTTP/1.0 200 Ok\r\n\r\ntest data");
@@ -19,16 +19,19 @@ use log::{error, trace};
 use pin_project::pin_project;
 
 use crate::{
-    body::{AnyBody, BodySize, MessageBody},
+    body::{AnyBody, BodySize, FileRegion, MessageBody},
     config::ServiceConfig,
     error::{DispatchError, ParseError, PayloadError},
+    h2::upgrade::{self, H2cUpgrade},
     service::HttpFlow,
+    stats::{ConnectionGuard, ConnectionStats},
     OnConnectData, Request, Response, StatusCode,
 };
 
 use super::{
     codec::Codec,
     payload::{Payload, PayloadSender, PayloadStatus},
+    sendfile::SendFileFn,
     Message, MessageType,
 };
 
@@ -43,6 +46,8 @@ bitflags! {
         const SHUTDOWN           = 0b0000_0100;
         const READ_DISCONNECT    = 0b0000_1000;
         const WRITE_DISCONNECT   = 0b0001_0000;
+        const BODY_EOF           = 0b0010_0000;
+        const H2C                = 0b0100_0000;
     }
 }
 
@@ -85,7 +90,9 @@ where
     U::Error: fmt::Display,
 {
     Normal(#[pin] InnerDispatcher<T, S, B, X, U>),
-    Upgrade(#[pin] U::Future),
+    // the connection guard is kept so upgraded connections are still counted as open
+    Upgrade(#[pin] U::Future, Option<ConnectionGuard>),
+    H2cUpgrade(Option<(H2cUpgrade<T>, Rc<HttpFlow<S, X, U>>)>),
 }
 
 #[pin_project(project = InnerDispatcherProj)]
@@ -122,11 +129,14 @@ where
     read_buf: BytesMut,
     write_buf: BytesMut,
     codec: Codec,
+    sendfile: Option<SendFileFn<T>>,
+    conn_guard: Option<ConnectionGuard>,
 }
 
 enum DispatcherMessage {
     Item(Request),
     Upgrade(Request),
+    H2cUpgrade(Option<Request>),
     Error(Response<()>),
 }
 
@@ -144,6 +154,7 @@ where
     ServiceCall(#[pin] S::Future),
     SendPayload(#[pin] B),
     SendErrorPayload(#[pin] AnyBody),
+    SendFile(FileRegion),
 }
 
 impl<S, B, X> State<S, B, X>
@@ -162,6 +173,7 @@ where
 
 enum PollResponse {
     Upgrade(Request),
+    H2cUpgrade(Option<Request>),
     DoNothing,
     DrainWriteBuf,
 }
@@ -203,6 +215,10 @@ where
             None => (config.now(), None),
         };
 
+        let conn_guard = config
+            .connection_stats()
+            .map(ConnectionStats::connection_opened);
+
         Dispatcher {
             inner: DispatcherState::Normal(InnerDispatcher {
                 read_buf: BytesMut::with_capacity(HW_BUFFER_SIZE),
@@ -213,18 +229,51 @@ where
                 messages: VecDeque::new(),
                 io: Some(io),
                 codec: Codec::new(config),
+                sendfile: None,
                 flow,
                 on_connect_data,
                 flags,
                 peer_addr,
                 ka_expire,
                 ka_timer,
+                conn_guard,
             }),
 
             #[cfg(test)]
             poll_count: 0,
         }
     }
+
+    /// Sets the function used to send file bodies, if enabled in the service config.
+    pub(crate) fn with_sendfile(mut self, sendfile: Option<SendFileFn<T>>) -> Self {
+        if let DispatcherState::Normal(ref mut inner) = self.inner {
+            if inner.codec.config().sendfile() {
+                inner.sendfile = sendfile;
+            }
+        }
+
+        self
+    }
+
+    /// Enables handing over the connection for h2c, by prior knowledge or by upgrade if enabled in
+    /// the service config.
+    pub(crate) fn with_h2c(mut self) -> Self {
+        if let DispatcherState::Normal(ref mut inner) = self.inner {
+            inner.flags.insert(Flags::H2C);
+        }
+
+        self
+    }
+
+    /// Takes the connection handed over for an h2c upgrade, once the dispatcher has resolved.
+    pub(crate) fn take_h2c_upgrade(
+        self: Pin<&mut Self>,
+    ) -> Option<(H2cUpgrade<T>, Rc<HttpFlow<S, X, U>>)> {
+        match self.project().inner.project() {
+            DispatcherStateProj::H2cUpgrade(upgrade) => upgrade.take(),
+            _ => None,
+        }
+    }
 }
 
 impl<T, S, B, X, U> InnerDispatcher<T, S, B, X, U>
@@ -388,6 +437,11 @@ where
                         return Ok(PollResponse::Upgrade(req));
                     }
 
+                    // return with h2c upgrade request and hand over the connection.
+                    Some(DispatcherMessage::H2cUpgrade(req)) => {
+                        return Ok(PollResponse::H2cUpgrade(req));
+                    }
+
                     // all messages are dealt with.
                     None => return Ok(PollResponse::DoNothing),
                 },
@@ -418,9 +472,30 @@ where
                 },
 
                 StateProj::SendPayload(mut stream) => {
+                    // bodies backed by a file are sent with sendfile when nothing of them has
+                    // been written yet and they are sent as is, without chunked encoding.
+                    if let (Some(_), BodySize::Sized(size)) =
+                        (this.sendfile, stream.size())
+                    {
+                        if size > 0 && this.codec.payload_remaining() == Some(size) {
+                            if let Some(region) = stream.as_mut().take_file_region() {
+                                if region.len() != size {
+                                    return Err(DispatchError::Body(
+                                        "file region does not match body size".into(),
+                                    ));
+                                }
+
+                                this.state.set(State::SendFile(region));
+                                continue 'res;
+                            }
+                        }
+                    }
+
                     // keep populate writer buffer until buffer size limit hit,
                     // get blocked or finished.
-                    while this.write_buf.len() < super::payload::MAX_BUFFER_SIZE {
+                    while !this.flags.contains(Flags::BODY_EOF)
+                        && this.write_buf.len() < super::payload::MAX_BUFFER_SIZE
+                    {
                         match stream.as_mut().poll_next(cx) {
                             Poll::Ready(Some(Ok(item))) => {
                                 this.codec.encode(
@@ -430,12 +505,9 @@ where
                             }
 
                             Poll::Ready(None) => {
-                                this.codec
-                                    .encode(Message::Chunk(None), &mut this.write_buf)?;
-                                // payload stream finished.
-                                // set state to None and handle next message
-                                this.state.set(State::None);
-                                continue 'res;
+                                // payload stream finished. poll trailers next.
+                                this.flags.insert(Flags::BODY_EOF);
+                                break;
                             }
 
                             Poll::Ready(Some(Err(err))) => {
@@ -445,9 +517,35 @@ where
                             Poll::Pending => return Ok(PollResponse::DoNothing),
                         }
                     }
-                    // buffer is beyond max size.
-                    // return and try to write the whole buffer to io stream.
-                    return Ok(PollResponse::DrainWriteBuf);
+
+                    if !this.flags.contains(Flags::BODY_EOF) {
+                        // buffer is beyond max size.
+                        // return and try to write the whole buffer to io stream.
+                        return Ok(PollResponse::DrainWriteBuf);
+                    }
+
+                    match stream.as_mut().poll_trailers(cx) {
+                        Poll::Ready(Ok(trailers)) => {
+                            match trailers {
+                                Some(trailers) => this
+                                    .codec
+                                    .encode_trailers(&trailers, &mut this.write_buf)?,
+                                None => this
+                                    .codec
+                                    .encode(Message::Chunk(None), &mut this.write_buf)?,
+                            }
+                            // set state to None and handle next message
+                            this.flags.remove(Flags::BODY_EOF);
+                            this.state.set(State::None);
+                            continue 'res;
+                        }
+
+                        Poll::Ready(Err(err)) => {
+                            return Err(DispatchError::Body(err.into()))
+                        }
+
+                        Poll::Pending => return Ok(PollResponse::DoNothing),
+                    }
                 }
 
                 StateProj::SendErrorPayload(mut stream) => {
@@ -455,7 +553,9 @@ where
 
                     // keep populate writer buffer until buffer size limit hit,
                     // get blocked or finished.
-                    while this.write_buf.len() < super::payload::MAX_BUFFER_SIZE {
+                    while !this.flags.contains(Flags::BODY_EOF)
+                        && this.write_buf.len() < super::payload::MAX_BUFFER_SIZE
+                    {
                         match stream.as_mut().poll_next(cx) {
                             Poll::Ready(Some(Ok(item))) => {
                                 this.codec.encode(
@@ -465,12 +565,9 @@ where
                             }
 
                             Poll::Ready(None) => {
-                                this.codec
-                                    .encode(Message::Chunk(None), &mut this.write_buf)?;
-                                // payload stream finished.
-                                // set state to None and handle next message
-                                this.state.set(State::None);
-                                continue 'res;
+                                // payload stream finished. poll trailers next.
+                                this.flags.insert(Flags::BODY_EOF);
+                                break;
                             }
 
                             Poll::Ready(Some(Err(err))) => {
@@ -480,9 +577,67 @@ where
                             Poll::Pending => return Ok(PollResponse::DoNothing),
                         }
                     }
-                    // buffer is beyond max size.
-                    // return and try to write the whole buffer to io stream.
-                    return Ok(PollResponse::DrainWriteBuf);
+
+                    if !this.flags.contains(Flags::BODY_EOF) {
+                        // buffer is beyond max size.
+                        // return and try to write the whole buffer to io stream.
+                        return Ok(PollResponse::DrainWriteBuf);
+                    }
+
+                    match stream.as_mut().poll_trailers(cx) {
+                        Poll::Ready(Ok(trailers)) => {
+                            match trailers {
+                                Some(trailers) => this
+                                    .codec
+                                    .encode_trailers(&trailers, &mut this.write_buf)?,
+                                None => this
+                                    .codec
+                                    .encode(Message::Chunk(None), &mut this.write_buf)?,
+                            }
+                            // set state to None and handle next message
+                            this.flags.remove(Flags::BODY_EOF);
+                            this.state.set(State::None);
+                            continue 'res;
+                        }
+
+                        Poll::Ready(Err(err)) => {
+                            return Err(DispatchError::Service(err.into()))
+                        }
+
+                        Poll::Pending => return Ok(PollResponse::DoNothing),
+                    }
+                }
+
+                StateProj::SendFile(region) => {
+                    // response head must be written before the file.
+                    if !this.write_buf.is_empty() {
+                        return Ok(PollResponse::DrainWriteBuf);
+                    }
+
+                    let send_file = this.sendfile.expect("sendfile is not enabled");
+                    let io = this.io.as_mut().unwrap();
+
+                    while !region.is_empty() {
+                        match send_file(io, cx, region) {
+                            Poll::Ready(Ok(0)) => {
+                                return Err(DispatchError::Io(io::Error::new(
+                                    io::ErrorKind::WriteZero,
+                                    "",
+                                )))
+                            }
+                            Poll::Ready(Ok(n)) => {
+                                region.advance(n as u64);
+                                this.codec.payload_written(n as u64);
+                            }
+                            Poll::Ready(Err(err)) => return Err(DispatchError::Io(err)),
+                            Poll::Pending => return Ok(PollResponse::DoNothing),
+                        }
+                    }
+
+                    this.codec
+                        .encode(Message::Chunk(None), &mut this.write_buf)?;
+                    this.state.set(State::None);
+                    continue 'res;
                 }
 
                 StateProj::ExpectCall(fut) => match fut.poll(cx) {
@@ -589,8 +744,26 @@ where
             return Ok(false);
         }
 
-        let mut updated = false;
         let mut this = self.as_mut().project();
+
+        // connections opened with the HTTP/2 preface (prior knowledge) are handed over to h2.
+        if this.flags.contains(Flags::H2C)
+            && !this.flags.contains(Flags::STARTED)
+            && !this.read_buf.is_empty()
+        {
+            let len = this.read_buf.len().min(upgrade::PREFACE.len());
+            if this.read_buf[..len] == upgrade::PREFACE[..len] {
+                if len < upgrade::PREFACE.len() {
+                    return Ok(false);
+                }
+
+                this.flags.insert(Flags::STARTED);
+                this.messages.push_back(DispatcherMessage::H2cUpgrade(None));
+                return Ok(true);
+            }
+        }
+
+        let mut updated = false;
         loop {
             match this.codec.decode(&mut this.read_buf) {
                 Ok(Some(msg)) => {
@@ -601,6 +774,21 @@ where
                         Message::Item(mut req) => {
                             req.head_mut().peer_addr = *this.peer_addr;
 
+                            // switch to HTTP/2 once previous requests are responded to.
+                            // on-connect data is kept for the HTTP/2 streams.
+                            if this.flags.contains(Flags::H2C)
+                                && this.codec.config().h2c_upgrade()
+                                && upgrade::is_h2c_upgrade(&req)
+                            {
+                                this.messages
+                                    .push_back(DispatcherMessage::H2cUpgrade(Some(req)));
+                                break;
+                            }
+
+                            if let Some(guard) = this.conn_guard {
+                                guard.request();
+                            }
+
                             // merge on_connect_ext data into request extensions
                             this.on_connect_data.merge_into(&mut req);
 
@@ -660,6 +848,9 @@ where
                         }
                         Message::Chunk(None) => {
                             if let Some(mut payload) = this.payload.take() {
+                                if let Some(trailers) = this.codec.take_trailers() {
+                                    payload.feed_trailers(trailers);
+                                }
                                 payload.feed_eof();
                             } else {
                                 error!("Internal server error: unexpected eof");
@@ -682,19 +873,20 @@ where
                     *this.error = Some(DispatchError::Io(err));
                     break;
                 }
-                Err(ParseError::TooLarge) => {
+                Err(err @ ParseError::TooLarge) | Err(err @ ParseError::UriTooLong) => {
                     if let Some(mut payload) = this.payload.take() {
                         payload.set_error(PayloadError::Overflow);
                     }
-                    // Requests overflow buffer size should be responded with 431
+                    // Requests over the head limits should be responded with 431 or 414
+                    let status = match err {
+                        ParseError::UriTooLong => StatusCode::URI_TOO_LONG,
+                        _ => StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+                    };
                     this.messages.push_back(DispatcherMessage::Error(
-                        Response::with_body(
-                            StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
-                            (),
-                        ),
+                        Response::with_body(status, ()),
                     ));
                     this.flags.insert(Flags::READ_DISCONNECT);
-                    *this.error = Some(ParseError::TooLarge.into());
+                    *this.error = Some(err.into());
                     break;
                 }
                 Err(err) => {
@@ -817,9 +1009,16 @@ where
 
         let mut read_some = false;
 
+        // buffer at least a whole request head
+        let max_buffer_size = this
+            .codec
+            .config()
+            .max_header_size()
+            .max(super::decoder::MAX_BUFFER_SIZE);
+
         loop {
             // Return early when read buf exceed decoder's max buffer size.
-            if this.read_buf.len() >= super::decoder::MAX_BUFFER_SIZE {
+            if this.read_buf.len() >= max_buffer_size {
                 /*
                  At this point it's not known IO stream is still scheduled
                  to be waked up. so force wake up dispatcher just in case.
@@ -902,6 +1101,26 @@ where
         let framed = Framed::from_parts(parts);
         this.flow.upgrade.as_ref().unwrap().call((req, framed))
     }
+
+    /// take connection for switching to h2c, with the upgrade request if there is one.
+    fn h2c_upgrade(
+        self: Pin<&mut Self>,
+        req: Option<Request>,
+    ) -> (H2cUpgrade<T>, Rc<HttpFlow<S, X, U>>) {
+        let this = self.project();
+        let upgrade = H2cUpgrade {
+            io: this.io.take().unwrap(),
+            read_buf: mem::take(this.read_buf),
+            write_buf: mem::take(this.write_buf),
+            req,
+            config: this.codec.config().clone(),
+            on_connect_data: mem::take(this.on_connect_data),
+            peer_addr: *this.peer_addr,
+            conn_guard: this.conn_guard.take(),
+        };
+
+        (upgrade, this.flow.clone())
+    }
 }
 
 impl<T, S, B, X, U> Future for Dispatcher<T, S, B, X, U>
@@ -969,13 +1188,25 @@ where
                             PollResponse::DoNothing => false,
                             // upgrade request and goes Upgrade variant of DispatcherState.
                             PollResponse::Upgrade(req) => {
+                                let conn_guard =
+                                    inner.as_mut().project().conn_guard.take();
                                 let upgrade = inner.upgrade(req);
                                 self.as_mut()
                                     .project()
                                     .inner
-                                    .set(DispatcherState::Upgrade(upgrade));
+                                    .set(DispatcherState::Upgrade(upgrade, conn_guard));
                                 return self.poll(cx);
                             }
+                            // hand over connection and goes H2cUpgrade variant of
+                            // DispatcherState.
+                            PollResponse::H2cUpgrade(req) => {
+                                let upgrade = inner.h2c_upgrade(req);
+                                self.as_mut()
+                                    .project()
+                                    .inner
+                                    .set(DispatcherState::H2cUpgrade(Some(upgrade)));
+                                return Poll::Ready(Ok(()));
+                            }
                         };
 
                         // we didn't get WouldBlock from write operation,
@@ -1025,10 +1256,11 @@ where
                     }
                 }
             }
-            DispatcherStateProj::Upgrade(fut) => fut.poll(cx).map_err(|e| {
+            DispatcherStateProj::Upgrade(fut, _) => fut.poll(cx).map_err(|e| {
                 error!("Upgrade handler error: {}", e);
                 DispatchError::Upgrade
             }),
+            DispatcherStateProj::H2cUpgrade(_) => Poll::Ready(Ok(())),
         }
     }
 }
@@ -1419,11 +1651,113 @@ mod tests {
             actix_rt::pin!(h1);
 
             assert!(h1.as_mut().poll(cx).is_ready());
-            assert!(matches!(&h1.inner, DispatcherState::Upgrade(_)));
+            assert!(matches!(&h1.inner, DispatcherState::Upgrade(..)));
 
             // polls: manual shutdown
             assert_eq!(h1.poll_count, 2);
         })
         .await;
     }
+
+    #[actix_rt::test]
+    async fn test_sendfile() {
+        use std::{
+            convert::Infallible,
+            fs::File,
+            io::{Read as _, Seek as _, SeekFrom},
+        };
+
+        struct FileBody(Option<File>);
+
+        impl MessageBody for FileBody {
+            type Error = Infallible;
+
+            fn size(&self) -> BodySize {
+                BodySize::Sized(20)
+            }
+
+            fn poll_next(
+                self: Pin<&mut Self>,
+                _: &mut Context<'_>,
+            ) -> Poll<Option<Result<bytes::Bytes, Self::Error>>> {
+                panic!("file body should be sent with sendfile")
+            }
+
+            fn take_file_region(mut self: Pin<&mut Self>) -> Option<FileRegion> {
+                self.0.take().map(|file| FileRegion::new(file, 10, 20))
+            }
+        }
+
+        // copies at most 8 bytes per call to exercise partial writes
+        fn send_file(
+            io: &mut TestBuffer,
+            _: &mut Context<'_>,
+            region: &FileRegion,
+        ) -> Poll<io::Result<usize>> {
+            let mut file = region.file().try_clone()?;
+            file.seek(SeekFrom::Start(region.offset()))?;
+
+            let mut buf = vec![0; region.len().min(8) as usize];
+            let n = file.read(&mut buf)?;
+            io.write_buf.extend_from_slice(&buf[..n]);
+
+            Poll::Ready(Ok(n))
+        }
+
+        lazy(|cx| {
+            let buf = TestBuffer::new("GET /test HTTP/1.1\r\nConnection: close\r\n\r\n");
+
+            let services = HttpFlow::new(
+                fn_service(|_: Request| {
+                    let file = File::open("Cargo.toml").unwrap();
+                    ready(Ok::<_, Error>(
+                        Response::ok().set_body(FileBody(Some(file))),
+                    ))
+                }),
+                ExpectHandler,
+                None,
+            );
+
+            let h1 = Dispatcher::<_, _, _, _, UpgradeHandler>::new(
+                buf,
+                ServiceConfig::default().with_sendfile(true),
+                services,
+                OnConnectData::default(),
+                None,
+            )
+            .with_sendfile(Some(send_file));
+
+            actix_rt::pin!(h1);
+
+            match h1.as_mut().poll(cx) {
+                Poll::Pending => panic!("first poll should not be pending"),
+                Poll::Ready(res) => assert!(res.is_ok()),
+            }
+
+            if let DispatcherStateProj::Normal(inner) = h1.project().inner.project() {
+                let res = &mut inner.project().io.take().unwrap().write_buf[..];
+                stabilize_date_header(res);
+
+                let mut exp = b"\
+                HTTP/1.1 200 OK\r\n\
+                content-length: 20\r\n\
+                connection: close\r\n\
+                date: Thu, 01 Jan 1970 12:34:56 UTC\r\n\r\n\
+                "
+                .to_vec();
+                exp.extend_from_slice(&std::fs::read("Cargo.toml").unwrap()[10..30]);
+
+                assert_eq!(
+                    res,
+                    &exp[..],
+                    "\nexpected response not in write buffer:\n\
+                     response: {:?}\n\
+                     expected: {:?}",
+                    String::from_utf8_lossy(res),
+                    String::from_utf8_lossy(&exp)
+                );
+            }
+        })
+        .await;
+    }
 }
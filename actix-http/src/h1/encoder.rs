@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use std::io::Write;
 use std::marker::PhantomData;
 use std::ptr::copy_nonoverlapping;
@@ -287,11 +288,18 @@ impl MessageType for RequestHeadType {
     fn encode_status(&mut self, dst: &mut BytesMut) -> io::Result<()> {
         let head = self.as_ref();
         dst.reserve(256 + head.headers.len() * AVERAGE_HEADER_SIZE);
+
+        let target = if head.full_target() {
+            Cow::Owned(head.uri.to_string())
+        } else {
+            Cow::Borrowed(head.uri.path_and_query().map(|u| u.as_str()).unwrap_or("/"))
+        };
+
         write!(
             helpers::MutWriter(dst),
             "{} {} {}",
             head.method,
-            head.uri.path_and_query().map(|u| u.as_str()).unwrap_or("/"),
+            target,
             match head.version {
                 Version::HTTP_09 => "HTTP/0.9",
                 Version::HTTP_10 => "HTTP/1.0",
@@ -320,6 +328,15 @@ impl<T: MessageType> MessageEncoder<T> {
         self.te.encode_eof(buf)
     }
 
+    /// Encode eof followed by trailing headers
+    pub fn encode_trailers(
+        &mut self,
+        trailers: &HeaderMap,
+        buf: &mut BytesMut,
+    ) -> io::Result<()> {
+        self.te.encode_trailers(trailers, buf)
+    }
+
     pub fn encode(
         &mut self,
         dst: &mut BytesMut,
@@ -448,6 +465,23 @@ impl TransferEncoding {
         }
     }
 
+    /// Returns the number of body bytes left to write when a `Content-Length` is set.
+    #[inline]
+    pub fn remaining(&self) -> Option<u64> {
+        match self.kind {
+            TransferEncodingKind::Length(remaining) => Some(remaining),
+            _ => None,
+        }
+    }
+
+    /// Records that `len` body bytes were written without the encoder.
+    #[inline]
+    pub fn advance(&mut self, len: u64) {
+        if let TransferEncodingKind::Length(ref mut remaining) = self.kind {
+            *remaining = remaining.saturating_sub(len);
+        }
+    }
+
     /// Encode eof. Return `EOF` state of encoder
     #[inline]
     pub fn encode_eof(&mut self, buf: &mut BytesMut) -> io::Result<()> {
@@ -469,6 +503,38 @@ impl TransferEncoding {
             }
         }
     }
+
+    /// Encode eof with trailing headers.
+    ///
+    /// Trailers can only be carried by chunked transfer encoding; for other encodings they are
+    /// discarded and this behaves like `encode_eof`.
+    #[inline]
+    pub fn encode_trailers(
+        &mut self,
+        trailers: &HeaderMap,
+        buf: &mut BytesMut,
+    ) -> io::Result<()> {
+        match self.kind {
+            TransferEncodingKind::Chunked(ref mut eof) => {
+                if !*eof {
+                    *eof = true;
+                    buf.extend_from_slice(b"0\r\n");
+
+                    for (name, value) in trailers.iter() {
+                        buf.reserve(name.as_str().len() + value.len() + 4);
+                        buf.extend_from_slice(name.as_str().as_bytes());
+                        buf.extend_from_slice(b": ");
+                        buf.extend_from_slice(value.as_bytes());
+                        buf.extend_from_slice(b"\r\n");
+                    }
+
+                    buf.extend_from_slice(b"\r\n");
+                }
+                Ok(())
+            }
+            _ => self.encode_eof(buf),
+        }
+    }
 }
 
 /// # Safety
@@ -514,7 +580,7 @@ mod tests {
 
     use super::*;
     use crate::http::header::{HeaderValue, CONTENT_TYPE};
-    use crate::RequestHead;
+    use crate::{RequestHead, Uri};
 
     #[test]
     fn test_chunked_te() {
@@ -530,6 +596,32 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_chunked_te_trailers() {
+        let mut trailers = HeaderMap::new();
+        trailers.insert(
+            HeaderName::from_static("grpc-status"),
+            HeaderValue::from_static("0"),
+        );
+
+        let mut bytes = BytesMut::new();
+        let mut enc = TransferEncoding::chunked();
+        assert!(!enc.encode(b"test", &mut bytes).unwrap());
+        enc.encode_trailers(&trailers, &mut bytes).unwrap();
+        // trailers are only written once
+        enc.encode_trailers(&trailers, &mut bytes).unwrap();
+        enc.encode_eof(&mut bytes).unwrap();
+        assert_eq!(
+            bytes.split().freeze(),
+            Bytes::from_static(b"4\r\ntest\r\n0\r\ngrpc-status: 0\r\n\r\n")
+        );
+
+        let mut enc = TransferEncoding::length(4);
+        assert!(enc.encode(b"test", &mut bytes).unwrap());
+        enc.encode_trailers(&trailers, &mut bytes).unwrap();
+        assert_eq!(bytes.split().freeze(), Bytes::from_static(b"test"));
+    }
+
     #[actix_rt::test]
     async fn test_camel_case() {
         let mut bytes = BytesMut::with_capacity(2048);
@@ -627,6 +719,29 @@ mod tests {
         assert!(data.contains("date: date\r\n"));
     }
 
+    #[test]
+    fn test_full_target() {
+        let mut bytes = BytesMut::with_capacity(2048);
+
+        let mut head = RequestHead::default();
+        head.uri = Uri::from_static("http://example.com/path?query");
+
+        let mut head = RequestHeadType::Owned(head);
+        head.encode_status(&mut bytes).unwrap();
+        assert_eq!(bytes.split().as_ref(), &b"GET /path?query HTTP/1.1"[..]);
+
+        let mut head =
+            RequestHeadType::Rc(Rc::new(head.into_owned()), None).into_owned();
+        head.set_full_target();
+
+        let mut head = RequestHeadType::Owned(head);
+        head.encode_status(&mut bytes).unwrap();
+        assert_eq!(
+            bytes.split().as_ref(),
+            &b"GET http://example.com/path?query HTTP/1.1"[..]
+        );
+    }
+
     #[actix_rt::test]
     async fn test_no_content_length() {
         let mut bytes = BytesMut::with_capacity(2048);
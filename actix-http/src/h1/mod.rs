@@ -3,11 +3,12 @@ use bytes::{Bytes, BytesMut};
 
 mod client;
 mod codec;
-mod decoder;
+pub(crate) mod decoder;
 mod dispatcher;
 mod encoder;
 mod expect;
 mod payload;
+pub(crate) mod sendfile;
 mod service;
 mod upgrade;
 mod utils;
@@ -9,6 +9,7 @@ use bytes::Bytes;
 use futures_core::Stream;
 
 use crate::error::PayloadError;
+use crate::header::HeaderMap;
 
 /// max buffer size 32k
 pub(crate) const MAX_BUFFER_SIZE: usize = 32_768;
@@ -85,6 +86,18 @@ impl Payload {
     ) -> Poll<Option<Result<Bytes, PayloadError>>> {
         self.inner.borrow_mut().readany(cx)
     }
+
+    /// Attempt to pull out the trailer fields sent after a chunked payload.
+    ///
+    /// Resolves once the payload stream is exhausted; should only be called after the stream
+    /// has yielded `None`.
+    #[inline]
+    pub fn poll_trailers(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, PayloadError>> {
+        self.inner.borrow_mut().poll_trailers(cx)
+    }
 }
 
 impl Stream for Payload {
@@ -125,6 +138,13 @@ impl PayloadSender {
         }
     }
 
+    #[inline]
+    pub fn feed_trailers(&mut self, trailers: HeaderMap) {
+        if let Some(shared) = self.inner.upgrade() {
+            shared.borrow_mut().feed_trailers(trailers)
+        }
+    }
+
     #[inline]
     pub fn need_read(&self, cx: &mut Context<'_>) -> PayloadStatus {
         // we check need_read only if Payload (other side) is alive,
@@ -149,6 +169,7 @@ struct Inner {
     err: Option<PayloadError>,
     need_read: bool,
     items: VecDeque<Bytes>,
+    trailers: Option<HeaderMap>,
     task: Option<Waker>,
     io_task: Option<Waker>,
 }
@@ -160,6 +181,7 @@ impl Inner {
             len: 0,
             err: None,
             items: VecDeque::new(),
+            trailers: None,
             need_read: true,
             task: None,
             io_task: None,
@@ -222,11 +244,34 @@ impl Inner {
         self.wake();
     }
 
+    #[inline]
+    fn feed_trailers(&mut self, trailers: HeaderMap) {
+        self.trailers = Some(trailers);
+    }
+
     #[cfg(test)]
     fn len(&self) -> usize {
         self.len
     }
 
+    fn poll_trailers(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, PayloadError>> {
+        if let Some(err) = self.err.take() {
+            Poll::Ready(Err(err))
+        } else if self.eof {
+            Poll::Ready(Ok(self.trailers.take()))
+        } else {
+            if self.items.is_empty() {
+                self.need_read = true;
+            }
+            self.register(cx);
+            self.wake_io();
+            Poll::Pending
+        }
+    }
+
     fn readany(
         &mut self,
         cx: &mut Context<'_>,
@@ -0,0 +1,85 @@
+//! Sending file regions with `sendfile(2)`.
+
+use std::{
+    io,
+    task::{Context, Poll},
+};
+
+use actix_rt::net::TcpStream;
+
+use crate::body::FileRegion;
+
+/// Writes the start of a file region to an IO stream, bypassing user space.
+///
+/// Returns the number of bytes written. Registers for wake up when the stream is not writable.
+pub(crate) type SendFileFn<T> =
+    fn(&mut T, &mut Context<'_>, &FileRegion) -> Poll<io::Result<usize>>;
+
+/// Returns the sendfile implementation for TCP streams, if the platform has one.
+pub(crate) fn tcp() -> Option<SendFileFn<TcpStream>> {
+    #[cfg(target_os = "linux")]
+    {
+        Some(linux::send_file)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        None
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::{
+        io,
+        os::unix::io::AsRawFd,
+        task::{Context, Poll},
+    };
+
+    use actix_rt::net::TcpStream;
+    use futures_core::ready;
+    use tokio::io::Interest;
+
+    use crate::body::FileRegion;
+
+    /// Largest count accepted by a single `sendfile` call.
+    const MAX_COUNT: u64 = 0x7fff_f000;
+
+    pub(super) fn send_file(
+        io: &mut TcpStream,
+        cx: &mut Context<'_>,
+        region: &FileRegion,
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            ready!(io.poll_write_ready(cx))?;
+
+            let res = io.try_io(Interest::WRITABLE, || {
+                let mut offset = region.offset() as libc::off_t;
+                let count = region.len().min(MAX_COUNT) as usize;
+
+                // SAFETY: both file descriptors are open for the duration of the call and
+                // `offset` is a valid pointer
+                let n = unsafe {
+                    libc::sendfile(
+                        io.as_raw_fd(),
+                        region.file().as_raw_fd(),
+                        &mut offset,
+                        count,
+                    )
+                };
+
+                if n < 0 {
+                    Err(io::Error::last_os_error())
+                } else {
+                    Ok(n as usize)
+                }
+            });
+
+            match res {
+                // readiness was cleared, poll again to register for wake up
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => continue,
+                res => return Poll::Ready(res),
+            }
+        }
+    }
+}
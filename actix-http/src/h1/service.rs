@@ -23,7 +23,10 @@ use crate::{
     ConnectCallback, OnConnectData, Request, Response,
 };
 
-use super::{codec::Codec, dispatcher::Dispatcher, ExpectHandler, UpgradeHandler};
+use super::{
+    codec::Codec, dispatcher::Dispatcher, sendfile, sendfile::SendFileFn, ExpectHandler,
+    UpgradeHandler,
+};
 
 /// `ServiceFactory` implementation for HTTP1 transport
 pub struct H1Service<T, S, B, X = ExpectHandler, U = UpgradeHandler> {
@@ -32,6 +35,7 @@ pub struct H1Service<T, S, B, X = ExpectHandler, U = UpgradeHandler> {
     expect: X,
     upgrade: Option<U>,
     on_connect_ext: Option<Rc<ConnectCallback<T>>>,
+    sendfile: Option<SendFileFn<T>>,
     _phantom: PhantomData<B>,
 }
 
@@ -54,6 +58,7 @@ where
             expect: ExpectHandler,
             upgrade: None,
             on_connect_ext: None,
+            sendfile: None,
             _phantom: PhantomData,
         }
     }
@@ -82,7 +87,7 @@ where
 {
     /// Create simple tcp stream service
     pub fn tcp(
-        self,
+        mut self,
     ) -> impl ServiceFactory<
         TcpStream,
         Config = (),
@@ -90,6 +95,8 @@ where
         Error = DispatchError,
         InitError = (),
     > {
+        self.sendfile = sendfile::tcp();
+
         fn_service(|io: TcpStream| {
             let peer_addr = io.peer_addr().ok();
             ready(Ok((io, peer_addr)))
@@ -236,6 +243,7 @@ where
             srv: self.srv,
             upgrade: self.upgrade,
             on_connect_ext: self.on_connect_ext,
+            sendfile: self.sendfile,
             _phantom: PhantomData,
         }
     }
@@ -252,6 +260,7 @@ where
             srv: self.srv,
             expect: self.expect,
             on_connect_ext: self.on_connect_ext,
+            sendfile: self.sendfile,
             _phantom: PhantomData,
         }
     }
@@ -299,6 +308,7 @@ where
         let expect = self.expect.new_service(());
         let upgrade = self.upgrade.as_ref().map(|s| s.new_service(()));
         let on_connect_ext = self.on_connect_ext.clone();
+        let sendfile = self.sendfile;
         let cfg = self.cfg.clone();
 
         Box::pin(async move {
@@ -326,6 +336,7 @@ where
                 expect,
                 upgrade,
                 on_connect_ext,
+                sendfile,
             ))
         })
     }
@@ -374,5 +385,6 @@ where
             on_connect_data,
             addr,
         )
+        .with_sendfile(self.sendfile)
     }
 }
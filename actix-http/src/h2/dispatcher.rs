@@ -7,14 +7,19 @@ use std::{
     pin::Pin,
     rc::Rc,
     task::{Context, Poll},
+    time::Duration,
 };
 
 use actix_codec::{AsyncRead, AsyncWrite};
+use actix_rt::time::{sleep, Instant, Sleep};
 use actix_service::Service;
 use actix_utils::future::poll_fn;
 use bytes::{Bytes, BytesMut};
-use futures_core::ready;
-use h2::server::{Connection, SendResponse};
+use futures_core::{future::LocalBoxFuture, ready};
+use h2::{
+    server::{Connection, SendResponse},
+    Ping, PingPong,
+};
 use http::header::{HeaderValue, CONNECTION, CONTENT_LENGTH, DATE, TRANSFER_ENCODING};
 use log::{error, trace};
 use pin_project_lite::pin_project;
@@ -23,6 +28,7 @@ use crate::{
     body::{AnyBody, BodySize, MessageBody},
     config::ServiceConfig,
     service::HttpFlow,
+    stats::{ConnectionGuard, ConnectionStats},
     OnConnectData, Payload, Request, Response, ResponseHead,
 };
 
@@ -36,11 +42,52 @@ pin_project! {
         on_connect_data: OnConnectData,
         config: ServiceConfig,
         peer_addr: Option<net::SocketAddr>,
+        conn_guard: Option<ConnectionGuard>,
+        keep_alive: Option<H2KeepAlive>,
+        shutdown: Option<LocalBoxFuture<'static, ()>>,
         _phantom: PhantomData<B>,
     }
 }
 
-impl<T, S, B, X, U> Dispatcher<T, S, B, X, U> {
+/// Keep-alive pings of a connection.
+struct H2KeepAlive {
+    ping_pong: PingPong,
+    interval: Duration,
+    /// Fires when the next ping is due.
+    timer: Pin<Box<Sleep>>,
+    /// Whether a ping was sent and its pong is not received yet.
+    in_flight: bool,
+}
+
+impl H2KeepAlive {
+    /// Sends pings at every interval.
+    ///
+    /// Returns `false` when the pong of a ping was not received by the time the next ping is due.
+    fn poll_alive(&mut self, cx: &mut Context<'_>) -> Result<bool, h2::Error> {
+        loop {
+            if self.in_flight && self.ping_pong.poll_pong(cx)?.is_ready() {
+                self.in_flight = false;
+            }
+
+            if self.timer.as_mut().poll(cx).is_pending() {
+                return Ok(true);
+            }
+
+            if self.in_flight {
+                return Ok(false);
+            }
+
+            self.ping_pong.send_ping(Ping::opaque())?;
+            self.in_flight = true;
+            self.timer.as_mut().reset(Instant::now() + self.interval);
+        }
+    }
+}
+
+impl<T, S, B, X, U> Dispatcher<T, S, B, X, U>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
     pub(crate) fn new(
         flow: Rc<HttpFlow<S, X, U>>,
         connection: Connection<T, Bytes>,
@@ -48,9 +95,53 @@ impl<T, S, B, X, U> Dispatcher<T, S, B, X, U> {
         config: ServiceConfig,
         peer_addr: Option<net::SocketAddr>,
     ) -> Self {
+        let conn_guard = config
+            .connection_stats()
+            .map(ConnectionStats::connection_opened);
+
+        Self::with_conn_guard(
+            flow,
+            connection,
+            on_connect_data,
+            config,
+            peer_addr,
+            conn_guard,
+        )
+    }
+
+    /// Constructs a dispatcher for a connection that is already counted by `conn_guard`.
+    pub(crate) fn with_conn_guard(
+        flow: Rc<HttpFlow<S, X, U>>,
+        mut connection: Connection<T, Bytes>,
+        on_connect_data: OnConnectData,
+        config: ServiceConfig,
+        peer_addr: Option<net::SocketAddr>,
+        conn_guard: Option<ConnectionGuard>,
+    ) -> Self {
+        let keep_alive = match config.h2_settings().keep_alive_interval {
+            0 => None,
+            interval => connection.ping_pong().map(|ping_pong| {
+                let interval = Duration::from_millis(interval);
+
+                H2KeepAlive {
+                    ping_pong,
+                    interval,
+                    timer: Box::pin(sleep(interval)),
+                    in_flight: false,
+                }
+            }),
+        };
+
+        let shutdown = config
+            .shutdown_signal()
+            .map(|signal| Box::pin(signal.clone().triggered()) as _);
+
         Self {
             flow,
             config,
+            conn_guard,
+            keep_alive,
+            shutdown,
             peer_addr,
             connection,
             on_connect_data,
@@ -77,6 +168,22 @@ where
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self.get_mut();
 
+        // stop accepting streams and close once in-flight streams are done
+        if let Some(shutdown) = &mut this.shutdown {
+            if shutdown.as_mut().poll(cx).is_ready() {
+                trace!("shutting down HTTP/2 connection gracefully");
+                this.connection.graceful_shutdown();
+                this.shutdown = None;
+            }
+        }
+
+        if let Some(keep_alive) = &mut this.keep_alive {
+            if !keep_alive.poll_alive(cx)? {
+                trace!("HTTP/2 keep-alive ping timed out, closing connection");
+                return Poll::Ready(Ok(()));
+            }
+        }
+
         while let Some((req, tx)) =
             ready!(Pin::new(&mut this.connection).poll_accept(cx)?)
         {
@@ -95,6 +202,10 @@ where
             // merge on_connect_ext data into request extensions
             this.on_connect_data.merge_into(&mut req);
 
+            if let Some(guard) = &mut this.conn_guard {
+                guard.request();
+            }
+
             let fut = this.flow.service.call(req);
             let config = this.config.clone();
 
@@ -192,10 +303,28 @@ where
         }
     }
 
-    // response body streaming finished. send end of stream and return.
-    stream
-        .send_data(Bytes::new(), true)
-        .map_err(DispatchError::SendData)?;
+    let trailers = poll_fn(|cx| body.as_mut().poll_trailers(cx))
+        .await
+        .map_err(|err| DispatchError::ResponseBody(err.into()))?;
+
+    match trailers {
+        // response body streaming finished. send trailing headers as end of stream.
+        Some(trailers) => {
+            let mut map = http::HeaderMap::with_capacity(trailers.len());
+            for (name, value) in trailers.iter() {
+                map.append(name.clone(), value.clone());
+            }
+
+            stream.send_trailers(map).map_err(DispatchError::SendData)?;
+        }
+
+        // response body streaming finished. send end of stream and return.
+        None => {
+            stream
+                .send_data(Bytes::new(), true)
+                .map_err(DispatchError::SendData)?;
+        }
+    }
 
     Ok(())
 }
@@ -5,16 +5,41 @@ use std::{
     task::{Context, Poll},
 };
 
+use actix_codec::{AsyncRead, AsyncWrite};
 use bytes::Bytes;
 use futures_core::{ready, Stream};
-use h2::RecvStream;
+use h2::{
+    server::{Builder, Handshake},
+    RecvStream,
+};
 
 mod dispatcher;
 mod service;
+pub(crate) mod upgrade;
 
 pub use self::dispatcher::Dispatcher;
 pub use self::service::H2Service;
-use crate::error::PayloadError;
+use crate::{config::ServiceConfig, error::PayloadError, header::HeaderMap};
+
+/// Starts the server side HTTP/2 handshake with the configured connection settings.
+pub(crate) fn handshake_with_config<T>(
+    io: T,
+    config: &ServiceConfig,
+) -> Handshake<T, Bytes>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    let settings = config.h2_settings();
+
+    let mut builder = Builder::new();
+    builder
+        .max_concurrent_streams(settings.max_concurrent_streams)
+        .initial_window_size(settings.initial_window_size)
+        .initial_connection_window_size(settings.initial_connection_window_size)
+        .max_frame_size(settings.max_frame_size)
+        .max_header_list_size(settings.max_header_list_size);
+    builder.handshake(io)
+}
 
 /// HTTP/2 peer stream.
 pub struct Payload {
@@ -25,6 +50,17 @@ impl Payload {
     pub(crate) fn new(stream: RecvStream) -> Self {
         Self { stream }
     }
+
+    /// Attempt to pull out the trailing HEADERS frame sent after the request body.
+    ///
+    /// Should only be called after the payload stream has yielded `None`.
+    pub fn poll_trailers(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, PayloadError>> {
+        let trailers = ready!(self.stream.poll_trailers(cx))?;
+        Poll::Ready(Ok(trailers.map(HeaderMap::from)))
+    }
 }
 
 impl Stream for Payload {
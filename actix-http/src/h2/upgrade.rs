@@ -0,0 +1,575 @@
+//! Switching HTTP/1.1 connections to cleartext HTTP/2 (h2c) with the `Upgrade` header or by prior
+//! knowledge.
+//!
+//! The `h2` crate can not take over a connection whose first stream was opened by an HTTP/1.1
+//! request. Instead, the upgrade request is encoded as a HEADERS frame of stream 1 and replayed
+//! to the HTTP/2 server right after the client's connection preface, so it is answered like any
+//! other stream.
+//!
+//! The settings of the `HTTP2-Settings` header are replayed the same way, prepended to the
+//! client's first SETTINGS frame. Entries of the frame itself come later and take precedence, and
+//! the server acknowledges both with the single ACK the client is waiting for.
+
+use std::{
+    future::Future,
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use actix_codec::{AsyncRead, AsyncWrite, ReadBuf};
+use actix_rt::time::Sleep;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use futures_core::ready;
+use http::header::{self, HeaderName};
+
+use crate::{
+    config::ServiceConfig, header::HeaderMap, stats::ConnectionGuard, OnConnectData,
+    Request,
+};
+
+/// Client connection preface, sent after the `101 Switching Protocols` response.
+pub(crate) const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+const SWITCHING_PROTOCOLS: &[u8] =
+    b"HTTP/1.1 101 Switching Protocols\r\nconnection: Upgrade\r\nupgrade: h2c\r\n\r\n";
+
+const FRAME_HEADER_LEN: usize = 9;
+
+/// Frame size every HTTP/2 peer accepts before settings are acknowledged.
+const MAX_FRAME_SIZE: usize = 16_384;
+
+const FRAME_HEADERS: u8 = 0x1;
+const FRAME_SETTINGS: u8 = 0x4;
+const FRAME_CONTINUATION: u8 = 0x9;
+
+const FLAG_END_STREAM: u8 = 0x1;
+const FLAG_END_HEADERS: u8 = 0x4;
+
+/// HTTP/1.1 connection handed over for h2c.
+///
+/// `req` is `None` when the client started with the connection preface (prior knowledge).
+pub(crate) struct H2cUpgrade<T> {
+    pub(crate) io: T,
+    pub(crate) read_buf: BytesMut,
+    pub(crate) write_buf: BytesMut,
+    pub(crate) req: Option<Request>,
+    pub(crate) config: ServiceConfig,
+    pub(crate) on_connect_data: OnConnectData,
+    pub(crate) peer_addr: Option<std::net::SocketAddr>,
+    pub(crate) conn_guard: Option<ConnectionGuard>,
+}
+
+/// Returns true if the request asks to switch to h2c and can be replayed as an HTTP/2 stream.
+///
+/// Requests with a body are served over HTTP/1.1 instead; RFC 7540 §3.2 lets servers ignore the
+/// upgrade.
+pub(crate) fn is_h2c_upgrade(req: &Request) -> bool {
+    let head = req.head();
+
+    if head.version != http::Version::HTTP_11
+        || !has_token(&head.headers, &header::UPGRADE, "h2c")
+        || !has_token(&head.headers, &header::CONNECTION, "upgrade")
+        || !has_token(&head.headers, &header::CONNECTION, "http2-settings")
+        || head.headers.contains_key(header::TRANSFER_ENCODING)
+    {
+        return false;
+    }
+
+    let has_body = head
+        .headers
+        .get(header::CONTENT_LENGTH)
+        .map_or(false, |len| len != "0");
+
+    !has_body && settings_payload(req).is_some()
+}
+
+/// Decodes the SETTINGS frame payload carried by the single `HTTP2-Settings` header.
+fn settings_payload(req: &Request) -> Option<Vec<u8>> {
+    let mut settings = req.head().headers.get_all("http2-settings");
+
+    match (settings.next(), settings.next()) {
+        (Some(value), None) => {
+            base64::decode_config(value.as_bytes(), base64::URL_SAFE_NO_PAD)
+                .ok()
+                .filter(|payload| {
+                    payload.len() % 6 == 0 && payload.len() <= MAX_FRAME_SIZE
+                })
+        }
+        _ => None,
+    }
+}
+
+fn has_token(headers: &HeaderMap, name: &HeaderName, token: &str) -> bool {
+    headers.get_all(name).any(|value| {
+        value.to_str().map_or(false, |value| {
+            value
+                .split(',')
+                .any(|item| item.trim().eq_ignore_ascii_case(token))
+        })
+    })
+}
+
+/// Encodes the upgrade request as the HEADERS (and CONTINUATION) frames of stream 1.
+///
+/// Fields are literals that are not added to the HPACK dynamic table, so the decoder state of
+/// the server stays in sync with the client's encoder.
+fn encode_request(req: &Request) -> Bytes {
+    let head = req.head();
+
+    let authority = head
+        .headers
+        .get(header::HOST)
+        .map(|host| host.as_bytes())
+        .or_else(|| head.uri.authority().map(|auth| auth.as_str().as_bytes()))
+        .unwrap_or(b"");
+    let path = head.uri.path_and_query().map_or("/", |path| path.as_str());
+
+    let mut block = BytesMut::new();
+    encode_field(&mut block, b":method", head.method.as_str().as_bytes());
+    encode_field(&mut block, b":scheme", b"http");
+    encode_field(&mut block, b":authority", authority);
+    encode_field(&mut block, b":path", path.as_bytes());
+
+    for (name, value) in head.headers.iter() {
+        match *name {
+            // connection-specific fields are not allowed in HTTP/2
+            header::CONNECTION
+            | header::UPGRADE
+            | header::HOST
+            | header::TRANSFER_ENCODING => continue,
+            header::TE if value != "trailers" => continue,
+            _ if name == "http2-settings"
+                || name == "keep-alive"
+                || name == "proxy-connection" =>
+            {
+                continue
+            }
+            _ => encode_field(&mut block, name.as_str().as_bytes(), value.as_bytes()),
+        }
+    }
+
+    let mut frames = BytesMut::with_capacity(block.len() + FRAME_HEADER_LEN);
+    let mut frame_type = FRAME_HEADERS;
+    let mut flags = FLAG_END_STREAM;
+
+    loop {
+        let len = block.len().min(MAX_FRAME_SIZE);
+        let fragment = block.split_to(len);
+
+        if block.is_empty() {
+            flags |= FLAG_END_HEADERS;
+        }
+
+        frames.put_uint(len as u64, 3);
+        frames.put_u8(frame_type);
+        frames.put_u8(flags);
+        frames.put_u32(1);
+        frames.extend_from_slice(&fragment);
+
+        if block.is_empty() {
+            return frames.freeze();
+        }
+
+        frame_type = FRAME_CONTINUATION;
+        flags = 0;
+    }
+}
+
+/// Encodes a literal header field without indexing, with a literal name.
+fn encode_field(dst: &mut BytesMut, name: &[u8], value: &[u8]) {
+    dst.put_u8(0);
+    encode_string(dst, name);
+    encode_string(dst, value);
+}
+
+/// Encodes a string literal without Huffman coding.
+fn encode_string(dst: &mut BytesMut, val: &[u8]) {
+    encode_int(dst, val.len());
+    dst.extend_from_slice(val);
+}
+
+/// Encodes an integer with a 7-bit prefix.
+fn encode_int(dst: &mut BytesMut, mut val: usize) {
+    const PREFIX_MAX: usize = 0x7f;
+
+    if val < PREFIX_MAX {
+        dst.put_u8(val as u8);
+        return;
+    }
+
+    dst.put_u8(PREFIX_MAX as u8);
+    val -= PREFIX_MAX;
+
+    while val >= 0x80 {
+        dst.put_u8((val & 0x7f) as u8 | 0x80);
+        val >>= 7;
+    }
+
+    dst.put_u8(val as u8);
+}
+
+/// Switches a connection to h2c.
+///
+/// For upgrades, writes the `101 Switching Protocols` response. Then waits for the client's
+/// connection preface and resolves to the connection with the upgrade request, if any, queued
+/// after the preface. Fails with [`io::ErrorKind::TimedOut`] if the preface does not arrive
+/// within the client timeout.
+pub(crate) struct H2cHandshake<T> {
+    io: Option<T>,
+    read_buf: BytesMut,
+    write_buf: BytesMut,
+    settings: Vec<u8>,
+    request: Bytes,
+    timer: Option<Pin<Box<Sleep>>>,
+}
+
+impl<T> H2cHandshake<T> {
+    pub(crate) fn new(
+        io: T,
+        read_buf: BytesMut,
+        mut write_buf: BytesMut,
+        req: Option<&Request>,
+        config: &ServiceConfig,
+    ) -> Self {
+        let (settings, request) = match req {
+            Some(req) => {
+                write_buf.extend_from_slice(SWITCHING_PROTOCOLS);
+                (
+                    settings_payload(req).unwrap_or_default(),
+                    encode_request(req),
+                )
+            }
+            None => (Vec::new(), Bytes::new()),
+        };
+
+        Self {
+            io: Some(io),
+            read_buf,
+            write_buf,
+            settings,
+            request,
+            timer: config.client_timer().map(Box::pin),
+        }
+    }
+}
+
+/// Returns the length of the preface and the first SETTINGS frame, once fully buffered.
+fn preface_len(buf: &[u8]) -> io::Result<Option<usize>> {
+    let len = buf.len().min(PREFACE.len());
+
+    if buf[..len] != PREFACE[..len] {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "invalid HTTP/2 connection preface",
+        ));
+    }
+
+    let frame = &buf[len..];
+    if frame.len() < FRAME_HEADER_LEN {
+        return Ok(None);
+    }
+
+    if frame[3] != FRAME_SETTINGS {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "HTTP/2 connection preface is not followed by SETTINGS",
+        ));
+    }
+
+    let payload_len = (&frame[..3]).get_uint(3) as usize;
+    let total = PREFACE.len() + FRAME_HEADER_LEN + payload_len;
+
+    Ok(if buf.len() >= total {
+        Some(total)
+    } else {
+        None
+    })
+}
+
+/// Writes the client's SETTINGS `frame` with the `settings` entries of the upgrade request
+/// inserted before its own.
+fn merge_settings(dst: &mut BytesMut, settings: &[u8], frame: &[u8]) -> io::Result<()> {
+    let payload = &frame[FRAME_HEADER_LEN..];
+    let len = settings.len() + payload.len();
+
+    if len > MAX_FRAME_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "HTTP/2 SETTINGS frame too large",
+        ));
+    }
+
+    dst.put_uint(len as u64, 3);
+    // type, flags and stream identifier
+    dst.extend_from_slice(&frame[3..FRAME_HEADER_LEN]);
+    dst.extend_from_slice(settings);
+    dst.extend_from_slice(payload);
+
+    Ok(())
+}
+
+impl<T> Future for H2cHandshake<T>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    type Output = io::Result<Rewind<T>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        if let Some(timer) = this.timer.as_mut() {
+            if timer.as_mut().poll(cx).is_ready() {
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "h2c connection preface timed out",
+                )));
+            }
+        }
+
+        let io = this.io.as_mut().unwrap();
+
+        while !this.write_buf.is_empty() {
+            match ready!(Pin::new(&mut *io).poll_write(cx, &this.write_buf))? {
+                0 => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+                n => this.write_buf.advance(n),
+            }
+        }
+        ready!(Pin::new(&mut *io).poll_flush(cx))?;
+
+        loop {
+            if let Some(len) = preface_len(&this.read_buf)? {
+                let mut preface = this.read_buf.split_to(len);
+                let frame = preface.split_off(PREFACE.len());
+
+                // preface and settings, then the upgrade request, then anything sent after
+                let mut prefix = BytesMut::with_capacity(
+                    len + this.settings.len() + this.request.len() + this.read_buf.len(),
+                );
+                prefix.extend_from_slice(&preface);
+                merge_settings(&mut prefix, &this.settings, &frame)?;
+                prefix.extend_from_slice(&this.request);
+                prefix.extend_from_slice(&this.read_buf);
+
+                let io = this.io.take().unwrap();
+                return Poll::Ready(Ok(Rewind::new(io, prefix.freeze())));
+            }
+
+            this.read_buf.reserve(MAX_FRAME_SIZE);
+            if ready!(actix_codec::poll_read_buf(
+                Pin::new(&mut *io),
+                cx,
+                &mut this.read_buf
+            ))? == 0
+            {
+                return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()));
+            }
+        }
+    }
+}
+
+/// IO stream which yields a prefix before reading from the underlying stream.
+pub(crate) struct Rewind<T> {
+    prefix: Bytes,
+    io: T,
+}
+
+impl<T> Rewind<T> {
+    pub(crate) fn new(io: T, prefix: Bytes) -> Self {
+        Self { prefix, io }
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Rewind<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        if this.prefix.is_empty() {
+            return Pin::new(&mut this.io).poll_read(cx, buf);
+        }
+
+        let len = this.prefix.len().min(buf.remaining());
+        buf.put_slice(&this.prefix.split_to(len));
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Rewind<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().io).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().io).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.io.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().io).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{TestBuffer, TestRequest};
+
+    fn upgrade_request() -> TestRequest {
+        TestRequest::default()
+            .insert_header((header::HOST, "example.com"))
+            .insert_header((header::CONNECTION, "Upgrade, HTTP2-Settings"))
+            .insert_header((header::UPGRADE, "h2c"))
+            .insert_header(("http2-settings", "AAMAAABkAARAAAAAAAIAAAAA"))
+            .take()
+    }
+
+    #[test]
+    fn test_is_h2c_upgrade() {
+        let req = upgrade_request().finish();
+        assert!(is_h2c_upgrade(&req));
+
+        let req = upgrade_request()
+            .insert_header((header::CONTENT_LENGTH, "4"))
+            .finish();
+        assert!(!is_h2c_upgrade(&req));
+
+        let req = upgrade_request()
+            .insert_header(("http2-settings", "not base64!"))
+            .finish();
+        assert!(!is_h2c_upgrade(&req));
+
+        let req = upgrade_request()
+            .insert_header((header::UPGRADE, "websocket"))
+            .finish();
+        assert!(!is_h2c_upgrade(&req));
+    }
+
+    #[test]
+    fn test_encode_int() {
+        let mut buf = BytesMut::new();
+        encode_int(&mut buf, 10);
+        assert_eq!(&buf[..], &[10]);
+
+        let mut buf = BytesMut::new();
+        encode_int(&mut buf, 1337);
+        assert_eq!(&buf[..], &[0x7f, 0xba, 0x09]);
+    }
+
+    #[test]
+    fn test_encode_request() {
+        let req = upgrade_request()
+            .uri("/test?q=1")
+            .insert_header(("x-test", "value"))
+            .finish();
+        let frames = encode_request(&req);
+
+        assert_eq!(frames[3], FRAME_HEADERS);
+        assert_eq!(frames[4], FLAG_END_STREAM | FLAG_END_HEADERS);
+        assert_eq!(&frames[5..9], &[0, 0, 0, 1]);
+
+        let len = (&frames[..3]).get_uint(3) as usize;
+        assert_eq!(frames.len(), FRAME_HEADER_LEN + len);
+
+        let block = &frames[FRAME_HEADER_LEN..];
+        let contains = |field: &[u8]| block.windows(field.len()).any(|w| w == field);
+        assert!(contains(b"\x00\x05:path\x09/test?q=1"));
+        assert!(contains(b"\x00\x0a:authority\x0bexample.com"));
+        assert!(contains(b"\x00\x06x-test\x05value"));
+        assert!(!contains(b"upgrade"));
+        assert!(!contains(b"http2-settings"));
+    }
+
+    #[test]
+    fn test_encode_request_continuation() {
+        let req = upgrade_request()
+            .insert_header(("x-large", "x".repeat(MAX_FRAME_SIZE)))
+            .finish();
+        let frames = encode_request(&req);
+
+        assert_eq!(frames[3], FRAME_HEADERS);
+        assert_eq!(frames[4], FLAG_END_STREAM);
+
+        let len = (&frames[..3]).get_uint(3) as usize;
+        assert_eq!(len, MAX_FRAME_SIZE);
+
+        let next = &frames[FRAME_HEADER_LEN + len..];
+        assert_eq!(next[3], FRAME_CONTINUATION);
+        assert_eq!(next[4], FLAG_END_HEADERS);
+    }
+
+    #[actix_rt::test]
+    async fn test_handshake_merges_settings() {
+        let req = upgrade_request().finish();
+        let header_settings = settings_payload(&req).unwrap();
+
+        // client SETTINGS frame with SETTINGS_MAX_CONCURRENT_STREAMS = 1
+        let mut read_buf = BytesMut::from(PREFACE);
+        read_buf.extend_from_slice(b"\0\0\x06\x04\0\0\0\0\0\0\x03\0\0\0\x01");
+
+        let handshake = H2cHandshake::new(
+            TestBuffer::empty(),
+            read_buf,
+            BytesMut::new(),
+            Some(&req),
+            &ServiceConfig::default(),
+        );
+        let io = handshake.await.unwrap();
+        assert!(io.io.write_buf.starts_with(SWITCHING_PROTOCOLS));
+
+        assert!(io.prefix.starts_with(PREFACE));
+        let frame = &io.prefix[PREFACE.len()..];
+        let len = header_settings.len() + 6;
+        assert_eq!(&frame[..3], &(len as u32).to_be_bytes()[1..]);
+        assert_eq!(
+            &frame[3..FRAME_HEADER_LEN],
+            &[FRAME_SETTINGS, 0, 0, 0, 0, 0]
+        );
+
+        // entries of the frame itself come last and take precedence
+        let payload = &frame[FRAME_HEADER_LEN..FRAME_HEADER_LEN + len];
+        assert_eq!(&payload[..header_settings.len()], &header_settings[..]);
+        assert_eq!(&payload[header_settings.len()..], b"\0\x03\0\0\0\x01");
+
+        let request = &frame[FRAME_HEADER_LEN + len..];
+        assert_eq!(request, &encode_request(&req)[..]);
+    }
+
+    #[actix_rt::test]
+    async fn test_handshake_prior_knowledge() {
+        let mut read_buf = BytesMut::from(PREFACE);
+        read_buf.extend_from_slice(b"\0\0\0\x04\0\0\0\0\0rest");
+
+        let handshake = H2cHandshake::new(
+            TestBuffer::empty(),
+            read_buf.clone(),
+            BytesMut::new(),
+            None,
+            &ServiceConfig::default(),
+        );
+        let io = handshake.await.unwrap();
+        assert!(io.io.write_buf.is_empty());
+        assert_eq!(io.prefix, read_buf);
+    }
+}
@@ -44,6 +44,8 @@ mod request;
 mod response;
 mod response_builder;
 mod service;
+mod shutdown;
+mod stats;
 mod time_parser;
 
 pub mod error;
@@ -53,7 +55,7 @@ pub mod test;
 pub mod ws;
 
 pub use self::builder::HttpServiceBuilder;
-pub use self::config::{KeepAlive, ServiceConfig};
+pub use self::config::{H2Settings, HeadLimits, KeepAlive, ServiceConfig};
 pub use self::error::Error;
 pub use self::extensions::Extensions;
 pub use self::header::ContentEncoding;
@@ -65,6 +67,8 @@ pub use self::request::Request;
 pub use self::response::Response;
 pub use self::response_builder::ResponseBuilder;
 pub use self::service::HttpService;
+pub use self::shutdown::ShutdownSignal;
+pub use self::stats::ConnectionStats;
 
 pub use ::http::{uri, uri::Uri};
 pub use ::http::{Method, StatusCode, Version};
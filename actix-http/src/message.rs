@@ -32,6 +32,7 @@ bitflags! {
         const EXPECT      = 0b0000_1000;
         const NO_CHUNKING = 0b0001_0000;
         const CAMEL_CASE  = 0b0010_0000;
+        const FULL_TARGET = 0b0100_0000;
     }
 }
 
@@ -189,6 +190,18 @@ impl RequestHead {
     pub(crate) fn set_expect(&mut self) {
         self.flags.insert(Flags::EXPECT);
     }
+
+    /// Request target is the whole URI instead of its path and query.
+    #[inline]
+    pub(crate) fn full_target(&self) -> bool {
+        self.flags.contains(Flags::FULL_TARGET)
+    }
+
+    /// Send the whole URI as request target, as needed by forward proxies and `CONNECT`.
+    #[inline]
+    pub(crate) fn set_full_target(&mut self) {
+        self.flags.insert(Flags::FULL_TARGET);
+    }
 }
 
 #[derive(Debug)]
@@ -204,6 +217,29 @@ impl RequestHeadType {
             RequestHeadType::Rc(_, headers) => headers.as_ref(),
         }
     }
+
+    /// Converts into an owned head, merging extra headers. Extensions of shared heads are lost.
+    pub(crate) fn into_owned(self) -> RequestHead {
+        match self {
+            RequestHeadType::Owned(head) => head,
+            RequestHeadType::Rc(head, extra_headers) => {
+                let mut headers = head.headers.clone();
+                if let Some(extra_headers) = extra_headers {
+                    headers.inner.extend(extra_headers.inner);
+                }
+
+                RequestHead {
+                    uri: head.uri.clone(),
+                    method: head.method.clone(),
+                    version: head.version,
+                    headers,
+                    extensions: RefCell::new(Extensions::new()),
+                    peer_addr: head.peer_addr,
+                    flags: head.flags,
+                }
+            }
+        }
+    }
 }
 
 impl AsRef<RequestHead> for RequestHeadType {
@@ -5,7 +5,7 @@ use bytes::Bytes;
 use futures_core::Stream;
 use h2::RecvStream;
 
-use crate::error::PayloadError;
+use crate::{error::PayloadError, header::HeaderMap};
 
 /// Type represent boxed payload
 pub type PayloadStream = Pin<Box<dyn Stream<Item = Result<Bytes, PayloadError>>>>;
@@ -47,6 +47,23 @@ impl<S> Payload<S> {
     pub fn take(&mut self) -> Payload<S> {
         std::mem::replace(self, Payload::None)
     }
+
+    /// Attempt to pull out the trailer fields sent after the payload.
+    ///
+    /// Trailers are received after the last chunk of a chunked HTTP/1.1 payload or as a
+    /// trailing HEADERS frame in HTTP/2. Resolves to `None` if the peer sent no trailers or the
+    /// payload type does not support them. Should only be called after the payload stream
+    /// has yielded `None`.
+    pub fn poll_trailers(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, PayloadError>> {
+        match self {
+            Payload::H1(ref mut pl) => pl.poll_trailers(cx),
+            Payload::H2(ref mut pl) => pl.poll_trailers(cx),
+            Payload::None | Payload::Stream(_) => Poll::Ready(Ok(None)),
+        }
+    }
 }
 
 impl<S> Stream for Payload<S>
@@ -9,7 +9,7 @@ use std::{
     task::{Context, Poll},
 };
 
-use ::h2::server::{handshake as h2_handshake, Handshake as H2Handshake};
+use ::h2::server::Handshake as H2Handshake;
 use actix_codec::{AsyncRead, AsyncWrite, Framed};
 use actix_rt::net::TcpStream;
 use actix_service::{
@@ -24,7 +24,13 @@ use crate::{
     builder::HttpServiceBuilder,
     config::{KeepAlive, ServiceConfig},
     error::DispatchError,
-    h1, h2, ConnectCallback, OnConnectData, Protocol, Request, Response,
+    h1::{self, sendfile, sendfile::SendFileFn},
+    h2::{
+        self,
+        upgrade::{H2cHandshake, Rewind},
+    },
+    stats::{ConnectionGuard, ConnectionStats},
+    ConnectCallback, OnConnectData, Protocol, Request, Response,
 };
 
 /// A `ServiceFactory` for HTTP/1.1 or HTTP/2 protocol.
@@ -34,6 +40,7 @@ pub struct HttpService<T, S, B, X = h1::ExpectHandler, U = h1::UpgradeHandler> {
     expect: X,
     upgrade: Option<U>,
     on_connect_ext: Option<Rc<ConnectCallback<T>>>,
+    sendfile: Option<SendFileFn<T>>,
     _phantom: PhantomData<B>,
 }
 
@@ -72,6 +79,7 @@ where
             expect: h1::ExpectHandler,
             upgrade: None,
             on_connect_ext: None,
+            sendfile: None,
             _phantom: PhantomData,
         }
     }
@@ -87,6 +95,7 @@ where
             expect: h1::ExpectHandler,
             upgrade: None,
             on_connect_ext: None,
+            sendfile: None,
             _phantom: PhantomData,
         }
     }
@@ -118,6 +127,7 @@ where
             srv: self.srv,
             upgrade: self.upgrade,
             on_connect_ext: self.on_connect_ext,
+            sendfile: self.sendfile,
             _phantom: PhantomData,
         }
     }
@@ -138,6 +148,7 @@ where
             srv: self.srv,
             expect: self.expect,
             on_connect_ext: self.on_connect_ext,
+            sendfile: self.sendfile,
             _phantom: PhantomData,
         }
     }
@@ -177,7 +188,7 @@ where
 {
     /// Create simple tcp stream service
     pub fn tcp(
-        self,
+        mut self,
     ) -> impl ServiceFactory<
         TcpStream,
         Config = (),
@@ -185,6 +196,8 @@ where
         Error = DispatchError,
         InitError = (),
     > {
+        self.sendfile = sendfile::tcp();
+
         fn_service(|io: TcpStream| async {
             let peer_addr = io.peer_addr().ok();
             Ok((io, Protocol::Http1, peer_addr))
@@ -369,6 +382,7 @@ where
         let expect = self.expect.new_service(());
         let upgrade = self.upgrade.as_ref().map(|s| s.new_service(()));
         let on_connect_ext = self.on_connect_ext.clone();
+        let sendfile = self.sendfile;
         let cfg = self.cfg.clone();
 
         Box::pin(async move {
@@ -396,6 +410,7 @@ where
                 expect,
                 upgrade,
                 on_connect_ext,
+                sendfile,
             ))
         })
     }
@@ -411,6 +426,7 @@ where
     pub(super) flow: Rc<HttpFlow<S, X, U>>,
     pub(super) cfg: ServiceConfig,
     pub(super) on_connect_ext: Option<Rc<ConnectCallback<T>>>,
+    pub(super) sendfile: Option<SendFileFn<T>>,
     _phantom: PhantomData<B>,
 }
 
@@ -429,10 +445,12 @@ where
         expect: X,
         upgrade: Option<U>,
         on_connect_ext: Option<Rc<ConnectCallback<T>>>,
+        sendfile: Option<SendFileFn<T>>,
     ) -> HttpServiceHandler<T, S, B, X, U> {
         HttpServiceHandler {
             cfg,
             on_connect_ext,
+            sendfile,
             flow: HttpFlow::new(service, expect, upgrade),
             _phantom: PhantomData,
         }
@@ -511,22 +529,29 @@ where
         match proto {
             Protocol::Http2 => HttpServiceHandlerResponse {
                 state: State::H2Handshake(Some((
-                    h2_handshake(io),
+                    h2::handshake_with_config(Rewind::new(io, Bytes::new()), &self.cfg),
                     self.cfg.clone(),
                     self.flow.clone(),
                     on_connect_data,
                     peer_addr,
+                    self.cfg
+                        .connection_stats()
+                        .map(ConnectionStats::connection_opened),
                 ))),
             },
 
             Protocol::Http1 => HttpServiceHandlerResponse {
-                state: State::H1(h1::Dispatcher::new(
-                    io,
-                    self.cfg.clone(),
-                    self.flow.clone(),
-                    on_connect_data,
-                    peer_addr,
-                )),
+                state: State::H1(
+                    h1::Dispatcher::new(
+                        io,
+                        self.cfg.clone(),
+                        self.flow.clone(),
+                        on_connect_data,
+                        peer_addr,
+                    )
+                    .with_sendfile(self.sendfile)
+                    .with_h2c(),
+                ),
             },
 
             proto => unimplemented!("Unsupported HTTP version: {:?}.", proto),
@@ -553,14 +578,25 @@ where
     U::Error: fmt::Display,
 {
     H1(#[pin] h1::Dispatcher<T, S, B, X, U>),
-    H2(#[pin] h2::Dispatcher<T, S, B, X, U>),
+    H2(#[pin] h2::Dispatcher<Rewind<T>, S, B, X, U>),
+    H2cHandshake(
+        Option<(
+            H2cHandshake<T>,
+            ServiceConfig,
+            Rc<HttpFlow<S, X, U>>,
+            OnConnectData,
+            Option<net::SocketAddr>,
+            Option<ConnectionGuard>,
+        )>,
+    ),
     H2Handshake(
         Option<(
-            H2Handshake<T, Bytes>,
+            H2Handshake<Rewind<T>, Bytes>,
             ServiceConfig,
             Rc<HttpFlow<S, X, U>>,
             OnConnectData,
             Option<net::SocketAddr>,
+            Option<ConnectionGuard>,
         )>,
     ),
 }
@@ -610,20 +646,67 @@ where
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         match self.as_mut().project().state.project() {
-            StateProj::H1(disp) => disp.poll(cx),
+            StateProj::H1(mut disp) => {
+                ready!(disp.as_mut().poll(cx))?;
+
+                match disp.take_h2c_upgrade() {
+                    Some((upgrade, srv)) => {
+                        let handshake = H2cHandshake::new(
+                            upgrade.io,
+                            upgrade.read_buf,
+                            upgrade.write_buf,
+                            upgrade.req.as_ref(),
+                            &upgrade.config,
+                        );
+                        self.as_mut().project().state.set(State::H2cHandshake(Some((
+                            handshake,
+                            upgrade.config,
+                            srv,
+                            upgrade.on_connect_data,
+                            upgrade.peer_addr,
+                            upgrade.conn_guard,
+                        ))));
+                        self.poll(cx)
+                    }
+                    None => Poll::Ready(Ok(())),
+                }
+            }
             StateProj::H2(disp) => disp.poll(cx),
+            StateProj::H2cHandshake(data) => {
+                match ready!(Pin::new(&mut data.as_mut().unwrap().0).poll(cx)) {
+                    Ok(io) => {
+                        let (_, cfg, srv, on_connect_data, peer_addr, conn_guard) =
+                            data.take().unwrap();
+                        let handshake = h2::handshake_with_config(io, &cfg);
+                        self.as_mut().project().state.set(State::H2Handshake(Some((
+                            handshake,
+                            cfg,
+                            srv,
+                            on_connect_data,
+                            peer_addr,
+                            conn_guard,
+                        ))));
+                        self.poll(cx)
+                    }
+                    Err(err) => {
+                        trace!("h2c handshake error: {}", err);
+                        Poll::Ready(Err(err.into()))
+                    }
+                }
+            }
             StateProj::H2Handshake(data) => {
                 match ready!(Pin::new(&mut data.as_mut().unwrap().0).poll(cx)) {
                     Ok(conn) => {
-                        let (_, cfg, srv, on_connect_data, peer_addr) =
+                        let (_, cfg, srv, on_connect_data, peer_addr, conn_guard) =
                             data.take().unwrap();
                         self.as_mut().project().state.set(State::H2(
-                            h2::Dispatcher::new(
+                            h2::Dispatcher::with_conn_guard(
                                 srv,
                                 conn,
                                 on_connect_data,
                                 cfg,
                                 peer_addr,
+                                conn_guard,
                             ),
                         ));
                         self.poll(cx)
@@ -0,0 +1,83 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use tokio::sync::Notify;
+
+/// Signal for shutting down HTTP/2 connections gracefully.
+///
+/// When triggered, every HTTP/2 connection of the services it was given to sends a GOAWAY frame,
+/// stops accepting new streams and closes once its in-flight streams have completed. Trigger it
+/// before stopping the server so that clients can retry their next requests on a new connection.
+///
+/// Clones share the same signal, so one value can be used by every worker.
+///
+/// # Examples
+/// ```
+/// use actix_http::{HttpService, Response, ShutdownSignal};
+///
+/// # actix_rt::System::new().block_on(async {
+/// let signal = ShutdownSignal::new();
+///
+/// let _factory = HttpService::build()
+///     .shutdown_signal(signal.clone())
+///     .h2(|_| async { Ok::<_, std::convert::Infallible>(Response::ok()) })
+///     .tcp();
+///
+/// // on shutdown
+/// signal.shutdown();
+/// assert!(signal.is_shutdown());
+/// # });
+/// ```
+#[derive(Clone, Default)]
+pub struct ShutdownSignal(Arc<Inner>);
+
+#[derive(Default)]
+struct Inner {
+    triggered: AtomicBool,
+    notify: Notify,
+}
+
+impl ShutdownSignal {
+    /// Constructs a new signal which has not been triggered yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Triggers the signal, asking all connections to shut down gracefully.
+    pub fn shutdown(&self) {
+        self.0.triggered.store(true, Ordering::SeqCst);
+        self.0.notify.notify_waiters();
+    }
+
+    /// Returns true if the signal has been triggered.
+    pub fn is_shutdown(&self) -> bool {
+        self.0.triggered.load(Ordering::SeqCst)
+    }
+
+    /// Resolves once the signal is triggered.
+    pub(crate) async fn triggered(self) {
+        loop {
+            // register before checking the flag so a concurrent trigger is not missed
+            let notified = self.0.notify.notified();
+
+            if self.is_shutdown() {
+                return;
+            }
+
+            notified.await;
+        }
+    }
+}
+
+impl fmt::Debug for ShutdownSignal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShutdownSignal")
+            .field("triggered", &self.is_shutdown())
+            .finish()
+    }
+}
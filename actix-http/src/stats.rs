@@ -0,0 +1,134 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+/// Connection-level counters maintained by the HTTP/1 and HTTP/2 dispatchers.
+///
+/// Clones share the same counters, so a value created outside of the server factory can be used
+/// by every worker and read from anywhere, e.g. by a metrics handler.
+///
+/// # Examples
+/// ```
+/// use actix_http::{ConnectionStats, HttpService, Response};
+///
+/// # actix_rt::System::new().block_on(async {
+/// let stats = ConnectionStats::new();
+///
+/// let _factory = HttpService::build()
+///     .connection_stats(stats.clone())
+///     .h1(|_| async { Ok::<_, std::convert::Infallible>(Response::ok()) })
+///     .tcp();
+///
+/// assert_eq!(stats.open_connections(), 0);
+/// # });
+/// ```
+#[derive(Clone, Default)]
+pub struct ConnectionStats(Arc<Inner>);
+
+#[derive(Default)]
+struct Inner {
+    open: AtomicUsize,
+    total: AtomicU64,
+    reuses: AtomicU64,
+}
+
+impl ConnectionStats {
+    /// Constructs a new set of counters, all starting at zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of connections currently being served.
+    pub fn open_connections(&self) -> usize {
+        self.0.open.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of connections accepted so far.
+    pub fn total_connections(&self) -> u64 {
+        self.0.total.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of requests served on an already used connection.
+    ///
+    /// For HTTP/1 this counts keep-alive (and pipelined) requests. For HTTP/2 every stream after
+    /// the first one on a connection is counted.
+    pub fn keep_alive_reuses(&self) -> u64 {
+        self.0.reuses.load(Ordering::Relaxed)
+    }
+
+    /// Records a new connection, which stays open until the returned guard is dropped.
+    pub(crate) fn connection_opened(&self) -> ConnectionGuard {
+        self.0.open.fetch_add(1, Ordering::Relaxed);
+        self.0.total.fetch_add(1, Ordering::Relaxed);
+
+        ConnectionGuard {
+            stats: self.clone(),
+            requests: 0,
+        }
+    }
+
+    pub(crate) fn record_reuse(&self) {
+        self.0.reuses.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl fmt::Debug for ConnectionStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectionStats")
+            .field("open_connections", &self.open_connections())
+            .field("total_connections", &self.total_connections())
+            .field("keep_alive_reuses", &self.keep_alive_reuses())
+            .finish()
+    }
+}
+
+/// Marks a connection as open for as long as it is alive.
+pub(crate) struct ConnectionGuard {
+    stats: ConnectionStats,
+    requests: u64,
+}
+
+impl ConnectionGuard {
+    /// Records a request received on this connection.
+    pub(crate) fn request(&mut self) {
+        if self.requests > 0 {
+            self.stats.record_reuse();
+        }
+
+        self.requests += 1;
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.stats.0.open.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counters() {
+        let stats = ConnectionStats::new();
+
+        let mut conn = stats.connection_opened();
+        conn.request();
+        conn.request();
+        let conn2 = stats.connection_opened();
+
+        assert_eq!(stats.open_connections(), 2);
+        assert_eq!(stats.total_connections(), 2);
+        assert_eq!(stats.keep_alive_reuses(), 1);
+
+        drop(conn);
+        drop(conn2);
+        assert_eq!(stats.open_connections(), 0);
+        assert_eq!(stats.total_connections(), 2);
+    }
+}
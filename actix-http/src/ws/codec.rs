@@ -3,6 +3,8 @@ use bitflags::bitflags;
 use bytes::{Bytes, BytesMut};
 use bytestring::ByteString;
 
+#[cfg(feature = "ws-deflate")]
+use super::deflate::{DeflateConfig, DeflateContext};
 use super::frame::Parser;
 use super::proto::{CloseReason, OpCode};
 use super::ProtocolError;
@@ -63,11 +65,13 @@ pub enum Item {
     Last(Bytes),
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 /// WebSocket protocol codec.
 pub struct Codec {
     flags: Flags,
     max_size: usize,
+    #[cfg(feature = "ws-deflate")]
+    deflate: Option<DeflateContext>,
 }
 
 bitflags! {
@@ -75,15 +79,21 @@ bitflags! {
         const SERVER         = 0b0000_0001;
         const CONTINUATION   = 0b0000_0010;
         const W_CONTINUATION = 0b0000_0100;
+        const COMPRESSED     = 0b0000_1000;
     }
 }
 
+/// RSV1 bit of the first frame header byte; marks compressed messages.
+const RSV1: u8 = 0x40;
+
 impl Codec {
     /// Create new WebSocket frames decoder.
     pub const fn new() -> Codec {
         Codec {
             max_size: 65_536,
             flags: Flags::SERVER,
+            #[cfg(feature = "ws-deflate")]
+            deflate: None,
         }
     }
 
@@ -102,6 +112,88 @@ impl Codec {
         self.flags.remove(Flags::SERVER);
         self
     }
+
+    /// Enable the `permessage-deflate` extension with parameters agreed during the handshake.
+    ///
+    /// Outgoing text and binary messages are compressed and incoming compressed messages are
+    /// transparently decompressed. Max frame size applies to decompressed payloads as well.
+    ///
+    /// Compression contexts are not shared between clones; a cloned codec starts fresh.
+    #[cfg(feature = "ws-deflate")]
+    pub fn permessage_deflate(mut self, params: DeflateConfig) -> Self {
+        self.deflate = Some(DeflateContext::new(params));
+        self
+    }
+
+    #[cfg(feature = "ws-deflate")]
+    fn deflate_enabled(&self) -> bool {
+        self.deflate.is_some()
+    }
+
+    #[cfg(not(feature = "ws-deflate"))]
+    fn deflate_enabled(&self) -> bool {
+        false
+    }
+
+    /// Write (a fragment of) a data message, compressing it if the extension is enabled.
+    fn write_data(
+        &mut self,
+        dst: &mut BytesMut,
+        data: &[u8],
+        op: OpCode,
+        fin: bool,
+    ) -> Result<(), ProtocolError> {
+        let server = self.flags.contains(Flags::SERVER);
+
+        #[cfg(feature = "ws-deflate")]
+        if let Some(ref mut deflate) = self.deflate {
+            let payload = deflate.compress(server, data, fin)?;
+
+            // only the first frame of a message is marked as compressed
+            let rsv1 = op != OpCode::Continue;
+            Parser::write_frame(dst, payload, op, fin, rsv1, !server);
+            return Ok(());
+        }
+
+        Parser::write_message(dst, data, op, fin, !server);
+        Ok(())
+    }
+
+    /// Check reserved bits of an incoming frame and return whether its payload is compressed.
+    fn check_reserved_bits(
+        &self,
+        rsv: u8,
+        opcode: OpCode,
+    ) -> Result<bool, ProtocolError> {
+        match opcode {
+            OpCode::Text | OpCode::Binary if rsv == RSV1 && self.deflate_enabled() => {
+                Ok(true)
+            }
+            OpCode::Continue if rsv == 0 => Ok(self.flags.contains(Flags::COMPRESSED)),
+            _ if rsv == 0 => Ok(false),
+            _ => Err(ProtocolError::UnexpectedReservedBits),
+        }
+    }
+
+    /// Decompress payload of an incoming data frame if the message is compressed.
+    #[allow(unused_variables)]
+    fn data_payload(
+        &mut self,
+        payload: Option<BytesMut>,
+        compressed: bool,
+        fin: bool,
+    ) -> Result<Bytes, ProtocolError> {
+        let payload = payload.map(|pl| pl.freeze()).unwrap_or_default();
+
+        #[cfg(feature = "ws-deflate")]
+        if compressed {
+            let server = self.flags.contains(Flags::SERVER);
+            let deflate = self.deflate.as_mut().unwrap();
+            return deflate.decompress(server, &payload, fin, self.max_size);
+        }
+
+        Ok(payload)
+    }
 }
 
 impl Encoder<Message> for Codec {
@@ -109,20 +201,10 @@ impl Encoder<Message> for Codec {
 
     fn encode(&mut self, item: Message, dst: &mut BytesMut) -> Result<(), Self::Error> {
         match item {
-            Message::Text(txt) => Parser::write_message(
-                dst,
-                txt,
-                OpCode::Text,
-                true,
-                !self.flags.contains(Flags::SERVER),
-            ),
-            Message::Binary(bin) => Parser::write_message(
-                dst,
-                bin,
-                OpCode::Binary,
-                true,
-                !self.flags.contains(Flags::SERVER),
-            ),
+            Message::Text(txt) => {
+                self.write_data(dst, txt.as_bytes(), OpCode::Text, true)?
+            }
+            Message::Binary(bin) => self.write_data(dst, &bin, OpCode::Binary, true)?,
             Message::Ping(txt) => Parser::write_message(
                 dst,
                 txt,
@@ -146,13 +228,7 @@ impl Encoder<Message> for Codec {
                         return Err(ProtocolError::ContinuationStarted);
                     } else {
                         self.flags.insert(Flags::W_CONTINUATION);
-                        Parser::write_message(
-                            dst,
-                            &data[..],
-                            OpCode::Text,
-                            false,
-                            !self.flags.contains(Flags::SERVER),
-                        )
+                        self.write_data(dst, &data[..], OpCode::Text, false)?;
                     }
                 }
                 Item::FirstBinary(data) => {
@@ -160,24 +236,12 @@ impl Encoder<Message> for Codec {
                         return Err(ProtocolError::ContinuationStarted);
                     } else {
                         self.flags.insert(Flags::W_CONTINUATION);
-                        Parser::write_message(
-                            dst,
-                            &data[..],
-                            OpCode::Binary,
-                            false,
-                            !self.flags.contains(Flags::SERVER),
-                        )
+                        self.write_data(dst, &data[..], OpCode::Binary, false)?;
                     }
                 }
                 Item::Continue(data) => {
                     if self.flags.contains(Flags::W_CONTINUATION) {
-                        Parser::write_message(
-                            dst,
-                            &data[..],
-                            OpCode::Continue,
-                            false,
-                            !self.flags.contains(Flags::SERVER),
-                        )
+                        self.write_data(dst, &data[..], OpCode::Continue, false)?;
                     } else {
                         return Err(ProtocolError::ContinuationNotStarted);
                     }
@@ -185,13 +249,7 @@ impl Encoder<Message> for Codec {
                 Item::Last(data) => {
                     if self.flags.contains(Flags::W_CONTINUATION) {
                         self.flags.remove(Flags::W_CONTINUATION);
-                        Parser::write_message(
-                            dst,
-                            &data[..],
-                            OpCode::Continue,
-                            true,
-                            !self.flags.contains(Flags::SERVER),
-                        )
+                        self.write_data(dst, &data[..], OpCode::Continue, true)?;
                     } else {
                         return Err(ProtocolError::ContinuationNotStarted);
                     }
@@ -208,17 +266,18 @@ impl Decoder for Codec {
     type Error = ProtocolError;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        match Parser::parse(src, self.flags.contains(Flags::SERVER), self.max_size) {
-            Ok(Some((finished, opcode, payload))) => {
+        match Parser::parse_frame(src, self.flags.contains(Flags::SERVER), self.max_size)
+        {
+            Ok(Some((finished, rsv, opcode, payload))) => {
+                let compressed = self.check_reserved_bits(rsv, opcode)?;
+
                 // continuation is not supported
                 if !finished {
                     return match opcode {
                         OpCode::Continue => {
                             if self.flags.contains(Flags::CONTINUATION) {
                                 Ok(Some(Frame::Continuation(Item::Continue(
-                                    payload
-                                        .map(|pl| pl.freeze())
-                                        .unwrap_or_else(Bytes::new),
+                                    self.data_payload(payload, compressed, false)?,
                                 ))))
                             } else {
                                 Err(ProtocolError::ContinuationNotStarted)
@@ -227,10 +286,9 @@ impl Decoder for Codec {
                         OpCode::Binary => {
                             if !self.flags.contains(Flags::CONTINUATION) {
                                 self.flags.insert(Flags::CONTINUATION);
+                                self.flags.set(Flags::COMPRESSED, compressed);
                                 Ok(Some(Frame::Continuation(Item::FirstBinary(
-                                    payload
-                                        .map(|pl| pl.freeze())
-                                        .unwrap_or_else(Bytes::new),
+                                    self.data_payload(payload, compressed, false)?,
                                 ))))
                             } else {
                                 Err(ProtocolError::ContinuationStarted)
@@ -239,10 +297,9 @@ impl Decoder for Codec {
                         OpCode::Text => {
                             if !self.flags.contains(Flags::CONTINUATION) {
                                 self.flags.insert(Flags::CONTINUATION);
+                                self.flags.set(Flags::COMPRESSED, compressed);
                                 Ok(Some(Frame::Continuation(Item::FirstText(
-                                    payload
-                                        .map(|pl| pl.freeze())
-                                        .unwrap_or_else(Bytes::new),
+                                    self.data_payload(payload, compressed, false)?,
                                 ))))
                             } else {
                                 Err(ProtocolError::ContinuationStarted)
@@ -258,9 +315,9 @@ impl Decoder for Codec {
                 match opcode {
                     OpCode::Continue => {
                         if self.flags.contains(Flags::CONTINUATION) {
-                            self.flags.remove(Flags::CONTINUATION);
+                            self.flags.remove(Flags::CONTINUATION | Flags::COMPRESSED);
                             Ok(Some(Frame::Continuation(Item::Last(
-                                payload.map(|pl| pl.freeze()).unwrap_or_else(Bytes::new),
+                                self.data_payload(payload, compressed, true)?,
                             ))))
                         } else {
                             Err(ProtocolError::ContinuationNotStarted)
@@ -282,10 +339,10 @@ impl Decoder for Codec {
                         payload.map(|pl| pl.freeze()).unwrap_or_else(Bytes::new),
                     ))),
                     OpCode::Binary => Ok(Some(Frame::Binary(
-                        payload.map(|pl| pl.freeze()).unwrap_or_else(Bytes::new),
+                        self.data_payload(payload, compressed, true)?,
                     ))),
                     OpCode::Text => Ok(Some(Frame::Text(
-                        payload.map(|pl| pl.freeze()).unwrap_or_else(Bytes::new),
+                        self.data_payload(payload, compressed, true)?,
                     ))),
                 }
             }
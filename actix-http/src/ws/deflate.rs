@@ -0,0 +1,756 @@
+//! Per-message compression extension (RFC 7692).
+
+use std::{cmp, fmt, io};
+
+use bytes::Bytes;
+use derive_more::{Display, Error};
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress};
+
+use super::ProtocolError;
+use crate::header::{HeaderMap, HeaderValue, SEC_WEBSOCKET_EXTENSIONS};
+
+const EXTENSION_NAME: &str = "permessage-deflate";
+
+const SERVER_NO_CONTEXT_TAKEOVER: &str = "server_no_context_takeover";
+const CLIENT_NO_CONTEXT_TAKEOVER: &str = "client_no_context_takeover";
+const SERVER_MAX_WINDOW_BITS: &str = "server_max_window_bits";
+const CLIENT_MAX_WINDOW_BITS: &str = "client_max_window_bits";
+
+/// Tail of a sync-flushed deflate block; stripped from the end of every compressed message.
+const DEFLATE_TRAILER: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+/// Smallest window size allowed by RFC 7692.
+const MIN_WINDOW_BITS: u8 = 8;
+
+/// Smallest window size supported by the deflate implementation when compressing.
+const MIN_COMPRESS_WINDOW_BITS: u8 = 9;
+
+const MAX_WINDOW_BITS: u8 = 15;
+
+/// Parameters of the `permessage-deflate` WebSocket extension.
+///
+/// Before the opening handshake this describes the local preferences: the largest LZ77 window
+/// each side may use and whether each side must reset its compression context after every
+/// message. The negotiation methods return a new `DeflateConfig` holding the parameters both
+/// peers agreed upon; only those should be passed to [`Codec::permessage_deflate`].
+///
+/// # Server
+/// ```
+/// use actix_http::{test::TestRequest, ws::{Codec, DeflateConfig}};
+///
+/// let req = TestRequest::default()
+///     .insert_header(("sec-websocket-extensions", "permessage-deflate"))
+///     .finish();
+///
+/// let (params, res_header) = DeflateConfig::new()
+///     .server_no_context_takeover()
+///     .accept_offer(req.head().headers())
+///     .unwrap();
+///
+/// assert_eq!(res_header, "permessage-deflate; server_no_context_takeover");
+///
+/// let codec = Codec::new().permessage_deflate(params);
+/// ```
+///
+/// [`Codec::permessage_deflate`]: super::Codec::permessage_deflate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeflateConfig {
+    server_max_window_bits: u8,
+    client_max_window_bits: u8,
+    server_no_context_takeover: bool,
+    client_no_context_takeover: bool,
+    compression_level: u32,
+}
+
+impl Default for DeflateConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeflateConfig {
+    /// Create config with the largest windows, context takeover allowed on both sides and fast
+    /// compression.
+    pub fn new() -> Self {
+        DeflateConfig {
+            server_max_window_bits: MAX_WINDOW_BITS,
+            client_max_window_bits: MAX_WINDOW_BITS,
+            server_no_context_takeover: false,
+            client_no_context_takeover: false,
+            compression_level: Compression::fast().level(),
+        }
+    }
+
+    /// Set the maximum LZ77 window size (as a base-2 logarithm) the server may compress with.
+    ///
+    /// # Panics
+    /// Panics if `bits` is not in the range `9..=15`.
+    pub fn server_max_window_bits(mut self, bits: u8) -> Self {
+        assert!(
+            (MIN_COMPRESS_WINDOW_BITS..=MAX_WINDOW_BITS).contains(&bits),
+            "window bits must be within 9..=15"
+        );
+        self.server_max_window_bits = bits;
+        self
+    }
+
+    /// Set the maximum LZ77 window size (as a base-2 logarithm) the client may compress with.
+    ///
+    /// # Panics
+    /// Panics if `bits` is not in the range `9..=15`.
+    pub fn client_max_window_bits(mut self, bits: u8) -> Self {
+        assert!(
+            (MIN_COMPRESS_WINDOW_BITS..=MAX_WINDOW_BITS).contains(&bits),
+            "window bits must be within 9..=15"
+        );
+        self.client_max_window_bits = bits;
+        self
+    }
+
+    /// Require the server to reset its compression context after every message.
+    ///
+    /// This trades compression ratio for a smaller per-connection memory footprint.
+    pub fn server_no_context_takeover(mut self) -> Self {
+        self.server_no_context_takeover = true;
+        self
+    }
+
+    /// Require the client to reset its compression context after every message.
+    pub fn client_no_context_takeover(mut self) -> Self {
+        self.client_no_context_takeover = true;
+        self
+    }
+
+    /// Set the compression level used for outgoing messages, from 0 (none) to 9 (best).
+    ///
+    /// By default fast compression (level 1) is used.
+    ///
+    /// # Panics
+    /// Panics if `level` is greater than 9.
+    pub fn compression_level(mut self, level: u32) -> Self {
+        assert!(level <= 9, "compression level must be within 0..=9");
+        self.compression_level = level;
+        self
+    }
+
+    /// Create the `Sec-WebSocket-Extensions` value a client sends to offer the extension.
+    pub fn offer(&self) -> HeaderValue {
+        let mut offer = String::from(EXTENSION_NAME);
+
+        if self.server_no_context_takeover {
+            push_param(&mut offer, SERVER_NO_CONTEXT_TAKEOVER, None);
+        }
+
+        if self.client_no_context_takeover {
+            push_param(&mut offer, CLIENT_NO_CONTEXT_TAKEOVER, None);
+        }
+
+        if self.server_max_window_bits < MAX_WINDOW_BITS {
+            push_param(
+                &mut offer,
+                SERVER_MAX_WINDOW_BITS,
+                Some(self.server_max_window_bits),
+            );
+        }
+
+        // always advertise that the server may limit our window
+        if self.client_max_window_bits < MAX_WINDOW_BITS {
+            push_param(
+                &mut offer,
+                CLIENT_MAX_WINDOW_BITS,
+                Some(self.client_max_window_bits),
+            );
+        } else {
+            push_param(&mut offer, CLIENT_MAX_WINDOW_BITS, None);
+        }
+
+        // only ASCII is ever written
+        HeaderValue::from_str(&offer).unwrap()
+    }
+
+    /// Select the first acceptable `permessage-deflate` offer from a client's request headers.
+    ///
+    /// Returns the agreed parameters and the `Sec-WebSocket-Extensions` value to put in the
+    /// handshake response, or `None` if the client made no acceptable offer; in that case the
+    /// connection should proceed without compression.
+    pub fn accept_offer(
+        &self,
+        headers: &HeaderMap,
+    ) -> Option<(DeflateConfig, HeaderValue)> {
+        parse_extensions(headers)
+            .into_iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case(EXTENSION_NAME))
+            .find_map(|(_, params)| self.accept_params(&params))
+    }
+
+    fn accept_params(&self, params: &[Param]) -> Option<(DeflateConfig, HeaderValue)> {
+        let mut agreed = *self;
+        let mut server_no_takeover = false;
+        let mut client_no_takeover = false;
+        let mut server_bits = None;
+        let mut client_bits = None;
+
+        for (name, value) in params {
+            match name.as_str() {
+                SERVER_NO_CONTEXT_TAKEOVER if !server_no_takeover && value.is_none() => {
+                    server_no_takeover = true;
+                }
+                CLIENT_NO_CONTEXT_TAKEOVER if !client_no_takeover && value.is_none() => {
+                    client_no_takeover = true;
+                }
+                SERVER_MAX_WINDOW_BITS if server_bits.is_none() => {
+                    server_bits = Some(parse_window_bits(value.as_deref()?)?);
+                }
+                CLIENT_MAX_WINDOW_BITS if client_bits.is_none() => {
+                    client_bits = Some(match value {
+                        Some(value) => parse_window_bits(value)?,
+                        None => MAX_WINDOW_BITS,
+                    });
+                }
+
+                // unknown or duplicated parameter; decline this offer
+                _ => return None,
+            }
+        }
+
+        agreed.server_no_context_takeover |= server_no_takeover;
+        agreed.client_no_context_takeover |= client_no_takeover;
+
+        agreed.server_max_window_bits = cmp::min(
+            server_bits.unwrap_or(MAX_WINDOW_BITS),
+            self.server_max_window_bits,
+        );
+
+        // a window smaller than the deflate implementation supports can not be honoured
+        if agreed.server_max_window_bits < MIN_COMPRESS_WINDOW_BITS {
+            return None;
+        }
+
+        // the client's window can only be limited when it indicated support for doing so;
+        // decompression works with any window size so there is no need to decline otherwise
+        agreed.client_max_window_bits = match client_bits {
+            Some(bits) => cmp::min(bits, self.client_max_window_bits),
+            None => MAX_WINDOW_BITS,
+        };
+
+        let mut response = String::from(EXTENSION_NAME);
+
+        if agreed.server_no_context_takeover {
+            push_param(&mut response, SERVER_NO_CONTEXT_TAKEOVER, None);
+        }
+
+        if agreed.client_no_context_takeover {
+            push_param(&mut response, CLIENT_NO_CONTEXT_TAKEOVER, None);
+        }
+
+        if server_bits.is_some() || agreed.server_max_window_bits < MAX_WINDOW_BITS {
+            push_param(
+                &mut response,
+                SERVER_MAX_WINDOW_BITS,
+                Some(agreed.server_max_window_bits),
+            );
+        }
+
+        if client_bits.is_some() && agreed.client_max_window_bits < MAX_WINDOW_BITS {
+            push_param(
+                &mut response,
+                CLIENT_MAX_WINDOW_BITS,
+                Some(agreed.client_max_window_bits),
+            );
+        }
+
+        // only ASCII is ever written
+        Some((agreed, HeaderValue::from_str(&response).unwrap()))
+    }
+
+    /// Validate the extensions a server accepted against the offer made from this config.
+    ///
+    /// Returns `Ok(None)` if the server declined compression and the agreed parameters if it
+    /// accepted. Servers responding with parameters that were not offered, with extensions other
+    /// than `permessage-deflate`, or with more than one agreement are rejected.
+    pub fn accept_response(
+        &self,
+        headers: &HeaderMap,
+    ) -> Result<Option<DeflateConfig>, DeflateNegotiationError> {
+        let mut agreed = None;
+
+        for (name, params) in parse_extensions(headers) {
+            if !name.eq_ignore_ascii_case(EXTENSION_NAME) || agreed.is_some() {
+                return Err(DeflateNegotiationError);
+            }
+
+            agreed = Some(
+                self.accept_response_params(&params)
+                    .ok_or(DeflateNegotiationError)?,
+            );
+        }
+
+        Ok(agreed)
+    }
+
+    fn accept_response_params(&self, params: &[Param]) -> Option<DeflateConfig> {
+        let mut agreed = *self;
+        agreed.server_no_context_takeover = false;
+
+        let mut client_no_takeover = false;
+        let mut server_bits = None;
+        let mut client_bits = None;
+
+        for (name, value) in params {
+            match name.as_str() {
+                SERVER_NO_CONTEXT_TAKEOVER
+                    if !agreed.server_no_context_takeover && value.is_none() =>
+                {
+                    agreed.server_no_context_takeover = true;
+                }
+                CLIENT_NO_CONTEXT_TAKEOVER if !client_no_takeover && value.is_none() => {
+                    client_no_takeover = true;
+                }
+                SERVER_MAX_WINDOW_BITS if server_bits.is_none() => {
+                    server_bits = Some(parse_window_bits(value.as_deref()?)?);
+                }
+                CLIENT_MAX_WINDOW_BITS if client_bits.is_none() => {
+                    client_bits = Some(parse_window_bits(value.as_deref()?)?);
+                }
+                _ => return None,
+            }
+        }
+
+        // server must honour the window limit that was requested of it
+        agreed.server_max_window_bits = match server_bits {
+            Some(bits) if bits <= self.server_max_window_bits => bits,
+            None if self.server_max_window_bits == MAX_WINDOW_BITS => MAX_WINDOW_BITS,
+            _ => return None,
+        };
+
+        agreed.client_max_window_bits = cmp::min(
+            client_bits.unwrap_or(MAX_WINDOW_BITS),
+            self.client_max_window_bits,
+        );
+
+        if agreed.client_max_window_bits < MIN_COMPRESS_WINDOW_BITS {
+            return None;
+        }
+
+        agreed.client_no_context_takeover |= client_no_takeover;
+
+        Some(agreed)
+    }
+}
+
+/// Error returned when a server's `permessage-deflate` response does not match the offer.
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Invalid permessage-deflate extension negotiation response.")]
+pub struct DeflateNegotiationError;
+
+type Param = (String, Option<String>);
+
+/// Parse all `Sec-WebSocket-Extensions` headers into extension names and their parameters.
+fn parse_extensions(headers: &HeaderMap) -> Vec<(String, Vec<Param>)> {
+    headers
+        .get_all(SEC_WEBSOCKET_EXTENSIONS)
+        .filter_map(|hdr| hdr.to_str().ok())
+        .flat_map(|hdr| hdr.split(','))
+        .filter_map(|ext| {
+            let mut parts = ext.split(';').map(str::trim);
+            let name = parts.next().filter(|name| !name.is_empty())?;
+
+            let params = parts
+                .filter(|param| !param.is_empty())
+                .map(|param| {
+                    let mut kv = param.splitn(2, '=');
+                    let name = kv.next().unwrap_or_default().trim().to_ascii_lowercase();
+                    let value =
+                        kv.next().map(|val| val.trim().trim_matches('"').to_owned());
+                    (name, value)
+                })
+                .collect();
+
+            Some((name.to_owned(), params))
+        })
+        .collect()
+}
+
+fn parse_window_bits(value: &str) -> Option<u8> {
+    // RFC 7692 forbids leading zeros and anything other than digits
+    if value.starts_with('0') || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    value
+        .parse()
+        .ok()
+        .filter(|bits| (MIN_WINDOW_BITS..=MAX_WINDOW_BITS).contains(bits))
+}
+
+fn push_param(buf: &mut String, name: &str, value: Option<u8>) {
+    buf.push_str("; ");
+    buf.push_str(name);
+
+    if let Some(value) = value {
+        buf.push('=');
+        buf.push_str(&value.to_string());
+    }
+}
+
+/// Compression state of a WebSocket codec using `permessage-deflate`.
+///
+/// Compressor and decompressor are created lazily so that a cloned codec, as used when reading
+/// and writing halves are driven separately, starts with fresh contexts.
+pub(super) struct DeflateContext {
+    config: DeflateConfig,
+    compress: Option<Compress>,
+    decompress: Option<Decompress>,
+}
+
+impl DeflateContext {
+    pub(super) fn new(config: DeflateConfig) -> Self {
+        DeflateContext {
+            config,
+            compress: None,
+            decompress: None,
+        }
+    }
+
+    /// Compress (a fragment of) an outgoing message.
+    ///
+    /// `fin` marks the final fragment, after which the deflate trailer is stripped and the
+    /// context is reset if context takeover is disabled for this side.
+    pub(super) fn compress(
+        &mut self,
+        server: bool,
+        data: &[u8],
+        fin: bool,
+    ) -> Result<Bytes, ProtocolError> {
+        let (window_bits, no_context_takeover) = if server {
+            (
+                self.config.server_max_window_bits,
+                self.config.server_no_context_takeover,
+            )
+        } else {
+            (
+                self.config.client_max_window_bits,
+                self.config.client_no_context_takeover,
+            )
+        };
+
+        let level = Compression::new(self.config.compression_level);
+        let compress = self.compress.get_or_insert_with(|| {
+            Compress::new_with_window_bits(level, false, window_bits)
+        });
+
+        let mut out = Vec::with_capacity(data.len() / 2 + 64);
+        let mut consumed = 0;
+
+        loop {
+            if out.len() == out.capacity() {
+                out.reserve(cmp::max(out.capacity(), 64));
+            }
+
+            let total_in = compress.total_in();
+            compress
+                .compress_vec(&data[consumed..], &mut out, FlushCompress::Sync)
+                .map_err(|err| invalid_data(err.to_string()))?;
+            consumed += (compress.total_in() - total_in) as usize;
+
+            // flush is complete once all input is consumed and output buffer was not filled
+            if consumed == data.len() && out.len() < out.capacity() {
+                break;
+            }
+        }
+
+        if fin {
+            if out.ends_with(&DEFLATE_TRAILER) {
+                out.truncate(out.len() - DEFLATE_TRAILER.len());
+            }
+
+            // an empty compressed message is represented by a single empty block
+            if out.is_empty() {
+                out.push(0x00);
+            }
+
+            if no_context_takeover {
+                compress.reset();
+            }
+        }
+
+        Ok(Bytes::from(out))
+    }
+
+    /// Decompress (a fragment of) an incoming message, failing if the output exceeds `max_size`.
+    pub(super) fn decompress(
+        &mut self,
+        server: bool,
+        data: &[u8],
+        fin: bool,
+        max_size: usize,
+    ) -> Result<Bytes, ProtocolError> {
+        let no_context_takeover = if server {
+            self.config.client_no_context_takeover
+        } else {
+            self.config.server_no_context_takeover
+        };
+
+        // decompressing with the largest window handles streams compressed with any window
+        let decompress = self
+            .decompress
+            .get_or_insert_with(|| Decompress::new(false));
+
+        let mut out = Vec::with_capacity(cmp::min(data.len() * 2 + 64, max_size + 1));
+
+        inflate(decompress, data, &mut out, max_size)?;
+
+        if fin {
+            inflate(decompress, &DEFLATE_TRAILER, &mut out, max_size)?;
+
+            if no_context_takeover {
+                decompress.reset(false);
+            }
+        }
+
+        Ok(Bytes::from(out))
+    }
+}
+
+fn inflate(
+    decompress: &mut Decompress,
+    data: &[u8],
+    out: &mut Vec<u8>,
+    max_size: usize,
+) -> Result<(), ProtocolError> {
+    let mut consumed = 0;
+
+    loop {
+        if out.len() == out.capacity() {
+            out.reserve(cmp::max(out.capacity(), 64));
+        }
+
+        let total_in = decompress.total_in();
+        let total_out = decompress.total_out();
+
+        decompress
+            .decompress_vec(&data[consumed..], out, FlushDecompress::Sync)
+            .map_err(|err| invalid_data(err.to_string()))?;
+
+        consumed += (decompress.total_in() - total_in) as usize;
+
+        if out.len() > max_size {
+            return Err(ProtocolError::Overflow);
+        }
+
+        if consumed == data.len() && out.len() < out.capacity() {
+            return Ok(());
+        }
+
+        // no progress while input remains means the stream ended early
+        if decompress.total_in() == total_in && decompress.total_out() == total_out {
+            return Err(invalid_data("unexpected end of compressed message"));
+        }
+    }
+}
+
+fn invalid_data(msg: impl Into<String>) -> ProtocolError {
+    ProtocolError::Io(io::Error::new(io::ErrorKind::InvalidData, msg.into()))
+}
+
+impl Clone for DeflateContext {
+    fn clone(&self) -> Self {
+        DeflateContext::new(self.config)
+    }
+}
+
+impl fmt::Debug for DeflateContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeflateContext")
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::TestRequest;
+
+    fn offer(ext: &'static str) -> HeaderMap {
+        TestRequest::default()
+            .insert_header((SEC_WEBSOCKET_EXTENSIONS, ext))
+            .finish()
+            .head()
+            .headers()
+            .clone()
+    }
+
+    #[test]
+    fn test_offer() {
+        assert_eq!(
+            DeflateConfig::new().offer(),
+            "permessage-deflate; client_max_window_bits"
+        );
+
+        assert_eq!(
+            DeflateConfig::new()
+                .server_no_context_takeover()
+                .server_max_window_bits(10)
+                .client_max_window_bits(12)
+                .offer(),
+            "permessage-deflate; server_no_context_takeover; \
+             server_max_window_bits=10; client_max_window_bits=12"
+        );
+    }
+
+    #[test]
+    fn test_accept_offer() {
+        let config = DeflateConfig::new();
+
+        assert!(config.accept_offer(&HeaderMap::new()).is_none());
+        assert!(config
+            .accept_offer(&offer("x-webkit-deflate-frame"))
+            .is_none());
+
+        let (params, res) = config.accept_offer(&offer("permessage-deflate")).unwrap();
+        assert_eq!(params, config);
+        assert_eq!(res, "permessage-deflate");
+
+        let (params, res) = config
+            .accept_offer(&offer(
+                "permessage-deflate; client_no_context_takeover; server_max_window_bits=10",
+            ))
+            .unwrap();
+        assert_eq!(
+            params,
+            config
+                .client_no_context_takeover()
+                .server_max_window_bits(10)
+        );
+        assert_eq!(
+            res,
+            "permessage-deflate; client_no_context_takeover; server_max_window_bits=10"
+        );
+
+        // client window is limited only when the client supports it
+        let config = DeflateConfig::new().client_max_window_bits(11);
+        let (params, res) = config.accept_offer(&offer("permessage-deflate")).unwrap();
+        assert_eq!(params, DeflateConfig::new());
+        assert_eq!(res, "permessage-deflate");
+
+        let (params, res) = config
+            .accept_offer(&offer("permessage-deflate; client_max_window_bits"))
+            .unwrap();
+        assert_eq!(params, config);
+        assert_eq!(res, "permessage-deflate; client_max_window_bits=11");
+    }
+
+    #[test]
+    fn test_accept_offer_fallback() {
+        let config = DeflateConfig::new();
+
+        // invalid offers are skipped in favour of the next one
+        let (params, res) = config
+            .accept_offer(&offer(
+                "permessage-deflate; server_max_window_bits=8, \
+                 permessage-deflate; unknown_param, \
+                 permessage-deflate; server_max_window_bits=016, \
+                 permessage-deflate; server_no_context_takeover",
+            ))
+            .unwrap();
+        assert_eq!(params, config.server_no_context_takeover());
+        assert_eq!(res, "permessage-deflate; server_no_context_takeover");
+
+        assert!(config
+            .accept_offer(&offer(
+                "permessage-deflate; server_no_context_takeover; server_no_context_takeover"
+            ))
+            .is_none());
+    }
+
+    #[test]
+    fn test_accept_response() {
+        let config = DeflateConfig::new().server_max_window_bits(12);
+
+        assert!(config.accept_response(&HeaderMap::new()).unwrap().is_none());
+
+        let params = config
+            .accept_response(&offer(
+                "permessage-deflate; server_max_window_bits=10; client_max_window_bits=9",
+            ))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            params,
+            DeflateConfig::new()
+                .server_max_window_bits(10)
+                .client_max_window_bits(9)
+        );
+
+        // requested server limit was not honoured
+        assert!(config
+            .accept_response(&offer("permessage-deflate"))
+            .is_err());
+        assert!(config
+            .accept_response(&offer("permessage-deflate; server_max_window_bits=13"))
+            .is_err());
+
+        let config = DeflateConfig::new();
+        assert!(config
+            .accept_response(&offer("permessage-deflate; client_max_window_bits=8"))
+            .is_err());
+        assert!(config
+            .accept_response(&offer("permessage-deflate, permessage-deflate"))
+            .is_err());
+        assert!(config.accept_response(&offer("x-unknown")).is_err());
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let config = DeflateConfig::new().server_max_window_bits(9);
+        let mut server = DeflateContext::new(config);
+        let mut client = DeflateContext::new(config);
+
+        let msg = b"{\"message\":\"hello\"}".repeat(32);
+
+        for _ in 0..3 {
+            let compressed = server.compress(true, &msg, true).unwrap();
+            assert!(compressed.len() < msg.len());
+            assert!(!compressed.ends_with(&DEFLATE_TRAILER));
+
+            let decompressed =
+                client.decompress(false, &compressed, true, 65_536).unwrap();
+            assert_eq!(&decompressed[..], &msg[..]);
+        }
+
+        let compressed = server.compress(true, b"", true).unwrap();
+        assert_eq!(&compressed[..], &[0x00]);
+        let decompressed = client.decompress(false, &compressed, true, 1024).unwrap();
+        assert!(decompressed.is_empty());
+    }
+
+    #[test]
+    fn test_no_context_takeover() {
+        let config = DeflateConfig::new().client_no_context_takeover();
+        let mut client = DeflateContext::new(config);
+
+        let msg = b"repetitive payload ".repeat(8);
+        let first = client.compress(false, &msg, true).unwrap();
+        let second = client.compress(false, &msg, true).unwrap();
+        assert_eq!(first, second);
+
+        // a fresh decompressor can read every message
+        let decompressed = DeflateContext::new(config)
+            .decompress(true, &second, true, 1024)
+            .unwrap();
+        assert_eq!(&decompressed[..], &msg[..]);
+    }
+
+    #[test]
+    fn test_decompress_overflow() {
+        let mut server = DeflateContext::new(DeflateConfig::new());
+        let compressed = server.compress(true, &[0u8; 4096], true).unwrap();
+
+        let mut client = DeflateContext::new(DeflateConfig::new());
+        assert!(matches!(
+            client.decompress(false, &compressed, true, 1024),
+            Err(ProtocolError::Overflow)
+        ));
+    }
+}
@@ -16,7 +16,7 @@ impl Parser {
         src: &[u8],
         server: bool,
         max_size: usize,
-    ) -> Result<Option<(usize, bool, OpCode, usize, Option<[u8; 4]>)>, ProtocolError>
+    ) -> Result<Option<(usize, bool, u8, OpCode, usize, Option<[u8; 4]>)>, ProtocolError>
     {
         let chunk_len = src.len();
 
@@ -28,6 +28,7 @@ impl Parser {
         let first = src[0];
         let second = src[1];
         let finished = first & 0x80 != 0;
+        let rsv = first & 0x70;
 
         // check masking
         let masked = second & 0x80 != 0;
@@ -87,7 +88,7 @@ impl Parser {
             None
         };
 
-        Ok(Some((idx, finished, opcode, length, mask)))
+        Ok(Some((idx, finished, rsv, opcode, length, mask)))
     }
 
     /// Parse the input stream into a frame.
@@ -96,8 +97,20 @@ impl Parser {
         server: bool,
         max_size: usize,
     ) -> Result<Option<(bool, OpCode, Option<BytesMut>)>, ProtocolError> {
+        Ok(Parser::parse_frame(src, server, max_size)?
+            .map(|(finished, _, opcode, payload)| (finished, opcode, payload)))
+    }
+
+    /// Parse the input stream into a frame, keeping the reserved bits of the first header byte.
+    ///
+    /// Reserved bits are returned in place (`0x40` for RSV1, `0x20` for RSV2, `0x10` for RSV3).
+    pub(crate) fn parse_frame(
+        src: &mut BytesMut,
+        server: bool,
+        max_size: usize,
+    ) -> Result<Option<(bool, u8, OpCode, Option<BytesMut>)>, ProtocolError> {
         // try to parse ws frame metadata
-        let (idx, finished, opcode, length, mask) =
+        let (idx, finished, rsv, opcode, length, mask) =
             match Parser::parse_metadata(src, server, max_size)? {
                 None => return Ok(None),
                 Some(res) => res,
@@ -113,7 +126,7 @@ impl Parser {
 
         // no need for body
         if length == 0 {
-            return Ok(Some((finished, opcode, None)));
+            return Ok(Some((finished, rsv, opcode, None)));
         }
 
         let mut data = src.split_to(length);
@@ -125,7 +138,7 @@ impl Parser {
             }
             OpCode::Close if length > 125 => {
                 debug!("Received close frame with payload length exceeding 125. Morphing to protocol close frame.");
-                return Ok(Some((true, OpCode::Close, None)));
+                return Ok(Some((true, rsv, OpCode::Close, None)));
             }
             _ => {}
         }
@@ -135,7 +148,7 @@ impl Parser {
             apply_mask(&mut data, mask);
         }
 
-        Ok(Some((finished, opcode, Some(data))))
+        Ok(Some((finished, rsv, opcode, Some(data))))
     }
 
     /// Parse the payload of a close frame.
@@ -161,13 +174,30 @@ impl Parser {
         op: OpCode,
         fin: bool,
         mask: bool,
+    ) {
+        Parser::write_frame(dst, pl, op, fin, false, mask)
+    }
+
+    /// Generate binary representation, optionally setting the RSV1 bit.
+    ///
+    /// RSV1 marks the first frame of a compressed message when `permessage-deflate` is in use.
+    pub(crate) fn write_frame<B: AsRef<[u8]>>(
+        dst: &mut BytesMut,
+        pl: B,
+        op: OpCode,
+        fin: bool,
+        rsv1: bool,
+        mask: bool,
     ) {
         let payload = pl.as_ref();
-        let one: u8 = if fin {
+        let mut one: u8 = if fin {
             0x80 | Into::<u8>::into(op)
         } else {
             op.into()
         };
+        if rsv1 {
+            one |= 0x40;
+        }
         let payload_len = payload.len();
         let (two, p_len) = if mask {
             (0x80, payload_len + 4)
@@ -379,4 +409,21 @@ mod tests {
         Parser::write_close(&mut buf, None, false);
         assert_eq!(&buf[..], &vec![0x88, 0x00][..]);
     }
+
+    #[test]
+    fn test_parse_frame_rsv() {
+        let mut buf = BytesMut::new();
+        Parser::write_frame(&mut buf, "data", OpCode::Text, true, true, false);
+        assert_eq!(buf[0], 0xC1);
+
+        match Parser::parse_frame(&mut buf, false, 1024) {
+            Ok(Some((finished, rsv, opcode, payload))) => {
+                assert!(finished);
+                assert_eq!(rsv, 0x40);
+                assert_eq!(opcode, OpCode::Text);
+                assert_eq!(&payload.unwrap()[..], b"data");
+            }
+            _ => unreachable!("error"),
+        }
+    }
 }
@@ -14,12 +14,16 @@ use crate::{
 };
 
 mod codec;
+#[cfg(feature = "ws-deflate")]
+mod deflate;
 mod dispatcher;
 mod frame;
 mod mask;
 mod proto;
 
 pub use self::codec::{Codec, Frame, Item, Message};
+#[cfg(feature = "ws-deflate")]
+pub use self::deflate::{DeflateConfig, DeflateNegotiationError};
 pub use self::dispatcher::Dispatcher;
 pub use self::frame::Parser;
 pub use self::proto::{hash_key, CloseCode, CloseReason, OpCode};
@@ -63,6 +67,10 @@ pub enum ProtocolError {
     #[display(fmt = "Unknown continuation fragment: {}.", _0)]
     ContinuationFragment(#[error(not(source))] OpCode),
 
+    /// Received a frame with reserved bits set that no negotiated extension defines.
+    #[display(fmt = "Received a frame with unexpected reserved bits set.")]
+    UnexpectedReservedBits,
+
     /// I/O error.
     #[display(fmt = "I/O error: {}", _0)]
     Io(io::Error),
@@ -1,22 +1,28 @@
 use std::{
     convert::Infallible,
     io::{Read, Write},
-    net, thread,
+    net,
+    pin::Pin,
+    task::{Context, Poll},
+    thread,
     time::Duration,
 };
 
 use actix_http::{
-    body::{AnyBody, Body, SizedStream},
-    header, http, Error, HttpMessage, HttpService, KeepAlive, Request, Response,
-    StatusCode,
+    body::{AnyBody, Body, BodySize, FileRegion, MessageBody, SizedStream},
+    header, http,
+    http::HeaderMap,
+    ConnectionStats, Error, HttpMessage, HttpService, KeepAlive, Request, Response,
+    ShutdownSignal, StatusCode,
 };
 use actix_http_test::test_server;
 use actix_rt::time::sleep;
 use actix_service::fn_service;
-use actix_utils::future::{err, ok, ready};
-use bytes::Bytes;
+use actix_utils::future::{err, ok, poll_fn, ready};
+use bytes::{Bytes, BytesMut};
 use derive_more::{Display, Error};
 use futures_util::{
+    future::{select, Either},
     stream::{once, StreamExt as _},
     FutureExt as _,
 };
@@ -131,6 +137,47 @@ async fn test_expect_continue_h1() {
     assert!(data.starts_with("HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\n"));
 }
 
+#[actix_rt::test]
+async fn test_head_limits() {
+    let srv = test_server(|| {
+        HttpService::build()
+            .max_headers(4)
+            .max_header_size(1024)
+            .max_uri_length(64)
+            .h1(|_| ok::<_, Infallible>(Response::ok()))
+            .tcp()
+    })
+    .await;
+
+    let mut stream = net::TcpStream::connect(srv.addr()).unwrap();
+    let _ = stream.write_all(
+        b"GET /test HTTP/1.1\r\na: 1\r\nb: 2\r\nc: 3\r\nd: 4\r\ne: 5\r\n\r\n",
+    );
+    let mut data = String::new();
+    let _ = stream.read_to_string(&mut data);
+    assert!(data.starts_with("HTTP/1.1 431 Request Header Fields Too Large\r\n"));
+
+    let mut stream = net::TcpStream::connect(srv.addr()).unwrap();
+    let req = format!("GET /test HTTP/1.1\r\nlarge: {}\r\n\r\n", "x".repeat(2048));
+    let _ = stream.write_all(req.as_bytes());
+    let mut data = String::new();
+    let _ = stream.read_to_string(&mut data);
+    assert!(data.starts_with("HTTP/1.1 431 Request Header Fields Too Large\r\n"));
+
+    let mut stream = net::TcpStream::connect(srv.addr()).unwrap();
+    let req = format!("GET /{} HTTP/1.1\r\n\r\n", "x".repeat(128));
+    let _ = stream.write_all(req.as_bytes());
+    let mut data = String::new();
+    let _ = stream.read_to_string(&mut data);
+    assert!(data.starts_with("HTTP/1.1 414 URI Too Long\r\n"));
+
+    let mut stream = net::TcpStream::connect(srv.addr()).unwrap();
+    let _ = stream.write_all(b"GET /test HTTP/1.1\r\nconnection: close\r\na: 1\r\n\r\n");
+    let mut data = String::new();
+    let _ = stream.read_to_string(&mut data);
+    assert!(data.starts_with("HTTP/1.1 200 OK\r\n"));
+}
+
 #[actix_rt::test]
 async fn test_chunked_payload() {
     let chunk_sizes = vec![32768, 32, 32768];
@@ -243,6 +290,38 @@ async fn test_http1_keepalive() {
     assert_eq!(&data[..17], b"HTTP/1.1 200 OK\r\n");
 }
 
+#[actix_rt::test]
+async fn test_http1_connection_stats() {
+    let stats = ConnectionStats::new();
+
+    let srv = test_server({
+        let stats = stats.clone();
+        move || {
+            HttpService::build()
+                .connection_stats(stats.clone())
+                .h1(|_| ok::<_, Infallible>(Response::ok()))
+                .tcp()
+        }
+    })
+    .await;
+
+    let mut stream = net::TcpStream::connect(srv.addr()).unwrap();
+    for _ in 0..3 {
+        let _ = stream.write_all(b"GET /test/tests/test HTTP/1.1\r\n\r\n");
+        let mut data = vec![0; 1024];
+        let _ = stream.read(&mut data);
+        assert_eq!(&data[..17], b"HTTP/1.1 200 OK\r\n");
+    }
+
+    assert_eq!(stats.open_connections(), 1);
+    assert_eq!(stats.total_connections(), 1);
+    assert_eq!(stats.keep_alive_reuses(), 2);
+
+    drop(stream);
+    sleep(Duration::from_millis(100)).await;
+    assert_eq!(stats.open_connections(), 0);
+}
+
 #[actix_rt::test]
 async fn test_http1_keepalive_timeout() {
     let srv = test_server(|| {
@@ -708,3 +787,421 @@ async fn test_h1_on_connect() {
     let response = srv.get("/").send().await.unwrap();
     assert!(response.status().is_success());
 }
+
+struct TrailersBody {
+    chunk: Option<Bytes>,
+}
+
+impl TrailersBody {
+    fn new() -> Self {
+        TrailersBody {
+            chunk: Some(Bytes::from_static(b"test")),
+        }
+    }
+}
+
+impl MessageBody for TrailersBody {
+    type Error = Infallible;
+
+    fn size(&self) -> BodySize {
+        BodySize::Stream
+    }
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        Poll::Ready(self.get_mut().chunk.take().map(Ok))
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        let mut trailers = HeaderMap::new();
+        trailers.insert(
+            header::HeaderName::from_static("x-checksum"),
+            header::HeaderValue::from_static("abc"),
+        );
+        Poll::Ready(Ok(Some(trailers)))
+    }
+}
+
+#[actix_rt::test]
+async fn test_h1_response_trailers() {
+    let srv = test_server(|| {
+        HttpService::build()
+            .h1(|_| ok::<_, Infallible>(Response::ok().set_body(TrailersBody::new())))
+            .tcp()
+    })
+    .await;
+
+    let mut stream = net::TcpStream::connect(srv.addr()).unwrap();
+    let _ = stream.write_all(b"GET / HTTP/1.1\r\nconnection: close\r\n\r\n");
+    let mut data = String::new();
+    let _ = stream.read_to_string(&mut data);
+    assert!(data.contains("transfer-encoding: chunked\r\n"));
+    assert!(data.ends_with("\r\n\r\n4\r\ntest\r\n0\r\nx-checksum: abc\r\n\r\n"));
+}
+
+struct FileBody {
+    file: Option<std::fs::File>,
+    len: u64,
+}
+
+impl FileBody {
+    fn open() -> Self {
+        let file = std::fs::File::open("tests/test.binary").unwrap();
+        let len = file.metadata().unwrap().len();
+        FileBody {
+            file: Some(file),
+            len,
+        }
+    }
+}
+
+impl MessageBody for FileBody {
+    type Error = std::io::Error;
+
+    fn size(&self) -> BodySize {
+        BodySize::Sized(self.len)
+    }
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        let file = self.get_mut().file.take();
+        Poll::Ready(file.map(|mut file| {
+            let mut buf = Vec::new();
+            file.read_to_end(&mut buf)?;
+            Ok(Bytes::from(buf))
+        }))
+    }
+
+    fn take_file_region(self: Pin<&mut Self>) -> Option<FileRegion> {
+        let this = self.get_mut();
+        let file = this.file.take()?;
+        Some(FileRegion::new(file, 0, this.len))
+    }
+}
+
+#[actix_rt::test]
+async fn test_h1_sendfile() {
+    let srv = test_server(|| {
+        HttpService::build()
+            .sendfile(true)
+            .h1(|_| ok::<_, Infallible>(Response::ok().set_body(FileBody::open())))
+            .tcp()
+    })
+    .await;
+
+    let expected = std::fs::read("tests/test.binary").unwrap();
+
+    let mut stream = net::TcpStream::connect(srv.addr()).unwrap();
+    let _ = stream
+        .write_all(b"GET / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\nconnection: close\r\n\r\n");
+    let mut data = Vec::new();
+    let _ = stream.read_to_end(&mut data);
+
+    // both responses are sent in full on the same connection
+    let head = format!("content-length: {}\r\n", expected.len());
+    let mut rest = &data[..];
+    for _ in 0..2 {
+        let end = rest.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+        assert!(String::from_utf8_lossy(&rest[..end]).contains(&head));
+        assert_eq!(&rest[end..end + expected.len()], &expected[..]);
+        rest = &rest[end + expected.len()..];
+    }
+    assert!(rest.is_empty());
+
+    // HEAD responses have no body
+    let mut stream = net::TcpStream::connect(srv.addr()).unwrap();
+    let _ = stream.write_all(b"HEAD / HTTP/1.1\r\nconnection: close\r\n\r\n");
+    let mut data = Vec::new();
+    let _ = stream.read_to_end(&mut data);
+    assert!(data.ends_with(b"\r\n\r\n"));
+}
+
+#[actix_rt::test]
+async fn test_h1_request_trailers() {
+    let srv = test_server(|| {
+        HttpService::build()
+            .h1(|mut req: Request| async move {
+                let mut pl = req.take_payload();
+                let mut body = BytesMut::new();
+                while let Some(chunk) = pl.next().await {
+                    body.extend_from_slice(&chunk?);
+                }
+
+                let trailers = poll_fn(|cx| pl.poll_trailers(cx)).await?.unwrap();
+                let checksum = trailers.get("x-checksum").unwrap().to_str().unwrap();
+                Ok::<_, Error>(
+                    Response::ok().set_body(format!("{:?}:{}", body, checksum)),
+                )
+            })
+            .tcp()
+    })
+    .await;
+
+    let mut stream = net::TcpStream::connect(srv.addr()).unwrap();
+    let _ = stream.write_all(
+        b"POST / HTTP/1.1\r\nconnection: close\r\ntransfer-encoding: chunked\r\n\r\n\
+          4\r\ntest\r\n0\r\nx-checksum: abc\r\n\r\n",
+    );
+    let mut data = String::new();
+    let _ = stream.read_to_string(&mut data);
+    assert!(data.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(data.ends_with("\r\n\r\nb\"test\":abc"));
+}
+
+#[actix_rt::test]
+async fn test_h2c_trailers() {
+    let srv = test_server(|| {
+        HttpService::build()
+            .h2(|mut req: Request| async move {
+                let mut pl = req.take_payload();
+                while let Some(chunk) = pl.next().await {
+                    chunk?;
+                }
+
+                let trailers = poll_fn(|cx| pl.poll_trailers(cx)).await?.unwrap();
+                assert_eq!(trailers.get("x-request").unwrap(), "1");
+
+                Ok::<_, Error>(Response::ok().set_body(TrailersBody::new()))
+            })
+            .tcp()
+    })
+    .await;
+
+    let io = actix_rt::net::TcpStream::connect(srv.addr()).await.unwrap();
+    let (mut client, conn) = h2::client::handshake(io).await.unwrap();
+    actix_rt::spawn(async move {
+        let _ = conn.await;
+    });
+
+    let req = ::http::Request::post(srv.url("/")).body(()).unwrap();
+    let (res, mut send) = client.send_request(req, false).unwrap();
+    send.send_data(Bytes::from_static(b"data"), false).unwrap();
+
+    let mut trailers = ::http::HeaderMap::new();
+    trailers.insert("x-request", ::http::HeaderValue::from_static("1"));
+    send.send_trailers(trailers).unwrap();
+
+    let mut body = res.await.unwrap().into_body();
+    let mut bytes = BytesMut::new();
+    while let Some(chunk) = body.data().await {
+        bytes.extend_from_slice(&chunk.unwrap());
+    }
+    assert_eq!(bytes, "test");
+
+    let trailers = body.trailers().await.unwrap().unwrap();
+    assert_eq!(trailers.get("x-checksum").unwrap(), "abc");
+}
+
+#[actix_rt::test]
+async fn test_h2c_settings_and_graceful_shutdown() {
+    let signal = ShutdownSignal::new();
+
+    let srv = test_server({
+        let signal = signal.clone();
+        move || {
+            HttpService::build()
+                .h2_max_concurrent_streams(8)
+                .h2_initial_window_size(1024 * 1024)
+                .h2_max_frame_size(32 * 1024)
+                .shutdown_signal(signal.clone())
+                .h2(|_| ok::<_, Infallible>(Response::ok()))
+                .tcp()
+        }
+    })
+    .await;
+
+    let io = actix_rt::net::TcpStream::connect(srv.addr()).await.unwrap();
+    let (mut client, mut conn) = h2::client::handshake(io).await.unwrap();
+
+    let req = ::http::Request::get(srv.url("/")).body(()).unwrap();
+    let (res, _) = client.send_request(req, true).unwrap();
+    let res = match select(res, &mut conn).await {
+        Either::Left((res, _)) => res.unwrap(),
+        Either::Right(_) => panic!("connection closed before response"),
+    };
+    assert!(res.status().is_success());
+    assert_eq!(conn.max_concurrent_send_streams(), 8);
+
+    // connection is closed cleanly after GOAWAY
+    signal.shutdown();
+    conn.await.unwrap();
+}
+
+#[actix_rt::test]
+async fn test_h2c_keep_alive_ping_timeout() {
+    let srv = test_server(|| {
+        HttpService::build()
+            .h2_keep_alive_interval(50)
+            .h2(|_| ok::<_, Infallible>(Response::ok()))
+            .tcp()
+    })
+    .await;
+
+    // client sends its preface and empty settings, then never answers pings
+    let mut stream = net::TcpStream::connect(srv.addr()).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let _ = stream.write_all(b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n\0\0\0\x04\0\0\0\0\0");
+
+    let mut data = Vec::new();
+    stream.read_to_end(&mut data).unwrap();
+
+    let mut frame_types = Vec::new();
+    let mut frames = &data[..];
+    while frames.len() >= 9 {
+        let len =
+            (frames[0] as usize) << 16 | (frames[1] as usize) << 8 | frames[2] as usize;
+        frame_types.push(frames[3]);
+        frames = &frames[(9 + len).min(frames.len())..];
+    }
+
+    // PING frame
+    assert!(frame_types.contains(&0x6));
+}
+
+#[actix_rt::test]
+async fn test_h2c_upgrade() {
+    let srv = test_server(|| {
+        HttpService::build()
+            .h2c_upgrade(true)
+            .finish(|req: Request| {
+                assert_eq!(req.version(), http::Version::HTTP_2);
+                assert_eq!(req.uri().path(), "/test");
+                assert_eq!(req.headers().get("x-test").unwrap(), "1");
+                ok::<_, Infallible>(Response::ok().set_body(STR))
+            })
+            .tcp()
+    })
+    .await;
+
+    let mut stream = net::TcpStream::connect(srv.addr()).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let _ = stream.write_all(
+        b"GET /test HTTP/1.1\r\nHost: localhost\r\nx-test: 1\r\n\
+          Connection: Upgrade, HTTP2-Settings\r\nUpgrade: h2c\r\n\
+          HTTP2-Settings: AAMAAABkAARAAAAAAAIAAAAA\r\n\r\n",
+    );
+
+    let mut data = Vec::new();
+    let mut buf = [0; 1024];
+    while !data.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut buf).unwrap();
+        assert_ne!(n, 0, "connection closed before upgrade");
+        data.extend_from_slice(&buf[..n]);
+    }
+    let head_len = data.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+    assert!(data.starts_with(b"HTTP/1.1 101 Switching Protocols\r\n"));
+
+    // client preface and empty settings, then read frames until stream 1 ends
+    let _ = stream.write_all(b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n\0\0\0\x04\0\0\0\0\0");
+    let mut data = data.split_off(head_len);
+    let mut status = None;
+    let mut body = Vec::new();
+    loop {
+        let mut pos = 0;
+        let mut done = false;
+        while data.len() - pos >= 9 {
+            let frame = &data[pos..];
+            let len =
+                (frame[0] as usize) << 16 | (frame[1] as usize) << 8 | frame[2] as usize;
+            if frame.len() < 9 + len {
+                break;
+            }
+            let stream_id = frame[5..9].iter().fold(0, |id, b| id << 8 | *b as u32);
+            let payload = &frame[9..9 + len];
+            if stream_id == 1 {
+                match frame[3] {
+                    // HEADERS, `:status 200` is indexed header field 8
+                    0x1 => status = payload.first().copied(),
+                    // DATA
+                    0x0 => body.extend_from_slice(payload),
+                    _ => {}
+                }
+                done |= frame[4] & 0x1 != 0;
+            }
+            pos += 9 + len;
+        }
+        data.drain(..pos);
+
+        if done {
+            break;
+        }
+        let n = stream.read(&mut buf).unwrap();
+        assert_ne!(n, 0, "connection closed before response");
+        data.extend_from_slice(&buf[..n]);
+    }
+
+    assert_eq!(status, Some(0x88));
+    assert_eq!(body, STR.as_bytes());
+}
+
+#[actix_rt::test]
+async fn test_h2c_upgrade_preface_timeout() {
+    let srv = test_server(|| {
+        HttpService::build()
+            .h2c_upgrade(true)
+            .client_timeout(1000)
+            .finish(|_| ok::<_, Infallible>(Response::ok()))
+            .tcp()
+    })
+    .await;
+
+    let mut stream = net::TcpStream::connect(srv.addr()).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let _ = stream.write_all(
+        b"GET / HTTP/1.1\r\nHost: localhost\r\n\
+          Connection: Upgrade, HTTP2-Settings\r\nUpgrade: h2c\r\n\
+          HTTP2-Settings: AAMAAABkAARAAAAAAAIAAAAA\r\n\r\n",
+    );
+
+    let mut data = Vec::new();
+    let mut buf = [0; 1024];
+    while !data.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut buf).unwrap();
+        assert_ne!(n, 0, "connection closed before upgrade");
+        data.extend_from_slice(&buf[..n]);
+    }
+    assert!(data.starts_with(b"HTTP/1.1 101 Switching Protocols\r\n"));
+
+    // partial preface; the connection is closed once the client timeout expires
+    let _ = stream.write_all(b"PRI * HTTP/2.0\r\n");
+    let start = std::time::Instant::now();
+    assert_eq!(stream.read(&mut buf).unwrap(), 0);
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[actix_rt::test]
+async fn test_h2c_upgrade_with_body_ignored() {
+    let srv = test_server(|| {
+        HttpService::build()
+            .h2c_upgrade(true)
+            .finish(|req: Request| {
+                assert_eq!(req.version(), http::Version::HTTP_11);
+                ok::<_, Infallible>(Response::ok())
+            })
+            .tcp()
+    })
+    .await;
+
+    let mut stream = net::TcpStream::connect(srv.addr()).unwrap();
+    let _ = stream.write_all(
+        b"POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 4\r\n\
+          Connection: Upgrade, HTTP2-Settings, close\r\nUpgrade: h2c\r\n\
+          HTTP2-Settings: AAMAAABkAARAAAAAAAIAAAAA\r\n\r\ntest",
+    );
+    let mut data = String::new();
+    let _ = stream.read_to_string(&mut data);
+    assert!(data.starts_with("HTTP/1.1 200 OK\r\n"));
+}
@@ -0,0 +1,294 @@
+//! Multipart form derive macro for Actix Web.
+//!
+//! See [`macro@MultipartForm`] for usage examples.
+
+use std::collections::HashSet;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Ident, Span};
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Lit, Meta, NestedMeta};
+
+/// Implements `MultipartCollect` for a struct so that it can be used with the `MultipartForm`
+/// extractor.
+///
+/// # Examples
+/// ```
+/// use actix_multipart::form::{tempfile::TempFile, text::Text, MultipartForm};
+///
+/// #[derive(MultipartForm)]
+/// #[multipart(deny_unknown_fields, duplicate_field = "deny")]
+/// struct Upload {
+///     #[multipart(rename = "desc")]
+///     description: Text<String>,
+///     #[multipart(limit = "2 MiB")]
+///     avatar: Option<TempFile>,
+/// }
+/// ```
+///
+/// Each member must map to a distinct form field name:
+/// ```compile_fail
+/// use actix_multipart::form::{text::Text, MultipartForm};
+///
+/// #[derive(MultipartForm)]
+/// struct Upload {
+///     name: Text<String>,
+///     #[multipart(rename = "name")]
+///     display_name: Text<String>,
+/// }
+/// ```
+///
+/// See the `actix_multipart::form` module docs for the available attributes.
+#[proc_macro_derive(MultipartForm, attributes(multipart))]
+pub fn impl_multipart_form(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Struct-level options.
+#[derive(Default)]
+struct FormAttrs {
+    deny_unknown_fields: bool,
+    duplicate_field: Option<Ident>,
+}
+
+/// Field-level options.
+#[derive(Default)]
+struct FieldAttrs {
+    rename: Option<String>,
+    limit: Option<usize>,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "MultipartForm can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "MultipartForm can only be derived for structs",
+            ))
+        }
+    };
+
+    let form_attrs = parse_form_attrs(&input.attrs)?;
+    let duplicate_field = form_attrs
+        .duplicate_field
+        .unwrap_or_else(|| Ident::new("Ignore", Span::call_site()));
+
+    let mut field_names = HashSet::new();
+    let mut limits = Vec::new();
+    let mut handlers = Vec::new();
+    let mut readers = Vec::new();
+
+    for field in fields {
+        let ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        let attrs = parse_field_attrs(&field.attrs)?;
+        let field_name = attrs.rename.unwrap_or_else(|| ident.to_string());
+
+        if !field_names.insert(field_name.clone()) {
+            return Err(syn::Error::new_spanned(
+                ident,
+                format!("duplicate form field name `{}`", field_name),
+            ));
+        }
+
+        if let Some(limit) = attrs.limit {
+            limits.push(quote!(#field_name => ::std::option::Option::Some(#limit),));
+        }
+
+        handlers.push(quote! {
+            #field_name => <#ty as ::actix_multipart::form::FieldGroupReader>::handle_field(
+                req,
+                field,
+                limits,
+                state,
+                ::actix_multipart::form::DuplicateField::#duplicate_field,
+            ),
+        });
+
+        readers.push(quote! {
+            #ident: <#ty as ::actix_multipart::form::FieldGroupReader>::from_state(
+                #field_name,
+                &mut state,
+            )?,
+        });
+    }
+
+    let unknown_field = if form_attrs.deny_unknown_fields {
+        quote! {
+            ::std::boxed::Box::pin(async move {
+                ::std::result::Result::Err(
+                    ::actix_multipart::MultipartError::UnsupportedField(name),
+                )
+            })
+        }
+    } else {
+        quote!(::actix_multipart::form::discard_field(field, limits))
+    };
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::actix_multipart::form::MultipartCollect for #name #ty_generics
+        #where_clause
+        {
+            fn limit(field_name: &str) -> ::std::option::Option<usize> {
+                match field_name {
+                    #(#limits)*
+                    _ => ::std::option::Option::None,
+                }
+            }
+
+            fn handle_field<'t>(
+                req: &'t ::actix_multipart::form::__private::HttpRequest,
+                field: ::actix_multipart::Field,
+                limits: &'t mut ::actix_multipart::form::Limits,
+                state: &'t mut ::actix_multipart::form::State,
+            ) -> ::actix_multipart::form::__private::LocalBoxFuture<
+                't,
+                ::std::result::Result<(), ::actix_multipart::MultipartError>,
+            > {
+                let name = field.name().unwrap_or_default().to_owned();
+
+                match name.as_str() {
+                    #(#handlers)*
+                    _ => #unknown_field,
+                }
+            }
+
+            fn from_state(
+                mut state: ::actix_multipart::form::State,
+            ) -> ::std::result::Result<Self, ::actix_multipart::MultipartError> {
+                ::std::result::Result::Ok(Self {
+                    #(#readers)*
+                })
+            }
+        }
+    })
+}
+
+/// Returns the nested items of every `#[multipart(...)]` attribute.
+fn multipart_attrs(attrs: &[syn::Attribute]) -> syn::Result<Vec<NestedMeta>> {
+    let mut items = Vec::new();
+
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("multipart")) {
+        match attr.parse_meta()? {
+            Meta::List(list) => items.extend(list.nested),
+            meta => {
+                return Err(syn::Error::new_spanned(
+                    meta,
+                    "expected attribute arguments, e.g. #[multipart(...)]",
+                ))
+            }
+        }
+    }
+
+    Ok(items)
+}
+
+fn parse_form_attrs(attrs: &[syn::Attribute]) -> syn::Result<FormAttrs> {
+    let mut form_attrs = FormAttrs::default();
+
+    for item in multipart_attrs(attrs)? {
+        match &item {
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("deny_unknown_fields") => {
+                form_attrs.deny_unknown_fields = true;
+            }
+
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("duplicate_field") => {
+                let policy = match &nv.lit {
+                    Lit::Str(lit) => match lit.value().as_str() {
+                        "ignore" => "Ignore",
+                        "deny" => "Deny",
+                        "replace" => "Replace",
+                        _ => {
+                            return Err(syn::Error::new_spanned(
+                                lit,
+                                "expected one of \"ignore\", \"deny\" or \"replace\"",
+                            ))
+                        }
+                    },
+                    lit => return Err(syn::Error::new_spanned(lit, "expected a string")),
+                };
+
+                form_attrs.duplicate_field = Some(Ident::new(policy, nv.lit.span()));
+            }
+
+            _ => return Err(syn::Error::new_spanned(item, "unknown multipart attribute")),
+        }
+    }
+
+    Ok(form_attrs)
+}
+
+fn parse_field_attrs(attrs: &[syn::Attribute]) -> syn::Result<FieldAttrs> {
+    let mut field_attrs = FieldAttrs::default();
+
+    for item in multipart_attrs(attrs)? {
+        match &item {
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("rename") => {
+                match &nv.lit {
+                    Lit::Str(lit) => field_attrs.rename = Some(lit.value()),
+                    lit => return Err(syn::Error::new_spanned(lit, "expected a string")),
+                }
+            }
+
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("limit") => {
+                let limit = match &nv.lit {
+                    Lit::Int(lit) => lit.base10_parse()?,
+                    Lit::Str(lit) => parse_size(&lit.value())
+                        .ok_or_else(|| syn::Error::new_spanned(lit, "invalid size"))?,
+                    lit => {
+                        return Err(syn::Error::new_spanned(
+                            lit,
+                            "expected a size, e.g. \"2 MiB\"",
+                        ))
+                    }
+                };
+
+                field_attrs.limit = Some(limit);
+            }
+
+            _ => return Err(syn::Error::new_spanned(item, "unknown multipart attribute")),
+        }
+    }
+
+    Ok(field_attrs)
+}
+
+/// Parses a size such as `"1024"`, `"512 KB"` or `"2 MiB"` into a number of bytes.
+fn parse_size(size: &str) -> Option<usize> {
+    let size = size.trim();
+    let split = size
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(size.len());
+    let (num, unit) = size.split_at(split);
+
+    let multiplier: usize = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "kb" => 1_000,
+        "kib" => 1 << 10,
+        "mb" => 1_000_000,
+        "mib" => 1 << 20,
+        "gb" => 1_000_000_000,
+        "gib" => 1 << 30,
+        _ => return None,
+    };
+
+    num.parse::<usize>().ok()?.checked_mul(multiplier)
+}
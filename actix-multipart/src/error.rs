@@ -31,14 +31,40 @@ pub enum MultipartError {
     /// Not consumed
     #[display(fmt = "Multipart stream is not consumed")]
     NotConsumed,
+    /// A field could not be read into its form type
+    #[display(fmt = "Failed to read field `{}`: {}", field_name, source)]
+    #[from(ignore)]
+    Field {
+        field_name: String,
+        source: actix_web::Error,
+    },
+    /// Form field is not declared by the form type
+    #[display(fmt = "Unsupported field `{}`", _0)]
+    #[from(ignore)]
+    UnsupportedField(String),
+    /// Form field was repeated but the form type does not allow it
+    #[display(fmt = "Duplicate field `{}`", _0)]
+    #[from(ignore)]
+    DuplicateField(String),
+    /// Required form field is missing
+    #[display(fmt = "Missing field `{}`", _0)]
+    #[from(ignore)]
+    MissingField(String),
+    /// Form has more fields than allowed
+    #[display(fmt = "Too many fields in multipart form")]
+    FieldCountExceeded,
 }
 
 impl std::error::Error for MultipartError {}
 
-/// Return `BadRequest` for `MultipartError`
+/// Return `BadRequest` for `MultipartError`, except for size limit errors
 impl ResponseError for MultipartError {
     fn status_code(&self) -> StatusCode {
-        StatusCode::BAD_REQUEST
+        match self {
+            MultipartError::Payload(PayloadError::Overflow) => StatusCode::PAYLOAD_TOO_LARGE,
+            MultipartError::Field { source, .. } => source.as_response_error().status_code(),
+            _ => StatusCode::BAD_REQUEST,
+        }
     }
 }
 
@@ -50,5 +76,8 @@ mod tests {
     fn test_multipart_error() {
         let resp = MultipartError::Boundary.error_response();
         assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let resp = MultipartError::Payload(PayloadError::Overflow).error_response();
+        assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
     }
 }
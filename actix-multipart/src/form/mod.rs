@@ -0,0 +1,737 @@
+//! Extract and process typed data from fields of a `multipart/form-data` request.
+//!
+//! The [`MultipartForm`] extractor collects named fields into a struct implementing
+//! [`MultipartCollect`], which is normally derived:
+//!
+//! ```
+//! # #[cfg(all(feature = "derive", feature = "tempfile"))]
+//! # mod example {
+//! use actix_multipart::form::{tempfile::TempFile, text::Text, MultipartForm};
+//!
+//! #[derive(MultipartForm)]
+//! #[multipart(deny_unknown_fields)]
+//! struct Upload {
+//!     #[multipart(rename = "desc", limit = "1 KiB")]
+//!     description: Option<Text<String>>,
+//!     #[multipart(limit = "100 MiB")]
+//!     files: Vec<TempFile>,
+//! }
+//!
+//! async fn upload(form: MultipartForm<Upload>) -> String {
+//!     format!("received {} files", form.files.len())
+//! }
+//! # }
+//! ```
+//!
+//! Fields are read into the type of the struct member:
+//! - `T` for required fields, `Option<T>` for optional fields and `Vec<T>` for repeated fields.
+//! - `T` is any [`FieldReader`], e.g. [`Text`](text::Text), [`Bytes`](bytes::Bytes) or
+//!   [`TempFile`](tempfile::TempFile).
+//!
+//! # Attributes
+//! On the struct:
+//! - `#[multipart(deny_unknown_fields)]` rejects fields not declared by the struct. By default
+//!   they are read and discarded.
+//! - `#[multipart(duplicate_field = "ignore" | "deny" | "replace")]` sets the
+//!   [`DuplicateField`] policy for repeated fields that are not collected into a `Vec`. The
+//!   default is `"ignore"`.
+//!
+//! On struct members:
+//! - `#[multipart(rename = "...")]` sets the form field name.
+//! - `#[multipart(limit = "...")]` limits the size of the field, given as a number of bytes or
+//!   with a unit such as `"512 KB"` or `"2 MiB"`. For `Vec` members the limit applies to the
+//!   sum of all fields with that name.
+
+use std::{
+    any::Any,
+    collections::HashMap,
+    ops::{Deref, DerefMut},
+    sync::Arc,
+};
+
+use actix_web::{dev, error::PayloadError, web, Error, FromRequest, HttpRequest};
+use futures_core::future::LocalBoxFuture;
+use futures_util::{future::FutureExt as _, TryStreamExt as _};
+
+use crate::{Field, Multipart, MultipartError};
+
+pub mod bytes;
+#[cfg(feature = "tempfile")]
+pub mod tempfile;
+pub mod text;
+
+#[cfg(feature = "derive")]
+pub use actix_multipart_derive::MultipartForm;
+
+/// Reads a single field into a value.
+pub trait FieldReader: Sized + 'static {
+    /// Reads the field, consuming its size from `limits`.
+    fn read_field<'t>(
+        req: &'t HttpRequest,
+        field: Field,
+        limits: &'t mut Limits,
+    ) -> LocalBoxFuture<'t, Result<Self, MultipartError>>;
+}
+
+/// Values collected so far, indexed by field name.
+#[doc(hidden)]
+pub type State = HashMap<String, Box<dyn Any>>;
+
+/// Reads one or more fields with the same name into a value.
+///
+/// Implemented for `T`, `Option<T>` and `Vec<T>` where `T` is a [`FieldReader`].
+pub trait FieldGroupReader: Sized {
+    /// Reads a field into `state`.
+    fn handle_field<'t>(
+        req: &'t HttpRequest,
+        field: Field,
+        limits: &'t mut Limits,
+        state: &'t mut State,
+        duplicate_field: DuplicateField,
+    ) -> LocalBoxFuture<'t, Result<(), MultipartError>>;
+
+    /// Constructs the value from the fields stored in `state` under `name`.
+    fn from_state(name: &str, state: &mut State) -> Result<Self, MultipartError>;
+}
+
+impl<T: FieldReader> FieldGroupReader for T {
+    fn handle_field<'t>(
+        req: &'t HttpRequest,
+        field: Field,
+        limits: &'t mut Limits,
+        state: &'t mut State,
+        duplicate_field: DuplicateField,
+    ) -> LocalBoxFuture<'t, Result<(), MultipartError>> {
+        read_single::<T>(req, field, limits, state, duplicate_field)
+    }
+
+    fn from_state(name: &str, state: &mut State) -> Result<Self, MultipartError> {
+        match state.remove(name) {
+            Some(val) => downcast(name, val),
+            None => Err(MultipartError::MissingField(name.to_owned())),
+        }
+    }
+}
+
+impl<T: FieldReader> FieldGroupReader for Option<T> {
+    fn handle_field<'t>(
+        req: &'t HttpRequest,
+        field: Field,
+        limits: &'t mut Limits,
+        state: &'t mut State,
+        duplicate_field: DuplicateField,
+    ) -> LocalBoxFuture<'t, Result<(), MultipartError>> {
+        read_single::<T>(req, field, limits, state, duplicate_field)
+    }
+
+    fn from_state(name: &str, state: &mut State) -> Result<Self, MultipartError> {
+        state
+            .remove(name)
+            .map(|val| downcast(name, val))
+            .transpose()
+    }
+}
+
+impl<T: FieldReader> FieldGroupReader for Vec<T> {
+    fn handle_field<'t>(
+        req: &'t HttpRequest,
+        field: Field,
+        limits: &'t mut Limits,
+        state: &'t mut State,
+        _duplicate_field: DuplicateField,
+    ) -> LocalBoxFuture<'t, Result<(), MultipartError>> {
+        Box::pin(async move {
+            let name = field_name(&field);
+            let item = T::read_field(req, field, limits).await?;
+
+            let items = state
+                .entry(name.clone())
+                .or_insert_with(|| Box::new(Vec::<T>::new()));
+
+            match items.downcast_mut::<Vec<T>>() {
+                Some(items) => {
+                    items.push(item);
+                    Ok(())
+                }
+                None => Err(type_mismatch(name)),
+            }
+        })
+    }
+
+    fn from_state(name: &str, state: &mut State) -> Result<Self, MultipartError> {
+        match state.remove(name) {
+            Some(val) => downcast(name, val),
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+/// Reads a field that may only be stored once, applying the duplicate field policy.
+fn read_single<'t, T: FieldReader>(
+    req: &'t HttpRequest,
+    field: Field,
+    limits: &'t mut Limits,
+    state: &'t mut State,
+    duplicate_field: DuplicateField,
+) -> LocalBoxFuture<'t, Result<(), MultipartError>> {
+    let name = field_name(&field);
+
+    if state.contains_key(&name) {
+        match duplicate_field {
+            DuplicateField::Ignore => return discard_field(field, limits),
+            DuplicateField::Deny => {
+                return Box::pin(async move { Err(MultipartError::DuplicateField(name)) })
+            }
+            DuplicateField::Replace => {}
+        }
+    }
+
+    Box::pin(async move {
+        let item = T::read_field(req, field, limits).await?;
+        state.insert(name, Box::new(item));
+        Ok(())
+    })
+}
+
+/// Takes a value out of `state`, failing if it was stored by a reader of another type.
+fn downcast<T: 'static>(name: &str, val: Box<dyn Any>) -> Result<T, MultipartError> {
+    val.downcast::<T>()
+        .map(|val| *val)
+        .map_err(|_| type_mismatch(name.to_owned()))
+}
+
+/// Two readers of different types were used for the same field name; a bug in the form type.
+fn type_mismatch(field_name: String) -> MultipartError {
+    MultipartError::Field {
+        field_name,
+        source: actix_web::error::ErrorInternalServerError(
+            "field is read into more than one type",
+        ),
+    }
+}
+
+fn field_name(field: &Field) -> String {
+    field.name().unwrap_or_default().to_owned()
+}
+
+/// Reads and drops the contents of a field, still counting them against the total limit.
+#[doc(hidden)]
+pub fn discard_field<'t>(
+    mut field: Field,
+    limits: &'t mut Limits,
+) -> LocalBoxFuture<'t, Result<(), MultipartError>> {
+    Box::pin(async move {
+        while let Some(chunk) = field.try_next().await? {
+            limits.try_consume_limits(chunk.len(), false)?;
+        }
+
+        Ok(())
+    })
+}
+
+/// A form that can be collected from the fields of a multipart request.
+///
+/// This trait should be derived using `#[derive(MultipartForm)]`.
+pub trait MultipartCollect: Sized {
+    /// Returns the size limit of the field with the given name, if any.
+    fn limit(field_name: &str) -> Option<usize>;
+
+    /// Reads a field into `state`.
+    fn handle_field<'t>(
+        req: &'t HttpRequest,
+        field: Field,
+        limits: &'t mut Limits,
+        state: &'t mut State,
+    ) -> LocalBoxFuture<'t, Result<(), MultipartError>>;
+
+    /// Constructs the form from the collected fields.
+    fn from_state(state: State) -> Result<Self, MultipartError>;
+}
+
+/// Policy for fields that are sent more than once but are not collected into a `Vec`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateField {
+    /// Keep the first field and discard the rest.
+    Ignore,
+
+    /// Fail the request with [`MultipartError::DuplicateField`].
+    Deny,
+
+    /// Keep the last field.
+    Replace,
+}
+
+/// Remaining sizes that can be read while processing a form.
+#[derive(Debug)]
+pub struct Limits {
+    /// Bytes that can still be read from all fields.
+    pub total_limit_remaining: usize,
+
+    /// Bytes that can still be buffered in memory.
+    pub memory_limit_remaining: usize,
+
+    /// Bytes that can still be read from fields with the current name, if limited.
+    pub field_limit_remaining: Option<usize>,
+}
+
+impl Limits {
+    /// Constructs limits with the given total and in-memory sizes and no field limit.
+    pub fn new(total_limit: usize, memory_limit: usize) -> Self {
+        Self {
+            total_limit_remaining: total_limit,
+            memory_limit_remaining: memory_limit,
+            field_limit_remaining: None,
+        }
+    }
+
+    /// Consumes `bytes` from the limits, returning an overflow error if any limit is exceeded.
+    pub fn try_consume_limits(
+        &mut self,
+        bytes: usize,
+        in_memory: bool,
+    ) -> Result<(), MultipartError> {
+        fn consume(remaining: &mut usize, bytes: usize) -> Result<(), MultipartError> {
+            *remaining = remaining
+                .checked_sub(bytes)
+                .ok_or(MultipartError::Payload(PayloadError::Overflow))?;
+            Ok(())
+        }
+
+        consume(&mut self.total_limit_remaining, bytes)?;
+
+        if in_memory {
+            consume(&mut self.memory_limit_remaining, bytes)?;
+        }
+
+        if let Some(field_limit) = self.field_limit_remaining.as_mut() {
+            consume(field_limit, bytes)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Typed `multipart/form-data` extractor.
+///
+/// See the [module docs](self) for usage. Limits and error handling are configured with
+/// [`MultipartFormConfig`].
+#[derive(Debug)]
+pub struct MultipartForm<T: MultipartCollect>(pub T);
+
+impl<T: MultipartCollect> MultipartForm<T> {
+    /// Unwrap into inner `T` value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T: MultipartCollect> Deref for MultipartForm<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: MultipartCollect> DerefMut for MultipartForm<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T> FromRequest for MultipartForm<T>
+where
+    T: MultipartCollect + 'static,
+{
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+    type Config = MultipartFormConfig;
+
+    fn from_request(req: &HttpRequest, payload: &mut dev::Payload) -> Self::Future {
+        let config = MultipartFormConfig::from_req(req);
+        let mut limits = Limits::new(config.total_limit, config.memory_limit);
+        let field_count_limit = config.field_count_limit;
+        let err_handler = config.err_handler.clone();
+
+        let mut multipart = Multipart::new(req.headers(), payload.take());
+        let req = req.clone();
+        let req2 = req.clone();
+
+        async move {
+            let mut state = State::new();
+            let mut field_count = 0;
+
+            // limits of each name are shared by all fields with that name
+            let mut field_limits = HashMap::<String, Option<usize>>::new();
+
+            while let Some(field) = multipart.try_next().await? {
+                field_count += 1;
+                if field_count > field_count_limit {
+                    return Err(MultipartError::FieldCountExceeded);
+                }
+
+                let name = field_name(&field);
+                let field_limit = field_limits
+                    .entry(name)
+                    .or_insert_with(|| T::limit(field.name().unwrap_or_default()));
+
+                limits.field_limit_remaining = *field_limit;
+                T::handle_field(&req, field, &mut limits, &mut state).await?;
+                *field_limit = limits.field_limit_remaining;
+            }
+
+            T::from_state(state).map(MultipartForm)
+        }
+        .map(move |res| match res {
+            Ok(form) => Ok(form),
+            Err(err) => match err_handler {
+                Some(err_handler) => Err((*err_handler)(err, &req2)),
+                None => Err(err.into()),
+            },
+        })
+        .boxed_local()
+    }
+}
+
+type MultipartFormErrorHandler =
+    Option<Arc<dyn Fn(MultipartError, &HttpRequest) -> Error + Send + Sync>>;
+
+/// [`MultipartForm`] extractor configuration.
+///
+/// # Examples
+/// ```
+/// use actix_multipart::form::MultipartFormConfig;
+/// use actix_web::{error, App, HttpResponse};
+///
+/// let form_cfg = MultipartFormConfig::default()
+///     // allow 100MB in total, of which 1MB may be held in memory
+///     .total_limit(100 * 1024 * 1024)
+///     .memory_limit(1024 * 1024)
+///     // use custom error handler
+///     .error_handler(|err, _req| {
+///         error::InternalError::from_response(err, HttpResponse::Conflict().into()).into()
+///     });
+///
+/// App::new().app_data(form_cfg);
+/// ```
+#[derive(Clone)]
+pub struct MultipartFormConfig {
+    total_limit: usize,
+    memory_limit: usize,
+    field_count_limit: usize,
+    err_handler: MultipartFormErrorHandler,
+}
+
+impl MultipartFormConfig {
+    /// Set maximum accepted size of all fields. By default this limit is 50MiB.
+    pub fn total_limit(mut self, total_limit: usize) -> Self {
+        self.total_limit = total_limit;
+        self
+    }
+
+    /// Set maximum size of fields held in memory. By default this limit is 2MiB.
+    ///
+    /// Fields read to disk, such as [`TempFile`](tempfile::TempFile), do not count towards it.
+    pub fn memory_limit(mut self, memory_limit: usize) -> Self {
+        self.memory_limit = memory_limit;
+        self
+    }
+
+    /// Set maximum number of fields in a form. By default this limit is 1000.
+    pub fn field_count_limit(mut self, field_count_limit: usize) -> Self {
+        self.field_count_limit = field_count_limit;
+        self
+    }
+
+    /// Set custom error handler.
+    pub fn error_handler<F>(mut self, f: F) -> Self
+    where
+        F: Fn(MultipartError, &HttpRequest) -> Error + Send + Sync + 'static,
+    {
+        self.err_handler = Some(Arc::new(f));
+        self
+    }
+
+    /// Extract form config from app data. Check both `T` and `Data<T>`, in that order, and fall
+    /// back to the default form config.
+    fn from_req(req: &HttpRequest) -> &Self {
+        req.app_data::<Self>()
+            .or_else(|| req.app_data::<web::Data<Self>>().map(|d| d.as_ref()))
+            .unwrap_or(&DEFAULT_CONFIG)
+    }
+}
+
+const DEFAULT_CONFIG: MultipartFormConfig = MultipartFormConfig {
+    total_limit: 52_428_800, // 50 MiB
+    memory_limit: 2_097_152, // 2 MiB
+    field_count_limit: 1000,
+    err_handler: None,
+};
+
+impl Default for MultipartFormConfig {
+    fn default() -> Self {
+        DEFAULT_CONFIG.clone()
+    }
+}
+
+/// Items used by the `MultipartForm` derive macro.
+#[doc(hidden)]
+pub mod __private {
+    pub use actix_web::HttpRequest;
+    pub use futures_core::future::LocalBoxFuture;
+}
+
+#[cfg(all(test, feature = "derive", feature = "tempfile"))]
+mod tests {
+    use actix_web::{
+        http::StatusCode,
+        test::{call_service, init_service, read_body, TestRequest},
+        web, App, HttpResponse,
+    };
+
+    use super::{bytes::Bytes, tempfile::TempFile, text::Text, *};
+
+    const BOUNDARY: &str = "abbc761f78ff4d7cb7573b5a23f96ef0";
+
+    /// Builds a multipart body from `(name, file name, data)` parts.
+    fn form_request(parts: &[(&str, Option<&str>, &str)]) -> TestRequest {
+        let mut body = String::new();
+
+        for (name, file_name, data) in parts {
+            body.push_str(&format!("--{}\r\n", BOUNDARY));
+            body.push_str(&format!(
+                "Content-Disposition: form-data; name=\"{}\"",
+                name
+            ));
+            if let Some(file_name) = file_name {
+                body.push_str(&format!("; filename=\"{}\"", file_name));
+            }
+            body.push_str(&format!("\r\n\r\n{}\r\n", data));
+        }
+
+        body.push_str(&format!("--{}--\r\n", BOUNDARY));
+
+        TestRequest::post()
+            .insert_header((
+                "content-type",
+                format!("multipart/form-data; boundary={}", BOUNDARY),
+            ))
+            .set_payload(body)
+    }
+
+    #[derive(MultipartForm)]
+    struct TestForm {
+        #[multipart(rename = "desc")]
+        description: Text<String>,
+        count: Option<Text<u32>>,
+        #[multipart(limit = "8 B")]
+        tags: Vec<Text<String>>,
+        data: Option<Bytes>,
+        file: Option<TempFile>,
+    }
+
+    async fn test_form_route(form: MultipartForm<TestForm>) -> HttpResponse {
+        let file = form.file.as_ref().map(|file| {
+            let contents = std::fs::read_to_string(file.file.path()).unwrap();
+            format!(
+                "{}:{}",
+                file.file_name.as_deref().unwrap_or_default(),
+                contents
+            )
+        });
+
+        HttpResponse::Ok().body(format!(
+            "{} {:?} {:?} {:?} {:?}",
+            *form.description,
+            form.count.as_ref().map(|count| count.0),
+            form.tags.iter().map(|tag| tag.as_str()).collect::<Vec<_>>(),
+            form.data.as_ref().map(|data| data.data.clone()),
+            file,
+        ))
+    }
+
+    #[actix_rt::test]
+    async fn test_typed_fields() {
+        let srv = init_service(App::new().route("/", web::post().to(test_form_route))).await;
+
+        let req = form_request(&[
+            ("desc", None, "hello"),
+            ("count", None, "42"),
+            ("tags", None, "a"),
+            ("unknown", None, "ignored"),
+            ("tags", None, "b"),
+            ("data", None, "bytes"),
+            ("file", Some("a.txt"), "file contents"),
+        ])
+        .to_request();
+        let res = call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            read_body(res).await,
+            r#"hello Some(42) ["a", "b"] Some(b"bytes") Some("a.txt:file contents")"#
+        );
+
+        // optional fields may be omitted
+        let req = form_request(&[("desc", None, "hello")]).to_request();
+        let res = call_service(&srv, req).await;
+        assert_eq!(read_body(res).await, "hello None [] None None");
+
+        // required fields may not
+        let req = form_request(&[("count", None, "1")]).to_request();
+        let res = call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        // text that fails to deserialize
+        let req =
+            form_request(&[("desc", None, "hello"), ("count", None, "many")]).to_request();
+        let res = call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        // field limit is shared by all fields with the same name
+        let req = form_request(&[
+            ("desc", None, "hello"),
+            ("tags", None, "1234"),
+            ("tags", None, "56789"),
+        ])
+        .to_request();
+        let res = call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[actix_rt::test]
+    async fn test_form_limits() {
+        let srv = init_service(
+            App::new()
+                .app_data(
+                    MultipartFormConfig::default()
+                        .total_limit(20)
+                        .memory_limit(10)
+                        .field_count_limit(3),
+                )
+                .route("/", web::post().to(test_form_route)),
+        )
+        .await;
+
+        let req = form_request(&[("desc", None, "0123456789")]).to_request();
+        let res = call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        // files are not held in memory
+        let req =
+            form_request(&[("desc", None, "hello"), ("file", None, "0123456789")]).to_request();
+        let res = call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let req =
+            form_request(&[("desc", None, "hello"), ("data", None, "0123456789")]).to_request();
+        let res = call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        // discarded fields count towards the total limit
+        let req = form_request(&[("desc", None, "hello"), ("other", None, "0123456789012345")])
+            .to_request();
+        let res = call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let req = form_request(&[
+            ("desc", None, "a"),
+            ("tags", None, "b"),
+            ("tags", None, "c"),
+            ("tags", None, "d"),
+        ])
+        .to_request();
+        let res = call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[derive(MultipartForm)]
+    #[multipart(deny_unknown_fields, duplicate_field = "deny")]
+    struct StrictForm {
+        name: Text<String>,
+    }
+
+    #[derive(MultipartForm)]
+    #[multipart(duplicate_field = "replace")]
+    struct ReplaceForm {
+        name: Text<String>,
+    }
+
+    #[actix_rt::test]
+    async fn test_field_policies() {
+        let srv = init_service(
+            App::new()
+                .route(
+                    "/strict",
+                    web::post().to(|form: MultipartForm<StrictForm>| async move {
+                        form.into_inner().name.into_inner()
+                    }),
+                )
+                .route(
+                    "/replace",
+                    web::post().to(|form: MultipartForm<ReplaceForm>| async move {
+                        form.into_inner().name.into_inner()
+                    }),
+                )
+                .route(
+                    "/ignore",
+                    web::post().to(|form: MultipartForm<TestForm>| async move {
+                        form.into_inner().description.into_inner()
+                    }),
+                ),
+        )
+        .await;
+
+        let parts = [("name", None, "a"), ("name", None, "b")];
+
+        let req = form_request(&parts).uri("/strict").to_request();
+        let res = call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let req = form_request(&[("name", None, "a"), ("other", None, "b")])
+            .uri("/strict")
+            .to_request();
+        let res = call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let req = form_request(&parts).uri("/replace").to_request();
+        assert_eq!(read_body(call_service(&srv, req).await).await, "b");
+
+        let req = form_request(&[("desc", None, "a"), ("desc", None, "b")])
+            .uri("/ignore")
+            .to_request();
+        assert_eq!(read_body(call_service(&srv, req).await).await, "a");
+    }
+
+    #[test]
+    fn test_state_type_mismatch() {
+        let mut state = State::new();
+        state.insert("count".to_owned(), Box::new(1u32));
+
+        let err =
+            <Option<Bytes> as FieldGroupReader>::from_state("count", &mut state).unwrap_err();
+        assert_eq!(
+            actix_web::ResponseError::status_code(&err),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_error_handler() {
+        let srv = init_service(
+            App::new()
+                .app_data(MultipartFormConfig::default().error_handler(|err, _req| {
+                    actix_web::error::InternalError::from_response(
+                        err,
+                        HttpResponse::Conflict().finish(),
+                    )
+                    .into()
+                }))
+                .route("/", web::post().to(test_form_route)),
+        )
+        .await;
+
+        let req = form_request(&[]).to_request();
+        let res = call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+    }
+}
@@ -0,0 +1,116 @@
+//! Writes a field to a temporary file on disk.
+
+use std::path::{Path, PathBuf};
+
+use actix_web::{error, web, HttpRequest};
+use futures_core::future::LocalBoxFuture;
+use futures_util::TryStreamExt as _;
+use mime::Mime;
+use tempfile::NamedTempFile;
+use tokio::io::AsyncWriteExt as _;
+
+use crate::{
+    form::{FieldReader, Limits},
+    Field, MultipartError,
+};
+
+/// Write the field to a temporary file on disk.
+///
+/// The file is deleted when this value is dropped, unless it is persisted with
+/// [`NamedTempFile::persist`]. The directory is configured with [`TempFileConfig`].
+#[derive(Debug)]
+pub struct TempFile {
+    /// The temporary file on disk.
+    pub file: NamedTempFile,
+
+    /// The value of the `Content-Type` header.
+    pub content_type: Mime,
+
+    /// The `filename` value in the `Content-Disposition` header.
+    pub file_name: Option<String>,
+
+    /// The size in bytes of the file.
+    pub size: usize,
+}
+
+impl FieldReader for TempFile {
+    fn read_field<'t>(
+        req: &'t HttpRequest,
+        mut field: Field,
+        limits: &'t mut Limits,
+    ) -> LocalBoxFuture<'t, Result<Self, MultipartError>> {
+        Box::pin(async move {
+            let field_name = field.name().unwrap_or_default().to_owned();
+            let io_err = |err| MultipartError::Field {
+                field_name: field_name.clone(),
+                source: error::ErrorInternalServerError(err),
+            };
+
+            let dir = TempFileConfig::from_req(req).directory.clone();
+            let file = web::block(move || match dir {
+                Some(dir) => NamedTempFile::new_in(dir),
+                None => NamedTempFile::new(),
+            })
+            .await
+            .map_err(|err| MultipartError::Field {
+                field_name: field_name.clone(),
+                source: err.into(),
+            })?
+            .map_err(io_err)?;
+
+            let mut async_file =
+                tokio::fs::File::from_std(file.as_file().try_clone().map_err(io_err)?);
+            let mut size = 0;
+
+            while let Some(chunk) = field.try_next().await? {
+                limits.try_consume_limits(chunk.len(), false)?;
+                size += chunk.len();
+                async_file.write_all(&chunk).await.map_err(io_err)?;
+            }
+
+            async_file.flush().await.map_err(io_err)?;
+
+            Ok(TempFile {
+                file,
+                content_type: field.content_type().clone(),
+                file_name: field
+                    .content_disposition()
+                    .and_then(|cd| cd.get_filename().map(str::to_owned)),
+                size,
+            })
+        })
+    }
+}
+
+/// [`TempFile`] configuration.
+///
+/// # Examples
+/// ```
+/// use actix_multipart::form::tempfile::TempFileConfig;
+/// use actix_web::App;
+///
+/// App::new().app_data(TempFileConfig::default().directory("./uploads"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TempFileConfig {
+    directory: Option<PathBuf>,
+}
+
+impl TempFileConfig {
+    /// Set the directory temporary files are created in. By default the system temporary
+    /// directory is used.
+    pub fn directory(mut self, dir: impl AsRef<Path>) -> Self {
+        self.directory = Some(dir.as_ref().to_owned());
+        self
+    }
+
+    /// Extract config from app data. Check both `T` and `Data<T>`, in that order, and fall back
+    /// to the default config.
+    fn from_req(req: &HttpRequest) -> &Self {
+        req.app_data::<Self>()
+            .or_else(|| req.app_data::<web::Data<Self>>().map(|d| d.as_ref()))
+            .unwrap_or(&DEFAULT_CONFIG)
+    }
+}
+
+const DEFAULT_CONFIG: TempFileConfig = TempFileConfig { directory: None };
@@ -0,0 +1,68 @@
+//! Deserializes a field from plain text.
+
+use std::ops::{Deref, DerefMut};
+
+use actix_web::{error, HttpRequest};
+use futures_core::future::LocalBoxFuture;
+use serde::de::DeserializeOwned;
+
+use crate::{
+    form::{bytes::Bytes, FieldReader, Limits},
+    Field, MultipartError,
+};
+
+/// Deserialize the field from its UTF-8 text using [`serde_plain`].
+///
+/// This suits strings, numbers, booleans and unit enum variants.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Text<T: DeserializeOwned>(pub T);
+
+impl<T: DeserializeOwned> Text<T> {
+    /// Unwrap into inner `T` value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T: DeserializeOwned> Deref for Text<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: DeserializeOwned> DerefMut for Text<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T> FieldReader for Text<T>
+where
+    T: DeserializeOwned + 'static,
+{
+    fn read_field<'t>(
+        req: &'t HttpRequest,
+        field: Field,
+        limits: &'t mut Limits,
+    ) -> LocalBoxFuture<'t, Result<Self, MultipartError>> {
+        Box::pin(async move {
+            let field_name = field.name().unwrap_or_default().to_owned();
+            let bytes = Bytes::read_field(req, field, limits).await?;
+
+            let text =
+                std::str::from_utf8(&bytes.data).map_err(|err| MultipartError::Field {
+                    field_name: field_name.clone(),
+                    source: error::ErrorBadRequest(err),
+                })?;
+
+            serde_plain::from_str(text)
+                .map(Text)
+                .map_err(|err| MultipartError::Field {
+                    field_name,
+                    source: error::ErrorBadRequest(err),
+                })
+        })
+    }
+}
@@ -3,8 +3,14 @@
 #![deny(rust_2018_idioms)]
 #![allow(clippy::borrow_interior_mutable_const)]
 
+// allows the derive macro's output to refer to this crate in unit tests; doc tests already
+// link it under its own name
+#[cfg(all(test, feature = "derive", feature = "tempfile"))]
+extern crate self as actix_multipart;
+
 mod error;
 mod extractor;
+pub mod form;
 mod server;
 
 pub use self::error::MultipartError;
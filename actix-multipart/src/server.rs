@@ -371,6 +371,7 @@ impl Drop for InnerMultipart {
 /// A single field in a multipart stream
 pub struct Field {
     ct: mime::Mime,
+    cd: Option<ContentDisposition>,
     headers: HeaderMap,
     inner: Rc<RefCell<InnerField>>,
     safety: Safety,
@@ -383,8 +384,15 @@ impl Field {
         ct: mime::Mime,
         inner: Rc<RefCell<InnerField>>,
     ) -> Self {
+        // RFC 7578: 'Each part MUST contain a Content-Disposition header field
+        // where the disposition type is "form-data".'
+        let cd = headers
+            .get(&header::CONTENT_DISPOSITION)
+            .and_then(|cd| ContentDisposition::from_raw(cd).ok());
+
         Field {
             ct,
+            cd,
             headers,
             inner,
             safety,
@@ -403,13 +411,12 @@ impl Field {
 
     /// Get the content disposition of the field, if it exists
     pub fn content_disposition(&self) -> Option<ContentDisposition> {
-        // RFC 7578: 'Each part MUST contain a Content-Disposition header field
-        // where the disposition type is "form-data".'
-        if let Some(content_disposition) = self.headers.get(&header::CONTENT_DISPOSITION) {
-            ContentDisposition::from_raw(content_disposition).ok()
-        } else {
-            None
-        }
+        self.cd.clone()
+    }
+
+    /// Get the name of the field from its content disposition, if it exists
+    pub fn name(&self) -> Option<&str> {
+        self.cd.as_ref().and_then(|cd| cd.get_name())
     }
 }
 
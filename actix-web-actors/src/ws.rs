@@ -15,13 +15,12 @@ use actix::{
     SpawnHandle,
 };
 use actix_codec::{Decoder, Encoder};
+#[cfg(feature = "ws-deflate")]
+pub use actix_http::ws::DeflateConfig;
 pub use actix_http::ws::{
-    CloseCode, CloseReason, Frame, HandshakeError, Message, ProtocolError,
-};
-use actix_http::{
-    http::HeaderValue,
-    ws::{hash_key, Codec},
+    CloseCode, CloseReason, Codec, Frame, HandshakeError, Message, ProtocolError,
 };
+use actix_http::{http::HeaderValue, ws::hash_key};
 use actix_web::{
     error::{Error, PayloadError},
     http::{header, Method, StatusCode},
@@ -84,6 +83,26 @@ where
     Ok(res.streaming(WebsocketContext::create(actor, stream)))
 }
 
+/// Do WebSocket handshake, negotiating `permessage-deflate`, and start ws actor.
+///
+/// `protocols` is a sequence of known protocols. Messages are compressed if the client offered
+/// compression parameters acceptable under `deflate`; otherwise the connection is uncompressed.
+#[cfg(feature = "ws-deflate")]
+pub fn start_with_deflate<A, T>(
+    actor: A,
+    protocols: &[&str],
+    deflate: &DeflateConfig,
+    req: &HttpRequest,
+    stream: T,
+) -> Result<HttpResponse, Error>
+where
+    A: Actor<Context = WebsocketContext<A>> + StreamHandler<Result<Message, ProtocolError>>,
+    T: Stream<Item = Result<Bytes, PayloadError>> + 'static,
+{
+    let (mut res, codec) = handshake_with_deflate(req, protocols, deflate)?;
+    Ok(res.streaming(WebsocketContext::with_codec(actor, stream, codec)))
+}
+
 /// Prepare WebSocket handshake response.
 ///
 /// This function returns handshake `HttpResponse`, ready to send to peer.
@@ -180,6 +199,31 @@ pub fn handshake_with_protocols(
     Ok(response)
 }
 
+/// Prepare WebSocket handshake response, negotiating the `permessage-deflate` extension.
+///
+/// Behaves like [`handshake_with_protocols`] and additionally accepts the first compression
+/// offer of the client that is compatible with `deflate`. Returns the response along with a
+/// [`Codec`] configured with the agreed parameters, which must be used for the connection, e.g.
+/// by passing it to [`WebsocketContext::with_codec`].
+#[cfg(feature = "ws-deflate")]
+pub fn handshake_with_deflate(
+    req: &HttpRequest,
+    protocols: &[&str],
+    deflate: &DeflateConfig,
+) -> Result<(HttpResponseBuilder, Codec), HandshakeError> {
+    let mut response = handshake_with_protocols(req, protocols)?;
+
+    let codec = match deflate.accept_offer(req.headers()) {
+        Some((params, extensions)) => {
+            response.insert_header((header::SEC_WEBSOCKET_EXTENSIONS, extensions));
+            Codec::new().permessage_deflate(params)
+        }
+        None => Codec::new(),
+    };
+
+    Ok((response, codec))
+}
+
 /// Execution context for `WebSockets` actors
 pub struct WebsocketContext<A>
 where
@@ -299,7 +343,7 @@ where
             inner: ContextParts::new(mb.sender_producer()),
             messages: VecDeque::new(),
         };
-        ctx.add_stream(WsStream::new(stream, codec));
+        ctx.add_stream(WsStream::new(stream, codec.clone()));
 
         WebsocketContextFut::new(ctx, actor, mb, codec)
     }
@@ -107,3 +107,68 @@ async fn test_with_credentials() {
     let item = framed.next().await.unwrap().unwrap();
     assert_eq!(item, ws::Frame::Close(Some(ws::CloseCode::Normal.into())));
 }
+
+#[cfg(feature = "ws-deflate")]
+#[actix_rt::test]
+async fn test_deflate() {
+    let srv = actix_test::start(|| {
+        App::new().service(web::resource("/").to(
+            |req: HttpRequest, stream: web::Payload| async move {
+                let deflate = ws::DeflateConfig::new().server_no_context_takeover();
+                ws::start_with_deflate(Ws, &[], &deflate, &req, stream)
+            },
+        ))
+    });
+
+    let (res, mut framed) = awc::Client::new()
+        .ws(srv.url("/"))
+        .deflate(ws::DeflateConfig::new())
+        .connect()
+        .await
+        .unwrap();
+
+    assert_eq!(
+        res.headers().get(header::SEC_WEBSOCKET_EXTENSIONS).unwrap(),
+        "permessage-deflate; server_no_context_takeover"
+    );
+
+    let text = "{\"text\":\"compressed\"}".repeat(64);
+    for _ in 0..3 {
+        framed
+            .send(ws::Message::Text(text.clone().into()))
+            .await
+            .unwrap();
+        let item = framed.next().await.unwrap().unwrap();
+        assert_eq!(item, ws::Frame::Text(Bytes::from(text.clone())));
+    }
+
+    framed
+        .send(ws::Message::Binary(Bytes::new()))
+        .await
+        .unwrap();
+    let item = framed.next().await.unwrap().unwrap();
+    assert_eq!(item, ws::Frame::Binary(Bytes::new()));
+
+    // server without deflate support ignores the offer
+    let srv = actix_test::start(|| {
+        App::new().service(web::resource("/").to(
+            |req: HttpRequest, stream: web::Payload| async move { ws::start(Ws, &req, stream) },
+        ))
+    });
+
+    let (res, mut framed) = awc::Client::new()
+        .ws(srv.url("/"))
+        .deflate(ws::DeflateConfig::new())
+        .connect()
+        .await
+        .unwrap();
+
+    assert!(!res.headers().contains_key(header::SEC_WEBSOCKET_EXTENSIONS));
+
+    framed
+        .send(ws::Message::Text(text.clone().into()))
+        .await
+        .unwrap();
+    let item = framed.next().await.unwrap().unwrap();
+    assert_eq!(item, ws::Frame::Text(Bytes::from(text)));
+}
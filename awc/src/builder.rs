@@ -5,7 +5,7 @@ use std::rc::Rc;
 use std::time::Duration;
 
 use actix_http::{
-    client::{Connector, ConnectorService, TcpConnect, TcpConnectError, TcpConnection},
+    client::{Connector, ConnectorService, Proxy, TcpConnect, TcpConnectError, TcpConnection},
     http::{self, header, Error as HttpError, HeaderMap, HeaderName, Uri},
 };
 use actix_rt::net::{ActixStream, TcpStream};
@@ -13,6 +13,8 @@ use actix_service::{boxed, Service};
 
 use crate::connect::DefaultConnector;
 use crate::error::SendRequestError;
+#[cfg(feature = "cookies")]
+use crate::middleware::{CookieStore, Cookies};
 use crate::middleware::{NestTransform, Redirect, Transform};
 use crate::{Client, ClientConfig, ConnectRequest, ConnectResponse};
 
@@ -30,6 +32,8 @@ pub struct ClientBuilder<S = (), M = ()> {
     connector: Connector<S>,
     middleware: M,
     local_address: Option<IpAddr>,
+    proxies: Vec<Proxy>,
+    proxy_from_env: bool,
     max_redirects: u8,
 }
 
@@ -49,6 +53,8 @@ impl ClientBuilder {
             headers: HeaderMap::new(),
             timeout: Some(Duration::from_secs(5)),
             local_address: None,
+            proxies: Vec::new(),
+            proxy_from_env: false,
             connector: Connector::new(),
             max_http_version: None,
             stream_window_size: None,
@@ -82,6 +88,8 @@ where
             headers: self.headers,
             timeout: self.timeout,
             local_address: self.local_address,
+            proxies: self.proxies,
+            proxy_from_env: self.proxy_from_env,
             connector,
             max_http_version: self.max_http_version,
             stream_window_size: self.stream_window_size,
@@ -111,6 +119,24 @@ where
         self
     }
 
+    /// Route connections through a forward proxy.
+    ///
+    /// See [`Connector::proxy`] for details.
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.proxies.push(proxy);
+        self
+    }
+
+    /// Route connections through the proxies set in the `HTTP_PROXY`, `HTTPS_PROXY` and
+    /// `NO_PROXY` environment variables.
+    ///
+    /// Proxies added with [`proxy`](Self::proxy) take precedence. See
+    /// [`Connector::proxy_from_env`] for details.
+    pub fn proxy_from_env(mut self) -> Self {
+        self.proxy_from_env = true;
+        self
+    }
+
     /// Maximum supported HTTP major version.
     ///
     /// Supported versions are HTTP/1.1 and HTTP/2.
@@ -226,10 +252,29 @@ where
             timeout: self.timeout,
             connector: self.connector,
             local_address: self.local_address,
+            proxies: self.proxies,
+            proxy_from_env: self.proxy_from_env,
             max_redirects: self.max_redirects,
         }
     }
 
+    /// Persist cookies across requests in the given store.
+    ///
+    /// Shorthand for wrapping the client with the [`Cookies`](crate::middleware::Cookies)
+    /// middleware. Cookies are also applied to requests made while following redirects.
+    #[cfg(feature = "cookies")]
+    pub fn cookie_store<S1, C>(
+        self,
+        store: C,
+    ) -> ClientBuilder<S, NestTransform<M, Cookies, S1, ConnectRequest>>
+    where
+        C: CookieStore + 'static,
+        M: Transform<S1, ConnectRequest>,
+        Cookies: Transform<M::Transform, ConnectRequest>,
+    {
+        self.wrap(Cookies::new(store))
+    }
+
     /// Finish build process and create `Client` instance.
     pub fn finish(self) -> Client
     where
@@ -267,6 +312,12 @@ where
         if let Some(val) = self.local_address {
             connector = connector.local_address(val);
         }
+        for proxy in self.proxies {
+            connector = connector.proxy(proxy);
+        }
+        if self.proxy_from_env {
+            connector = connector.proxy_from_env();
+        }
 
         let connector = DefaultConnector::new(connector.finish());
         let connector = boxed::rc_service(self.middleware.new_transform(connector));
@@ -3,6 +3,8 @@
 pub use actix_http::client::{ConnectError, FreezeRequestError, InvalidUrl, SendRequestError};
 pub use actix_http::error::PayloadError;
 pub use actix_http::http::Error as HttpError;
+#[cfg(feature = "ws-deflate")]
+pub use actix_http::ws::DeflateNegotiationError as WsDeflateNegotiationError;
 pub use actix_http::ws::HandshakeError as WsHandshakeError;
 pub use actix_http::ws::ProtocolError as WsProtocolError;
 
@@ -38,6 +40,11 @@ pub enum WsClientError {
     #[display(fmt = "Invalid challenge response")]
     InvalidChallengeResponse([u8; 28], HeaderValue),
 
+    /// Invalid permessage-deflate negotiation response
+    #[cfg(feature = "ws-deflate")]
+    #[display(fmt = "{}", _0)]
+    DeflateNegotiation(WsDeflateNegotiationError),
+
     /// Protocol error
     #[display(fmt = "{}", _0)]
     Protocol(WsProtocolError),
@@ -109,7 +109,10 @@ use std::{convert::TryFrom, rc::Rc, time::Duration};
 #[cfg(feature = "cookies")]
 pub use cookie;
 
-pub use actix_http::{client::Connector, http};
+pub use actix_http::{
+    client::{Connector, Proxy},
+    http,
+};
 
 use actix_http::{
     client::{TcpConnect, TcpConnectError, TcpConnection},
@@ -124,6 +127,7 @@ mod connect;
 pub mod error;
 mod frozen;
 pub mod middleware;
+pub mod multipart;
 mod request;
 mod response;
 mod sender;
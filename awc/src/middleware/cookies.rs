@@ -0,0 +1,588 @@
+use std::{
+    cell::RefCell,
+    convert::TryFrom,
+    fmt,
+    net::IpAddr,
+    rc::Rc,
+    time::{Duration, SystemTime},
+};
+
+use actix_http::{
+    client::SendRequestError,
+    http::{header, HeaderMap, HeaderValue, Uri},
+    RequestHeadType,
+};
+use actix_service::Service;
+use futures_core::future::LocalBoxFuture;
+
+use super::Transform;
+
+use crate::connect::{ConnectRequest, ConnectResponse};
+use crate::cookie::Cookie;
+
+/// Storage for cookies used by the [`Cookies`] middleware.
+///
+/// Methods take `&self`; implementations are expected to use interior mutability.
+pub trait CookieStore {
+    /// Stores cookies received in the `Set-Cookie` headers of a response to a request for `uri`.
+    fn set_cookies(&self, cookies: Vec<Cookie<'static>>, uri: &Uri);
+
+    /// Returns cookies that should be sent with a request for `uri`.
+    fn cookies(&self, uri: &Uri) -> Vec<Cookie<'static>>;
+}
+
+/// In-memory cookie store.
+///
+/// Applies the domain, path, secure and expiry rules of RFC 6265. Cookies with a single-label
+/// `Domain`, such as `com`, are rejected; other public suffixes are not recognized. Cloning a
+/// `Jar` yields a handle to the same storage, so cookies can be pre-seeded and inspected after
+/// handing a clone over to the client.
+///
+/// # Examples
+/// ```
+/// use awc::{cookie::Cookie, http::Uri, middleware::Jar, ClientBuilder};
+///
+/// let jar = Jar::new();
+/// jar.insert(Cookie::new("token", "secret"), &Uri::from_static("https://example.com/"));
+///
+/// let client = ClientBuilder::new().cookie_store(jar.clone()).finish();
+///
+/// // ... send requests ...
+///
+/// assert_eq!(jar.get("token").unwrap().value(), "secret");
+/// ```
+#[derive(Clone, Default)]
+pub struct Jar {
+    cookies: Rc<RefCell<Vec<StoredCookie>>>,
+}
+
+struct StoredCookie {
+    cookie: Cookie<'static>,
+    domain: String,
+    host_only: bool,
+    path: String,
+    secure: bool,
+    expires: Option<SystemTime>,
+}
+
+impl StoredCookie {
+    fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires.map_or(false, |expires| expires <= now)
+    }
+
+    fn matches(&self, uri: &Uri) -> bool {
+        let host = match uri.host() {
+            Some(host) => host.to_ascii_lowercase(),
+            None => return false,
+        };
+
+        let domain_match = if self.host_only {
+            host == self.domain
+        } else {
+            domain_match(&host, &self.domain)
+        };
+
+        let secure = matches!(uri.scheme_str(), Some("https") | Some("wss"));
+
+        domain_match && path_match(uri.path(), &self.path) && (secure || !self.secure)
+    }
+}
+
+impl Jar {
+    /// Constructs an empty cookie jar.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stores a cookie as if it was set by a response to a request for `uri`.
+    ///
+    /// Returns `false` if the cookie was rejected, e.g. because its domain does not match `uri`,
+    /// or if it is already expired.
+    pub fn insert(&self, cookie: Cookie<'static>, uri: &Uri) -> bool {
+        let host = match uri.host() {
+            Some(host) => host.to_ascii_lowercase(),
+            None => return false,
+        };
+
+        let (domain, host_only) = match cookie.domain() {
+            Some(domain) => {
+                let domain = domain.trim_start_matches('.').to_ascii_lowercase();
+
+                // single-label domains, such as top-level domains, are treated like public
+                // suffixes: only the host itself may set them, and the cookie stays host-only
+                if !domain.contains('.') {
+                    if domain == host {
+                        (host, true)
+                    } else {
+                        return false;
+                    }
+                } else if domain == host || (!is_ip(&host) && domain_match(&host, &domain)) {
+                    (domain, false)
+                } else {
+                    return false;
+                }
+            }
+            None => (host, true),
+        };
+
+        let path = match cookie.path() {
+            Some(path) if path.starts_with('/') => path.to_owned(),
+            _ => default_path(uri.path()).to_owned(),
+        };
+
+        let now = SystemTime::now();
+        let expires = match cookie.max_age() {
+            Some(max_age) => Some(
+                Duration::try_from(max_age)
+                    .map(|max_age| now + max_age)
+                    .unwrap_or(SystemTime::UNIX_EPOCH),
+            ),
+            None => cookie.expires_datetime().map(SystemTime::from),
+        };
+
+        let stored = StoredCookie {
+            secure: cookie.secure().unwrap_or(false),
+            cookie,
+            domain,
+            host_only,
+            path,
+            expires,
+        };
+
+        let mut cookies = self.cookies.borrow_mut();
+
+        // newer cookie replaces (or removes, if expired) existing one
+        cookies.retain(|c| {
+            !(c.cookie.name() == stored.cookie.name()
+                && c.domain == stored.domain
+                && c.path == stored.path)
+        });
+
+        if stored.is_expired(now) {
+            false
+        } else {
+            cookies.push(stored);
+            true
+        }
+    }
+
+    /// Returns stored cookie with the given name, if it is not expired.
+    ///
+    /// If multiple cookies share a name, the first one stored is returned.
+    pub fn get(&self, name: &str) -> Option<Cookie<'static>> {
+        let now = SystemTime::now();
+
+        self.cookies
+            .borrow()
+            .iter()
+            .find(|c| c.cookie.name() == name && !c.is_expired(now))
+            .map(|c| c.cookie.clone())
+    }
+
+    /// Returns all stored cookies that are not expired.
+    pub fn to_vec(&self) -> Vec<Cookie<'static>> {
+        let now = SystemTime::now();
+
+        self.cookies
+            .borrow()
+            .iter()
+            .filter(|c| !c.is_expired(now))
+            .map(|c| c.cookie.clone())
+            .collect()
+    }
+
+    /// Removes all cookies.
+    pub fn clear(&self) {
+        self.cookies.borrow_mut().clear();
+    }
+}
+
+impl CookieStore for Jar {
+    fn set_cookies(&self, cookies: Vec<Cookie<'static>>, uri: &Uri) {
+        for cookie in cookies {
+            self.insert(cookie, uri);
+        }
+    }
+
+    fn cookies(&self, uri: &Uri) -> Vec<Cookie<'static>> {
+        let now = SystemTime::now();
+
+        let mut cookies = self.cookies.borrow_mut();
+        cookies.retain(|c| !c.is_expired(now));
+
+        let mut matched = cookies
+            .iter()
+            .filter(|c| c.matches(uri))
+            .collect::<Vec<_>>();
+
+        // cookies with longer paths are listed first
+        matched.sort_by_key(|c| std::cmp::Reverse(c.path.len()));
+
+        matched.into_iter().map(|c| c.cookie.clone()).collect()
+    }
+}
+
+impl fmt::Debug for Jar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Jar")
+            .field("cookies", &self.cookies.borrow().len())
+            .finish()
+    }
+}
+
+fn is_ip(host: &str) -> bool {
+    host.starts_with('[') || host.parse::<IpAddr>().is_ok()
+}
+
+/// Domain matching as per RFC 6265 §5.1.3.
+fn domain_match(host: &str, domain: &str) -> bool {
+    host == domain
+        || (host.len() > domain.len()
+            && host.ends_with(domain)
+            && host.as_bytes()[host.len() - domain.len() - 1] == b'.'
+            && !is_ip(host))
+}
+
+/// Path matching as per RFC 6265 §5.1.4.
+fn path_match(req_path: &str, cookie_path: &str) -> bool {
+    req_path == cookie_path
+        || (req_path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || req_path.as_bytes()[cookie_path.len()] == b'/'))
+}
+
+/// Default cookie path as per RFC 6265 §5.1.4.
+fn default_path(req_path: &str) -> &str {
+    match req_path.rfind('/') {
+        Some(0) | None => "/",
+        Some(idx) => &req_path[..idx],
+    }
+}
+
+/// Middleware for persisting cookies across requests.
+///
+/// Cookies from `Set-Cookie` response headers are saved to a [`CookieStore`] and matching
+/// cookies are attached to subsequent requests. Install it with
+/// [`ClientBuilder::cookie_store`](crate::ClientBuilder::cookie_store) or
+/// [`ClientBuilder::wrap`](crate::ClientBuilder::wrap); either way it runs for every request
+/// made while following redirects.
+pub struct Cookies {
+    store: Rc<dyn CookieStore>,
+}
+
+impl Default for Cookies {
+    fn default() -> Self {
+        Self::new(Jar::default())
+    }
+}
+
+impl Cookies {
+    /// Constructs cookie middleware backed by the given store.
+    pub fn new<C>(store: C) -> Self
+    where
+        C: CookieStore + 'static,
+    {
+        Self {
+            store: Rc::new(store),
+        }
+    }
+}
+
+impl<S> Transform<S, ConnectRequest> for Cookies
+where
+    S: Service<ConnectRequest, Response = ConnectResponse, Error = SendRequestError> + 'static,
+{
+    type Transform = CookiesService<S>;
+
+    fn new_transform(self, service: S) -> Self::Transform {
+        CookiesService {
+            store: self.store,
+            connector: Rc::new(service),
+        }
+    }
+}
+
+pub struct CookiesService<S> {
+    store: Rc<dyn CookieStore>,
+    connector: Rc<S>,
+}
+
+impl<S> Service<ConnectRequest> for CookiesService<S>
+where
+    S: Service<ConnectRequest, Response = ConnectResponse, Error = SendRequestError> + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = LocalBoxFuture<'static, Result<ConnectResponse, SendRequestError>>;
+
+    actix_service::forward_ready!(connector);
+
+    fn call(&self, req: ConnectRequest) -> Self::Future {
+        let (req, uri) = match req {
+            ConnectRequest::Client(head, body, addr) => {
+                let uri = head.as_ref().uri.clone();
+
+                let head = match head {
+                    RequestHeadType::Owned(mut head) => {
+                        add_cookies(&mut head.headers, self.store.cookies(&uri));
+                        RequestHeadType::Owned(head)
+                    }
+                    RequestHeadType::Rc(head, extra_headers) => {
+                        let mut extra_headers = extra_headers.unwrap_or_default();
+
+                        if !extra_headers.contains_key(header::COOKIE) {
+                            if let Some(value) = head.headers.get(header::COOKIE) {
+                                extra_headers.insert(header::COOKIE, value.clone());
+                            }
+                        }
+
+                        add_cookies(&mut extra_headers, self.store.cookies(&uri));
+                        RequestHeadType::Rc(head, Some(extra_headers))
+                    }
+                };
+
+                (ConnectRequest::Client(head, body, addr), uri)
+            }
+            ConnectRequest::Tunnel(mut head, addr) => {
+                let uri = head.uri.clone();
+                add_cookies(&mut head.headers, self.store.cookies(&uri));
+                (ConnectRequest::Tunnel(head, addr), uri)
+            }
+        };
+
+        let store = self.store.clone();
+        let fut = self.connector.call(req);
+
+        Box::pin(async move {
+            let res = fut.await?;
+
+            let headers = match res {
+                ConnectResponse::Client(ref res) => res.headers(),
+                ConnectResponse::Tunnel(ref head, _) => &head.headers,
+            };
+
+            let cookies = headers
+                .get_all(header::SET_COOKIE)
+                .filter_map(|value| value.to_str().ok())
+                .filter_map(|value| Cookie::parse_encoded(value.to_owned()).ok())
+                .collect::<Vec<_>>();
+
+            if !cookies.is_empty() {
+                store.set_cookies(cookies, &uri);
+            }
+
+            Ok(res)
+        })
+    }
+}
+
+/// Appends cookies to the `Cookie` header, keeping cookies already set on the request.
+fn add_cookies(headers: &mut HeaderMap, cookies: Vec<Cookie<'static>>) {
+    if cookies.is_empty() {
+        return;
+    }
+
+    let mut value = headers
+        .get(header::COOKIE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned)
+        .unwrap_or_default();
+
+    for cookie in cookies {
+        // cookies set explicitly on the request take precedence
+        let name = format!("{}=", cookie.name());
+        if value.split("; ").any(|pair| pair.starts_with(&name)) {
+            continue;
+        }
+
+        if !value.is_empty() {
+            value.push_str("; ");
+        }
+        value.push_str(&cookie.stripped().encoded().to_string());
+    }
+
+    if let Ok(value) = HeaderValue::from_str(&value) {
+        headers.insert(header::COOKIE, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{cookie::Cookie as WebCookie, web, App, Error, HttpRequest, HttpResponse};
+
+    use super::*;
+    use crate::ClientBuilder;
+
+    fn uri(uri: &'static str) -> Uri {
+        Uri::from_static(uri)
+    }
+
+    fn names(cookies: Vec<Cookie<'static>>) -> Vec<String> {
+        cookies.iter().map(|c| c.name().to_owned()).collect()
+    }
+
+    #[test]
+    fn test_domain_rules() {
+        let jar = Jar::new();
+        let origin = uri("http://www.example.com/");
+
+        assert!(jar.insert(Cookie::new("host", "1"), &origin));
+        assert!(jar.insert(
+            Cookie::build("parent", "1").domain(".example.com").finish(),
+            &origin
+        ));
+        assert!(!jar.insert(
+            Cookie::build("other", "1").domain("example.org").finish(),
+            &origin
+        ));
+        assert!(!jar.insert(
+            Cookie::build("sub", "1")
+                .domain("a.www.example.com")
+                .finish(),
+            &origin
+        ));
+
+        assert_eq!(names(jar.cookies(&origin)), vec!["host", "parent"]);
+        assert_eq!(
+            names(jar.cookies(&uri("http://api.example.com/"))),
+            vec!["parent"]
+        );
+        assert_eq!(
+            names(jar.cookies(&uri("http://a.www.example.com/"))),
+            vec!["parent"]
+        );
+        assert!(jar.cookies(&uri("http://example.org/")).is_empty());
+        assert!(jar.cookies(&uri("http://badexample.com/")).is_empty());
+    }
+
+    #[test]
+    fn test_single_label_domain() {
+        let jar = Jar::new();
+
+        assert!(!jar.insert(
+            Cookie::build("tld", "1").domain("com").finish(),
+            &uri("http://a.example.com/")
+        ));
+        assert!(!jar.insert(
+            Cookie::build("tld", "1").domain(".COM").finish(),
+            &uri("http://a.example.com/")
+        ));
+        assert!(jar.cookies(&uri("http://other.com/")).is_empty());
+
+        // a single-label host may set its own name as domain, but only for itself
+        assert!(jar.insert(
+            Cookie::build("local", "1").domain("localhost").finish(),
+            &uri("http://localhost/")
+        ));
+        assert_eq!(names(jar.cookies(&uri("http://localhost/"))), vec!["local"]);
+        assert!(jar.cookies(&uri("http://a.localhost/")).is_empty());
+    }
+
+    #[test]
+    fn test_path_rules() {
+        let jar = Jar::new();
+
+        // default path is the directory of the request path
+        assert!(jar.insert(Cookie::new("a", "1"), &uri("http://localhost/docs/index")));
+        assert!(jar.insert(
+            Cookie::build("b", "1").path("/docs/api").finish(),
+            &uri("http://localhost/")
+        ));
+
+        assert_eq!(
+            names(jar.cookies(&uri("http://localhost/docs/api/v1"))),
+            vec!["b", "a"]
+        );
+        assert_eq!(names(jar.cookies(&uri("http://localhost/docs"))), vec!["a"]);
+        assert!(jar.cookies(&uri("http://localhost/docsx")).is_empty());
+        assert!(jar.cookies(&uri("http://localhost/")).is_empty());
+    }
+
+    #[test]
+    fn test_secure_and_expiry() {
+        let jar = Jar::new();
+        let origin = uri("https://localhost/");
+
+        assert!(jar.insert(Cookie::build("secure", "1").secure(true).finish(), &origin));
+        assert!(jar.insert(Cookie::new("session", "1"), &origin));
+
+        assert_eq!(names(jar.cookies(&origin)), vec!["secure", "session"]);
+        assert_eq!(
+            names(jar.cookies(&uri("http://localhost/"))),
+            vec!["session"]
+        );
+
+        // expired cookie removes stored one
+        let mut expired = Cookie::new("session", "");
+        expired.make_removal();
+        assert!(!jar.insert(expired, &origin));
+        assert!(jar.get("session").is_none());
+
+        let max_age = Cookie::parse("max-age=1; Max-Age=60").unwrap();
+        assert!(jar.insert(max_age, &origin));
+        assert_eq!(jar.get("max-age").unwrap().value(), "1");
+
+        assert_eq!(jar.to_vec().len(), 2);
+        jar.clear();
+        assert!(jar.to_vec().is_empty());
+    }
+
+    #[test]
+    fn test_add_cookies() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, HeaderValue::from_static("a=explicit"));
+
+        add_cookies(
+            &mut headers,
+            vec![Cookie::new("a", "stored"), Cookie::new("b", "stored")],
+        );
+        assert_eq!(headers.get(header::COOKIE).unwrap(), "a=explicit; b=stored");
+    }
+
+    #[actix_rt::test]
+    async fn test_cookie_store_with_redirect() {
+        let srv = actix_test::start(|| {
+            App::new()
+                .service(web::resource("/login").to(|| async {
+                    Ok::<_, Error>(
+                        HttpResponse::Found()
+                            .cookie(WebCookie::build("session", "abc").path("/").finish())
+                            .append_header((header::LOCATION, "/profile"))
+                            .finish(),
+                    )
+                }))
+                .service(web::resource("/profile").to(|req: HttpRequest| async move {
+                    match req.cookie("session") {
+                        Some(cookie) if cookie.value() == "abc" => {
+                            Ok::<_, Error>(HttpResponse::Ok().finish())
+                        }
+                        _ => Ok(HttpResponse::Unauthorized().finish()),
+                    }
+                }))
+        });
+
+        let jar = Jar::new();
+        let client = ClientBuilder::new().cookie_store(jar.clone()).finish();
+
+        // cookie set on redirect response is sent to redirect target
+        let res = client.get(srv.url("/login")).send().await.unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        assert_eq!(jar.get("session").unwrap().value(), "abc");
+
+        // and to later requests
+        let res = client.get(srv.url("/profile")).send().await.unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+
+        // pre-seeded cookies are sent too
+        let jar = Jar::new();
+        jar.insert(
+            Cookie::new("session", "abc"),
+            &srv.url("/").parse::<Uri>().unwrap(),
+        );
+        let client = ClientBuilder::new()
+            .disable_redirects()
+            .cookie_store(jar)
+            .finish();
+        let res = client.get(srv.url("/profile")).send().await.unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+    }
+}
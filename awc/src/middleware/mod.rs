@@ -1,6 +1,14 @@
+#[cfg(feature = "cookies")]
+mod cookies;
+mod propagate;
 mod redirect;
+mod retry;
 
+#[cfg(feature = "cookies")]
+pub use self::cookies::{CookieStore, Cookies, Jar};
+pub use self::propagate::Propagate;
 pub use self::redirect::Redirect;
+pub use self::retry::Retry;
 
 use std::marker::PhantomData;
 
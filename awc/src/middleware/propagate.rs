@@ -0,0 +1,224 @@
+use std::{fmt, rc::Rc};
+
+use actix_http::{http::HeaderMap, RequestHeadType};
+use actix_service::Service;
+
+use super::Transform;
+
+use crate::connect::ConnectRequest;
+
+/// Middleware that adds context headers to every outbound request.
+///
+/// The hook is called for each request, including those made while following redirects, and
+/// fills a header map. Its headers are added to the request unless already set. This is used to
+/// propagate request IDs or trace context from the surrounding server request.
+///
+/// # Examples
+/// ```no_run
+/// use actix_web::middleware::RequestId;
+/// use awc::{middleware::Propagate, ClientBuilder};
+///
+/// // forward the ID of the request being handled, if any
+/// let client = ClientBuilder::new()
+///     .wrap(Propagate::new(|headers| {
+///         if let Some(id) = RequestId::current() {
+///             id.propagate(headers);
+///         }
+///     }))
+///     .finish();
+/// ```
+pub struct Propagate {
+    hook: Rc<dyn Fn(&mut HeaderMap)>,
+}
+
+impl Propagate {
+    /// Constructs propagation middleware using the given hook.
+    pub fn new<F>(hook: F) -> Self
+    where
+        F: Fn(&mut HeaderMap) + 'static,
+    {
+        Self {
+            hook: Rc::new(hook),
+        }
+    }
+}
+
+impl fmt::Debug for Propagate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Propagate").finish()
+    }
+}
+
+impl<S> Transform<S, ConnectRequest> for Propagate
+where
+    S: Service<ConnectRequest>,
+{
+    type Transform = PropagateService<S>;
+
+    fn new_transform(self, service: S) -> Self::Transform {
+        PropagateService {
+            hook: self.hook,
+            connector: service,
+        }
+    }
+}
+
+pub struct PropagateService<S> {
+    hook: Rc<dyn Fn(&mut HeaderMap)>,
+    connector: S,
+}
+
+impl<S> Service<ConnectRequest> for PropagateService<S>
+where
+    S: Service<ConnectRequest>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    actix_service::forward_ready!(connector);
+
+    fn call(&self, req: ConnectRequest) -> Self::Future {
+        let mut extra = HeaderMap::new();
+        (self.hook)(&mut extra);
+
+        if extra.is_empty() {
+            return self.connector.call(req);
+        }
+
+        let req = match req {
+            ConnectRequest::Client(head, body, addr) => {
+                let head = match head {
+                    RequestHeadType::Owned(mut head) => {
+                        merge_missing(&mut head.headers, extra);
+                        RequestHeadType::Owned(head)
+                    }
+                    RequestHeadType::Rc(head, extra_headers) => {
+                        let mut extra_headers = extra_headers.unwrap_or_default();
+
+                        for (name, value) in extra.iter() {
+                            if !head.headers.contains_key(name)
+                                && !extra_headers.contains_key(name)
+                            {
+                                extra_headers.append(name.clone(), value.clone());
+                            }
+                        }
+
+                        RequestHeadType::Rc(head, Some(extra_headers))
+                    }
+                };
+
+                ConnectRequest::Client(head, body, addr)
+            }
+            ConnectRequest::Tunnel(mut head, addr) => {
+                merge_missing(&mut head.headers, extra);
+                ConnectRequest::Tunnel(head, addr)
+            }
+        };
+
+        self.connector.call(req)
+    }
+}
+
+/// Adds headers from `extra` whose names are not yet present in `headers`.
+fn merge_missing(headers: &mut HeaderMap, extra: HeaderMap) {
+    for (name, value) in extra.iter() {
+        if !headers.contains_key(name) {
+            headers.append(name.clone(), value.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        middleware::{RequestId, RequestIdentifier},
+        web, App, Error, HttpRequest, HttpResponse,
+    };
+
+    use super::*;
+    use crate::{http::HeaderValue, ClientBuilder};
+
+    async fn echo(req: HttpRequest) -> Result<HttpResponse, Error> {
+        let header = |name| {
+            req.headers()
+                .get(name)
+                .and_then(|val| val.to_str().ok())
+                .unwrap_or("-")
+                .to_owned()
+        };
+
+        Ok(HttpResponse::Ok().body(format!("{} {}", header("x-a"), header("x-b"))))
+    }
+
+    #[actix_rt::test]
+    async fn test_propagate_headers() {
+        let srv = actix_test::start(|| App::new().route("/", web::get().to(echo)));
+
+        let client = ClientBuilder::new()
+            .wrap(Propagate::new(|headers| {
+                headers.insert("x-a".parse().unwrap(), HeaderValue::from_static("a"));
+                headers.insert("x-b".parse().unwrap(), HeaderValue::from_static("b"));
+            }))
+            .finish();
+
+        let mut res = client.get(srv.url("/")).send().await.unwrap();
+        assert_eq!(res.body().await.unwrap(), "a b");
+
+        // headers set on the request take precedence
+        let mut res = client
+            .get(srv.url("/"))
+            .insert_header(("x-b", "explicit"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.body().await.unwrap(), "a explicit");
+    }
+
+    #[actix_rt::test]
+    async fn test_propagate_request_id() {
+        let downstream = actix_test::start(|| {
+            App::new().route(
+                "/",
+                web::get().to(|req: HttpRequest| async move {
+                    let id = req.headers().get("x-request-id").unwrap().clone();
+                    assert!(req.headers().contains_key("traceparent"));
+                    Ok::<_, Error>(HttpResponse::Ok().body(id.as_bytes().to_vec()))
+                }),
+            )
+        });
+        let url = downstream.url("/");
+
+        let srv = actix_test::start(move || {
+            let url = url.clone();
+
+            App::new().wrap(RequestIdentifier::default()).route(
+                "/",
+                web::get().to(move || {
+                    let client = ClientBuilder::new()
+                        .wrap(Propagate::new(|headers| {
+                            if let Some(id) = RequestId::current() {
+                                id.propagate(headers);
+                            }
+                        }))
+                        .finish();
+                    let req = client.get(url.clone());
+
+                    async move {
+                        let mut res = req.send().await.unwrap();
+                        let body = res.body().await.unwrap();
+                        Ok::<_, Error>(HttpResponse::Ok().body(body))
+                    }
+                }),
+            )
+        });
+
+        let mut res = srv
+            .get("/")
+            .insert_header(("x-request-id", "abc-123"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.body().await.unwrap(), "abc-123");
+    }
+}
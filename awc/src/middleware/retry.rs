@@ -0,0 +1,515 @@
+use std::{
+    cmp,
+    rc::Rc,
+    time::{Duration, SystemTime},
+};
+
+use actix_http::{
+    body::Body,
+    client::{ConnectError, SendRequestError},
+    http::{
+        header::{self, HttpDate},
+        Method, StatusCode,
+    },
+    RequestHeadType,
+};
+use actix_rt::time::{sleep, timeout};
+use actix_service::Service;
+use futures_core::future::LocalBoxFuture;
+use rand::Rng;
+
+use super::Transform;
+
+use crate::connect::{ConnectRequest, ConnectResponse};
+use crate::ClientResponse;
+
+/// Middleware for retrying failed requests with exponential backoff.
+///
+/// Requests are retried when connecting to the host fails, when an attempt times out or the
+/// connection breaks, and when the response has one of the configured status codes (by default
+/// `429`, `502`, `503` and `504`).
+///
+/// Only requests with idempotent methods are retried after they have been sent, unless
+/// [`allow_non_idempotent`](Self::allow_non_idempotent) is enabled; failing to connect is always
+/// safe to retry. Requests with a streaming body are never retried because the body can not be
+/// replayed.
+///
+/// # Examples
+/// ```
+/// use std::time::Duration;
+/// use awc::{middleware::Retry, ClientBuilder};
+///
+/// let client = ClientBuilder::new()
+///     .wrap(
+///         Retry::new()
+///             .max_retries(5)
+///             .backoff(Duration::from_millis(50), Duration::from_secs(2)),
+///     )
+///     .finish();
+/// ```
+#[derive(Debug, Clone)]
+pub struct Retry {
+    max_retries: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    jitter: bool,
+    statuses: Vec<StatusCode>,
+    non_idempotent: bool,
+    retry_after: bool,
+    attempt_timeout: Option<Duration>,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Retry {
+    /// Constructs retry middleware with default settings.
+    ///
+    /// Up to 3 retries with delays starting at 100ms and capped at 10s, with jitter.
+    pub fn new() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(10),
+            jitter: true,
+            statuses: vec![
+                StatusCode::TOO_MANY_REQUESTS,
+                StatusCode::BAD_GATEWAY,
+                StatusCode::SERVICE_UNAVAILABLE,
+                StatusCode::GATEWAY_TIMEOUT,
+            ],
+            non_idempotent: false,
+            retry_after: true,
+            attempt_timeout: None,
+        }
+    }
+
+    /// Set maximum number of retries after the initial attempt.
+    pub fn max_retries(mut self, retries: u32) -> Self {
+        self.max_retries = retries;
+        self
+    }
+
+    /// Set delay before the first retry and the upper bound of delays.
+    ///
+    /// The delay doubles after each retry until it reaches `max`.
+    pub fn backoff(mut self, base: Duration, max: Duration) -> Self {
+        self.base_delay = base;
+        self.max_delay = cmp::max(base, max);
+        self
+    }
+
+    /// Enable or disable randomizing delays.
+    ///
+    /// When enabled, each delay is picked uniformly between half and the full computed delay.
+    /// Enabled by default.
+    pub fn jitter(mut self, enabled: bool) -> Self {
+        self.jitter = enabled;
+        self
+    }
+
+    /// Set response status codes that trigger a retry.
+    pub fn statuses<I>(mut self, statuses: I) -> Self
+    where
+        I: IntoIterator<Item = StatusCode>,
+    {
+        self.statuses = statuses.into_iter().collect();
+        self
+    }
+
+    /// Allow retrying requests with non-idempotent methods (e.g. `POST`) after they have been
+    /// sent. Disabled by default.
+    pub fn allow_non_idempotent(mut self, allow: bool) -> Self {
+        self.non_idempotent = allow;
+        self
+    }
+
+    /// Enable or disable honouring the `Retry-After` response header.
+    ///
+    /// When enabled, the delay requested by the server is used instead of the computed backoff.
+    /// Responses asking to wait longer than the maximum backoff delay are returned without
+    /// retrying. Enabled by default.
+    pub fn retry_after(mut self, enabled: bool) -> Self {
+        self.retry_after = enabled;
+        self
+    }
+
+    /// Set timeout for a single attempt.
+    ///
+    /// Attempts exceeding it are aborted and retried. Note that the client-wide timeout still
+    /// covers all attempts together.
+    pub fn attempt_timeout(mut self, dur: Duration) -> Self {
+        self.attempt_timeout = Some(dur);
+        self
+    }
+
+    fn delay(&self, retry: u32) -> Duration {
+        let factor = 1u32.checked_shl(retry).unwrap_or(u32::MAX);
+        let delay = self
+            .base_delay
+            .checked_mul(factor)
+            .map_or(self.max_delay, |delay| cmp::min(delay, self.max_delay));
+
+        if self.jitter && delay > Duration::from_millis(1) {
+            let half = delay / 2;
+            let millis = rand::thread_rng().gen_range(0..=(delay - half).as_millis() as u64);
+            half + Duration::from_millis(millis)
+        } else {
+            delay
+        }
+    }
+}
+
+impl<S> Transform<S, ConnectRequest> for Retry
+where
+    S: Service<ConnectRequest, Response = ConnectResponse, Error = SendRequestError> + 'static,
+{
+    type Transform = RetryService<S>;
+
+    fn new_transform(self, service: S) -> Self::Transform {
+        RetryService {
+            config: Rc::new(self),
+            connector: Rc::new(service),
+        }
+    }
+}
+
+pub struct RetryService<S> {
+    config: Rc<Retry>,
+    connector: Rc<S>,
+}
+
+impl<S> Service<ConnectRequest> for RetryService<S>
+where
+    S: Service<ConnectRequest, Response = ConnectResponse, Error = SendRequestError> + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = LocalBoxFuture<'static, Result<ConnectResponse, SendRequestError>>;
+
+    actix_service::forward_ready!(connector);
+
+    fn call(&self, req: ConnectRequest) -> Self::Future {
+        let (head, body, addr) = match req {
+            ConnectRequest::Client(head, body, addr) => (head, body, addr),
+            req @ ConnectRequest::Tunnel(..) => return Box::pin(self.connector.call(req)),
+        };
+
+        // streaming bodies can not be replayed so they get a single attempt.
+        if replay_body(&body).is_none() {
+            return Box::pin(
+                self.connector
+                    .call(ConnectRequest::Client(head, body, addr)),
+            );
+        }
+
+        // share the head between attempts.
+        let (head, extra_headers) = match head {
+            RequestHeadType::Owned(head) => (Rc::new(head), None),
+            RequestHeadType::Rc(head, extra_headers) => (head, extra_headers),
+        };
+
+        let config = self.config.clone();
+        let connector = self.connector.clone();
+
+        Box::pin(async move {
+            let idempotent = config.non_idempotent || is_idempotent(&head.method);
+            let mut retries = 0;
+
+            loop {
+                let req_head = RequestHeadType::Rc(head.clone(), extra_headers.clone());
+                let req_body = replay_body(&body).unwrap();
+                let fut = connector.call(ConnectRequest::Client(req_head, req_body, addr));
+
+                let res = match config.attempt_timeout {
+                    Some(dur) => timeout(dur, fut)
+                        .await
+                        .unwrap_or(Err(SendRequestError::Timeout)),
+                    None => fut.await,
+                };
+
+                if retries >= config.max_retries {
+                    return res;
+                }
+
+                let delay = match res {
+                    Ok(ConnectResponse::Client(ref res))
+                        if idempotent && config.statuses.contains(&res.status()) =>
+                    {
+                        match retry_after(res).filter(|_| config.retry_after) {
+                            // server asks to wait longer than we are willing to.
+                            Some(delay) if delay > config.max_delay => None,
+                            Some(delay) => Some(delay),
+                            None => Some(config.delay(retries)),
+                        }
+                    }
+                    Err(ref err) if is_retryable(err, idempotent) => {
+                        Some(config.delay(retries))
+                    }
+                    _ => None,
+                };
+
+                match delay {
+                    Some(delay) => {
+                        log::debug!("retrying request to {} in {:?}", head.uri, delay);
+
+                        // release the connection before waiting.
+                        drop(res);
+                        sleep(delay).await;
+                        retries += 1;
+                    }
+                    None => return res,
+                }
+            }
+        })
+    }
+}
+
+/// Clones a buffered body; returns `None` for streaming bodies.
+fn replay_body(body: &Body) -> Option<Body> {
+    match body {
+        Body::None => Some(Body::None),
+        Body::Empty => Some(Body::Empty),
+        Body::Bytes(ref bytes) => Some(Body::Bytes(bytes.clone())),
+        Body::Message(_) => None,
+    }
+}
+
+fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET
+            | Method::HEAD
+            | Method::OPTIONS
+            | Method::TRACE
+            | Method::PUT
+            | Method::DELETE
+    )
+}
+
+fn is_retryable(err: &SendRequestError, idempotent: bool) -> bool {
+    match err {
+        // nothing was sent when connecting fails so it is always safe to try again.
+        SendRequestError::Connect(ConnectError::SslIsNotSupported)
+        | SendRequestError::Connect(ConnectError::Unresolved) => false,
+        SendRequestError::Connect(_) => true,
+
+        // the request may have been (partially) processed by the server.
+        SendRequestError::Timeout | SendRequestError::Send(_) | SendRequestError::H2(_) => {
+            idempotent
+        }
+
+        _ => false,
+    }
+}
+
+/// Parses `Retry-After` header in either delay-seconds or HTTP-date form.
+fn retry_after(res: &ClientResponse) -> Option<Duration> {
+    let value = res
+        .headers()
+        .get(header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let date: SystemTime = value.parse::<HttpDate>().ok()?.into();
+    Some(date.duration_since(SystemTime::now()).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use actix_utils::future::ok;
+    use actix_web::{web, App, Error, HttpResponse};
+    use bytes::Bytes;
+    use futures_util::stream::once;
+
+    use super::*;
+    use crate::ClientBuilder;
+
+    fn retry() -> Retry {
+        Retry::new().backoff(Duration::from_millis(1), Duration::from_millis(10))
+    }
+
+    /// Starts a server responding with `status` until `failures` requests were received.
+    fn flaky_server(
+        failures: usize,
+        status: StatusCode,
+    ) -> (actix_test::TestServer, Arc<AtomicUsize>) {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+
+        let srv = actix_test::start(move || {
+            let counter = counter.clone();
+
+            App::new().default_service(web::to(move |body: Bytes| {
+                let hit = counter.fetch_add(1, Ordering::SeqCst);
+
+                async move {
+                    if hit < failures {
+                        Ok::<_, Error>(HttpResponse::build(status).finish())
+                    } else {
+                        Ok(HttpResponse::Ok().body(body))
+                    }
+                }
+            }))
+        });
+
+        (srv, hits)
+    }
+
+    #[actix_rt::test]
+    async fn test_retry_status() {
+        let (srv, hits) = flaky_server(2, StatusCode::SERVICE_UNAVAILABLE);
+        let client = ClientBuilder::new().wrap(retry()).finish();
+
+        let res = client.get(srv.url("/")).send().await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[actix_rt::test]
+    async fn test_retry_limit() {
+        let (srv, hits) = flaky_server(10, StatusCode::BAD_GATEWAY);
+        let client = ClientBuilder::new().wrap(retry().max_retries(2)).finish();
+
+        let res = client.get(srv.url("/")).send().await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[actix_rt::test]
+    async fn test_retry_unlisted_status() {
+        let (srv, hits) = flaky_server(1, StatusCode::INTERNAL_SERVER_ERROR);
+        let client = ClientBuilder::new().wrap(retry()).finish();
+
+        let res = client.get(srv.url("/")).send().await.unwrap();
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[actix_rt::test]
+    async fn test_retry_non_idempotent() {
+        let (srv, hits) = flaky_server(1, StatusCode::SERVICE_UNAVAILABLE);
+        let client = ClientBuilder::new().wrap(retry()).finish();
+
+        let res = client.post(srv.url("/")).send_body("data").await.unwrap();
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        let (srv, hits) = flaky_server(1, StatusCode::SERVICE_UNAVAILABLE);
+        let client = ClientBuilder::new()
+            .wrap(retry().allow_non_idempotent(true))
+            .finish();
+
+        // buffered body is replayed
+        let mut res = client.post(srv.url("/")).send_body("data").await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.body().await.unwrap(), Bytes::from_static(b"data"));
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[actix_rt::test]
+    async fn test_retry_streaming_body() {
+        let (srv, hits) = flaky_server(1, StatusCode::SERVICE_UNAVAILABLE);
+        let client = ClientBuilder::new().wrap(retry()).finish();
+
+        let body = once(ok::<_, actix_http::Error>(Bytes::from_static(b"data")));
+        let res = client.put(srv.url("/")).send_stream(body).await.unwrap();
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[actix_rt::test]
+    async fn test_retry_after() {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+
+        let srv = actix_test::start(move || {
+            let counter = counter.clone();
+
+            App::new().default_service(web::to(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async {
+                    Ok::<_, Error>(
+                        HttpResponse::TooManyRequests()
+                            .insert_header((header::RETRY_AFTER, "3600"))
+                            .finish(),
+                    )
+                }
+            }))
+        });
+
+        // server asks to wait longer than max backoff delay
+        let client = ClientBuilder::new().wrap(retry()).finish();
+        let res = client.get(srv.url("/")).send().await.unwrap();
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        // header ignored
+        let client = ClientBuilder::new()
+            .wrap(retry().retry_after(false).max_retries(1))
+            .finish();
+        let res = client.get(srv.url("/")).send().await.unwrap();
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[actix_rt::test]
+    async fn test_retry_attempt_timeout() {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+
+        let srv = actix_test::start(move || {
+            let counter = counter.clone();
+
+            App::new().default_service(web::to(move || {
+                let hit = counter.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    if hit == 0 {
+                        sleep(Duration::from_secs(1)).await;
+                    }
+                    Ok::<_, Error>(HttpResponse::Ok().finish())
+                }
+            }))
+        });
+
+        let client = ClientBuilder::new()
+            .wrap(retry().attempt_timeout(Duration::from_millis(100)))
+            .finish();
+        let res = client.get(srv.url("/")).send().await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_delay() {
+        let retry = Retry::new()
+            .backoff(Duration::from_millis(100), Duration::from_secs(1))
+            .jitter(false);
+
+        assert_eq!(retry.delay(0), Duration::from_millis(100));
+        assert_eq!(retry.delay(1), Duration::from_millis(200));
+        assert_eq!(retry.delay(3), Duration::from_millis(800));
+        assert_eq!(retry.delay(4), Duration::from_secs(1));
+        assert_eq!(retry.delay(40), Duration::from_secs(1));
+
+        let retry = retry.jitter(true);
+        for _ in 0..100 {
+            let delay = retry.delay(2);
+            assert!(delay >= Duration::from_millis(200));
+            assert!(delay <= Duration::from_millis(400));
+        }
+    }
+}
@@ -0,0 +1,529 @@
+//! Multipart form (`multipart/form-data`) request bodies.
+//!
+//! # Examples
+//! ```no_run
+//! use awc::multipart::{Form, Part};
+//!
+//! # #[actix_rt::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let form = Form::new()
+//!     .text("description", "holiday photos")
+//!     .part("photo", Part::file("./photo.jpg").await?.content_type(mime::IMAGE_JPEG));
+//!
+//! let res = awc::Client::new()
+//!     .post("http://localhost:8080/upload")
+//!     .send_multipart(form)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    collections::VecDeque,
+    error::Error as StdError,
+    fmt, io,
+    path::Path,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use actix_http::{
+    body::{BodySize, MessageBody},
+    http::{HeaderMap, HeaderName, HeaderValue},
+};
+use bytes::{BufMut as _, Bytes, BytesMut};
+use futures_core::{ready, Stream};
+use mime::Mime;
+use rand::{distributions::Alphanumeric, Rng as _};
+use tokio::io::{AsyncRead, ReadBuf};
+
+type BoxStream = Pin<Box<dyn Stream<Item = Result<Bytes, Box<dyn StdError>>>>>;
+
+/// A `multipart/form-data` request body.
+///
+/// Sent with [`ClientRequest::send_multipart`](crate::ClientRequest::send_multipart), which
+/// always sets the `Content-Type` header with the form's boundary. The body has a known length,
+/// and is sent with a `Content-Length` header, if the sizes of all parts are known.
+pub struct Form {
+    boundary: String,
+    parts: Vec<(String, Part)>,
+}
+
+impl Form {
+    /// Constructs an empty form with a random boundary.
+    pub fn new() -> Self {
+        let boundary = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect();
+
+        Form {
+            boundary,
+            parts: Vec::new(),
+        }
+    }
+
+    /// Returns the boundary separating the parts of the form.
+    pub fn boundary(&self) -> &str {
+        &self.boundary
+    }
+
+    /// Returns the `Content-Type` header value for the form.
+    pub fn content_type(&self) -> String {
+        format!("multipart/form-data; boundary={}", self.boundary)
+    }
+
+    /// Adds a text field.
+    pub fn text(self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.part(name, Part::text(value))
+    }
+
+    /// Adds a field with the given contents.
+    pub fn bytes(self, name: impl Into<String>, value: impl Into<Bytes>) -> Self {
+        self.part(name, Part::bytes(value))
+    }
+
+    /// Adds a part.
+    pub fn part(mut self, name: impl Into<String>, part: Part) -> Self {
+        self.parts.push((name.into(), part));
+        self
+    }
+
+    /// Encodes the form into a request body.
+    pub(crate) fn into_body(self) -> FormBody {
+        let mut size = Some(0u64);
+        let mut parts = VecDeque::with_capacity(self.parts.len());
+
+        for (idx, (name, part)) in self.parts.into_iter().enumerate() {
+            let head = part.encode_head(&self.boundary, &name, idx > 0);
+
+            size = match (size, part.body.size()) {
+                (Some(size), Some(body_size)) => Some(size + head.len() as u64 + body_size),
+                _ => None,
+            };
+
+            parts.push_back((head, part.body));
+        }
+
+        let tail = if parts.is_empty() {
+            Bytes::from(format!("--{}--\r\n", self.boundary))
+        } else {
+            Bytes::from(format!("\r\n--{}--\r\n", self.boundary))
+        };
+
+        let size = match size {
+            Some(size) => BodySize::Sized(size + tail.len() as u64),
+            None => BodySize::Stream,
+        };
+
+        FormBody {
+            size,
+            parts,
+            current: None,
+            tail: Some(tail),
+        }
+    }
+}
+
+impl Default for Form {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Form {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Form")
+            .field("boundary", &self.boundary)
+            .field("parts", &self.parts)
+            .finish()
+    }
+}
+
+/// A single part of a [`Form`].
+pub struct Part {
+    body: PartBody,
+    file_name: Option<String>,
+    content_type: Option<Mime>,
+    headers: HeaderMap,
+}
+
+impl Part {
+    fn new(body: PartBody) -> Self {
+        Part {
+            body,
+            file_name: None,
+            content_type: None,
+            headers: HeaderMap::new(),
+        }
+    }
+
+    /// Constructs a text part. No `Content-Type` is sent, meaning `text/plain` to the server.
+    pub fn text(value: impl Into<String>) -> Self {
+        Self::new(PartBody::Bytes(Some(Bytes::from(value.into()))))
+    }
+
+    /// Constructs a part with the given contents, sent as `application/octet-stream`.
+    pub fn bytes(value: impl Into<Bytes>) -> Self {
+        Self::new(PartBody::Bytes(Some(value.into())))
+            .content_type(mime::APPLICATION_OCTET_STREAM)
+    }
+
+    /// Constructs a part of unknown size from a stream, sent as `application/octet-stream`.
+    ///
+    /// The form will be sent without a `Content-Length` header.
+    pub fn stream<S, E>(stream: S) -> Self
+    where
+        S: Stream<Item = Result<Bytes, E>> + 'static,
+        E: Into<Box<dyn StdError>> + 'static,
+    {
+        Self::new(PartBody::Stream {
+            stream: Box::pin(ErrInto(Box::pin(stream))),
+            remaining: None,
+        })
+        .content_type(mime::APPLICATION_OCTET_STREAM)
+    }
+
+    /// Constructs a part of known size from a stream, sent as `application/octet-stream`.
+    ///
+    /// Sending the form fails if the stream does not yield exactly `size` bytes.
+    pub fn sized_stream<S, E>(stream: S, size: u64) -> Self
+    where
+        S: Stream<Item = Result<Bytes, E>> + 'static,
+        E: Into<Box<dyn StdError>> + 'static,
+    {
+        Self::new(PartBody::Stream {
+            stream: Box::pin(ErrInto(Box::pin(stream))),
+            remaining: Some(size),
+        })
+        .content_type(mime::APPLICATION_OCTET_STREAM)
+    }
+
+    /// Opens a file to be streamed as a part, sent as `application/octet-stream`.
+    ///
+    /// The file name is set to the last component of `path`.
+    pub async fn file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let file = tokio::fs::File::open(path).await?;
+        let size = file.metadata().await?.len();
+
+        let mut part = Self::sized_stream(FileStream::new(file), size);
+        part.file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned());
+
+        Ok(part)
+    }
+
+    /// Sets the file name sent in the `Content-Disposition` header.
+    pub fn file_name(mut self, file_name: impl Into<String>) -> Self {
+        self.file_name = Some(file_name.into());
+        self
+    }
+
+    /// Sets the `Content-Type` of the part.
+    pub fn content_type(mut self, content_type: Mime) -> Self {
+        self.content_type = Some(content_type);
+        self
+    }
+
+    /// Inserts a header into the part, replacing any header with the same name.
+    ///
+    /// The `Content-Disposition` and `Content-Type` headers are generated from the form field
+    /// name, [file name](Self::file_name) and [content type](Self::content_type).
+    pub fn insert_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    fn encode_head(&self, boundary: &str, name: &str, leading_crlf: bool) -> Bytes {
+        let mut buf = BytesMut::with_capacity(128);
+
+        if leading_crlf {
+            buf.put_slice(b"\r\n");
+        }
+
+        buf.put_slice(format!("--{}\r\n", boundary).as_bytes());
+        buf.put_slice(b"content-disposition: form-data; name=\"");
+        buf.put_slice(escape_quoted(name).as_bytes());
+        buf.put_u8(b'"');
+
+        if let Some(file_name) = &self.file_name {
+            buf.put_slice(b"; filename=\"");
+            buf.put_slice(escape_quoted(file_name).as_bytes());
+            buf.put_u8(b'"');
+        }
+
+        buf.put_slice(b"\r\n");
+
+        if let Some(content_type) = &self.content_type {
+            buf.put_slice(format!("content-type: {}\r\n", content_type).as_bytes());
+        }
+
+        for (name, value) in self.headers.iter() {
+            if name == actix_http::http::header::CONTENT_DISPOSITION
+                || name == actix_http::http::header::CONTENT_TYPE
+            {
+                continue;
+            }
+
+            buf.put_slice(name.as_str().as_bytes());
+            buf.put_slice(b": ");
+            buf.put_slice(value.as_bytes());
+            buf.put_slice(b"\r\n");
+        }
+
+        buf.put_slice(b"\r\n");
+        buf.freeze()
+    }
+}
+
+impl fmt::Debug for Part {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Part")
+            .field("size", &self.body.size())
+            .field("file_name", &self.file_name)
+            .field("content_type", &self.content_type)
+            .field("headers", &self.headers)
+            .finish()
+    }
+}
+
+/// Escapes a quoted `Content-Disposition` parameter the way browsers do.
+fn escape_quoted(val: &str) -> String {
+    val.replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+enum PartBody {
+    Bytes(Option<Bytes>),
+    Stream {
+        stream: BoxStream,
+        remaining: Option<u64>,
+    },
+}
+
+impl PartBody {
+    fn size(&self) -> Option<u64> {
+        match self {
+            PartBody::Bytes(bytes) => {
+                Some(bytes.as_ref().map_or(0, |bytes| bytes.len() as u64))
+            }
+            PartBody::Stream { remaining, .. } => *remaining,
+        }
+    }
+
+    fn poll_next(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Box<dyn StdError>>>> {
+        match self {
+            PartBody::Bytes(bytes) => Poll::Ready(bytes.take().map(Ok)),
+
+            PartBody::Stream { stream, remaining } => {
+                let res = ready!(stream.as_mut().poll_next(cx));
+
+                // a sized part must match the announced content length
+                match (res, remaining) {
+                    (Some(Ok(chunk)), Some(remaining)) => {
+                        if chunk.len() as u64 > *remaining {
+                            return Poll::Ready(Some(Err(size_mismatch())));
+                        }
+
+                        *remaining -= chunk.len() as u64;
+                        Poll::Ready(Some(Ok(chunk)))
+                    }
+                    (None, Some(remaining)) if *remaining > 0 => {
+                        Poll::Ready(Some(Err(size_mismatch())))
+                    }
+                    (res, _) => Poll::Ready(res),
+                }
+            }
+        }
+    }
+}
+
+fn size_mismatch() -> Box<dyn StdError> {
+    Box::new(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "multipart stream did not match its declared size",
+    ))
+}
+
+/// Body stream of an encoded [`Form`].
+pub(crate) struct FormBody {
+    size: BodySize,
+    parts: VecDeque<(Bytes, PartBody)>,
+    current: Option<PartBody>,
+    tail: Option<Bytes>,
+}
+
+impl MessageBody for FormBody {
+    type Error = Box<dyn StdError>;
+
+    fn size(&self) -> BodySize {
+        self.size
+    }
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        let this = self.get_mut();
+
+        loop {
+            if let Some(body) = this.current.as_mut() {
+                match ready!(body.poll_next(cx)) {
+                    Some(Ok(chunk)) if chunk.is_empty() => continue,
+                    Some(res) => return Poll::Ready(Some(res)),
+                    None => this.current = None,
+                }
+            }
+
+            if let Some((head, body)) = this.parts.pop_front() {
+                this.current = Some(body);
+                return Poll::Ready(Some(Ok(head)));
+            }
+
+            return Poll::Ready(this.tail.take().map(Ok));
+        }
+    }
+}
+
+/// Boxes the error type of a stream.
+struct ErrInto<S>(Pin<Box<S>>);
+
+impl<S, E> Stream for ErrInto<S>
+where
+    S: Stream<Item = Result<Bytes, E>>,
+    E: Into<Box<dyn StdError>>,
+{
+    type Item = Result<Bytes, Box<dyn StdError>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.0
+            .as_mut()
+            .poll_next(cx)
+            .map(|res| res.map(|res| res.map_err(Into::into)))
+    }
+}
+
+/// Reads a file in chunks.
+struct FileStream {
+    file: tokio::fs::File,
+    buf: Vec<u8>,
+}
+
+impl FileStream {
+    fn new(file: tokio::fs::File) -> Self {
+        FileStream {
+            file,
+            buf: vec![0; 65_536],
+        }
+    }
+}
+
+impl Stream for FileStream {
+    type Item = Result<Bytes, io::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let mut buf = ReadBuf::new(&mut this.buf);
+
+        ready!(Pin::new(&mut this.file).poll_read(cx, &mut buf))?;
+
+        let filled = buf.filled();
+
+        if filled.is_empty() {
+            Poll::Ready(None)
+        } else {
+            Poll::Ready(Some(Ok(Bytes::copy_from_slice(filled))))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_http::body::to_bytes;
+
+    use super::*;
+
+    fn body_bytes(form: Form) -> Bytes {
+        actix_rt::System::new()
+            .block_on(to_bytes(form.into_body()))
+            .unwrap()
+    }
+
+    #[test]
+    fn encode_form() {
+        let form = Form::new()
+            .text("a", "1")
+            .part("file", Part::bytes("data").file_name("a \"b\".txt"));
+        let boundary = form.boundary().to_owned();
+
+        let body = form.into_body();
+        let size = body.size();
+        let bytes = actix_rt::System::new().block_on(to_bytes(body)).unwrap();
+
+        assert_eq!(size, BodySize::Sized(bytes.len() as u64));
+        assert_eq!(
+            bytes,
+            format!(
+                "--{b}\r\n\
+                 content-disposition: form-data; name=\"a\"\r\n\
+                 \r\n\
+                 1\r\n\
+                 --{b}\r\n\
+                 content-disposition: form-data; name=\"file\"; filename=\"a %22b%22.txt\"\r\n\
+                 content-type: application/octet-stream\r\n\
+                 \r\n\
+                 data\r\n\
+                 --{b}--\r\n",
+                b = boundary
+            )
+        );
+
+        let form = Form::new();
+        let boundary = form.boundary().to_owned();
+        assert_eq!(body_bytes(form), format!("--{}--\r\n", boundary));
+    }
+
+    #[test]
+    fn stream_sizes() {
+        fn chunks() -> impl Stream<Item = Result<Bytes, io::Error>> {
+            struct Chunks(Vec<Bytes>);
+
+            impl Stream for Chunks {
+                type Item = Result<Bytes, io::Error>;
+
+                fn poll_next(
+                    mut self: Pin<&mut Self>,
+                    _: &mut Context<'_>,
+                ) -> Poll<Option<Self::Item>> {
+                    Poll::Ready(self.0.pop().map(Ok))
+                }
+            }
+
+            Chunks(vec![Bytes::from_static(b"cd"), Bytes::from_static(b"ab")])
+        }
+
+        let form = Form::new().part("s", Part::stream(chunks()));
+        assert_eq!(form.into_body().size(), BodySize::Stream);
+
+        let form = Form::new().part("s", Part::sized_stream(chunks(), 4));
+        let body = form.into_body();
+        let size = body.size();
+        let bytes = actix_rt::System::new().block_on(to_bytes(body)).unwrap();
+        assert_eq!(size, BodySize::Sized(bytes.len() as u64));
+        assert!(bytes.windows(4).any(|chunk| chunk == b"abcd"));
+
+        // too short and too long
+        for size in &[3, 5] {
+            let form = Form::new().part("s", Part::sized_stream(chunks(), *size));
+            let res = actix_rt::System::new().block_on(to_bytes(form.into_body()));
+            assert!(res.is_err());
+        }
+    }
+}
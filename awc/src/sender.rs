@@ -27,6 +27,7 @@ use actix_http::{encoding::Decoder, http::header::ContentEncoding, Payload, Payl
 
 use crate::{
     error::{FreezeRequestError, InvalidUrl, SendRequestError},
+    multipart::Form,
     ClientConfig, ClientResponse, ConnectRequest, ConnectResponse,
 };
 
@@ -269,6 +270,28 @@ impl RequestSender {
         )
     }
 
+    pub(crate) fn send_multipart(
+        mut self,
+        addr: Option<net::SocketAddr>,
+        response_decompress: bool,
+        timeout: Option<Duration>,
+        config: &ClientConfig,
+        form: Form,
+    ) -> SendClientRequest {
+        // the body can only be parsed with the form's own boundary
+        if let Err(e) = self.set_header(header::CONTENT_TYPE, form.content_type()) {
+            return e.into();
+        }
+
+        self.send_body(
+            addr,
+            response_decompress,
+            timeout,
+            config,
+            Body::from_message(form.into_body()),
+        )
+    }
+
     pub(crate) fn send_stream<S, E>(
         self,
         addr: Option<net::SocketAddr>,
@@ -300,6 +323,27 @@ impl RequestSender {
         self.send_body(addr, response_decompress, timeout, config, Body::Empty)
     }
 
+    fn set_header<V>(&mut self, key: HeaderName, value: V) -> Result<(), HttpError>
+    where
+        V: IntoHeaderValue,
+    {
+        let value = value.try_into_value().map_err(Into::<HttpError>::into)?;
+
+        match self {
+            RequestSender::Owned(head) => {
+                head.headers.insert(key, value);
+            }
+            // extra headers take precedence over those of the shared head
+            RequestSender::Rc(_, extra_headers) => {
+                extra_headers
+                    .get_or_insert(HeaderMap::new())
+                    .insert(key, value);
+            }
+        }
+
+        Ok(())
+    }
+
     fn set_header_if_none<V>(&mut self, key: HeaderName, value: V) -> Result<(), HttpError>
     where
         V: IntoHeaderValue,
@@ -35,6 +35,8 @@ use actix_http::{ws, Payload, RequestHead};
 use actix_rt::time::timeout;
 use actix_service::Service;
 
+#[cfg(feature = "ws-deflate")]
+pub use actix_http::ws::DeflateConfig;
 pub use actix_http::ws::{CloseCode, CloseReason, Codec, Frame, Message};
 
 use crate::connect::{BoxedSocket, ConnectRequest};
@@ -57,6 +59,9 @@ pub struct WebsocketsRequest {
     server_mode: bool,
     config: ClientConfig,
 
+    #[cfg(feature = "ws-deflate")]
+    deflate: Option<DeflateConfig>,
+
     #[cfg(feature = "cookies")]
     cookies: Option<CookieJar>,
 }
@@ -92,6 +97,8 @@ impl WebsocketsRequest {
             protocols: None,
             max_size: 65_536,
             server_mode: false,
+            #[cfg(feature = "ws-deflate")]
+            deflate: None,
             #[cfg(feature = "cookies")]
             cookies: None,
         }
@@ -160,6 +167,17 @@ impl WebsocketsRequest {
         self
     }
 
+    /// Offer the `permessage-deflate` extension to the server.
+    ///
+    /// If the server accepts, the returned codec transparently compresses outgoing and
+    /// decompresses incoming messages. Servers may decline, in which case the connection
+    /// proceeds uncompressed.
+    #[cfg(feature = "ws-deflate")]
+    pub fn deflate(mut self, config: DeflateConfig) -> Self {
+        self.deflate = Some(config);
+        self
+    }
+
     /// Append a header.
     ///
     /// Header gets appended to existing header.
@@ -312,6 +330,13 @@ impl WebsocketsRequest {
             );
         }
 
+        #[cfg(feature = "ws-deflate")]
+        if let Some(ref deflate) = self.deflate {
+            self.head
+                .headers
+                .insert(header::SEC_WEBSOCKET_EXTENSIONS, deflate.offer());
+        }
+
         // Generate a random key for the `Sec-WebSocket-Key` header.
         // a base64-encoded (see Section 4 of [RFC4648]) value that,
         // when decoded, is 16 bytes in length (RFC 6455)
@@ -326,6 +351,8 @@ impl WebsocketsRequest {
         let head = self.head;
         let max_size = self.max_size;
         let server_mode = self.server_mode;
+        #[cfg(feature = "ws-deflate")]
+        let deflate = self.deflate;
 
         let req = ConnectRequest::Tunnel(head, self.addr);
 
@@ -398,16 +425,26 @@ impl WebsocketsRequest {
             return Err(WsClientError::MissingWebSocketAcceptHeader);
         };
 
+        let codec = if server_mode {
+            ws::Codec::new().max_size(max_size)
+        } else {
+            ws::Codec::new().max_size(max_size).client_mode()
+        };
+
+        // server may have declined compression
+        #[cfg(feature = "ws-deflate")]
+        let codec = match deflate
+            .map(|deflate| deflate.accept_response(&head.headers))
+            .transpose()?
+        {
+            Some(Some(params)) => codec.permessage_deflate(params),
+            _ => codec,
+        };
+
         // response and ws framed
         Ok((
             ClientResponse::new(head, Payload::None),
-            framed.into_map_codec(|_| {
-                if server_mode {
-                    ws::Codec::new().max_size(max_size)
-                } else {
-                    ws::Codec::new().max_size(max_size).client_mode()
-                }
-            }),
+            framed.replace_codec(codec),
         ))
     }
 }
@@ -112,6 +112,111 @@ async fn test_form() {
     assert!(response.status().is_success());
 }
 
+#[actix_rt::test]
+async fn test_multipart() {
+    use futures_util::TryStreamExt as _;
+
+    async fn echo_parts(req: HttpRequest, mut form: actix_multipart::Multipart) -> String {
+        let mut out = format!("{}\n", req.headers().contains_key(header::CONTENT_LENGTH));
+
+        while let Some(mut field) = form.try_next().await.unwrap() {
+            let cd = field.content_disposition().unwrap();
+            let mut data = Vec::new();
+            while let Some(chunk) = field.try_next().await.unwrap() {
+                data.extend_from_slice(&chunk);
+            }
+
+            out.push_str(&format!(
+                "{} {:?} {} {:?} {}\n",
+                cd.get_name().unwrap(),
+                cd.get_filename(),
+                field.content_type(),
+                field
+                    .headers()
+                    .get("x-part")
+                    .map(|val| val.to_str().unwrap()),
+                data.len(),
+            ));
+        }
+
+        out
+    }
+
+    let srv = actix_test::start(|| App::new().route("/", web::post().to(echo_parts)));
+
+    let file_len = std::fs::metadata("Cargo.toml").unwrap().len();
+    let form = awc::multipart::Form::new()
+        .text("name", "value")
+        .part(
+            "data",
+            awc::multipart::Part::bytes(STR)
+                .file_name("hello.txt")
+                .content_type(mime::TEXT_PLAIN)
+                .insert_header(
+                    header::HeaderName::from_static("x-part"),
+                    header::HeaderValue::from_static("1"),
+                ),
+        )
+        .part(
+            "file",
+            awc::multipart::Part::file("Cargo.toml").await.unwrap(),
+        );
+
+    let mut res = srv.post("/").send_multipart(form).await.unwrap();
+    assert!(res.status().is_success());
+    assert_eq!(
+        res.body().await.unwrap(),
+        format!(
+            "true\n\
+             name None application/octet-stream None 5\n\
+             data Some(\"hello.txt\") text/plain Some(\"1\") {}\n\
+             file Some(\"Cargo.toml\") application/octet-stream None {}\n",
+            STR.len(),
+            file_len,
+        )
+    );
+
+    // streams of unknown size are sent chunked
+    let chunks = stream::iter(vec![
+        Ok::<_, Error>(Bytes::from_static(b"ab")),
+        Ok(Bytes::from_static(b"cd")),
+    ]);
+    let form = awc::multipart::Form::new().part("s", awc::multipart::Part::stream(chunks));
+
+    let mut res = srv.post("/").send_multipart(form).await.unwrap();
+    assert_eq!(
+        res.body().await.unwrap(),
+        "false\ns None application/octet-stream None 4\n"
+    );
+
+    // a preset content type is replaced by the form's
+    let form = awc::multipart::Form::new().text("name", "value");
+    let mut res = srv
+        .post("/")
+        .content_type("multipart/form-data; boundary=other")
+        .send_multipart(form)
+        .await
+        .unwrap();
+    assert_eq!(
+        res.body().await.unwrap(),
+        "true\nname None application/octet-stream None 5\n"
+    );
+
+    let form = awc::multipart::Form::new().text("name", "value");
+    let mut res = srv
+        .post("/")
+        .insert_header((header::CONTENT_TYPE, "text/plain"))
+        .freeze()
+        .unwrap()
+        .send_multipart(form)
+        .await
+        .unwrap();
+    assert_eq!(
+        res.body().await.unwrap(),
+        "true\nname None application/octet-stream None 5\n"
+    );
+}
+
 #[actix_rt::test]
 async fn test_timeout() {
     let srv = actix_test::start(|| {
@@ -904,3 +1009,110 @@ async fn test_local_address() {
 
     assert_eq!(res.status(), 200);
 }
+
+#[actix_rt::test]
+async fn test_forward_proxy() {
+    // the test server plays the proxy, echoing the request target and credentials
+    let srv = actix_test::start(|| {
+        App::new().default_service(web::to(|req: HttpRequest| {
+            let auth = req
+                .headers()
+                .get(header::PROXY_AUTHORIZATION)
+                .map(|value| value.to_str().unwrap().to_owned())
+                .unwrap_or_default();
+            let host = req.headers().get(header::HOST).unwrap().to_str().unwrap();
+            HttpResponse::Ok().body(format!("{} {} {}", req.uri(), host, auth))
+        }))
+    });
+
+    let proxy = awc::Proxy::http(srv.url("/"))
+        .unwrap()
+        .basic_auth("user", "secret");
+    let client = awc::Client::builder().proxy(proxy).finish();
+
+    let mut res = client
+        .get("http://example.invalid/path?q=1")
+        .send()
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+
+    let body = res.body().await.unwrap();
+    assert_eq!(
+        body,
+        "http://example.invalid/path?q=1 example.invalid Basic dXNlcjpzZWNyZXQ="
+    );
+}
+
+#[actix_rt::test]
+async fn test_socks5_proxy() {
+    let srv = actix_test::start(|| {
+        App::new().service(web::resource("/").route(web::to(|| HttpResponse::Ok().body(STR))))
+    });
+
+    // minimal SOCKS5 proxy, connecting to the test server whatever the requested host
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let proxy_addr = listener.local_addr().unwrap();
+    let srv_addr = srv.addr();
+
+    std::thread::spawn(move || {
+        let (mut client, _) = listener.accept().unwrap();
+        let mut buf = [0; 512];
+
+        client.read_exact(&mut buf[..3]).unwrap();
+        assert_eq!(&buf[..3], &[5, 1, 0]);
+        client.write_all(&[5, 0]).unwrap();
+
+        client.read_exact(&mut buf[..5]).unwrap();
+        assert_eq!(&buf[..4], &[5, 1, 0, 3]);
+        let len = buf[4] as usize;
+        client.read_exact(&mut buf[..len + 2]).unwrap();
+        assert_eq!(&buf[..len], b"localhost");
+        client.write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0]).unwrap();
+
+        let mut server = std::net::TcpStream::connect(srv_addr).unwrap();
+        let mut client_read = client.try_clone().unwrap();
+        let mut server_write = server.try_clone().unwrap();
+        std::thread::spawn(move || std::io::copy(&mut client_read, &mut server_write));
+        let _ = std::io::copy(&mut server, &mut client);
+    });
+
+    let proxy = awc::Proxy::all(format!("socks5://{}", proxy_addr)).unwrap();
+    let client = awc::Client::builder().proxy(proxy).finish();
+
+    let mut res = client
+        .get(format!("http://localhost:{}/", srv_addr.port()))
+        .send()
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+
+    let body = res.body().await.unwrap();
+    assert_eq!(body, Bytes::from_static(STR.as_ref()));
+}
+
+#[actix_rt::test]
+async fn test_h2c_prior_knowledge() {
+    let srv = test_server(|| {
+        HttpService::new(map_config(
+            App::new().service(web::resource("/").route(web::to(|req: HttpRequest| {
+                assert_eq!(req.version(), http::Version::HTTP_2);
+                HttpResponse::Ok().body(STR)
+            }))),
+            |_| AppConfig::default(),
+        ))
+        .tcp()
+    })
+    .await;
+
+    let client = awc::Client::builder()
+        .connector(awc::Connector::new().h2c(true))
+        .finish();
+
+    let mut res = client.get(srv.url("/")).send().await.unwrap();
+    assert!(res.status().is_success());
+    assert_eq!(res.version(), http::Version::HTTP_2);
+
+    let body = res.body().await.unwrap();
+    assert_eq!(body, Bytes::from_static(STR.as_ref()));
+}
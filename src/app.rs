@@ -17,6 +17,7 @@ use crate::config::ServiceConfig;
 use crate::data::{Data, DataFactory, FnDataFactory};
 use crate::dev::ResourceDef;
 use crate::error::Error;
+use crate::info::TrustedProxies;
 use crate::resource::Resource;
 use crate::route::Route;
 use crate::service::{
@@ -36,6 +37,7 @@ pub struct App<T, B> {
     data_factories: Vec<FnDataFactory>,
     external: Vec<ResourceDef>,
     extensions: Extensions,
+    trusted_proxies: Option<TrustedProxies>,
     _phantom: PhantomData<B>,
 }
 
@@ -53,6 +55,7 @@ impl App<AppEntry, Body> {
             factory_ref,
             external: Vec::new(),
             extensions: Extensions::new(),
+            trusted_proxies: None,
             _phantom: PhantomData,
         }
     }
@@ -339,6 +342,19 @@ where
         self
     }
 
+    /// Sets the proxies whose forwarding headers are honoured by
+    /// [`ConnectionInfo`](crate::dev::ConnectionInfo), overriding the server configuration.
+    ///
+    /// ```
+    /// use actix_web::{dev::TrustedProxies, App};
+    ///
+    /// let app = App::new().trusted_proxies(TrustedProxies::new().trust("10.0.0.0/8"));
+    /// ```
+    pub fn trusted_proxies(mut self, proxies: TrustedProxies) -> Self {
+        self.trusted_proxies = Some(proxies);
+        self
+    }
+
     /// Registers middleware, in the form of a middleware component (type),
     /// that runs during inbound and/or outbound processing in the request
     /// life-cycle (request -> response), modifying request/response as
@@ -400,6 +416,7 @@ where
             factory_ref: self.factory_ref,
             external: self.external,
             extensions: self.extensions,
+            trusted_proxies: self.trusted_proxies,
             _phantom: PhantomData,
         }
     }
@@ -461,6 +478,7 @@ where
             factory_ref: self.factory_ref,
             external: self.external,
             extensions: self.extensions,
+            trusted_proxies: self.trusted_proxies,
             _phantom: PhantomData,
         }
     }
@@ -487,6 +505,7 @@ where
             default: self.default,
             factory_ref: self.factory_ref,
             extensions: RefCell::new(Some(self.extensions)),
+            trusted_proxies: self.trusted_proxies,
         }
     }
 }
@@ -6,7 +6,7 @@
 pub use crate::config::{AppConfig, AppService};
 #[doc(hidden)]
 pub use crate::handler::Handler;
-pub use crate::info::{ConnectionInfo, PeerAddr};
+pub use crate::info::{ConnectionInfo, PeerAddr, TrustedProxies};
 pub use crate::rmap::ResourceMap;
 pub use crate::service::{HttpServiceFactory, ServiceRequest, ServiceResponse, WebService};
 
@@ -14,11 +14,16 @@ pub use crate::types::form::UrlEncoded;
 pub use crate::types::json::JsonBody;
 pub use crate::types::readlines::Readlines;
 
-pub use actix_http::body::{AnyBody, Body, BodySize, MessageBody, ResponseBody, SizedStream};
+pub use actix_http::body::{
+    AnyBody, Body, BodySize, FileRegion, MessageBody, ResponseBody, SizedStream,
+};
 
 #[cfg(feature = "__compress")]
 pub use actix_http::encoding::Decoder as Decompress;
-pub use actix_http::{Extensions, Payload, PayloadStream, RequestHead, ResponseHead};
+pub use actix_http::{
+    ConnectionStats, Extensions, Payload, PayloadStream, RequestHead, ResponseHead,
+    ShutdownSignal,
+};
 pub use actix_router::{Path, ResourceDef, ResourcePath, Url};
 pub use actix_server::Server;
 pub use actix_service::{
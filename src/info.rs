@@ -1,4 +1,8 @@
-use std::{cell::Ref, convert::Infallible, net::SocketAddr};
+use std::{
+    cell::Ref,
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+};
 
 use actix_utils::future::{err, ok, Ready};
 use derive_more::{Display, Error};
@@ -32,6 +36,212 @@ fn first_header_value<'a>(req: &'a RequestHead, name: &'_ HeaderName) -> Option<
     Some(val)
 }
 
+/// Returns all comma-separated values of the given header, in order.
+fn all_header_values<'a>(
+    req: &'a RequestHead,
+    name: &'_ HeaderName,
+) -> impl Iterator<Item = &'a str> {
+    req.headers
+        .get_all(name)
+        .filter_map(|hdr| hdr.to_str().ok())
+        .flat_map(|hdr| hdr.split(','))
+        .map(str::trim)
+}
+
+/// Parses a forwarding header node, e.g. `192.0.2.60`, `"[2001:db8::17]:4711"`.
+///
+/// Returns `None` for obfuscated or unknown nodes.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = unquote(node);
+
+    if let Ok(addr) = node.parse::<SocketAddr>() {
+        return Some(addr.ip());
+    }
+
+    node.trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .ok()
+}
+
+/// A network in CIDR notation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct IpNet {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpNet {
+    fn parse(cidr: &str) -> Option<Self> {
+        let mut parts = cidr.trim().splitn(2, '/');
+        let addr = parts.next()?.parse::<IpAddr>().ok()?;
+
+        let max_len = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+
+        let prefix_len = match parts.next() {
+            Some(len) => len.parse::<u8>().ok().filter(|len| *len <= max_len)?,
+            None => max_len,
+        };
+
+        Some(IpNet { addr, prefix_len })
+    }
+
+    fn contains(&self, addr: IpAddr) -> bool {
+        match (self.addr, canonical_ip(addr)) {
+            (IpAddr::V4(net), IpAddr::V4(addr)) => {
+                prefix_eq(&net.octets(), &addr.octets(), self.prefix_len)
+            }
+            (IpAddr::V6(net), IpAddr::V6(addr)) => {
+                prefix_eq(&net.octets(), &addr.octets(), self.prefix_len)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Converts IPv4-mapped IPv6 addresses (as seen on dual-stack sockets) to IPv4.
+fn canonical_ip(addr: IpAddr) -> IpAddr {
+    match addr {
+        IpAddr::V6(v6) => match v6.segments() {
+            [0, 0, 0, 0, 0, 0xffff, ..] => IpAddr::V4(v6.to_ipv4().unwrap()),
+            _ => addr,
+        },
+        addr => addr,
+    }
+}
+
+fn prefix_eq(a: &[u8], b: &[u8], prefix_len: u8) -> bool {
+    let full = usize::from(prefix_len / 8);
+    let rest = prefix_len % 8;
+
+    if a[..full] != b[..full] {
+        return false;
+    }
+
+    rest == 0 || {
+        let mask = !0u8 << (8 - rest);
+        a[full] & mask == b[full] & mask
+    }
+}
+
+/// Proxies whose forwarding headers are honoured by [`ConnectionInfo`].
+///
+/// `Forwarded` and `X-Forwarded-*` headers are only used when the request's peer is a trusted
+/// proxy. The real client address is then found by walking the forwarding chain from the nearest
+/// hop, skipping trusted proxies, up to [`max_hops`](Self::max_hops) proxies in total.
+///
+/// By default, for backwards compatibility, all peers are trusted and the first forwarded address
+/// is used as-is. Configure trusted proxies with [`HttpServer::trusted_proxies`] or
+/// [`App::trusted_proxies`].
+///
+/// # Examples
+/// ```
+/// use actix_web::{dev::TrustedProxies, App};
+///
+/// let proxies = TrustedProxies::new()
+///     .trust("10.0.0.0/8")
+///     .trust("::1")
+///     .max_hops(2);
+///
+/// let app = App::new().trusted_proxies(proxies);
+/// ```
+///
+/// [`HttpServer::trusted_proxies`]: crate::HttpServer::trusted_proxies
+/// [`App::trusted_proxies`]: crate::App::trusted_proxies
+#[derive(Debug, Clone)]
+pub struct TrustedProxies {
+    /// Trusted networks; `None` trusts every peer.
+    networks: Option<Vec<IpNet>>,
+    max_hops: usize,
+}
+
+impl TrustedProxies {
+    /// Constructs a configuration that trusts no proxies, ignoring all forwarding headers.
+    pub fn new() -> Self {
+        Self {
+            networks: Some(Vec::new()),
+            max_hops: usize::MAX,
+        }
+    }
+
+    /// Constructs a configuration that trusts forwarding headers from any peer.
+    ///
+    /// This is the default. Only use it if all requests come from trusted hosts, otherwise it is
+    /// trivial for clients to falsify their address, host and scheme.
+    pub fn trust_all() -> Self {
+        Self {
+            networks: None,
+            max_hops: usize::MAX,
+        }
+    }
+
+    /// Trusts proxies in the given network, in CIDR notation (`10.0.0.0/8`) or as a single
+    /// address (`192.0.2.1`).
+    ///
+    /// Has no effect if all proxies are trusted.
+    ///
+    /// # Panics
+    /// Panics if `cidr` is not a valid network.
+    pub fn trust(mut self, cidr: &str) -> Self {
+        let net = IpNet::parse(cidr)
+            .unwrap_or_else(|| panic!("Invalid trusted proxy network: {:?}", cidr));
+
+        if let Some(ref mut networks) = self.networks {
+            networks.push(net);
+        }
+
+        self
+    }
+
+    /// Sets the maximum number of trusted proxies a request may have passed through, including the
+    /// peer itself.
+    ///
+    /// The address reported by the last trusted proxy is used once the limit is reached, even if
+    /// it belongs to a trusted network. Unlimited by default.
+    pub fn max_hops(mut self, hops: usize) -> Self {
+        self.max_hops = hops.max(1);
+        self
+    }
+
+    /// Returns true if forwarding headers sent by `peer` should be honoured.
+    pub fn is_trusted(&self, peer: IpAddr) -> bool {
+        match self.networks {
+            None => true,
+            Some(ref networks) => networks.iter().any(|net| net.contains(peer)),
+        }
+    }
+
+    fn trusts_all(&self) -> bool {
+        self.networks.is_none()
+    }
+
+    /// Walks the forwarding chain from the nearest hop and returns the client node.
+    fn client<'a>(&self, chain: &[&'a str]) -> Option<&'a str> {
+        let mut client = None;
+
+        // the peer is the first hop
+        for (hops, &node) in (1..).zip(chain.iter().rev()) {
+            client = Some(node);
+
+            let trusted = parse_node(node).map_or(false, |ip| self.is_trusted(ip));
+            if !trusted || hops >= self.max_hops {
+                break;
+            }
+        }
+
+        client.map(unquote)
+    }
+}
+
+impl Default for TrustedProxies {
+    fn default() -> Self {
+        Self::trust_all()
+    }
+}
+
 /// HTTP connection information.
 ///
 /// `ConnectionInfo` implements `FromRequest` and can be extracted in handlers.
@@ -60,6 +270,9 @@ fn first_header_value<'a>(req: &'a RequestHead, name: &'_ HeaderName) -> Option<
 /// If the older, related headers are also present (eg. `X-Forwarded-For`), then `Forwarded`
 /// is preferred.
 ///
+/// Forwarding headers are only honoured when the peer is trusted according to the app's
+/// [`TrustedProxies`] configuration.
+///
 /// [rfc7239]: https://datatracker.ietf.org/doc/html/rfc7239
 /// [rfc7239-62]: https://datatracker.ietf.org/doc/html/rfc7239#section-6.2
 /// [rfc7239-63]: https://datatracker.ietf.org/doc/html/rfc7239#section-6.3
@@ -85,62 +298,87 @@ impl ConnectionInfo {
         let mut scheme = None;
         let mut realip_remote_addr = None;
 
-        for (name, val) in req
-            .headers
-            .get_all(&header::FORWARDED)
-            .into_iter()
-            .filter_map(|hdr| hdr.to_str().ok())
-            // "for=1.2.3.4, for=5.6.7.8; scheme=https"
-            .flat_map(|val| val.split(';'))
-            // ["for=1.2.3.4, for=5.6.7.8", " scheme=https"]
-            .flat_map(|vals| vals.split(','))
-            // ["for=1.2.3.4", " for=5.6.7.8", " scheme=https"]
-            .flat_map(|pair| {
-                let mut items = pair.trim().splitn(2, '=');
-                Some((items.next()?, items.next()?))
-            })
-        {
-            // [(name , val      ), ...                                    ]
-            // [("for", "1.2.3.4"), ("for", "5.6.7.8"), ("scheme", "https")]
-
-            // taking the first value for each property is correct because spec states that first
-            // "for" value is client and rest are proxies; multiple values other properties have
-            // no defined semantics
-            //
-            // > In a chain of proxy servers where this is fully utilized, the first
-            // > "for" parameter will disclose the client where the request was first
-            // > made, followed by any subsequent proxy identifiers.
-            // --- https://datatracker.ietf.org/doc/html/rfc7239#section-5.2
-
-            match name.trim().to_lowercase().as_str() {
-                "for" => realip_remote_addr.get_or_insert_with(|| unquote(val)),
-                "proto" => scheme.get_or_insert_with(|| unquote(val)),
-                "host" => host.get_or_insert_with(|| unquote(val)),
-                "by" => {
-                    // TODO: implement https://datatracker.ietf.org/doc/html/rfc7239#section-5.1
-                    continue;
-                }
-                _ => continue,
+        let proxies = cfg.trusted_proxies();
+        let peer_trusted = proxies.trusts_all()
+            || req
+                .peer_addr
+                .map_or(false, |addr| proxies.is_trusted(addr.ip()));
+
+        if peer_trusted {
+            let mut forwarded_for = Vec::new();
+
+            for (name, val) in req
+                .headers
+                .get_all(&header::FORWARDED)
+                .into_iter()
+                .filter_map(|hdr| hdr.to_str().ok())
+                // "for=1.2.3.4, for=5.6.7.8; scheme=https"
+                .flat_map(|val| val.split(';'))
+                // ["for=1.2.3.4, for=5.6.7.8", " scheme=https"]
+                .flat_map(|vals| vals.split(','))
+                // ["for=1.2.3.4", " for=5.6.7.8", " scheme=https"]
+                .flat_map(|pair| {
+                    let mut items = pair.trim().splitn(2, '=');
+                    Some((items.next()?, items.next()?))
+                })
+            {
+                // [(name , val      ), ...                                    ]
+                // [("for", "1.2.3.4"), ("for", "5.6.7.8"), ("scheme", "https")]
+
+                // taking the first value for each property is correct because spec states that
+                // first "for" value is client and rest are proxies; multiple values other
+                // properties have no defined semantics
+                //
+                // > In a chain of proxy servers where this is fully utilized, the first
+                // > "for" parameter will disclose the client where the request was first
+                // > made, followed by any subsequent proxy identifiers.
+                // --- https://datatracker.ietf.org/doc/html/rfc7239#section-5.2
+
+                match name.trim().to_lowercase().as_str() {
+                    "for" => forwarded_for.push(val),
+                    "proto" => {
+                        scheme.get_or_insert_with(|| unquote(val));
+                    }
+                    "host" => {
+                        host.get_or_insert_with(|| unquote(val));
+                    }
+                    "by" => {
+                        // TODO: implement https://datatracker.ietf.org/doc/html/rfc7239#section-5.1
+                        continue;
+                    }
+                    _ => continue,
+                };
+            }
+
+            scheme = scheme.or_else(|| first_header_value(req, &*X_FORWARDED_PROTO));
+            host = host.or_else(|| first_header_value(req, &*X_FORWARDED_HOST));
+
+            if forwarded_for.is_empty() {
+                forwarded_for = all_header_values(req, &*X_FORWARDED_FOR).collect();
+            }
+
+            // when only some proxies are trusted, untrusted hops further down the chain could
+            // have been forged by the client
+            realip_remote_addr = if proxies.trusts_all() {
+                forwarded_for.first().map(|node| unquote(node))
+            } else {
+                proxies.client(&forwarded_for)
             };
         }
 
         let scheme = scheme
-            .or_else(|| first_header_value(req, &*X_FORWARDED_PROTO))
             .or_else(|| req.uri.scheme().map(Scheme::as_str))
             .or_else(|| Some("https").filter(|_| cfg.secure()))
             .unwrap_or("http")
             .to_owned();
 
         let host = host
-            .or_else(|| first_header_value(req, &*X_FORWARDED_HOST))
             .or_else(|| req.headers.get(&header::HOST)?.to_str().ok())
             .or_else(|| req.uri.authority().map(Authority::as_str))
             .unwrap_or_else(|| cfg.host())
             .to_owned();
 
-        let realip_remote_addr = realip_remote_addr
-            .or_else(|| first_header_value(req, &*X_FORWARDED_FOR))
-            .map(str::to_owned);
+        let realip_remote_addr = realip_remote_addr.map(str::to_owned);
 
         let remote_addr = req.peer_addr.map(|addr| addr.to_string());
 
@@ -194,8 +432,9 @@ impl ConnectionInfo {
     ///
     /// # Security
     /// Do not use this function for security purposes, unless you can ensure the Forwarded and
-    /// X-Forwarded-For headers cannot be spoofed by the client. If you want the client's socket
-    /// address explicitly, use [`HttpRequest::peer_addr()`][peer_addr] instead.
+    /// X-Forwarded-For headers cannot be spoofed by the client, e.g. by configuring
+    /// [`TrustedProxies`]. If you want the client's socket address explicitly, use
+    /// [`HttpRequest::peer_addr()`][peer_addr] instead.
     ///
     /// [peer_addr]: crate::web::HttpRequest::peer_addr()
     #[inline]
@@ -204,6 +443,21 @@ impl ConnectionInfo {
             .as_deref()
             .or_else(|| self.remote_addr.as_deref())
     }
+
+    /// Client address reported by forwarding headers, if it is an IP address.
+    ///
+    /// Addresses without a port are given port 0.
+    pub(crate) fn forwarded_peer_addr(&self) -> Option<SocketAddr> {
+        let node = self.realip_remote_addr.as_deref()?;
+
+        node.parse()
+            .ok()
+            .or_else(|| parse_node(node).map(|ip| SocketAddr::new(ip, 0)))
+    }
+
+    pub(crate) fn set_remote_addr(&mut self, addr: SocketAddr) {
+        self.remote_addr = Some(addr.to_string());
+    }
 }
 
 impl FromRequest for ConnectionInfo {
@@ -430,6 +684,104 @@ mod tests {
         assert_eq!(info.host(), "actix.rs");
     }
 
+    fn trusted_info(req: TestRequest, proxies: TrustedProxies) -> ConnectionInfo {
+        let req = req.to_http_request();
+        let mut cfg = AppConfig::default();
+        cfg.set_trusted_proxies(proxies);
+        ConnectionInfo::new(req.head(), &cfg)
+    }
+
+    #[test]
+    fn untrusted_peer() {
+        let req = TestRequest::default()
+            .peer_addr("192.0.2.1:8080".parse().unwrap())
+            .insert_header((header::HOST, "rust-lang.org"))
+            .insert_header((X_FORWARDED_FOR, "192.0.2.60"))
+            .insert_header((X_FORWARDED_HOST, "evil.com"))
+            .insert_header((X_FORWARDED_PROTO, "https"))
+            .insert_header((header::FORWARDED, "for=192.0.2.61; host=evil.com"));
+
+        let info = trusted_info(req, TrustedProxies::new().trust("10.0.0.0/8"));
+        assert_eq!(info.scheme(), "http");
+        assert_eq!(info.host(), "rust-lang.org");
+        assert_eq!(info.realip_remote_addr(), Some("192.0.2.1:8080"));
+
+        // missing peer address is never trusted
+        let req = TestRequest::default().insert_header((X_FORWARDED_FOR, "192.0.2.60"));
+        let info = trusted_info(req, TrustedProxies::new().trust("0.0.0.0/0"));
+        assert_eq!(info.realip_remote_addr(), None);
+    }
+
+    #[test]
+    fn trusted_proxy_chain() {
+        let proxies = TrustedProxies::new().trust("10.0.0.0/8").trust("::1");
+
+        let req = TestRequest::default()
+            .peer_addr("10.0.0.1:8080".parse().unwrap())
+            .insert_header((X_FORWARDED_FOR, "1.1.1.1, 192.0.2.60, 10.0.0.3, 10.0.0.2"))
+            .insert_header((X_FORWARDED_PROTO, "https"));
+        let info = trusted_info(req, proxies.clone());
+        assert_eq!(info.scheme(), "https");
+        // the first untrusted hop, not the spoofable first entry, is the client
+        assert_eq!(info.realip_remote_addr(), Some("192.0.2.60"));
+
+        let req = TestRequest::default()
+            .peer_addr("[::1]:8080".parse().unwrap())
+            .insert_header((
+                header::FORWARDED,
+                r#"for=192.0.2.60;proto=https, for="[::1]:4711";host=example.com"#,
+            ));
+        let info = trusted_info(req, proxies.clone());
+        assert_eq!(info.scheme(), "https");
+        assert_eq!(info.host(), "example.com");
+        assert_eq!(info.realip_remote_addr(), Some("192.0.2.60"));
+
+        // only trusted proxies in the chain
+        let req = TestRequest::default()
+            .peer_addr("[::ffff:10.0.0.1]:8080".parse().unwrap())
+            .insert_header((X_FORWARDED_FOR, "10.0.0.3, 10.0.0.2"));
+        let info = trusted_info(req, proxies);
+        assert_eq!(info.realip_remote_addr(), Some("10.0.0.3"));
+    }
+
+    #[test]
+    fn trusted_proxy_max_hops() {
+        let req = || {
+            TestRequest::default()
+                .peer_addr("10.0.0.1:8080".parse().unwrap())
+                .insert_header((X_FORWARDED_FOR, "192.0.2.60, 10.0.0.3, 10.0.0.2"))
+        };
+
+        let proxies = TrustedProxies::new().trust("10.0.0.0/8");
+        let info = trusted_info(req(), proxies.clone().max_hops(1));
+        assert_eq!(info.realip_remote_addr(), Some("10.0.0.2"));
+
+        let info = trusted_info(req(), proxies.clone().max_hops(2));
+        assert_eq!(info.realip_remote_addr(), Some("10.0.0.3"));
+
+        let info = trusted_info(req(), proxies.max_hops(3));
+        assert_eq!(info.realip_remote_addr(), Some("192.0.2.60"));
+    }
+
+    #[test]
+    fn ip_net() {
+        let net = IpNet::parse("192.168.0.0/23").unwrap();
+        assert!(net.contains("192.168.1.255".parse().unwrap()));
+        assert!(!net.contains("192.168.2.0".parse().unwrap()));
+        assert!(net.contains("::ffff:192.168.0.1".parse().unwrap()));
+        assert!(!net.contains("::1".parse().unwrap()));
+
+        let net = IpNet::parse("2001:db8::/32").unwrap();
+        assert!(net.contains("2001:db8:cafe::17".parse().unwrap()));
+        assert!(!net.contains("2001:db9::".parse().unwrap()));
+
+        assert!(IpNet::parse("10.0.0.1")
+            .unwrap()
+            .contains("10.0.0.1".parse().unwrap()));
+        assert!(IpNet::parse("10.0.0.0/33").is_none());
+        assert!(IpNet::parse("example.com").is_none());
+    }
+
     #[actix_rt::test]
     async fn conn_info_extract() {
         let req = TestRequest::default()
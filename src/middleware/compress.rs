@@ -5,14 +5,15 @@ use std::{
     future::Future,
     marker::PhantomData,
     pin::Pin,
+    rc::Rc,
     str::FromStr,
     task::{Context, Poll},
 };
 
 use actix_http::{
-    body::{MessageBody, ResponseBody},
-    encoding::Encoder,
-    http::header::{ContentEncoding, ACCEPT_ENCODING},
+    body::{BodySize, MessageBody, ResponseBody},
+    encoding::{CompressionLevels, Encoder},
+    http::header::{ContentEncoding, ACCEPT_ENCODING, CONTENT_TYPE},
 };
 use actix_service::{Service, Transform};
 use actix_utils::future::{ok, Ready};
@@ -30,6 +31,9 @@ use crate::{
 /// Use `BodyEncoding` trait for overriding response compression. To disable compression set
 /// encoding to `ContentEncoding::Identity`.
 ///
+/// Compression levels, a minimum body size, the content types to compress and a preference
+/// order for encodings the client accepts equally can be configured with builder methods.
+///
 /// # Examples
 /// ```
 /// use actix_web::{web, middleware, App, HttpResponse};
@@ -38,13 +42,134 @@ use crate::{
 ///     .wrap(middleware::Compress::default())
 ///     .default_service(web::to(|| HttpResponse::NotFound()));
 /// ```
+///
+/// ```
+/// use actix_web::{http::ContentEncoding, middleware::Compress, App};
+///
+/// let app = App::new().wrap(
+///     Compress::default()
+///         .gzip_level(6)
+///         .brotli_level(5)
+///         .min_size(1024)
+///         .deny_content_type(mime::IMAGE_STAR)
+///         .deny_content_type("application/zip".parse().unwrap())
+///         .preference(vec![ContentEncoding::Br, ContentEncoding::Gzip]),
+/// );
+/// ```
 #[derive(Debug, Clone)]
-pub struct Compress(ContentEncoding);
+pub struct Compress {
+    encoding: ContentEncoding,
+    levels: CompressionLevels,
+    min_size: u64,
+    allow: Vec<mime::Mime>,
+    deny: Vec<mime::Mime>,
+    preference: Vec<ContentEncoding>,
+}
 
 impl Compress {
     /// Create new `Compress` middleware with the specified encoding.
     pub fn new(encoding: ContentEncoding) -> Self {
-        Compress(encoding)
+        Compress {
+            encoding,
+            levels: CompressionLevels::default(),
+            min_size: 0,
+            allow: Vec::new(),
+            deny: Vec::new(),
+            preference: Vec::new(),
+        }
+    }
+
+    /// Set the gzip compression level, from 0 to 9.
+    ///
+    /// By default level 1 is used.
+    pub fn gzip_level(mut self, level: u32) -> Self {
+        self.levels = self.levels.gzip(level);
+        self
+    }
+
+    /// Set the deflate compression level, from 0 to 9.
+    ///
+    /// By default level 1 is used.
+    pub fn deflate_level(mut self, level: u32) -> Self {
+        self.levels = self.levels.deflate(level);
+        self
+    }
+
+    /// Set the brotli compression level, from 0 to 11.
+    ///
+    /// By default level 3 is used.
+    pub fn brotli_level(mut self, level: u32) -> Self {
+        self.levels = self.levels.brotli(level);
+        self
+    }
+
+    /// Set the zstd compression level, from 1 to 22.
+    ///
+    /// By default level 3 is used.
+    pub fn zstd_level(mut self, level: i32) -> Self {
+        self.levels = self.levels.zstd(level);
+        self
+    }
+
+    /// Set the minimum size in bytes of bodies to compress.
+    ///
+    /// Smaller bodies are sent uncompressed. Streaming bodies of unknown size are always
+    /// compressed. By default bodies of any size are compressed.
+    pub fn min_size(mut self, size: u64) -> Self {
+        self.min_size = size;
+        self
+    }
+
+    /// Only compress responses of the given content type.
+    ///
+    /// Can be called multiple times to allow several types. Wildcards such as `text/*` are
+    /// supported. Once a type is allowed, responses without a `Content-Type` header are not
+    /// compressed. By default all content types are compressed.
+    pub fn allow_content_type(mut self, mime: mime::Mime) -> Self {
+        self.allow.push(mime);
+        self
+    }
+
+    /// Do not compress responses of the given content type, e.g. images or archives that are
+    /// already compressed.
+    ///
+    /// Can be called multiple times. Wildcards such as `image/*` are supported and denied types
+    /// take precedence over allowed ones.
+    pub fn deny_content_type(mut self, mime: mime::Mime) -> Self {
+        self.deny.push(mime);
+        self
+    }
+
+    /// Set the order in which encodings are preferred when the client accepts them with the same
+    /// quality.
+    ///
+    /// Encodings without a quality value in `Accept-Encoding` are then treated as having a
+    /// quality of 1. Listed encodings are preferred over unlisted ones. By default, the client's
+    /// order and built-in defaults (brotli, then gzip, then deflate) are used.
+    pub fn preference<I>(mut self, encodings: I) -> Self
+    where
+        I: IntoIterator<Item = ContentEncoding>,
+    {
+        self.preference = encodings.into_iter().collect();
+        self
+    }
+
+    /// Returns true if a response with the given body size and content type should be compressed.
+    fn should_compress(&self, size: BodySize, content_type: Option<&mime::Mime>) -> bool {
+        if let BodySize::Sized(size) = size {
+            if size < self.min_size {
+                return false;
+            }
+        }
+
+        match content_type {
+            Some(ct) => {
+                !self.deny.iter().any(|mime| mime_matches(mime, ct))
+                    && (self.allow.is_empty()
+                        || self.allow.iter().any(|mime| mime_matches(mime, ct)))
+            }
+            None => self.allow.is_empty(),
+        }
     }
 }
 
@@ -54,6 +179,12 @@ impl Default for Compress {
     }
 }
 
+/// Returns true if `mime` matches `content_type`, supporting `*` wildcards in `mime`.
+fn mime_matches(mime: &mime::Mime, content_type: &mime::Mime) -> bool {
+    (mime.type_() == mime::STAR || mime.type_() == content_type.type_())
+        && (mime.subtype() == mime::STAR || mime.subtype() == content_type.subtype())
+}
+
 impl<S, B> Transform<S, ServiceRequest> for Compress
 where
     B: MessageBody,
@@ -68,14 +199,14 @@ where
     fn new_transform(&self, service: S) -> Self::Future {
         ok(CompressMiddleware {
             service,
-            encoding: self.0,
+            config: Rc::new(self.clone()),
         })
     }
 }
 
 pub struct CompressMiddleware<S> {
     service: S,
-    encoding: ContentEncoding,
+    config: Rc<Compress>,
 }
 
 impl<S, B> Service<ServiceRequest> for CompressMiddleware<S>
@@ -94,7 +225,7 @@ where
         // negotiate content-encoding
         let encoding = if let Some(val) = req.headers().get(&ACCEPT_ENCODING) {
             if let Ok(enc) = val.to_str() {
-                AcceptEncoding::parse(enc, self.encoding)
+                AcceptEncoding::parse(enc, self.config.encoding, &self.config.preference)
             } else {
                 ContentEncoding::Identity
             }
@@ -104,6 +235,7 @@ where
 
         CompressResponse {
             encoding,
+            config: self.config.clone(),
             fut: self.service.call(req),
             _phantom: PhantomData,
         }
@@ -119,6 +251,7 @@ where
     #[pin]
     fut: S::Future,
     encoding: ContentEncoding,
+    config: Rc<Compress>,
     _phantom: PhantomData<B>,
 }
 
@@ -137,11 +270,26 @@ where
                 let enc = if let Some(enc) = resp.response().get_encoding() {
                     enc
                 } else {
-                    *this.encoding
+                    let content_type = resp
+                        .headers()
+                        .get(CONTENT_TYPE)
+                        .and_then(|ct| ct.to_str().ok())
+                        .and_then(|ct| ct.parse::<mime::Mime>().ok());
+
+                    if this
+                        .config
+                        .should_compress(resp.response().body().size(), content_type.as_ref())
+                    {
+                        *this.encoding
+                    } else {
+                        ContentEncoding::Identity
+                    }
                 };
 
+                let levels = this.config.levels;
+
                 Poll::Ready(Ok(resp.map_body(move |head, body| {
-                    Encoder::response(enc, head, ResponseBody::Body(body))
+                    Encoder::response_with_levels(enc, head, ResponseBody::Body(body), levels)
                 })))
             }
             Err(e) => Poll::Ready(Err(e)),
@@ -182,28 +330,63 @@ impl PartialEq for AcceptEncoding {
 }
 
 impl AcceptEncoding {
-    fn new(tag: &str) -> Option<AcceptEncoding> {
+    fn new(tag: &str, default_quality: Option<f64>) -> Option<AcceptEncoding> {
         let parts: Vec<&str> = tag.split(';').collect();
         let encoding = match parts.len() {
             0 => return None,
             _ => ContentEncoding::from(parts[0]),
         };
         let quality = match parts.len() {
-            1 => encoding.quality(),
-            _ => f64::from_str(parts[1]).unwrap_or(0.0),
+            1 => default_quality.unwrap_or_else(|| encoding.quality()),
+            _ => {
+                let param = parts[1].trim();
+                let quality = param
+                    .get(..2)
+                    .filter(|name| name.eq_ignore_ascii_case("q="))
+                    .and_then(|_| f64::from_str(&param[2..]).ok())
+                    .unwrap_or(0.0);
+
+                // `q=0` marks the encoding as not acceptable
+                if quality <= 0.0 {
+                    return None;
+                }
+
+                quality
+            }
         };
+
         Some(AcceptEncoding { encoding, quality })
     }
 
     /// Parse a raw Accept-Encoding header value into an ordered list.
-    pub fn parse(raw: &str, encoding: ContentEncoding) -> ContentEncoding {
+    ///
+    /// Encodings of equal quality are ordered by `preference`, if not empty.
+    pub fn parse(
+        raw: &str,
+        encoding: ContentEncoding,
+        preference: &[ContentEncoding],
+    ) -> ContentEncoding {
+        let default_quality = if preference.is_empty() {
+            None
+        } else {
+            Some(1.0)
+        };
+
         let mut encodings = raw
             .replace(' ', "")
             .split(',')
-            .filter_map(|l| AcceptEncoding::new(l))
+            .filter_map(|l| AcceptEncoding::new(l, default_quality))
             .collect::<Vec<_>>();
 
-        encodings.sort();
+        let rank = |enc: &AcceptEncoding| {
+            preference
+                .iter()
+                .position(|pref| *pref == enc.encoding)
+                .unwrap_or(preference.len())
+        };
+
+        // stable sort keeps the client's order for encodings of equal quality and rank
+        encodings.sort_by(|a, b| a.cmp(b).then_with(|| rank(a).cmp(&rank(b))));
 
         for enc in encodings {
             if encoding == ContentEncoding::Auto {
@@ -216,3 +399,134 @@ impl AcceptEncoding {
         ContentEncoding::Identity
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use actix_service::IntoService;
+    use actix_utils::future::ok;
+
+    use super::*;
+    use crate::{
+        http::header::CONTENT_ENCODING,
+        test::{self, TestRequest},
+        HttpResponse,
+    };
+
+    #[test]
+    fn test_accept_encoding_preference() {
+        let parse = |raw, preference: &[ContentEncoding]| {
+            AcceptEncoding::parse(raw, ContentEncoding::Auto, preference)
+        };
+
+        assert_eq!(parse("gzip, br", &[]), ContentEncoding::Br);
+        assert_eq!(parse("deflate, gzip", &[]), ContentEncoding::Gzip);
+        assert_eq!(parse("zstd", &[]), ContentEncoding::Zstd);
+
+        let preference = [ContentEncoding::Gzip, ContentEncoding::Br];
+        assert_eq!(parse("br, gzip", &preference), ContentEncoding::Gzip);
+        assert_eq!(parse("br, deflate", &preference), ContentEncoding::Br);
+        assert_eq!(
+            parse("deflate, zstd", &preference),
+            ContentEncoding::Deflate
+        );
+
+        // quality takes precedence over preference
+        assert_eq!(parse("br, gzip;q=0.5", &preference), ContentEncoding::Br);
+        assert_eq!(
+            parse("gzip;q=0.5, br;q=0.8", &preference),
+            ContentEncoding::Br
+        );
+        assert_eq!(
+            parse("gzip; Q=0.5, br;q=0.8", &preference),
+            ContentEncoding::Br
+        );
+
+        // explicit qualities are compared without preference
+        assert_eq!(parse("br;q=0.8, gzip;q=0.5", &[]), ContentEncoding::Br);
+        assert_eq!(parse("br;q=0.5, gzip;q=0.8", &[]), ContentEncoding::Gzip);
+
+        // encodings with zero or invalid quality are not acceptable
+        assert_eq!(parse("gzip;q=0, br", &preference), ContentEncoding::Br);
+        assert_eq!(parse("gzip;q=0", &preference), ContentEncoding::Identity);
+        assert_eq!(
+            parse("gzip;level=1", &preference),
+            ContentEncoding::Identity
+        );
+    }
+
+    #[test]
+    fn test_should_compress() {
+        let compress = Compress::default()
+            .min_size(100)
+            .deny_content_type(mime::IMAGE_STAR);
+
+        assert!(compress.should_compress(BodySize::Sized(100), Some(&mime::TEXT_HTML)));
+        assert!(compress.should_compress(BodySize::Stream, None));
+        assert!(!compress.should_compress(BodySize::Sized(99), Some(&mime::TEXT_HTML)));
+        assert!(!compress.should_compress(BodySize::Sized(100), Some(&mime::IMAGE_PNG)));
+
+        let compress = compress.allow_content_type(mime::TEXT_STAR);
+        assert!(compress.should_compress(BodySize::Stream, Some(&mime::TEXT_CSS)));
+        assert!(!compress.should_compress(BodySize::Stream, Some(&mime::APPLICATION_JSON)));
+        assert!(!compress.should_compress(BodySize::Stream, None));
+    }
+
+    #[cfg(feature = "compress-gzip")]
+    #[actix_rt::test]
+    async fn test_compress_filters() {
+        let srv = |req: ServiceRequest| {
+            let res = match req.path() {
+                "/small" => HttpResponse::Ok().body("small"),
+                "/image" => HttpResponse::Ok()
+                    .content_type("image/png")
+                    .body("a".repeat(100)),
+                _ => HttpResponse::Ok().body("a".repeat(100)),
+            };
+            ok(req.into_response(res))
+        };
+
+        let mw = Compress::default()
+            .min_size(10)
+            .deny_content_type(mime::IMAGE_STAR)
+            .new_transform(srv.into_service())
+            .await
+            .unwrap();
+
+        for (path, compressed) in &[("/", true), ("/small", false), ("/image", false)] {
+            let req = TestRequest::with_uri(path)
+                .insert_header((ACCEPT_ENCODING, "gzip"))
+                .to_srv_request();
+            let res = mw.call(req).await.unwrap();
+            assert_eq!(
+                res.headers().contains_key(CONTENT_ENCODING),
+                *compressed,
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[cfg(feature = "compress-gzip")]
+    #[actix_rt::test]
+    async fn test_compress_level() {
+        let body = "actix-web ".repeat(100);
+
+        let srv = move |req: ServiceRequest| {
+            ok(req.into_response(HttpResponse::Ok().body(body.clone())))
+        };
+        let srv = &srv.into_service();
+
+        let compressed_len = |compress: Compress| async move {
+            let mw = compress.new_transform(srv).await.unwrap();
+            let req = TestRequest::default()
+                .insert_header((ACCEPT_ENCODING, "gzip"))
+                .to_srv_request();
+            let res = mw.call(req).await.unwrap();
+            test::read_body(res).await.len()
+        };
+
+        // level 0 stores the body uncompressed
+        assert!(compressed_len(Compress::default().gzip_level(0)).await > 1000);
+        assert!(compressed_len(Compress::default().gzip_level(9)).await < 100);
+    }
+}
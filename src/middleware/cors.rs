@@ -0,0 +1,876 @@
+//! For middleware documentation, see [`Cors`].
+
+use std::{convert::TryFrom, fmt, rc::Rc};
+
+use actix_http::body::{AnyBody, MessageBody};
+use actix_service::{Service, Transform};
+use actix_utils::future::{ready, Ready};
+use derive_more::{Display, Error};
+use futures_core::future::LocalBoxFuture;
+use regex::Regex;
+
+use crate::{
+    dev::{RequestHead, ServiceRequest, ServiceResponse},
+    http::{
+        header::{self, HeaderName, HeaderValue},
+        Error as HttpError, Method, StatusCode,
+    },
+    Error, HttpResponse, ResponseError,
+};
+
+/// Errors that can occur when processing CORS requests.
+#[derive(Debug, Clone, PartialEq, Display, Error)]
+#[non_exhaustive]
+pub enum CorsError {
+    /// Origin of the request is not in the allowed set.
+    #[display(fmt = "Origin is not allowed to make this request.")]
+    OriginNotAllowed,
+
+    /// The `Access-Control-Request-Method` header of a preflight request is invalid.
+    #[display(fmt = "Requested method could not be parsed.")]
+    BadRequestMethod,
+
+    /// The requested method is not in the allowed set.
+    #[display(fmt = "Requested method is not allowed.")]
+    MethodNotAllowed,
+
+    /// The `Access-Control-Request-Headers` header of a preflight request is invalid.
+    #[display(fmt = "Requested headers could not be parsed.")]
+    BadRequestHeaders,
+
+    /// One or more requested headers are not in the allowed set.
+    #[display(fmt = "One or more requested headers are not allowed.")]
+    HeadersNotAllowed,
+}
+
+impl ResponseError for CorsError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+}
+
+type OriginFn = dyn Fn(&HeaderValue, &RequestHead) -> bool;
+
+enum OriginMatcher {
+    Exact(HeaderValue),
+    Regex(Regex),
+    Fn(Box<OriginFn>),
+}
+
+impl OriginMatcher {
+    fn is_match(&self, origin: &HeaderValue, head: &RequestHead) -> bool {
+        match self {
+            OriginMatcher::Exact(val) => val == origin,
+            OriginMatcher::Regex(re) => origin.to_str().map_or(false, |s| re.is_match(s)),
+            OriginMatcher::Fn(f) => f(origin, head),
+        }
+    }
+}
+
+impl fmt::Debug for OriginMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OriginMatcher::Exact(val) => f.debug_tuple("Exact").field(val).finish(),
+            OriginMatcher::Regex(re) => f.debug_tuple("Regex").field(&re.as_str()).finish(),
+            OriginMatcher::Fn(_) => f.write_str("Fn"),
+        }
+    }
+}
+
+/// Either every item is allowed or only those in the list.
+#[derive(Debug)]
+enum AllOrSome<T> {
+    All,
+    Some(Vec<T>),
+}
+
+impl<T> AllOrSome<T> {
+    fn push(&mut self, item: T) {
+        if let AllOrSome::Some(items) = self {
+            items.push(item);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    origins: AllOrSome<OriginMatcher>,
+    methods: AllOrSome<Method>,
+    headers: AllOrSome<HeaderName>,
+    expose_headers: AllOrSome<HeaderName>,
+    supports_credentials: bool,
+    max_age: Option<usize>,
+    block_on_origin_mismatch: bool,
+}
+
+/// Middleware implementing [Cross-Origin Resource Sharing][mdn].
+///
+/// Preflight (`OPTIONS`) requests are answered directly by the middleware so that wrapped
+/// resources and scopes do not need to register an `OPTIONS` route. Simple and actual requests
+/// are passed to the wrapped service and have the relevant `Access-Control-*` headers added to
+/// their responses. Requests without an `Origin` header are not affected.
+///
+/// The default configuration is restrictive: no origins are allowed, only the `GET`, `HEAD`,
+/// `POST`, `PUT`, `PATCH` and `DELETE` methods are allowed and no non-safelisted request headers
+/// are allowed. Use [`Cors::permissive`] to start from a configuration that allows everything.
+///
+/// Requests from disallowed origins are rejected with `400 Bad Request`, unless
+/// [`block_on_origin_mismatch(false)`](Self::block_on_origin_mismatch) is set.
+///
+/// # Examples
+/// ```
+/// use actix_web::{http::{header, Method}, middleware::Cors, web, App, HttpResponse};
+///
+/// let cors = Cors::default()
+///     .allowed_origin("https://www.rust-lang.org")
+///     .allowed_origin_regex(r"^https://[a-z0-9-]+\.example\.com$")
+///     .allowed_methods(vec![Method::GET, Method::POST])
+///     .allowed_headers(vec![header::AUTHORIZATION, header::CONTENT_TYPE])
+///     .expose_headers(vec!["x-request-id"])
+///     .supports_credentials()
+///     .max_age(3600);
+///
+/// let app = App::new()
+///     .wrap(cors)
+///     .service(web::resource("/index.html").route(web::get().to(HttpResponse::Ok)));
+/// ```
+///
+/// [mdn]: https://developer.mozilla.org/en-US/docs/Web/HTTP/CORS
+#[derive(Debug)]
+pub struct Cors {
+    inner: Rc<Inner>,
+}
+
+impl Default for Cors {
+    fn default() -> Self {
+        Cors {
+            inner: Rc::new(Inner {
+                origins: AllOrSome::Some(Vec::new()),
+                methods: AllOrSome::Some(vec![
+                    Method::GET,
+                    Method::HEAD,
+                    Method::POST,
+                    Method::PUT,
+                    Method::PATCH,
+                    Method::DELETE,
+                ]),
+                headers: AllOrSome::Some(Vec::new()),
+                expose_headers: AllOrSome::Some(Vec::new()),
+                supports_credentials: false,
+                max_age: None,
+                block_on_origin_mismatch: true,
+            }),
+        }
+    }
+}
+
+impl Cors {
+    /// Constructs a very permissive `Cors` middleware.
+    ///
+    /// Any origin, method and request header is allowed. Intended for development; production
+    /// services should list their allowed origins explicitly.
+    pub fn permissive() -> Self {
+        Cors::default()
+            .allow_any_origin()
+            .allow_any_method()
+            .allow_any_header()
+    }
+
+    fn inner_mut(&mut self) -> &mut Inner {
+        Rc::get_mut(&mut self.inner).expect("Multiple copies exist")
+    }
+
+    /// Adds an origin that is allowed to make requests.
+    ///
+    /// The origin must be an exact match for the `Origin` header sent by clients, e.g.
+    /// `https://www.rust-lang.org` (scheme, host and non-default port, no trailing slash).
+    /// Passing `"*"` is equivalent to calling [`allow_any_origin`](Self::allow_any_origin).
+    ///
+    /// # Panics
+    /// Panics if `origin` is not a valid header value.
+    pub fn allowed_origin(mut self, origin: &str) -> Self {
+        if origin == "*" {
+            return self.allow_any_origin();
+        }
+
+        let origin = HeaderValue::try_from(origin).expect("Invalid origin");
+        self.inner_mut().origins.push(OriginMatcher::Exact(origin));
+        self
+    }
+
+    /// Adds a regular expression that allowed origins are matched against.
+    ///
+    /// The expression is matched against the `Origin` header value; anchor it with `^` and `$` to
+    /// match the whole value.
+    ///
+    /// # Panics
+    /// Panics if `pattern` is not a valid regular expression.
+    pub fn allowed_origin_regex(mut self, pattern: &str) -> Self {
+        let re = Regex::new(pattern).expect("Invalid origin regex");
+        self.inner_mut().origins.push(OriginMatcher::Regex(re));
+        self
+    }
+
+    /// Adds a predicate that decides whether an origin is allowed.
+    ///
+    /// The predicate receives the `Origin` header value and the request head.
+    pub fn allowed_origin_fn<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&HeaderValue, &RequestHead) -> bool + 'static,
+    {
+        self.inner_mut()
+            .origins
+            .push(OriginMatcher::Fn(Box::new(f)));
+        self
+    }
+
+    /// Allows requests from any origin.
+    ///
+    /// Responses use the `*` wildcard unless credentials are supported, in which case the
+    /// request's origin is echoed back instead.
+    pub fn allow_any_origin(mut self) -> Self {
+        self.inner_mut().origins = AllOrSome::All;
+        self
+    }
+
+    /// Sets the methods that are allowed for actual requests, replacing the default set.
+    pub fn allowed_methods<U>(mut self, methods: U) -> Self
+    where
+        U: IntoIterator<Item = Method>,
+    {
+        self.inner_mut().methods = AllOrSome::Some(methods.into_iter().collect());
+        self
+    }
+
+    /// Allows any request method.
+    pub fn allow_any_method(mut self) -> Self {
+        self.inner_mut().methods = AllOrSome::All;
+        self
+    }
+
+    /// Adds request headers that clients are allowed to send.
+    ///
+    /// # Panics
+    /// Panics if any item is not a valid header name.
+    pub fn allowed_headers<U, H>(mut self, headers: U) -> Self
+    where
+        U: IntoIterator<Item = H>,
+        HeaderName: TryFrom<H>,
+        <HeaderName as TryFrom<H>>::Error: Into<HttpError>,
+    {
+        let inner = self.inner_mut();
+        for name in headers {
+            let name = HeaderName::try_from(name)
+                .map_err(Into::into)
+                .expect("Can not create header name");
+            inner.headers.push(name);
+        }
+        self
+    }
+
+    /// Allows clients to send any request header.
+    pub fn allow_any_header(mut self) -> Self {
+        self.inner_mut().headers = AllOrSome::All;
+        self
+    }
+
+    /// Adds response headers that browsers are allowed to expose to client scripts.
+    ///
+    /// # Panics
+    /// Panics if any item is not a valid header name.
+    pub fn expose_headers<U, H>(mut self, headers: U) -> Self
+    where
+        U: IntoIterator<Item = H>,
+        HeaderName: TryFrom<H>,
+        <HeaderName as TryFrom<H>>::Error: Into<HttpError>,
+    {
+        let inner = self.inner_mut();
+        for name in headers {
+            let name = HeaderName::try_from(name)
+                .map_err(Into::into)
+                .expect("Can not create header name");
+            inner.expose_headers.push(name);
+        }
+        self
+    }
+
+    /// Exposes all response headers to client scripts using the `*` wildcard.
+    ///
+    /// Browsers do not honor the wildcard for requests made with credentials.
+    pub fn expose_any_header(mut self) -> Self {
+        self.inner_mut().expose_headers = AllOrSome::All;
+        self
+    }
+
+    /// Allows requests to include credentials (cookies, HTTP authentication, client certificates).
+    pub fn supports_credentials(mut self) -> Self {
+        self.inner_mut().supports_credentials = true;
+        self
+    }
+
+    /// Sets how long, in seconds, browsers may cache preflight responses.
+    pub fn max_age(mut self, max_age: usize) -> Self {
+        self.inner_mut().max_age = Some(max_age);
+        self
+    }
+
+    /// Sets whether requests from disallowed origins are rejected.
+    ///
+    /// When disabled, such requests are passed to the wrapped service and their responses are
+    /// left without CORS headers, leaving enforcement to the browser. Preflight requests from
+    /// disallowed origins are always rejected. Enabled by default.
+    pub fn block_on_origin_mismatch(mut self, block: bool) -> Self {
+        self.inner_mut().block_on_origin_mismatch = block;
+        self
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for Cors
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+    B::Error: Into<Box<dyn std::error::Error + 'static>>,
+{
+    type Response = ServiceResponse;
+    type Error = Error;
+    type Transform = CorsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CorsMiddleware {
+            service,
+            inner: self.inner.clone(),
+        }))
+    }
+}
+
+pub struct CorsMiddleware<S> {
+    service: S,
+    inner: Rc<Inner>,
+}
+
+impl Inner {
+    fn is_origin_allowed(&self, origin: &HeaderValue, head: &RequestHead) -> bool {
+        match self.origins {
+            AllOrSome::All => true,
+            AllOrSome::Some(ref matchers) => matchers.iter().any(|m| m.is_match(origin, head)),
+        }
+    }
+
+    fn validate_method(&self, head: &RequestHead) -> Result<Method, CorsError> {
+        let method = head
+            .headers()
+            .get(header::ACCESS_CONTROL_REQUEST_METHOD)
+            .and_then(|val| Method::from_bytes(val.as_bytes()).ok())
+            .ok_or(CorsError::BadRequestMethod)?;
+
+        match self.methods {
+            AllOrSome::Some(ref methods) if !methods.contains(&method) => {
+                Err(CorsError::MethodNotAllowed)
+            }
+            _ => Ok(method),
+        }
+    }
+
+    fn validate_headers(&self, head: &RequestHead) -> Result<Option<HeaderValue>, CorsError> {
+        let requested = match head.headers().get(header::ACCESS_CONTROL_REQUEST_HEADERS) {
+            Some(val) => val,
+            None => return Ok(None),
+        };
+
+        let list = requested
+            .to_str()
+            .map_err(|_| CorsError::BadRequestHeaders)?;
+
+        for name in list.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let name = HeaderName::try_from(name).map_err(|_| CorsError::BadRequestHeaders)?;
+
+            if let AllOrSome::Some(ref allowed) = self.headers {
+                if !allowed.contains(&name) {
+                    return Err(CorsError::HeadersNotAllowed);
+                }
+            }
+        }
+
+        Ok(Some(requested.clone()))
+    }
+
+    /// Value of `Access-Control-Allow-Origin` for an allowed origin.
+    fn allow_origin_value(&self, origin: &HeaderValue) -> HeaderValue {
+        match self.origins {
+            AllOrSome::All if !self.supports_credentials => HeaderValue::from_static("*"),
+            _ => origin.clone(),
+        }
+    }
+
+    fn preflight_response(&self, req: &ServiceRequest) -> Result<HttpResponse, CorsError> {
+        let head = req.head();
+
+        let origin = head.headers().get(header::ORIGIN).unwrap();
+        if !self.is_origin_allowed(origin, head) {
+            return Err(CorsError::OriginNotAllowed);
+        }
+
+        let method = self.validate_method(head)?;
+        let requested_headers = self.validate_headers(head)?;
+
+        let mut res = HttpResponse::Ok();
+        res.insert_header((
+            header::ACCESS_CONTROL_ALLOW_ORIGIN,
+            self.allow_origin_value(origin),
+        ));
+
+        match self.methods {
+            AllOrSome::All => {
+                res.insert_header((header::ACCESS_CONTROL_ALLOW_METHODS, method.as_str()));
+            }
+            AllOrSome::Some(ref methods) => {
+                res.insert_header((header::ACCESS_CONTROL_ALLOW_METHODS, join(methods)));
+            }
+        }
+
+        match self.headers {
+            AllOrSome::All => {
+                if let Some(val) = requested_headers {
+                    res.insert_header((header::ACCESS_CONTROL_ALLOW_HEADERS, val));
+                }
+            }
+            AllOrSome::Some(ref headers) if !headers.is_empty() => {
+                res.insert_header((header::ACCESS_CONTROL_ALLOW_HEADERS, join(headers)));
+            }
+            AllOrSome::Some(_) => {}
+        }
+
+        if self.supports_credentials {
+            res.insert_header((header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true"));
+        }
+
+        if let Some(max_age) = self.max_age {
+            res.insert_header((header::ACCESS_CONTROL_MAX_AGE, max_age.to_string()));
+        }
+
+        res.insert_header((
+            header::VARY,
+            "Origin, Access-Control-Request-Method, Access-Control-Request-Headers",
+        ));
+
+        Ok(res.finish())
+    }
+
+    /// Appends `Vary: Origin` unless any origin is allowed, since responses then differ by
+    /// whether the request's origin is in the list.
+    fn vary_origin<B>(&self, res: &mut ServiceResponse<B>) {
+        if let AllOrSome::Some(_) = self.origins {
+            res.headers_mut()
+                .append(header::VARY, HeaderValue::from_static("Origin"));
+        }
+    }
+
+    fn add_response_headers<B>(&self, origin: &HeaderValue, res: &mut ServiceResponse<B>) {
+        let value = self.allow_origin_value(origin);
+        let echoes_origin = value != "*";
+
+        let headers = res.headers_mut();
+        headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, value);
+
+        if self.supports_credentials {
+            headers.insert(
+                header::ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                HeaderValue::from_static("true"),
+            );
+        }
+
+        match self.expose_headers {
+            AllOrSome::All => {
+                headers.insert(
+                    header::ACCESS_CONTROL_EXPOSE_HEADERS,
+                    HeaderValue::from_static("*"),
+                );
+            }
+            AllOrSome::Some(ref names) if !names.is_empty() => {
+                if let Ok(val) = HeaderValue::try_from(join(names)) {
+                    headers.insert(header::ACCESS_CONTROL_EXPOSE_HEADERS, val);
+                }
+            }
+            AllOrSome::Some(_) => {}
+        }
+
+        if echoes_origin {
+            headers.append(header::VARY, HeaderValue::from_static("Origin"));
+        }
+    }
+}
+
+fn join<T: AsRef<str>>(items: &[T]) -> String {
+    items
+        .iter()
+        .map(AsRef::as_ref)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl<S, B> Service<ServiceRequest> for CorsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+    B::Error: Into<Box<dyn std::error::Error + 'static>>,
+{
+    type Response = ServiceResponse;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    actix_service::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let origin = match req.headers().get(header::ORIGIN) {
+            Some(origin) => origin.clone(),
+            None => {
+                let inner = self.inner.clone();
+                let fut = self.service.call(req);
+                return Box::pin(async move {
+                    let mut res = fut.await?;
+                    inner.vary_origin(&mut res);
+                    Ok(res.map_body(|_, body| AnyBody::from_message(body)))
+                });
+            }
+        };
+
+        let is_preflight = req.method() == Method::OPTIONS
+            && req
+                .headers()
+                .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+
+        if is_preflight {
+            let res = match self.inner.preflight_response(&req) {
+                Ok(res) => req.into_response(res),
+                Err(err) => {
+                    let mut res = req.error_response(err);
+                    self.inner.vary_origin(&mut res);
+                    res
+                }
+            };
+            return Box::pin(async move { Ok(res) });
+        }
+
+        let allowed = self.inner.is_origin_allowed(&origin, req.head());
+        if !allowed && self.inner.block_on_origin_mismatch {
+            let mut res = req.error_response(CorsError::OriginNotAllowed);
+            self.inner.vary_origin(&mut res);
+            return Box::pin(async move { Ok(res) });
+        }
+
+        let inner = self.inner.clone();
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let mut res = fut.await?;
+
+            if allowed {
+                inner.add_response_headers(&origin, &mut res);
+            } else {
+                inner.vary_origin(&mut res);
+            }
+
+            Ok(res.map_body(|_, body| AnyBody::from_message(body)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_service::IntoService;
+    use actix_utils::future::ok;
+
+    use super::*;
+    use crate::{
+        test::{self, ok_service, TestRequest},
+        web, App,
+    };
+
+    fn preflight(origin: &str) -> TestRequest {
+        TestRequest::default()
+            .method(Method::OPTIONS)
+            .insert_header((header::ORIGIN, origin))
+            .insert_header((header::ACCESS_CONTROL_REQUEST_METHOD, "GET"))
+    }
+
+    #[actix_rt::test]
+    async fn test_no_origin_passthrough() {
+        let mw = Cors::default().new_transform(ok_service()).await.unwrap();
+
+        let res = mw
+            .call(TestRequest::default().to_srv_request())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(!res
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+    }
+
+    #[actix_rt::test]
+    async fn test_preflight() {
+        let mw = Cors::default()
+            .allowed_origin("https://www.example.com")
+            .allowed_methods(vec![Method::GET, Method::POST])
+            .allowed_headers(vec![header::AUTHORIZATION, header::CONTENT_TYPE])
+            .supports_credentials()
+            .max_age(3600)
+            .new_transform(ok_service())
+            .await
+            .unwrap();
+
+        let req = preflight("https://www.example.com")
+            .insert_header((
+                header::ACCESS_CONTROL_REQUEST_HEADERS,
+                "authorization, content-type",
+            ))
+            .to_srv_request();
+        let res = mw.call(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let headers = res.headers();
+        assert_eq!(
+            headers.get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+            "https://www.example.com"
+        );
+        assert_eq!(
+            headers.get(header::ACCESS_CONTROL_ALLOW_METHODS).unwrap(),
+            "GET, POST"
+        );
+        assert_eq!(
+            headers.get(header::ACCESS_CONTROL_ALLOW_HEADERS).unwrap(),
+            "authorization, content-type"
+        );
+        assert_eq!(
+            headers
+                .get(header::ACCESS_CONTROL_ALLOW_CREDENTIALS)
+                .unwrap(),
+            "true"
+        );
+        assert_eq!(headers.get(header::ACCESS_CONTROL_MAX_AGE).unwrap(), "3600");
+
+        let req = preflight("https://evil.example.org").to_srv_request();
+        let res = mw.call(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(res.headers().get(header::VARY).unwrap(), "Origin");
+
+        let req = preflight("https://www.example.com")
+            .insert_header((header::ACCESS_CONTROL_REQUEST_METHOD, "DELETE"))
+            .to_srv_request();
+        let res = mw.call(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let req = preflight("https://www.example.com")
+            .insert_header((header::ACCESS_CONTROL_REQUEST_HEADERS, "x-custom"))
+            .to_srv_request();
+        let res = mw.call(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_rt::test]
+    async fn test_permissive_preflight_echoes_request() {
+        let mw = Cors::permissive()
+            .new_transform(ok_service())
+            .await
+            .unwrap();
+
+        let req = preflight("https://any.example.com")
+            .insert_header((header::ACCESS_CONTROL_REQUEST_METHOD, "PATCH"))
+            .insert_header((header::ACCESS_CONTROL_REQUEST_HEADERS, "x-one, x-two"))
+            .to_srv_request();
+        let res = mw.call(req).await.unwrap();
+
+        let headers = res.headers();
+        assert_eq!(
+            headers.get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+            "*"
+        );
+        assert_eq!(
+            headers.get(header::ACCESS_CONTROL_ALLOW_METHODS).unwrap(),
+            "PATCH"
+        );
+        assert_eq!(
+            headers.get(header::ACCESS_CONTROL_ALLOW_HEADERS).unwrap(),
+            "x-one, x-two"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_origin_matchers() {
+        let mw = Cors::default()
+            .allowed_origin_regex(r"^https://[a-z]+\.example\.com$")
+            .allowed_origin_fn(|origin, _| origin.as_bytes().ends_with(b".test"))
+            .new_transform(ok_service())
+            .await
+            .unwrap();
+
+        for (origin, status) in &[
+            ("https://api.example.com", StatusCode::OK),
+            ("http://localhost.test", StatusCode::OK),
+            ("https://api.example.com.evil", StatusCode::BAD_REQUEST),
+            ("https://example.org", StatusCode::BAD_REQUEST),
+        ] {
+            let req = TestRequest::default()
+                .insert_header((header::ORIGIN, *origin))
+                .to_srv_request();
+            let res = mw.call(req).await.unwrap();
+            assert_eq!(res.status(), *status, "origin: {}", origin);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_actual_request_headers() {
+        let srv = |req: ServiceRequest| {
+            ok(req.into_response(
+                HttpResponse::Ok()
+                    .insert_header((header::VARY, "Accept-Encoding"))
+                    .finish(),
+            ))
+        };
+        let mw = Cors::default()
+            .allowed_origin("https://www.example.com")
+            .expose_headers(vec!["x-request-id", "x-total-count"])
+            .new_transform(srv.into_service())
+            .await
+            .unwrap();
+
+        let req = TestRequest::default()
+            .insert_header((header::ORIGIN, "https://www.example.com"))
+            .to_srv_request();
+        let res = mw.call(req).await.unwrap();
+
+        let headers = res.headers();
+        assert_eq!(
+            headers.get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+            "https://www.example.com"
+        );
+        assert_eq!(
+            headers.get(header::ACCESS_CONTROL_EXPOSE_HEADERS).unwrap(),
+            "x-request-id, x-total-count"
+        );
+        let vary = headers.get_all(header::VARY).collect::<Vec<_>>();
+        assert_eq!(vary, vec!["Accept-Encoding", "Origin"]);
+        assert!(!headers.contains_key(header::ACCESS_CONTROL_ALLOW_CREDENTIALS));
+    }
+
+    #[actix_rt::test]
+    async fn test_origin_mismatch_not_blocked() {
+        let mw = Cors::default()
+            .allowed_origin("https://www.example.com")
+            .block_on_origin_mismatch(false)
+            .new_transform(ok_service())
+            .await
+            .unwrap();
+
+        let req = TestRequest::default()
+            .insert_header((header::ORIGIN, "https://example.org"))
+            .to_srv_request();
+        let res = mw.call(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(!res
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+        assert_eq!(res.headers().get(header::VARY).unwrap(), "Origin");
+    }
+
+    #[actix_rt::test]
+    async fn test_disallowed_origin_varies() {
+        let mw = Cors::default()
+            .allowed_origin("https://www.example.com")
+            .new_transform(ok_service())
+            .await
+            .unwrap();
+
+        // rejected, and without an origin, responses could be cached for an allowed origin
+        let req = TestRequest::default()
+            .insert_header((header::ORIGIN, "https://example.org"))
+            .to_srv_request();
+        let res = mw.call(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(res.headers().get(header::VARY).unwrap(), "Origin");
+
+        let res = mw
+            .call(TestRequest::default().to_srv_request())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get(header::VARY).unwrap(), "Origin");
+
+        // any origin gets the same response
+        let mw = Cors::permissive()
+            .new_transform(ok_service())
+            .await
+            .unwrap();
+        let res = mw
+            .call(TestRequest::default().to_srv_request())
+            .await
+            .unwrap();
+        assert!(!res.headers().contains_key(header::VARY));
+    }
+
+    #[actix_rt::test]
+    async fn test_any_origin_with_credentials_echoes_origin() {
+        let mw = Cors::permissive()
+            .supports_credentials()
+            .new_transform(ok_service())
+            .await
+            .unwrap();
+
+        let req = TestRequest::default()
+            .insert_header((header::ORIGIN, "https://www.example.com"))
+            .to_srv_request();
+        let res = mw.call(req).await.unwrap();
+        assert_eq!(
+            res.headers()
+                .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+                .unwrap(),
+            "https://www.example.com"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_resource_and_scope_without_options_route() {
+        let srv = test::init_service(
+            App::new()
+                .service(
+                    web::resource("/res")
+                        .wrap(Cors::permissive())
+                        .route(web::get().to(HttpResponse::Ok)),
+                )
+                .service(
+                    web::scope("/scope")
+                        .wrap(Cors::default().allowed_origin("https://www.example.com"))
+                        .route("/item", web::post().to(HttpResponse::Ok)),
+                ),
+        )
+        .await;
+
+        let req = preflight("https://www.example.com")
+            .uri("/res")
+            .to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(res
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_METHODS));
+
+        let req = preflight("https://www.example.com")
+            .uri("/scope/item")
+            .insert_header((header::ACCESS_CONTROL_REQUEST_METHOD, "POST"))
+            .to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        // without CORS headers, OPTIONS is still routed normally
+        let req = TestRequest::default()
+            .method(Method::OPTIONS)
+            .uri("/res")
+            .to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+}
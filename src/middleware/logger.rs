@@ -18,13 +18,15 @@ use bytes::Bytes;
 use futures_core::ready;
 use log::{debug, warn};
 use regex::{Regex, RegexSet};
+use serde_json::Value;
 use time::OffsetDateTime;
 
 use crate::{
-    dev::{BodySize, MessageBody},
-    http::{HeaderName, StatusCode},
+    dev::{BodySize, Extensions, FileRegion, MessageBody},
+    http::{HeaderMap, HeaderName, StatusCode},
+    middleware::RequestId,
     service::{ServiceRequest, ServiceResponse},
-    Error, HttpResponse, Result,
+    Error, HttpRequest, HttpResponse, Result,
 };
 
 /// Middleware for logging request and response summaries to the terminal.
@@ -71,6 +73,39 @@ use crate::{
 /// `%{FOO}o` | `response.headers["FOO"]`
 /// `%{FOO}e` | `env_var["FOO"]`
 /// `%{FOO}xi` | [Custom request replacement](Logger::custom_request_replace) labelled "FOO"
+/// `%R` | Request ID assigned by [`RequestIdentifier`](super::RequestIdentifier)
+///
+/// # Structured Output
+/// With [`structured`](Logger::structured), each unit of the format is instead emitted as a named
+/// field of a [`LogRecord`] passed to a [`LogSink`]. Literal text in the format is ignored and
+/// missing values are `null`.
+///
+/// Variable | Field | Type
+/// -------- | ----- | ----
+/// `%a` | `remote_addr` | string
+/// `%{r}a` | `realip_remote_addr` | string
+/// `%t` | `request_time` | string
+/// `%r` | `request_line` | string
+/// `%s` | `status` | number
+/// `%b` | `size` | number
+/// `%T` | `time` | number
+/// `%D` | `time_ms` | number
+/// `%U` | `url_path` | string
+/// `%R` | `request_id` | string
+/// `%{FOO}i` | `request_header.foo` | string
+/// `%{FOO}o` | `response_header.foo` | string
+/// `%{FOO}e` | `env.FOO` | string
+/// `%{FOO}xi` | `FOO` | string
+///
+/// Fields computed from request extensions can be added with
+/// [`extension_field`](Logger::extension_field).
+///
+/// ```
+/// use actix_web::middleware::{JsonSink, Logger};
+///
+/// // {"remote_addr":"127.0.0.1:54278","request_line":"GET /test HTTP/1.1","status":404,...}
+/// let logger = Logger::new("%a %r %s %b %T").structured(JsonSink);
+/// ```
 ///
 /// # Security
 /// **\*** "Real IP" remote address is calculated using
@@ -86,6 +121,8 @@ struct Inner {
     format: Format,
     exclude: HashSet<String>,
     exclude_regex: RegexSet,
+    sink: Option<Sink>,
+    extension_fields: Vec<ExtensionField>,
 }
 
 impl Logger {
@@ -95,6 +132,8 @@ impl Logger {
             format: Format::new(format),
             exclude: HashSet::new(),
             exclude_regex: RegexSet::empty(),
+            sink: None,
+            extension_fields: Vec::new(),
         }))
     }
 
@@ -156,6 +195,43 @@ impl Logger {
 
         self
     }
+
+    /// Emit log entries as structured records to `sink` instead of formatted text.
+    ///
+    /// See the [structured output](Logger#structured-output) section for field names.
+    pub fn structured<S: LogSink + 'static>(mut self, sink: S) -> Self {
+        Rc::get_mut(&mut self.0).unwrap().sink = Some(Sink(Rc::new(sink)));
+        self
+    }
+
+    /// Add a field computed from a request extension of type `T` to structured records.
+    ///
+    /// The field is `null` if the extension is not present when the response is ready. Has no
+    /// effect unless [structured](Logger::structured) output is enabled.
+    ///
+    /// # Example
+    /// ```
+    /// # use actix_web::middleware::{JsonSink, Logger};
+    /// struct UserId(u64);
+    ///
+    /// Logger::default()
+    ///     .structured(JsonSink)
+    ///     .extension_field("user_id", |id: &UserId| id.0.into());
+    /// ```
+    pub fn extension_field<T, F>(mut self, name: &str, f: F) -> Self
+    where
+        T: 'static,
+        F: Fn(&T) -> Value + 'static,
+    {
+        Rc::get_mut(&mut self.0)
+            .unwrap()
+            .extension_fields
+            .push(ExtensionField {
+                name: name.to_owned(),
+                field_fn: Rc::new(move |ext: &Extensions| ext.get::<T>().map(&f)),
+            });
+        self
+    }
 }
 
 impl Default for Logger {
@@ -169,10 +245,100 @@ impl Default for Logger {
             format: Format::default(),
             exclude: HashSet::new(),
             exclude_regex: RegexSet::empty(),
+            sink: None,
+            extension_fields: Vec::new(),
         }))
     }
 }
 
+/// A structured access log entry produced by [`Logger::structured`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LogRecord {
+    fields: Vec<(String, Value)>,
+}
+
+impl LogRecord {
+    /// Returns the fields of this record in format order.
+    pub fn fields(&self) -> &[(String, Value)] {
+        &self.fields
+    }
+
+    /// Returns the value of the field with the given name.
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, val)| val)
+    }
+
+    /// Serializes the record as a single-line JSON object, keeping field order.
+    pub fn to_json(&self) -> String {
+        let mut buf = String::from("{");
+
+        for (idx, (name, val)) in self.fields.iter().enumerate() {
+            if idx > 0 {
+                buf.push(',');
+            }
+            buf.push_str(&Value::from(name.as_str()).to_string());
+            buf.push(':');
+            buf.push_str(&val.to_string());
+        }
+
+        buf.push('}');
+        buf
+    }
+}
+
+/// Destination for structured access log records.
+///
+/// Implemented for closures taking a `&LogRecord`.
+pub trait LogSink {
+    /// Handles a completed log record.
+    fn log(&self, record: &LogRecord);
+}
+
+impl<F> LogSink for F
+where
+    F: Fn(&LogRecord),
+{
+    fn log(&self, record: &LogRecord) {
+        (self)(record)
+    }
+}
+
+/// Log sink writing records as JSON lines through the `log` crate at the INFO level.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonSink;
+
+impl LogSink for JsonSink {
+    fn log(&self, record: &LogRecord) {
+        log::info!("{}", record.to_json());
+    }
+}
+
+#[derive(Clone)]
+struct Sink(Rc<dyn LogSink>);
+
+impl fmt::Debug for Sink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("log_sink")
+    }
+}
+
+#[derive(Clone)]
+struct ExtensionField {
+    name: String,
+    field_fn: Rc<dyn Fn(&Extensions) -> Option<Value>>,
+}
+
+impl fmt::Debug for ExtensionField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExtensionField")
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
 impl<S, B> Transform<S, ServiceRequest> for Logger
 where
     S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
@@ -227,6 +393,7 @@ where
                 fut: self.service.call(req),
                 format: None,
                 time: OffsetDateTime::now_utc(),
+                inner: self.inner.clone(),
                 _phantom: PhantomData,
             }
         } else {
@@ -240,6 +407,7 @@ where
                 fut: self.service.call(req),
                 format: Some(format),
                 time: now,
+                inner: self.inner.clone(),
                 _phantom: PhantomData,
             }
         }
@@ -256,6 +424,7 @@ where
     fut: S::Future,
     time: OffsetDateTime,
     format: Option<Format>,
+    inner: Rc<Inner>,
     _phantom: PhantomData<B>,
 }
 
@@ -283,16 +452,41 @@ where
         if let Some(ref mut format) = this.format {
             for unit in &mut format.0 {
                 unit.render_response(res.response());
+                unit.render_request_id(res.request());
             }
         }
 
         let time = *this.time;
         let format = this.format.take();
 
+        let structured = match this.inner.sink {
+            Some(_) if format.is_some() => {
+                let ext = res.request().extensions();
+                let fields = this
+                    .inner
+                    .extension_fields
+                    .iter()
+                    .map(|field| {
+                        (
+                            field.name.clone(),
+                            (field.field_fn)(&ext).unwrap_or(Value::Null),
+                        )
+                    })
+                    .collect();
+
+                Some(StructuredLog {
+                    inner: this.inner.clone(),
+                    extension_fields: fields,
+                })
+            }
+            _ => None,
+        };
+
         Poll::Ready(Ok(res.map_body(move |_, body| StreamLog {
             body,
             time,
             format,
+            structured,
             size: 0,
         })))
     }
@@ -305,14 +499,35 @@ pub struct StreamLog<B> {
     #[pin]
     body: B,
     format: Option<Format>,
+    structured: Option<StructuredLog>,
     size: usize,
     time: OffsetDateTime,
 }
 
+/// State needed to emit a structured record once the response body is complete.
+struct StructuredLog {
+    inner: Rc<Inner>,
+    extension_fields: Vec<(String, Value)>,
+}
+
 #[pinned_drop]
 impl<B> PinnedDrop for StreamLog<B> {
     fn drop(self: Pin<&mut Self>) {
-        if let Some(ref format) = self.format {
+        if let (Some(format), Some(structured)) = (&self.format, &self.structured) {
+            let units = structured.inner.format.0.iter().zip(&format.0);
+
+            let mut fields = units
+                .filter_map(|(unit, rendered)| {
+                    let name = unit.field_name()?;
+                    Some((name, unit.field_value(rendered, self.size, self.time)))
+                })
+                .collect::<Vec<_>>();
+            fields.extend(structured.extension_fields.iter().cloned());
+
+            if let Some(Sink(ref sink)) = structured.inner.sink {
+                sink.log(&LogRecord { fields });
+            }
+        } else if let Some(ref format) = self.format {
             let render = |fmt: &mut fmt::Formatter<'_>| {
                 for unit in &format.0 {
                     unit.render(fmt, self.size, self.time)?;
@@ -351,6 +566,20 @@ where
             None => Poll::Ready(None),
         }
     }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        self.project().body.poll_trailers(cx).map_err(Into::into)
+    }
+
+    fn take_file_region(self: Pin<&mut Self>) -> Option<FileRegion> {
+        let this = self.project();
+        let region = this.body.take_file_region()?;
+        *this.size += region.len() as usize;
+        Some(region)
+    }
 }
 
 /// A formatting style for the `Logger` consisting of multiple concatenated `FormatText` items.
@@ -370,7 +599,7 @@ impl Format {
     /// Returns `None` if the format string syntax is incorrect.
     pub fn new(s: &str) -> Format {
         log::trace!("Access log format: {}", s);
-        let fmt = Regex::new(r"%(\{([A-Za-z0-9\-_]+)\}([aioe]|xi)|[%atPrUsbTD]?)").unwrap();
+        let fmt = Regex::new(r"%(\{([A-Za-z0-9\-_]+)\}([aioe]|xi)|[%atPrUsbTDR]?)").unwrap();
 
         let mut idx = 0;
         let mut results = Vec::new();
@@ -413,6 +642,7 @@ impl Format {
                     "U" => FormatText::UrlPath,
                     "T" => FormatText::Time,
                     "D" => FormatText::TimeMillis,
+                    "R" => FormatText::RequestId,
                     _ => FormatText::Str(m.as_str().to_owned()),
                 });
             }
@@ -442,6 +672,7 @@ enum FormatText {
     RemoteAddr,
     RealIpRemoteAddr,
     UrlPath,
+    RequestId,
     RequestHeader(HeaderName),
     ResponseHeader(HeaderName),
     EnvironHeader(String),
@@ -466,6 +697,62 @@ impl fmt::Debug for CustomRequestFn {
 }
 
 impl FormatText {
+    /// Name of the field this unit is emitted as in structured output.
+    fn field_name(&self) -> Option<String> {
+        let name = match self {
+            FormatText::RemoteAddr => "remote_addr",
+            FormatText::RealIpRemoteAddr => "realip_remote_addr",
+            FormatText::RequestTime => "request_time",
+            FormatText::RequestLine => "request_line",
+            FormatText::ResponseStatus => "status",
+            FormatText::ResponseSize => "size",
+            FormatText::Time => "time",
+            FormatText::TimeMillis => "time_ms",
+            FormatText::UrlPath => "url_path",
+            FormatText::RequestId => "request_id",
+            FormatText::RequestHeader(name) => return Some(format!("request_header.{}", name)),
+            FormatText::ResponseHeader(name) => {
+                return Some(format!("response_header.{}", name))
+            }
+            FormatText::EnvironHeader(name) => return Some(format!("env.{}", name)),
+            FormatText::CustomRequest(label, _) => return Some(label.clone()),
+            FormatText::Str(_) | FormatText::Percent => return None,
+        };
+
+        Some(name.to_owned())
+    }
+
+    /// Value of this unit in structured output, given its rendered counterpart.
+    fn field_value(
+        &self,
+        rendered: &FormatText,
+        size: usize,
+        entry_time: OffsetDateTime,
+    ) -> Value {
+        match self {
+            FormatText::ResponseStatus => match rendered {
+                FormatText::Str(status) => {
+                    status.parse::<u16>().map_or(Value::Null, Value::from)
+                }
+                _ => Value::Null,
+            },
+            FormatText::ResponseSize => Value::from(size),
+            FormatText::Time => {
+                let rt = OffsetDateTime::now_utc() - entry_time;
+                Value::from(rt.as_seconds_f64())
+            }
+            FormatText::TimeMillis => {
+                let rt = OffsetDateTime::now_utc() - entry_time;
+                Value::from((rt.whole_nanoseconds() as f64) / 1_000_000.0)
+            }
+            FormatText::EnvironHeader(name) => env::var(name).map_or(Value::Null, Value::from),
+            _ => match rendered {
+                FormatText::Str(s) if s != "-" => Value::from(s.as_str()),
+                _ => Value::Null,
+            },
+        }
+    }
+
     fn render(
         &self,
         fmt: &mut fmt::Formatter<'_>,
@@ -518,6 +805,15 @@ impl FormatText {
         }
     }
 
+    fn render_request_id(&mut self, req: &HttpRequest) {
+        if let FormatText::RequestId = self {
+            *self = match req.extensions().get::<RequestId>() {
+                Some(id) => FormatText::Str(id.to_string()),
+                None => FormatText::Str("-".to_owned()),
+            };
+        }
+    }
+
     fn render_request(&mut self, now: OffsetDateTime, req: &ServiceRequest) {
         match self {
             FormatText::RequestLine => {
@@ -599,7 +895,9 @@ mod tests {
 
     use super::*;
     use crate::http::{header, StatusCode};
-    use crate::test::{self, TestRequest};
+    use crate::middleware::RequestIdentifier;
+    use crate::test::{self, ok_service, TestRequest};
+    use crate::HttpMessage as _;
 
     #[actix_rt::test]
     async fn test_logger() {
@@ -623,6 +921,83 @@ mod tests {
         let _res = srv.call(req).await;
     }
 
+    #[actix_rt::test]
+    async fn test_structured_logger() {
+        struct UserId(u64);
+
+        let srv = |req: ServiceRequest| {
+            req.extensions_mut().insert(UserId(42));
+            ok(req.into_response(
+                HttpResponse::build(StatusCode::CREATED)
+                    .insert_header(("X-Test", "ttt"))
+                    .body("hello"),
+            ))
+        };
+
+        let records = Rc::new(std::cell::RefCell::new(Vec::new()));
+        let sink_records = records.clone();
+
+        let logger = Logger::new("%% %r %s %b %{User-Agent}i %{X-Missing}o %T %{LABEL}xi")
+            .custom_request_replace("LABEL", |_| "custom".to_owned())
+            .extension_field("user_id", |id: &UserId| id.0.into())
+            .extension_field("missing", |id: &String| id.as_str().into())
+            .structured(move |record: &LogRecord| {
+                sink_records.borrow_mut().push(record.clone())
+            });
+
+        let srv = logger.new_transform(srv.into_service()).await.unwrap();
+
+        let req = TestRequest::with_uri("/test")
+            .insert_header((header::USER_AGENT, "ACTIX-WEB"))
+            .to_srv_request();
+        let res = srv.call(req).await.unwrap();
+        let _ = test::read_body(res).await;
+
+        let records = records.borrow();
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+
+        let names = record
+            .fields()
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                "request_line",
+                "status",
+                "size",
+                "request_header.user-agent",
+                "response_header.x-missing",
+                "time",
+                "LABEL",
+                "user_id",
+                "missing"
+            ]
+        );
+
+        assert_eq!(record.get("request_line").unwrap(), "GET /test HTTP/1.1");
+        assert_eq!(record.get("status").unwrap(), 201);
+        assert_eq!(record.get("size").unwrap(), 5);
+        assert_eq!(
+            record.get("request_header.user-agent").unwrap(),
+            "ACTIX-WEB"
+        );
+        assert!(record.get("response_header.x-missing").unwrap().is_null());
+        assert!(record.get("time").unwrap().is_f64());
+        assert_eq!(record.get("LABEL").unwrap(), "custom");
+        assert_eq!(record.get("user_id").unwrap(), 42);
+        assert!(record.get("missing").unwrap().is_null());
+
+        let json = record.to_json();
+        assert!(
+            json.starts_with(r#"{"request_line":"GET /test HTTP/1.1","status":201,"size":5,"#)
+        );
+        let parsed: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed["user_id"], 42);
+    }
+
     #[actix_rt::test]
     async fn test_logger_exclude_regex() {
         let srv = |req: ServiceRequest| {
@@ -804,6 +1179,33 @@ mod tests {
         assert!(s.contains("192.0.2.60"));
     }
 
+    #[actix_rt::test]
+    async fn test_request_id_format() {
+        let mut format = Format::new("%R");
+
+        let srv = RequestIdentifier::default()
+            .new_transform(ok_service())
+            .await
+            .unwrap();
+        let req = TestRequest::default()
+            .insert_header(("x-request-id", "abc-123"))
+            .to_srv_request();
+        let res = srv.call(req).await.unwrap();
+
+        let now = OffsetDateTime::now_utc();
+        for unit in &mut format.0 {
+            unit.render_request_id(res.request());
+        }
+
+        let render = |fmt: &mut fmt::Formatter<'_>| {
+            for unit in &format.0 {
+                unit.render(fmt, 1024, now)?;
+            }
+            Ok(())
+        };
+        assert_eq!(FormatDisplay(&render).to_string(), "abc-123");
+    }
+
     #[actix_rt::test]
     async fn test_custom_closure_log() {
         let mut logger = Logger::new("test %{CUSTOM}xi")
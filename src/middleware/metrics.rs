@@ -0,0 +1,817 @@
+//! For middleware documentation, see [`Metrics`].
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::{self, Write as _},
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard, PoisonError, Weak},
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use actix_http::ConnectionStats;
+use actix_service::{Service, Transform};
+use actix_utils::future::{ok, ready, Ready};
+use bytes::Bytes;
+use futures_core::ready;
+use pin_project::pin_project;
+
+use crate::{
+    dev::{BodySize, FileRegion, MessageBody},
+    http::{header, HeaderMap, StatusCode},
+    service::{ServiceRequest, ServiceResponse},
+    Error, HttpResponse,
+};
+
+/// Label used for requests that did not match any registered route.
+const UNMATCHED_PATH: &str = "<unmatched>";
+
+/// Default histogram buckets for request durations, in seconds.
+const DEFAULT_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Middleware for collecting request metrics in the Prometheus text exposition format.
+///
+/// Requests are labelled by method and by the matched route pattern (see
+/// [`HttpRequest::match_pattern`](crate::HttpRequest::match_pattern)) instead of the raw path,
+/// keeping the number of series bounded. Requests that match no route share the `<unmatched>`
+/// path label.
+///
+/// | Metric                                   | Type      | Labels                     |
+/// | ---------------------------------------- | --------- | -------------------------- |
+/// | `http_requests_total`                    | counter   | `method`, `path`, `status` |
+/// | `http_request_duration_seconds`          | histogram | `method`, `path`           |
+/// | `http_requests_in_flight`                | gauge     | `method`, `path`           |
+/// | `http_response_size_bytes_total`         | counter   | `method`, `path`, `status` |
+/// | `http_connections_open` **\***           | gauge     |                            |
+/// | `http_connections_total` **\***          | counter   |                            |
+/// | `http_connection_reuses_total` **\***    | counter   |                            |
+///
+/// The duration of a request is measured until its response body has been fully sent.
+///
+/// **\*** Connection metrics are only rendered when [`ConnectionStats`] are configured with
+/// [`connection_stats`](Self::connection_stats) and given to the server as well.
+///
+/// Each worker records into its own registry and [`render`](Self::render) sums them up. For
+/// metrics of the whole server, construct the middleware once before `HttpServer::new`, wrap
+/// every app in a clone, and expose it with [`handler`](Self::handler).
+///
+/// # Examples
+/// ```
+/// use actix_web::{dev::ConnectionStats, middleware::Metrics, web, App, HttpResponse, HttpServer};
+///
+/// # fn run() -> std::io::Result<actix_web::dev::Server> {
+/// let stats = ConnectionStats::new();
+/// let metrics = Metrics::new()
+///     .namespace("my_app")
+///     .exclude("/metrics")
+///     .connection_stats(stats.clone());
+///
+/// let srv = HttpServer::new(move || {
+///     App::new()
+///         .wrap(metrics.clone())
+///         .route("/metrics", web::get().to(metrics.handler()))
+///         .route("/users/{id}", web::get().to(HttpResponse::Ok))
+/// })
+/// .connection_stats(stats)
+/// .bind("127.0.0.1:8080")?
+/// .run();
+/// # Ok(srv)
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Metrics {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    namespace: Option<String>,
+    buckets: Vec<f64>,
+    exclude: HashSet<String>,
+    connection_stats: Option<ConnectionStats>,
+
+    /// Registries of each middleware instance, i.e. one per worker. Requests only lock the
+    /// registry of their own worker; rendering merges all of them.
+    registries: Mutex<Registries>,
+}
+
+#[derive(Default)]
+struct Registries {
+    workers: Vec<Weak<WorkerRegistry>>,
+
+    /// Counts of dropped middleware instances, so totals never go backwards.
+    retired: Registry,
+}
+
+impl Registries {
+    /// Forgets middleware instances that have been dropped.
+    fn prune(&mut self) {
+        self.workers.retain(|worker| worker.strong_count() > 0);
+    }
+}
+
+/// Registry of a single middleware instance. It is folded into [`Registries::retired`] once the
+/// instance and all of its in-flight requests are dropped.
+struct WorkerRegistry {
+    inner: Arc<Inner>,
+    registry: Mutex<Registry>,
+}
+
+impl WorkerRegistry {
+    fn lock(&self) -> MutexGuard<'_, Registry> {
+        self.registry.lock().unwrap()
+    }
+}
+
+impl Drop for WorkerRegistry {
+    fn drop(&mut self) {
+        let registry = self
+            .registry
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        let mut registries = self.inner.registries.lock().unwrap();
+        registries.retired.merge(registry);
+        registries.prune();
+    }
+}
+
+impl Metrics {
+    /// Constructs metrics middleware with an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn inner_mut(&mut self) -> &mut Inner {
+        Arc::get_mut(&mut self.inner).expect("Multiple copies exist")
+    }
+
+    /// Sets a prefix for metric names, e.g. `my_app` produces `my_app_http_requests_total`.
+    pub fn namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.inner_mut().namespace = Some(namespace.into());
+        self
+    }
+
+    /// Sets the upper bounds, in seconds, of the request duration histogram buckets.
+    ///
+    /// The `+Inf` bucket is always added. By default, buckets range from 5ms to 10s.
+    pub fn buckets(mut self, buckets: &[f64]) -> Self {
+        let mut buckets = buckets.to_vec();
+        buckets.retain(|bound| bound.is_finite());
+        buckets.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        buckets.dedup();
+
+        self.inner_mut().buckets = buckets;
+        self
+    }
+
+    /// Ignore requests to the specified path, e.g. the metrics endpoint itself.
+    pub fn exclude<T: Into<String>>(mut self, path: T) -> Self {
+        self.inner_mut().exclude.insert(path.into());
+        self
+    }
+
+    /// Renders connection-level metrics from `stats`.
+    ///
+    /// The same [`ConnectionStats`] must be passed to
+    /// [`HttpServer::connection_stats`](crate::HttpServer::connection_stats) to be updated.
+    pub fn connection_stats(mut self, stats: ConnectionStats) -> Self {
+        self.inner_mut().connection_stats = Some(stats);
+        self
+    }
+
+    /// Renders all metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let inner = &*self.inner;
+        let mut out = String::new();
+
+        let mut registry = Registry::default();
+        let mut registries = inner.registries.lock().unwrap();
+        registries.prune();
+        registry.merge(&registries.retired);
+
+        let workers = registries
+            .workers
+            .iter()
+            .filter_map(Weak::upgrade)
+            .collect::<Vec<_>>();
+        for worker in &workers {
+            registry.merge(&worker.lock());
+        }
+
+        // the last handle to a worker registry folds it in on drop, which needs the lock
+        drop(registries);
+        drop(workers);
+
+        let name = |metric: &str| match inner.namespace {
+            Some(ref ns) => format!("{}_{}", ns, metric),
+            None => metric.to_owned(),
+        };
+
+        let metric = name("http_requests_total");
+        write_header(
+            &mut out,
+            &metric,
+            "counter",
+            "Total number of HTTP requests.",
+        );
+        for ((method, path, status), count) in &registry.requests {
+            let labels = labels(&[("method", method), ("path", path), ("status", status)]);
+            let _ = writeln!(out, "{}{} {}", metric, labels, count);
+        }
+
+        let metric = name("http_request_duration_seconds");
+        write_header(
+            &mut out,
+            &metric,
+            "histogram",
+            "HTTP request duration in seconds.",
+        );
+        for ((method, path), hist) in &registry.durations {
+            let mut cumulative = 0;
+
+            for (bound, count) in inner.buckets.iter().zip(&hist.counts) {
+                cumulative += count;
+                let le = bound.to_string();
+                let labels = labels(&[("method", method), ("path", path), ("le", &le)]);
+                let _ = writeln!(out, "{}_bucket{} {}", metric, labels, cumulative);
+            }
+
+            let labels_inf = labels(&[("method", method), ("path", path), ("le", "+Inf")]);
+            let _ = writeln!(out, "{}_bucket{} {}", metric, labels_inf, hist.count);
+
+            let labels = labels(&[("method", method), ("path", path)]);
+            let _ = writeln!(out, "{}_sum{} {}", metric, labels, hist.sum);
+            let _ = writeln!(out, "{}_count{} {}", metric, labels, hist.count);
+        }
+
+        let metric = name("http_requests_in_flight");
+        write_header(
+            &mut out,
+            &metric,
+            "gauge",
+            "Number of HTTP requests being processed.",
+        );
+        for ((method, path), count) in &registry.in_flight {
+            let labels = labels(&[("method", method), ("path", path)]);
+            let _ = writeln!(out, "{}{} {}", metric, labels, count);
+        }
+
+        let metric = name("http_response_size_bytes_total");
+        write_header(
+            &mut out,
+            &metric,
+            "counter",
+            "Total size of HTTP response bodies in bytes.",
+        );
+        for ((method, path, status), size) in &registry.response_bytes {
+            let labels = labels(&[("method", method), ("path", path), ("status", status)]);
+            let _ = writeln!(out, "{}{} {}", metric, labels, size);
+        }
+
+        if let Some(ref stats) = inner.connection_stats {
+            let metric = name("http_connections_open");
+            write_header(&mut out, &metric, "gauge", "Number of open connections.");
+            let _ = writeln!(out, "{} {}", metric, stats.open_connections());
+
+            let metric = name("http_connections_total");
+            write_header(&mut out, &metric, "counter", "Total number of connections.");
+            let _ = writeln!(out, "{} {}", metric, stats.total_connections());
+
+            let metric = name("http_connection_reuses_total");
+            write_header(
+                &mut out,
+                &metric,
+                "counter",
+                "Total number of requests served on a reused connection.",
+            );
+            let _ = writeln!(out, "{} {}", metric, stats.keep_alive_reuses());
+        }
+
+        out
+    }
+
+    /// Returns a handler that responds with the [rendered](Self::render) metrics.
+    ///
+    /// ```
+    /// use actix_web::{middleware::Metrics, web, App};
+    ///
+    /// let metrics = Metrics::new();
+    ///
+    /// let app = App::new()
+    ///     .wrap(metrics.clone())
+    ///     .route("/metrics", web::get().to(metrics.handler()));
+    /// ```
+    pub fn handler(&self) -> impl Fn() -> Ready<HttpResponse> + Clone + 'static {
+        let metrics = self.clone();
+
+        move || {
+            ready(
+                HttpResponse::Ok()
+                    .insert_header((
+                        header::CONTENT_TYPE,
+                        "text/plain; version=0.0.4; charset=utf-8",
+                    ))
+                    .body(metrics.render()),
+            )
+        }
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                namespace: None,
+                buckets: DEFAULT_BUCKETS.to_vec(),
+                exclude: HashSet::new(),
+                connection_stats: None,
+                registries: Mutex::new(Registries::default()),
+            }),
+        }
+    }
+}
+
+impl fmt::Debug for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Metrics")
+            .field("namespace", &self.inner.namespace)
+            .field("buckets", &self.inner.buckets)
+            .field("exclude", &self.inner.exclude)
+            .finish()
+    }
+}
+
+fn write_header(out: &mut String, metric: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", metric, help);
+    let _ = writeln!(out, "# TYPE {} {}", metric, kind);
+}
+
+/// Formats a label set, escaping values as required by the exposition format.
+fn labels(pairs: &[(&str, &str)]) -> String {
+    let mut out = String::from("{");
+
+    for (idx, (name, value)) in pairs.iter().enumerate() {
+        if idx > 0 {
+            out.push(',');
+        }
+
+        out.push_str(name);
+        out.push_str("=\"");
+
+        for ch in value.chars() {
+            match ch {
+                '\\' => out.push_str("\\\\"),
+                '"' => out.push_str("\\\""),
+                '\n' => out.push_str("\\n"),
+                ch => out.push(ch),
+            }
+        }
+
+        out.push('"');
+    }
+
+    out.push('}');
+    out
+}
+
+#[derive(Default)]
+struct Registry {
+    requests: BTreeMap<(String, String, String), u64>,
+    response_bytes: BTreeMap<(String, String, String), u64>,
+    in_flight: BTreeMap<(String, String), u64>,
+    durations: BTreeMap<(String, String), Histogram>,
+}
+
+impl Registry {
+    fn merge(&mut self, other: &Registry) {
+        fn add<K: Ord + Clone>(dst: &mut BTreeMap<K, u64>, src: &BTreeMap<K, u64>) {
+            for (key, value) in src {
+                *dst.entry(key.clone()).or_insert(0) += value;
+            }
+        }
+
+        add(&mut self.requests, &other.requests);
+        add(&mut self.response_bytes, &other.response_bytes);
+        add(&mut self.in_flight, &other.in_flight);
+
+        for (key, hist) in &other.durations {
+            self.durations
+                .entry(key.clone())
+                .or_insert_with(|| Histogram::new(hist.counts.len()))
+                .merge(hist);
+        }
+    }
+}
+
+struct Histogram {
+    /// Per-bucket (non-cumulative) observation counts, excluding the `+Inf` bucket.
+    counts: Vec<u64>,
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn new(buckets: usize) -> Self {
+        Self {
+            counts: vec![0; buckets],
+            count: 0,
+            sum: 0.0,
+        }
+    }
+
+    fn observe(&mut self, buckets: &[f64], value: f64) {
+        if let Some(idx) = buckets.iter().position(|bound| value <= *bound) {
+            self.counts[idx] += 1;
+        }
+
+        self.count += 1;
+        self.sum += value;
+    }
+
+    fn merge(&mut self, other: &Histogram) {
+        for (count, other) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other;
+        }
+
+        self.count += other.count;
+        self.sum += other.sum;
+    }
+}
+
+/// Tracks a single request from the service call until its response body is dropped.
+///
+/// The request is counted as in flight for the lifetime of this value. Once a status is known,
+/// dropping it records the request.
+struct Recorder {
+    inner: Arc<Inner>,
+    registry: Arc<WorkerRegistry>,
+    method: String,
+    path: String,
+    start: Instant,
+    status: Option<StatusCode>,
+    size: u64,
+}
+
+impl Recorder {
+    fn new(inner: Arc<Inner>, registry: Arc<WorkerRegistry>, req: &ServiceRequest) -> Self {
+        let method = req.method().as_str().to_owned();
+        let path = req
+            .match_pattern()
+            .unwrap_or_else(|| UNMATCHED_PATH.to_owned());
+
+        *registry
+            .lock()
+            .in_flight
+            .entry((method.clone(), path.clone()))
+            .or_insert(0) += 1;
+
+        Self {
+            inner,
+            registry,
+            method,
+            path,
+            start: Instant::now(),
+            status: None,
+            size: 0,
+        }
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let mut registry = self.registry.lock();
+        let key = (self.method.clone(), self.path.clone());
+
+        if let Some(count) = registry.in_flight.get_mut(&key) {
+            *count = count.saturating_sub(1);
+        }
+
+        // requests abandoned before producing a response are not recorded
+        let status = match self.status {
+            Some(status) => status.as_str().to_owned(),
+            None => return,
+        };
+
+        let buckets = &self.inner.buckets;
+        registry
+            .durations
+            .entry(key)
+            .or_insert_with(|| Histogram::new(buckets.len()))
+            .observe(buckets, self.start.elapsed().as_secs_f64());
+
+        let key = (self.method.clone(), self.path.clone(), status);
+        *registry.requests.entry(key.clone()).or_insert(0) += 1;
+        *registry.response_bytes.entry(key).or_insert(0) += self.size;
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for Metrics
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+    B::Error: Into<Error>,
+{
+    type Response = ServiceResponse<MetricsBody<B>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = MetricsMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        let registry = Arc::new(WorkerRegistry {
+            inner: self.inner.clone(),
+            registry: Mutex::new(Registry::default()),
+        });
+
+        let mut registries = self.inner.registries.lock().unwrap();
+        registries.prune();
+        registries.workers.push(Arc::downgrade(&registry));
+        drop(registries);
+
+        ok(MetricsMiddleware {
+            service,
+            inner: self.inner.clone(),
+            registry,
+        })
+    }
+}
+
+/// Metrics middleware service.
+pub struct MetricsMiddleware<S> {
+    service: S,
+    inner: Arc<Inner>,
+    registry: Arc<WorkerRegistry>,
+}
+
+impl<S, B> Service<ServiceRequest> for MetricsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+    B::Error: Into<Error>,
+{
+    type Response = ServiceResponse<MetricsBody<B>>;
+    type Error = Error;
+    type Future = MetricsResponse<S, B>;
+
+    actix_service::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let recorder = if self.inner.exclude.contains(req.path()) {
+            None
+        } else {
+            Some(Recorder::new(
+                self.inner.clone(),
+                self.registry.clone(),
+                &req,
+            ))
+        };
+
+        MetricsResponse {
+            fut: self.service.call(req),
+            recorder,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+#[pin_project]
+pub struct MetricsResponse<S, B>
+where
+    S: Service<ServiceRequest>,
+{
+    #[pin]
+    fut: S::Future,
+    recorder: Option<Recorder>,
+    _phantom: PhantomData<B>,
+}
+
+impl<S, B> Future for MetricsResponse<S, B>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+{
+    type Output = Result<ServiceResponse<MetricsBody<B>>, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+
+        let res = match ready!(this.fut.poll(cx)) {
+            Ok(res) => res,
+            Err(err) => {
+                if let Some(mut recorder) = this.recorder.take() {
+                    recorder.status = Some(err.as_response_error().status_code());
+                }
+
+                return Poll::Ready(Err(err));
+            }
+        };
+
+        let mut recorder = this.recorder.take();
+        if let Some(ref mut recorder) = recorder {
+            recorder.status = Some(res.status());
+        }
+
+        Poll::Ready(Ok(
+            res.map_body(move |_, body| MetricsBody { body, recorder })
+        ))
+    }
+}
+
+/// Response body wrapper that counts the bytes sent and records the request once dropped.
+#[pin_project]
+pub struct MetricsBody<B> {
+    #[pin]
+    body: B,
+    recorder: Option<Recorder>,
+}
+
+impl<B> MessageBody for MetricsBody<B>
+where
+    B: MessageBody,
+    B::Error: Into<Error>,
+{
+    type Error = Error;
+
+    fn size(&self) -> BodySize {
+        self.body.size()
+    }
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        let this = self.project();
+
+        // TODO: MSRV 1.51: poll_map_err
+        match ready!(this.body.poll_next(cx)) {
+            Some(Ok(chunk)) => {
+                if let Some(recorder) = this.recorder {
+                    recorder.size += chunk.len() as u64;
+                }
+                Poll::Ready(Some(Ok(chunk)))
+            }
+            Some(Err(err)) => Poll::Ready(Some(Err(err.into()))),
+            None => Poll::Ready(None),
+        }
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        self.project().body.poll_trailers(cx).map_err(Into::into)
+    }
+
+    fn take_file_region(self: Pin<&mut Self>) -> Option<FileRegion> {
+        let this = self.project();
+        let region = this.body.take_file_region()?;
+        if let Some(recorder) = this.recorder {
+            recorder.size += region.len();
+        }
+        Some(region)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test::{call_service, init_service, read_body, TestRequest},
+        web, App, HttpResponse,
+    };
+
+    #[actix_rt::test]
+    async fn test_request_metrics() {
+        let metrics = Metrics::new().buckets(&[0.5, 0.1]).exclude("/metrics");
+
+        let srv = init_service(
+            App::new()
+                .wrap(metrics.clone())
+                .route(
+                    "/users/{id}",
+                    web::get().to(|| HttpResponse::Ok().body("hello")),
+                )
+                .route("/metrics", web::get().to(metrics.handler())),
+        )
+        .await;
+
+        for path in &["/users/1", "/users/2", "/nope"] {
+            let req = TestRequest::get().uri(path).to_request();
+            let res = call_service(&srv, req).await;
+            read_body(res).await;
+        }
+
+        let req = TestRequest::get().uri("/metrics").to_request();
+        let res = call_service(&srv, req).await;
+        assert_eq!(
+            res.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/plain; version=0.0.4; charset=utf-8"
+        );
+        let body = read_body(res).await;
+        let body = std::str::from_utf8(&body).unwrap();
+
+        assert!(body.contains("# TYPE http_requests_total counter\n"));
+        assert!(body.contains(
+            "http_requests_total{method=\"GET\",path=\"/users/{id}\",status=\"200\"} 2\n"
+        ));
+        assert!(body.contains(
+            "http_requests_total{method=\"GET\",path=\"<unmatched>\",status=\"404\"} 1\n"
+        ));
+        assert!(body.contains(
+            "http_response_size_bytes_total{method=\"GET\",path=\"/users/{id}\",status=\"200\"} 10\n"
+        ));
+        assert!(body.contains(
+            "http_request_duration_seconds_bucket{method=\"GET\",path=\"/users/{id}\",le=\"0.1\"} 2\n"
+        ));
+        assert!(body.contains(
+            "http_request_duration_seconds_bucket{method=\"GET\",path=\"/users/{id}\",le=\"+Inf\"} 2\n"
+        ));
+        assert!(body.contains(
+            "http_request_duration_seconds_count{method=\"GET\",path=\"/users/{id}\"} 2\n"
+        ));
+        assert!(
+            body.contains("http_requests_in_flight{method=\"GET\",path=\"/users/{id}\"} 0\n")
+        );
+        assert!(!body.contains("/metrics"));
+        assert!(!body.contains("http_connections_open"));
+    }
+
+    #[actix_rt::test]
+    async fn test_in_flight() {
+        let metrics = Metrics::new().namespace("app");
+
+        let srv = init_service(
+            App::new()
+                .wrap(metrics.clone())
+                .route("/", web::get().to(|| HttpResponse::Ok().body("data"))),
+        )
+        .await;
+
+        let res = call_service(&srv, TestRequest::default().to_request()).await;
+        assert!(metrics
+            .render()
+            .contains("app_http_requests_in_flight{method=\"GET\",path=\"/\"} 1\n"));
+
+        // request is recorded once the body is dropped
+        assert!(!metrics.render().contains("app_http_requests_total{"));
+        drop(res);
+
+        let out = metrics.render();
+        assert!(out.contains("app_http_requests_in_flight{method=\"GET\",path=\"/\"} 0\n"));
+        assert!(out
+            .contains("app_http_requests_total{method=\"GET\",path=\"/\",status=\"200\"} 1\n"));
+    }
+
+    #[actix_rt::test]
+    async fn test_merge_workers() {
+        let metrics = Metrics::new().buckets(&[1.0]);
+
+        // every app instance, i.e. every worker, records into its own registry
+        for _ in 0..2 {
+            let srv = init_service(
+                App::new()
+                    .wrap(metrics.clone())
+                    .route("/", web::get().to(|| HttpResponse::Ok().body("data"))),
+            )
+            .await;
+
+            let res = call_service(&srv, TestRequest::default().to_request()).await;
+            read_body(res).await;
+        }
+
+        let out = metrics.render();
+        assert!(
+            out.contains("http_requests_total{method=\"GET\",path=\"/\",status=\"200\"} 2\n")
+        );
+        assert!(out.contains(
+            "http_request_duration_seconds_bucket{method=\"GET\",path=\"/\",le=\"1\"} 2\n"
+        ));
+        assert!(out.contains(
+            "http_response_size_bytes_total{method=\"GET\",path=\"/\",status=\"200\"} 8\n"
+        ));
+
+        // both app instances were dropped and folded into the retired counts
+        assert!(metrics.inner.registries.lock().unwrap().workers.is_empty());
+    }
+
+    #[test]
+    fn test_connection_metrics() {
+        let metrics = Metrics::new().connection_stats(ConnectionStats::new());
+
+        let out = metrics.render();
+        assert!(out.contains("# TYPE http_connections_open gauge\nhttp_connections_open 0\n"));
+        assert!(out.contains("http_connections_total 0\n"));
+        assert!(out.contains("http_connection_reuses_total 0\n"));
+    }
+
+    #[test]
+    fn test_label_escaping() {
+        assert_eq!(
+            labels(&[("path", "/a\"b\\c\nd"), ("method", "GET")]),
+            r#"{path="/a\"b\\c\nd",method="GET"}"#
+        );
+    }
+}
@@ -2,20 +2,40 @@
 
 mod compat;
 mod condition;
+mod cors;
 mod default_headers;
 mod err_handlers;
 mod logger;
+mod metrics;
 mod normalize;
+mod rate_limit;
+mod real_ip;
+mod request_id;
+mod sweep;
 
 pub use self::compat::Compat;
 pub use self::condition::Condition;
+pub use self::cors::{Cors, CorsError};
 pub use self::default_headers::DefaultHeaders;
 pub use self::err_handlers::{ErrorHandlerResponse, ErrorHandlers};
-pub use self::logger::Logger;
+pub use self::logger::{JsonSink, LogRecord, LogSink, Logger};
+pub use self::metrics::Metrics;
 pub use self::normalize::{NormalizePath, TrailingSlash};
+pub use self::rate_limit::{MemoryStore, RateLimitStatus, RateLimitStore, RateLimiter};
+pub use self::real_ip::RealIp;
+pub use self::request_id::{RequestId, RequestIdentifier, TraceParent};
 
 #[cfg(feature = "__compress")]
 mod compress;
 
 #[cfg(feature = "__compress")]
 pub use self::compress::Compress;
+
+#[cfg(feature = "secure-cookies")]
+mod session;
+
+#[cfg(feature = "secure-cookies")]
+pub use self::session::{
+    CookieContentSecurity, CookieSessionStore, MemorySessionStore, Session, SessionMiddleware,
+    SessionState, SessionStatus, SessionStore,
+};
@@ -0,0 +1,522 @@
+//! For middleware documentation, see [`RateLimiter`].
+
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    fmt,
+    net::SocketAddr,
+    rc::Rc,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use actix_http::body::{AnyBody, MessageBody};
+use actix_service::{Service, Transform};
+use actix_utils::future::{ready, Ready};
+use futures_core::future::LocalBoxFuture;
+
+use crate::{
+    dev::{ServiceRequest, ServiceResponse},
+    http::{
+        header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER},
+        Error as HttpError,
+    },
+    Error, HttpResponse,
+};
+
+use super::sweep::Sweeper;
+
+/// Outcome of a rate limit check for a single request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitStatus {
+    /// Whether the request may proceed.
+    pub allowed: bool,
+
+    /// Maximum number of requests allowed in a burst.
+    pub limit: u32,
+
+    /// Number of requests that may still be made right now.
+    pub remaining: u32,
+
+    /// Time until the quota is fully replenished.
+    pub reset: Duration,
+
+    /// Time until the next request will be allowed; only set when the request was rejected.
+    pub retry_after: Option<Duration>,
+}
+
+/// Storage backend used by [`RateLimiter`] to track request quotas.
+///
+/// Implementations record a request for the given key and report whether it may proceed. Stores
+/// backed by external services (e.g. Redis) can perform I/O in the returned future.
+pub trait RateLimitStore {
+    /// Records a request for `key` and returns the resulting quota status.
+    fn check(&self, key: &str) -> LocalBoxFuture<'static, Result<RateLimitStatus, Error>>;
+}
+
+/// In-memory [`RateLimitStore`] using the generic cell rate algorithm (GCRA).
+///
+/// Each key may make up to `burst` requests at once; quota is replenished continuously so that
+/// `burst` requests are available again after `period`. This is equivalent to a token bucket of
+/// size `burst` refilled at a rate of `burst / period`.
+///
+/// All clones of a store count requests against the same buckets. Construct it before
+/// `HttpServer::new` and move a clone into the app factory to limit clients across all workers;
+/// a store created inside the factory gives each worker its own limit.
+#[derive(Clone)]
+pub struct MemoryStore {
+    burst: u32,
+    period: Duration,
+    interval: Duration,
+    state: Arc<Mutex<MemoryState>>,
+}
+
+#[derive(Default)]
+struct MemoryState {
+    /// Theoretical arrival time of the next request per key.
+    tats: HashMap<String, Instant>,
+
+    /// Drops keys whose bucket is full again.
+    sweeper: Sweeper,
+}
+
+impl MemoryStore {
+    /// Constructs a store allowing `burst` requests per `period` for each key.
+    ///
+    /// # Panics
+    /// Panics if `burst` is zero or `period` is zero.
+    pub fn new(burst: u32, period: Duration) -> Self {
+        assert!(burst > 0, "Burst size must be greater than zero");
+        assert!(period > Duration::from_secs(0), "Period must be non-zero");
+
+        MemoryStore {
+            burst,
+            period,
+            interval: period / burst,
+            state: Arc::new(Mutex::new(MemoryState::default())),
+        }
+    }
+
+    fn check_at(&self, key: &str, now: Instant) -> RateLimitStatus {
+        let mut state = self.state.lock().unwrap();
+
+        let tat = state.tats.get(key).copied().unwrap_or(now).max(now);
+        let new_tat = tat + self.interval;
+
+        // the request is allowed if the new TAT is at most one full period ahead of now
+        if new_tat > now + self.period {
+            let retry_after = new_tat - self.period - now;
+
+            return RateLimitStatus {
+                allowed: false,
+                limit: self.burst,
+                remaining: 0,
+                reset: tat - now,
+                retry_after: Some(retry_after),
+            };
+        }
+
+        let ahead = new_tat - now;
+        let remaining = duration_div(self.period - ahead, self.interval);
+
+        state.tats.insert(key.to_owned(), new_tat);
+
+        let MemoryState { tats, sweeper } = &mut *state;
+        sweeper.sweep(tats, |_, tat| *tat > now);
+
+        RateLimitStatus {
+            allowed: true,
+            limit: self.burst,
+            remaining,
+            reset: ahead,
+            retry_after: None,
+        }
+    }
+}
+
+fn duration_div(a: Duration, b: Duration) -> u32 {
+    (a.as_nanos() / b.as_nanos().max(1)) as u32
+}
+
+impl RateLimitStore for MemoryStore {
+    fn check(&self, key: &str) -> LocalBoxFuture<'static, Result<RateLimitStatus, Error>> {
+        let status = self.check_at(key, Instant::now());
+        Box::pin(ready(Ok(status)))
+    }
+}
+
+impl fmt::Debug for MemoryStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryStore")
+            .field("burst", &self.burst)
+            .field("period", &self.period)
+            .finish()
+    }
+}
+
+type KeyFn = dyn Fn(&ServiceRequest) -> Option<String>;
+
+/// How the client key of a request is determined.
+enum KeyExtractor {
+    RealIp,
+    Header(HeaderName),
+    Fn(Box<KeyFn>),
+}
+
+impl KeyExtractor {
+    fn extract(&self, req: &ServiceRequest) -> Option<String> {
+        match self {
+            KeyExtractor::RealIp => {
+                let info = req.connection_info();
+                let addr = info.realip_remote_addr()?;
+
+                // peer addresses include the port, which should not affect the key
+                Some(match addr.parse::<SocketAddr>() {
+                    Ok(addr) => addr.ip().to_string(),
+                    Err(_) => addr.to_owned(),
+                })
+            }
+            KeyExtractor::Header(name) => req
+                .headers()
+                .get(name)
+                .and_then(|val| val.to_str().ok())
+                .map(ToOwned::to_owned),
+            KeyExtractor::Fn(f) => f(req),
+        }
+    }
+}
+
+struct Inner {
+    store: Box<dyn RateLimitStore>,
+    key: KeyExtractor,
+}
+
+/// Middleware for throttling requests per client.
+///
+/// Each request is assigned a key, by default the client IP as reported by
+/// [`ConnectionInfo::realip_remote_addr`](crate::dev::ConnectionInfo::realip_remote_addr), and
+/// checked against a [`RateLimitStore`]. Requests over the limit are rejected with
+/// `429 Too Many Requests` and a `Retry-After` header. All responses from limited requests carry
+/// `RateLimit-Limit`, `RateLimit-Remaining` and `RateLimit-Reset` headers.
+///
+/// Requests for which no key can be determined are not limited.
+///
+/// The middleware can be registered on an `App`, `Scope` or `Resource`; each registration
+/// enforces its own limit unless they share a store.
+///
+/// # Examples
+/// ```
+/// use std::time::Duration;
+/// use actix_web::{middleware::{MemoryStore, RateLimiter}, web, App, HttpResponse};
+///
+/// // 10 requests per minute per client, shared by all workers
+/// let store = MemoryStore::new(10, Duration::from_secs(60));
+///
+/// let app = App::new().service(
+///     web::scope("/api")
+///         .wrap(RateLimiter::new(store.clone()).key_header("x-api-key"))
+///         .route("/items", web::get().to(HttpResponse::Ok)),
+/// );
+/// ```
+pub struct RateLimiter {
+    inner: Rc<Inner>,
+}
+
+impl RateLimiter {
+    /// Constructs a `RateLimiter` backed by `store`, keyed by client IP.
+    pub fn new<T: RateLimitStore + 'static>(store: T) -> Self {
+        RateLimiter {
+            inner: Rc::new(Inner {
+                store: Box::new(store),
+                key: KeyExtractor::RealIp,
+            }),
+        }
+    }
+
+    fn inner_mut(&mut self) -> &mut Inner {
+        Rc::get_mut(&mut self.inner).expect("Multiple copies exist")
+    }
+
+    /// Uses the value of a request header as the key.
+    ///
+    /// # Panics
+    /// Panics if `name` is not a valid header name.
+    pub fn key_header<K>(mut self, name: K) -> Self
+    where
+        HeaderName: TryFrom<K>,
+        <HeaderName as TryFrom<K>>::Error: Into<HttpError>,
+    {
+        let name = HeaderName::try_from(name)
+            .map_err(Into::into)
+            .expect("Can not create header name");
+        self.inner_mut().key = KeyExtractor::Header(name);
+        self
+    }
+
+    /// Uses a closure to derive the key from the request.
+    ///
+    /// Returning `None` exempts the request from rate limiting.
+    pub fn key_fn<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&ServiceRequest) -> Option<String> + 'static,
+    {
+        self.inner_mut().key = KeyExtractor::Fn(Box::new(f));
+        self
+    }
+}
+
+impl fmt::Debug for RateLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateLimiter").finish()
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimiter
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+    B::Error: Into<Box<dyn std::error::Error + 'static>>,
+{
+    type Response = ServiceResponse;
+    type Error = Error;
+    type Transform = RateLimiterMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimiterMiddleware {
+            service: Rc::new(service),
+            inner: self.inner.clone(),
+        }))
+    }
+}
+
+pub struct RateLimiterMiddleware<S> {
+    service: Rc<S>,
+    inner: Rc<Inner>,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimiterMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+    B::Error: Into<Box<dyn std::error::Error + 'static>>,
+{
+    type Response = ServiceResponse;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    actix_service::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+
+        let key = match self.inner.key.extract(&req) {
+            Some(key) => key,
+            None => {
+                return Box::pin(async move {
+                    let res = service.call(req).await?;
+                    Ok(res.map_body(|_, body| AnyBody::from_message(body)))
+                })
+            }
+        };
+
+        let check = self.inner.store.check(&key);
+
+        Box::pin(async move {
+            let status = check.await?;
+
+            if !status.allowed {
+                let mut res = HttpResponse::TooManyRequests().finish();
+                insert_headers(res.headers_mut(), &status);
+                return Ok(req.into_response(res));
+            }
+
+            let mut res = service.call(req).await?;
+            insert_headers(res.headers_mut(), &status);
+            Ok(res.map_body(|_, body| AnyBody::from_message(body)))
+        })
+    }
+}
+
+/// Rounds a duration up to whole seconds.
+fn ceil_secs(dur: Duration) -> u64 {
+    dur.as_secs() + u64::from(dur.subsec_nanos() > 0)
+}
+
+fn insert_headers(headers: &mut HeaderMap, status: &RateLimitStatus) {
+    headers.insert(
+        HeaderName::from_static("ratelimit-limit"),
+        HeaderValue::from(status.limit),
+    );
+    headers.insert(
+        HeaderName::from_static("ratelimit-remaining"),
+        HeaderValue::from(status.remaining),
+    );
+    headers.insert(
+        HeaderName::from_static("ratelimit-reset"),
+        HeaderValue::from(ceil_secs(status.reset)),
+    );
+
+    if let Some(retry_after) = status.retry_after {
+        headers.insert(RETRY_AFTER, HeaderValue::from(ceil_secs(retry_after)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        http::StatusCode,
+        test::{self, ok_service, TestRequest},
+        web, App,
+    };
+
+    #[test]
+    fn test_memory_store_gcra() {
+        let store = MemoryStore::new(3, Duration::from_secs(3));
+        let now = Instant::now();
+
+        for remaining in (0..3).rev() {
+            let status = store.check_at("a", now);
+            assert!(status.allowed);
+            assert_eq!(status.remaining, remaining);
+        }
+
+        let status = store.check_at("a", now);
+        assert!(!status.allowed);
+        assert_eq!(status.retry_after, Some(Duration::from_secs(1)));
+        assert_eq!(status.reset, Duration::from_secs(3));
+
+        // other keys are unaffected
+        assert!(store.check_at("b", now).allowed);
+
+        // one request is replenished per second
+        let status = store.check_at("a", now + Duration::from_secs(1));
+        assert!(status.allowed);
+        assert_eq!(status.remaining, 0);
+
+        let status = store.check_at("a", now + Duration::from_secs(10));
+        assert!(status.allowed);
+        assert_eq!(status.remaining, 2);
+    }
+
+    #[actix_rt::test]
+    async fn test_rate_limiter_headers() {
+        let mw = RateLimiter::new(MemoryStore::new(2, Duration::from_secs(60)))
+            .new_transform(ok_service())
+            .await
+            .unwrap();
+
+        let req = || {
+            TestRequest::default()
+                .peer_addr("127.0.0.1:8080".parse().unwrap())
+                .to_srv_request()
+        };
+
+        let res = mw.call(req()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get("ratelimit-limit").unwrap(), "2");
+        assert_eq!(res.headers().get("ratelimit-remaining").unwrap(), "1");
+        assert_eq!(res.headers().get("ratelimit-reset").unwrap(), "30");
+
+        // different port, same client
+        let req2 = TestRequest::default()
+            .peer_addr("127.0.0.1:9090".parse().unwrap())
+            .to_srv_request();
+        let res = mw.call(req2).await.unwrap();
+        assert_eq!(res.headers().get("ratelimit-remaining").unwrap(), "0");
+
+        let res = mw.call(req()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res.headers().get(RETRY_AFTER).unwrap(), "30");
+        assert!(res.headers().contains_key("ratelimit-reset"));
+    }
+
+    #[actix_rt::test]
+    async fn test_rate_limiter_key_extractors() {
+        let mw = RateLimiter::new(MemoryStore::new(1, Duration::from_secs(60)))
+            .key_header("x-api-key")
+            .new_transform(ok_service())
+            .await
+            .unwrap();
+
+        let req = |key: &'static str| {
+            TestRequest::default()
+                .insert_header(("x-api-key", key))
+                .to_srv_request()
+        };
+
+        assert_eq!(mw.call(req("a")).await.unwrap().status(), StatusCode::OK);
+        assert_eq!(mw.call(req("b")).await.unwrap().status(), StatusCode::OK);
+        assert_eq!(
+            mw.call(req("a")).await.unwrap().status(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+
+        // requests without a key are not limited
+        for _ in 0..3 {
+            let res = mw
+                .call(TestRequest::default().to_srv_request())
+                .await
+                .unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+            assert!(!res.headers().contains_key("ratelimit-limit"));
+        }
+
+        let mw = RateLimiter::new(MemoryStore::new(1, Duration::from_secs(60)))
+            .key_fn(|req| Some(req.path().to_owned()))
+            .new_transform(ok_service())
+            .await
+            .unwrap();
+
+        let req = |path: &'static str| TestRequest::with_uri(path).to_srv_request();
+        assert_eq!(mw.call(req("/a")).await.unwrap().status(), StatusCode::OK);
+        assert_eq!(mw.call(req("/b")).await.unwrap().status(), StatusCode::OK);
+        assert_eq!(
+            mw.call(req("/a")).await.unwrap().status(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_rate_limiter_scope_and_resource() {
+        let srv = test::init_service(
+            App::new()
+                .service(
+                    web::scope("/scope")
+                        .wrap(RateLimiter::new(MemoryStore::new(
+                            1,
+                            Duration::from_secs(60),
+                        )))
+                        .route("/", web::get().to(HttpResponse::Ok)),
+                )
+                .service(
+                    web::resource("/res")
+                        .wrap(RateLimiter::new(MemoryStore::new(
+                            2,
+                            Duration::from_secs(60),
+                        )))
+                        .route(web::get().to(HttpResponse::Ok)),
+                ),
+        )
+        .await;
+
+        let req = |path: &'static str| {
+            TestRequest::with_uri(path)
+                .peer_addr("10.0.0.1:1234".parse().unwrap())
+                .to_request()
+        };
+
+        let res = test::call_service(&srv, req("/scope/")).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = test::call_service(&srv, req("/scope/")).await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        let res = test::call_service(&srv, req("/res")).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get("ratelimit-remaining").unwrap(), "1");
+    }
+}
@@ -0,0 +1,127 @@
+//! For middleware documentation, see [`RealIp`].
+
+use actix_service::{Service, Transform};
+use actix_utils::future::{ok, Ready};
+
+use crate::{dev::ConnectionInfo, service::ServiceRequest};
+
+/// Middleware for replacing the peer address with the client address reported by proxies.
+///
+/// The client address is resolved by [`ConnectionInfo`] from the `Forwarded` or
+/// `X-Forwarded-For` headers, honouring the app's [`TrustedProxies`](crate::dev::TrustedProxies).
+/// Downstream middleware, extractors and handlers then see the client address in
+/// [`HttpRequest::peer_addr`](crate::HttpRequest::peer_addr) and
+/// [`ConnectionInfo::remote_addr`]. Addresses reported without a port are given port 0.
+///
+/// The peer address is left unchanged if no trusted proxy reported an IP address.
+///
+/// # Security
+/// With the default configuration all peers are trusted, allowing any client to choose its
+/// address. Configure [`TrustedProxies`](crate::dev::TrustedProxies) when using this middleware.
+///
+/// # Examples
+/// ```
+/// use actix_web::{dev::TrustedProxies, middleware::RealIp, App};
+///
+/// let app = App::new()
+///     .trusted_proxies(TrustedProxies::new().trust("10.0.0.0/8"))
+///     .wrap(RealIp);
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RealIp;
+
+impl<S> Transform<S, ServiceRequest> for RealIp
+where
+    S: Service<ServiceRequest>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Transform = RealIpMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(RealIpMiddleware { service })
+    }
+}
+
+pub struct RealIpMiddleware<S> {
+    service: S,
+}
+
+impl<S> Service<ServiceRequest> for RealIpMiddleware<S>
+where
+    S: Service<ServiceRequest>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    actix_service::forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        // resolved against the original peer, then cached for the rest of the request
+        let client = req.connection_info().forwarded_peer_addr();
+
+        if let Some(addr) = client {
+            req.head_mut().peer_addr = Some(addr);
+
+            if let Some(info) = req.head().extensions_mut().get_mut::<ConnectionInfo>() {
+                info.set_remote_addr(addr);
+            }
+        }
+
+        self.service.call(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dev::TrustedProxies,
+        test::{call_service, init_service, read_body, TestRequest},
+        web, App, HttpRequest, HttpResponse,
+    };
+
+    async fn peer(req: HttpRequest) -> HttpResponse {
+        HttpResponse::Ok().body(format!(
+            "{} {}",
+            req.peer_addr().unwrap(),
+            req.connection_info().remote_addr().unwrap()
+        ))
+    }
+
+    #[actix_rt::test]
+    async fn test_rewrite_peer_addr() {
+        let srv = init_service(
+            App::new()
+                .trusted_proxies(TrustedProxies::new().trust("10.0.0.0/8"))
+                .wrap(RealIp)
+                .route("/", web::get().to(peer)),
+        )
+        .await;
+
+        let req = TestRequest::default()
+            .peer_addr("10.0.0.1:8080".parse().unwrap())
+            .insert_header(("x-forwarded-for", "192.0.2.60, 10.0.0.2"))
+            .to_request();
+        let body = read_body(call_service(&srv, req).await).await;
+        assert_eq!(body, "192.0.2.60:0 192.0.2.60:0");
+
+        let req = TestRequest::default()
+            .peer_addr("10.0.0.1:8080".parse().unwrap())
+            .insert_header(("forwarded", r#"for="[2001:db8::17]:4711""#))
+            .to_request();
+        let body = read_body(call_service(&srv, req).await).await;
+        assert_eq!(body, "[2001:db8::17]:4711 [2001:db8::17]:4711");
+
+        // untrusted peer
+        let req = TestRequest::default()
+            .peer_addr("192.0.2.1:8080".parse().unwrap())
+            .insert_header(("x-forwarded-for", "192.0.2.60"))
+            .to_request();
+        let body = read_body(call_service(&srv, req).await).await;
+        assert_eq!(body, "192.0.2.1:8080 192.0.2.1:8080");
+    }
+}
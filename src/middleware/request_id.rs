@@ -0,0 +1,613 @@
+//! For middleware documentation, see [`RequestIdentifier`].
+
+use std::{
+    cell::RefCell,
+    convert::TryFrom,
+    fmt,
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+};
+
+use actix_service::{Service, Transform};
+use actix_utils::future::{ready, Ready};
+use futures_core::ready;
+use pin_project::pin_project;
+use rand::{rngs::OsRng, RngCore as _};
+
+use crate::{
+    dev::{Payload, ServiceRequest, ServiceResponse},
+    error,
+    http::{
+        header::{HeaderName, HeaderValue},
+        Error as HttpError, HeaderMap,
+    },
+    Error, FromRequest, HttpMessage as _, HttpRequest,
+};
+
+const TRACEPARENT: HeaderName = HeaderName::from_static("traceparent");
+const TRACESTATE: HeaderName = HeaderName::from_static("tracestate");
+
+thread_local! {
+    static CURRENT: RefCell<Option<RequestId>> = RefCell::new(None);
+}
+
+/// A W3C Trace Context [`traceparent`](https://www.w3.org/TR/trace-context/#traceparent-header).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceParent {
+    trace_id: String,
+    parent_id: String,
+    flags: u8,
+}
+
+impl TraceParent {
+    /// Parses a `traceparent` header value.
+    ///
+    /// Returns `None` if the value is malformed or uses invalid (all zero) IDs.
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.trim().splitn(5, '-');
+
+        let version = parts.next().filter(|v| is_hex(v, 2) && *v != "ff")?;
+        let trace_id = parts.next().filter(|id| is_hex(id, 32))?;
+        let parent_id = parts.next().filter(|id| is_hex(id, 16))?;
+        let flags = parts.next().filter(|f| is_hex(f, 2))?;
+
+        // version 00 has no further fields; future versions may append more
+        if version == "00" && parts.next().is_some() {
+            return None;
+        }
+
+        if is_zero(trace_id) || is_zero(parent_id) {
+            return None;
+        }
+
+        Some(TraceParent {
+            trace_id: trace_id.to_owned(),
+            parent_id: parent_id.to_owned(),
+            flags: u8::from_str_radix(flags, 16).ok()?,
+        })
+    }
+
+    /// Starts a new trace with a random trace ID.
+    fn new_root() -> Self {
+        TraceParent {
+            trace_id: random_hex(16),
+            parent_id: random_hex(8),
+            flags: 0x01,
+        }
+    }
+
+    /// Continues this trace with a new span ID.
+    fn child(&self) -> Self {
+        TraceParent {
+            trace_id: self.trace_id.clone(),
+            parent_id: random_hex(8),
+            flags: self.flags,
+        }
+    }
+
+    /// Returns the 32 character hex trace ID shared by all spans of a trace.
+    pub fn trace_id(&self) -> &str {
+        &self.trace_id
+    }
+
+    /// Returns the 16 character hex ID of the span handling this request.
+    ///
+    /// This is the ID sent as `parent-id` to downstream services.
+    pub fn parent_id(&self) -> &str {
+        &self.parent_id
+    }
+
+    /// Returns the trace flags.
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    /// Returns true if the sampled flag is set.
+    pub fn sampled(&self) -> bool {
+        self.flags & 0x01 == 0x01
+    }
+}
+
+impl fmt::Display for TraceParent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "00-{}-{}-{:02x}",
+            self.trace_id, self.parent_id, self.flags
+        )
+    }
+}
+
+fn is_hex(s: &str, len: usize) -> bool {
+    s.len() == len && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+fn is_zero(s: &str) -> bool {
+    s.bytes().all(|b| b == b'0')
+}
+
+fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0; len];
+
+    loop {
+        OsRng.fill_bytes(&mut bytes);
+        if bytes.iter().any(|b| *b != 0) {
+            break;
+        }
+    }
+
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+struct RequestIdInner {
+    id: String,
+    header: HeaderName,
+    trace_parent: Option<TraceParent>,
+    trace_state: Option<HeaderValue>,
+}
+
+/// Correlation ID of a request, assigned by [`RequestIdentifier`].
+///
+/// `RequestId` is an extractor. It fails with `500 Internal Server Error` if the
+/// `RequestIdentifier` middleware is not registered.
+///
+/// # Examples
+/// ```
+/// use actix_web::middleware::RequestId;
+///
+/// async fn index(id: RequestId) -> String {
+///     format!("Request ID: {}", id)
+/// }
+/// ```
+#[derive(Clone)]
+pub struct RequestId(Rc<RequestIdInner>);
+
+impl RequestId {
+    /// Returns the request ID as a string.
+    pub fn as_str(&self) -> &str {
+        &self.0.id
+    }
+
+    /// Returns the trace context of this request, if trace context support is enabled.
+    pub fn trace_parent(&self) -> Option<&TraceParent> {
+        self.0.trace_parent.as_ref()
+    }
+
+    /// Returns the ID of the request currently being handled on this thread.
+    ///
+    /// Only set while the handler (or an inner middleware) of a request wrapped by
+    /// [`RequestIdentifier`] is being polled. Tasks spawned from a handler do not inherit it.
+    pub fn current() -> Option<RequestId> {
+        CURRENT.with(|current| current.borrow().clone())
+    }
+
+    /// Inserts the request ID and trace context headers into `headers` for propagation to
+    /// downstream services.
+    ///
+    /// Headers already present are left unchanged.
+    pub fn propagate(&self, headers: &mut HeaderMap) {
+        let inner = &self.0;
+
+        if !headers.contains_key(&inner.header) {
+            if let Ok(val) = HeaderValue::try_from(inner.id.as_str()) {
+                headers.insert(inner.header.clone(), val);
+            }
+        }
+
+        if let Some(ref trace_parent) = inner.trace_parent {
+            if !headers.contains_key(TRACEPARENT) {
+                if let Ok(val) = HeaderValue::try_from(trace_parent.to_string()) {
+                    headers.insert(TRACEPARENT, val);
+                }
+
+                if let Some(ref trace_state) = inner.trace_state {
+                    headers.insert(TRACESTATE, trace_state.clone());
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0.id)
+    }
+}
+
+impl fmt::Debug for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestId")
+            .field("id", &self.0.id)
+            .field("trace_parent", &self.0.trace_parent)
+            .finish()
+    }
+}
+
+impl FromRequest for RequestId {
+    type Error = Error;
+    type Future = Ready<Result<RequestId, Error>>;
+    type Config = ();
+
+    #[inline]
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        match req.extensions().get::<RequestId>() {
+            Some(id) => ready(Ok(id.clone())),
+            None => {
+                log::debug!(
+                    "Failed to extract `RequestId`; `RequestIdentifier` is not registered"
+                );
+                ready(Err(error::ErrorInternalServerError(
+                    "RequestIdentifier middleware is not registered",
+                )))
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    header: HeaderName,
+    use_incoming: bool,
+    trace_context: bool,
+}
+
+/// Middleware for assigning a correlation ID to every request.
+///
+/// The ID is taken from the `X-Request-Id` request header when present and valid, or generated
+/// otherwise. It is stored in request extensions, available through the [`RequestId`] extractor,
+/// echoed on the response and can be printed by [`Logger`](super::Logger) with `%R`.
+///
+/// [W3C Trace Context] is supported as well: an incoming `traceparent` header is continued with a
+/// new span ID, otherwise a new trace is started. When no request ID was received, the trace ID
+/// is used as the request ID.
+///
+/// To propagate the IDs on outbound requests, call [`RequestId::propagate`] on their headers, for
+/// example from an `awc::middleware::Propagate` hook using [`RequestId::current`].
+///
+/// # Examples
+/// ```
+/// use actix_web::{middleware::{Logger, RequestIdentifier}, App};
+///
+/// let app = App::new()
+///     .wrap(Logger::new("%R %a \"%r\" %s"))
+///     .wrap(RequestIdentifier::default());
+/// ```
+///
+/// [W3C Trace Context]: https://www.w3.org/TR/trace-context/
+#[derive(Debug)]
+pub struct RequestIdentifier {
+    inner: Rc<Inner>,
+}
+
+impl Default for RequestIdentifier {
+    fn default() -> Self {
+        RequestIdentifier {
+            inner: Rc::new(Inner {
+                header: HeaderName::from_static("x-request-id"),
+                use_incoming: true,
+                trace_context: true,
+            }),
+        }
+    }
+}
+
+impl RequestIdentifier {
+    /// Constructs a `RequestIdentifier` middleware using the `X-Request-Id` header.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn inner_mut(&mut self) -> &mut Inner {
+        Rc::get_mut(&mut self.inner).expect("Multiple copies exist")
+    }
+
+    /// Sets the header used to receive, echo and propagate the request ID.
+    ///
+    /// # Panics
+    /// Panics if `name` is not a valid header name.
+    pub fn header<K>(mut self, name: K) -> Self
+    where
+        HeaderName: TryFrom<K>,
+        <HeaderName as TryFrom<K>>::Error: Into<HttpError>,
+    {
+        let name = HeaderName::try_from(name)
+            .map_err(Into::into)
+            .expect("Can not create header name");
+        self.inner_mut().header = name;
+        self
+    }
+
+    /// Sets whether IDs sent by clients are used. Enabled by default.
+    ///
+    /// Disable when clients are untrusted and IDs must be unique. This also applies to
+    /// incoming `traceparent` headers.
+    pub fn use_incoming(mut self, use_incoming: bool) -> Self {
+        self.inner_mut().use_incoming = use_incoming;
+        self
+    }
+
+    /// Sets whether W3C trace context headers are handled. Enabled by default.
+    pub fn trace_context(mut self, enabled: bool) -> Self {
+        self.inner_mut().trace_context = enabled;
+        self
+    }
+}
+
+impl Inner {
+    fn request_id(&self, req: &ServiceRequest) -> RequestId {
+        let headers = req.headers();
+
+        let incoming_id = if self.use_incoming {
+            headers
+                .get(&self.header)
+                .and_then(|val| val.to_str().ok())
+                .filter(|id| is_valid_id(id))
+        } else {
+            None
+        };
+
+        let (trace_parent, trace_state) = if !self.trace_context {
+            (None, None)
+        } else {
+            let parent = if self.use_incoming {
+                headers
+                    .get(TRACEPARENT)
+                    .and_then(|val| val.to_str().ok())
+                    .and_then(TraceParent::parse)
+            } else {
+                None
+            };
+
+            match parent {
+                Some(parent) => (Some(parent.child()), headers.get(TRACESTATE).cloned()),
+                None => (Some(TraceParent::new_root()), None),
+            }
+        };
+
+        let id = match (incoming_id, &trace_parent) {
+            (Some(id), _) => id.to_owned(),
+            (None, Some(trace_parent)) => trace_parent.trace_id.clone(),
+            (None, None) => random_hex(16),
+        };
+
+        RequestId(Rc::new(RequestIdInner {
+            id,
+            header: self.header.clone(),
+            trace_parent,
+            trace_state,
+        }))
+    }
+}
+
+/// Accepts non-empty IDs of up to 128 visible ASCII characters.
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= 128 && id.bytes().all(|b| (0x21..=0x7e).contains(&b))
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequestIdentifier
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestIdentifierMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestIdentifierMiddleware {
+            service,
+            inner: self.inner.clone(),
+        }))
+    }
+}
+
+pub struct RequestIdentifierMiddleware<S> {
+    service: S,
+    inner: Rc<Inner>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestIdentifierMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = RequestIdentifierFuture<S::Future>;
+
+    actix_service::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let id = self.inner.request_id(&req);
+        req.extensions_mut().insert(id.clone());
+
+        let fut = with_current(&id, || self.service.call(req));
+
+        RequestIdentifierFuture { fut, id }
+    }
+}
+
+/// Runs `f` with `id` set as the current request ID.
+fn with_current<R>(id: &RequestId, f: impl FnOnce() -> R) -> R {
+    let prev = CURRENT.with(|current| current.replace(Some(id.clone())));
+    let res = f();
+    CURRENT.with(|current| *current.borrow_mut() = prev);
+    res
+}
+
+#[pin_project]
+pub struct RequestIdentifierFuture<F> {
+    #[pin]
+    fut: F,
+    id: RequestId,
+}
+
+impl<F, B> Future for RequestIdentifierFuture<F>
+where
+    F: Future<Output = Result<ServiceResponse<B>, Error>>,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let id = this.id;
+        let fut = this.fut;
+
+        let mut res = ready!(with_current(id, || fut.poll(cx)))?;
+
+        let header = &id.0.header;
+        if !res.headers().contains_key(header) {
+            if let Ok(val) = HeaderValue::try_from(id.as_str()) {
+                res.headers_mut().insert(header.clone(), val);
+            }
+        }
+
+        Poll::Ready(Ok(res))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test::{self, ok_service, TestRequest},
+        web, App, HttpResponse,
+    };
+
+    #[test]
+    fn test_trace_parent_parse() {
+        let tp = TraceParent::parse("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01")
+            .unwrap();
+        assert_eq!(tp.trace_id(), "0af7651916cd43dd8448eb211c80319c");
+        assert_eq!(tp.parent_id(), "b7ad6b7169203331");
+        assert!(tp.sampled());
+        assert_eq!(
+            tp.to_string(),
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01"
+        );
+
+        // future versions may carry extra fields
+        assert!(TraceParent::parse(
+            "01-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-00-extra"
+        )
+        .is_some());
+
+        for invalid in &[
+            "",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01-extra",
+            "ff-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+            "00-00000000000000000000000000000000-b7ad6b7169203331-01",
+            "00-0af7651916cd43dd8448eb211c80319c-0000000000000000-01",
+            "00-0AF7651916CD43DD8448EB211C80319C-b7ad6b7169203331-01",
+        ] {
+            assert!(TraceParent::parse(invalid).is_none(), "{}", invalid);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_generated_id() {
+        let mw = RequestIdentifier::default()
+            .new_transform(ok_service())
+            .await
+            .unwrap();
+
+        let req = TestRequest::default().to_srv_request();
+        let res = mw.call(req).await.unwrap();
+
+        let id = res
+            .request()
+            .extensions()
+            .get::<RequestId>()
+            .cloned()
+            .unwrap();
+        let trace_parent = id.trace_parent().unwrap();
+        assert_eq!(id.as_str(), trace_parent.trace_id());
+        assert_eq!(res.headers().get("x-request-id").unwrap(), id.as_str());
+    }
+
+    #[actix_rt::test]
+    async fn test_incoming_ids() {
+        let mw = RequestIdentifier::default()
+            .new_transform(ok_service())
+            .await
+            .unwrap();
+
+        let req = TestRequest::default()
+            .insert_header(("x-request-id", "abc-123"))
+            .insert_header((
+                "traceparent",
+                "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+            ))
+            .insert_header(("tracestate", "vendor=value"))
+            .to_srv_request();
+        let res = mw.call(req).await.unwrap();
+        assert_eq!(res.headers().get("x-request-id").unwrap(), "abc-123");
+
+        let id = res
+            .request()
+            .extensions()
+            .get::<RequestId>()
+            .cloned()
+            .unwrap();
+        let trace_parent = id.trace_parent().unwrap();
+        assert_eq!(trace_parent.trace_id(), "0af7651916cd43dd8448eb211c80319c");
+        assert_ne!(trace_parent.parent_id(), "b7ad6b7169203331");
+
+        let mut headers = HeaderMap::new();
+        id.propagate(&mut headers);
+        assert_eq!(headers.get("x-request-id").unwrap(), "abc-123");
+        assert_eq!(
+            headers.get(TRACEPARENT).unwrap().to_str().unwrap(),
+            trace_parent.to_string()
+        );
+        assert_eq!(headers.get(TRACESTATE).unwrap(), "vendor=value");
+
+        // untrusted and invalid IDs are replaced
+        let mw = RequestIdentifier::new()
+            .header("x-correlation-id")
+            .use_incoming(false)
+            .trace_context(false)
+            .new_transform(ok_service())
+            .await
+            .unwrap();
+
+        let req = TestRequest::default()
+            .insert_header(("x-correlation-id", "abc-123"))
+            .to_srv_request();
+        let res = mw.call(req).await.unwrap();
+        let id = res.headers().get("x-correlation-id").unwrap();
+        assert_ne!(id, "abc-123");
+        assert_eq!(id.len(), 32);
+        assert!(!res.headers().contains_key("x-request-id"));
+    }
+
+    #[actix_rt::test]
+    async fn test_extractor_and_current() {
+        let srv = test::init_service(App::new().wrap(RequestIdentifier::default()).route(
+            "/",
+            web::get().to(|id: RequestId| {
+                let current = RequestId::current().unwrap();
+                assert_eq!(current.as_str(), id.as_str());
+                HttpResponse::Ok().body(id.to_string())
+            }),
+        ))
+        .await;
+
+        let req = TestRequest::default()
+            .insert_header(("x-request-id", "abc-123"))
+            .to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(test::read_body(res).await, "abc-123");
+        assert!(RequestId::current().is_none());
+
+        let srv = test::init_service(
+            App::new().route("/", web::get().to(|_: RequestId| HttpResponse::Ok())),
+        )
+        .await;
+        let res = test::call_service(&srv, TestRequest::default().to_request()).await;
+        assert!(res.status().is_server_error());
+    }
+}
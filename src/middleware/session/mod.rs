@@ -0,0 +1,671 @@
+//! For middleware documentation, see [`SessionMiddleware`].
+
+use std::{
+    cell::{Ref, RefCell},
+    convert::TryFrom,
+    fmt,
+    rc::Rc,
+    time::Duration,
+};
+
+use actix_service::{Service, Transform};
+use actix_utils::future::{ready, Ready};
+use cookie::{Cookie, CookieJar, Key, SameSite};
+use futures_core::future::LocalBoxFuture;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    dev::{Extensions, Payload, ServiceRequest, ServiceResponse},
+    Error, FromRequest, HttpMessage, HttpRequest,
+};
+
+mod store;
+
+pub use self::store::{CookieSessionStore, MemorySessionStore, SessionState, SessionStore};
+
+/// Current status of a [`Session`] within a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionStatus {
+    /// Session state has not been modified.
+    Unchanged,
+
+    /// Session state has been modified and will be persisted.
+    Changed,
+
+    /// Session will be deleted and its cookie removed.
+    Purged,
+
+    /// Session will be persisted under a new key and the old key invalidated.
+    Renewed,
+}
+
+impl Default for SessionStatus {
+    fn default() -> Self {
+        SessionStatus::Unchanged
+    }
+}
+
+#[derive(Default)]
+struct SessionInner {
+    state: SessionState,
+    status: SessionStatus,
+}
+
+/// The primary interface to read and modify session state.
+///
+/// `Session` is an extractor; it is backed by the state loaded by [`SessionMiddleware`]. Changes
+/// are persisted by the middleware once the handler's response is ready. Without the middleware,
+/// an empty session is returned and changes are discarded.
+///
+/// Values are serialized to JSON.
+///
+/// # Examples
+/// ```
+/// use actix_web::{middleware::Session, Error};
+///
+/// async fn index(session: Session) -> Result<String, Error> {
+///     let counter = session.get::<i32>("counter")?.unwrap_or(0) + 1;
+///     session.insert("counter", counter)?;
+///
+///     Ok(format!("Counter: {}", counter))
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Session(Rc<RefCell<SessionInner>>);
+
+impl Session {
+    /// Gets a value from the session.
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, Error> {
+        match self.0.borrow().state.get(key) {
+            Some(val) => Ok(Some(serde_json::from_str(val)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns all raw key-value pairs of the session state.
+    pub fn entries(&self) -> Ref<'_, SessionState> {
+        Ref::map(self.0.borrow(), |inner| &inner.state)
+    }
+
+    /// Returns the session status.
+    pub fn status(&self) -> SessionStatus {
+        self.0.borrow().status
+    }
+
+    /// Inserts a key-value pair into the session.
+    ///
+    /// Any serializable value can be used and will be encoded as JSON in the session state.
+    pub fn insert<T: Serialize>(&self, key: impl Into<String>, value: T) -> Result<(), Error> {
+        let mut inner = self.0.borrow_mut();
+
+        if inner.status != SessionStatus::Purged {
+            let val = serde_json::to_string(&value)?;
+            inner.state.insert(key.into(), val);
+            inner.mark_changed();
+        }
+
+        Ok(())
+    }
+
+    /// Removes a value from the session, returning its raw JSON representation.
+    pub fn remove(&self, key: &str) -> Option<String> {
+        let mut inner = self.0.borrow_mut();
+
+        if inner.status != SessionStatus::Purged {
+            inner.mark_changed();
+            return inner.state.remove(key);
+        }
+
+        None
+    }
+
+    /// Clears the session state.
+    pub fn clear(&self) {
+        let mut inner = self.0.borrow_mut();
+
+        if inner.status != SessionStatus::Purged {
+            inner.mark_changed();
+            inner.state.clear();
+        }
+    }
+
+    /// Removes the session from both the client and the store.
+    pub fn purge(&self) {
+        let mut inner = self.0.borrow_mut();
+        inner.status = SessionStatus::Purged;
+        inner.state.clear();
+    }
+
+    /// Keeps the session state but moves it to a new session key.
+    ///
+    /// Call this whenever the privilege level of a session changes, most notably on login, to
+    /// protect against session fixation attacks.
+    pub fn renew(&self) {
+        let mut inner = self.0.borrow_mut();
+
+        if inner.status != SessionStatus::Purged {
+            inner.status = SessionStatus::Renewed;
+        }
+    }
+
+    fn set_session(extensions: &mut Extensions, state: SessionState) {
+        let session = Session::get_session(extensions);
+        session.0.borrow_mut().state = state;
+    }
+
+    fn get_session(extensions: &mut Extensions) -> Session {
+        if let Some(inner) = extensions.get::<Rc<RefCell<SessionInner>>>() {
+            return Session(Rc::clone(inner));
+        }
+
+        let inner = Rc::new(RefCell::new(SessionInner::default()));
+        extensions.insert(inner.clone());
+        Session(inner)
+    }
+
+    fn take_changes<B>(res: &ServiceResponse<B>) -> (SessionStatus, SessionState) {
+        match res
+            .request()
+            .extensions()
+            .get::<Rc<RefCell<SessionInner>>>()
+        {
+            Some(inner) => {
+                let mut inner = inner.borrow_mut();
+                let state = std::mem::take(&mut inner.state);
+                (inner.status, state)
+            }
+            None => (SessionStatus::Unchanged, SessionState::new()),
+        }
+    }
+}
+
+impl SessionInner {
+    fn mark_changed(&mut self) {
+        if self.status == SessionStatus::Unchanged {
+            self.status = SessionStatus::Changed;
+        }
+    }
+}
+
+impl fmt::Debug for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Session")
+            .field("status", &self.status())
+            .finish()
+    }
+}
+
+impl FromRequest for Session {
+    type Error = Error;
+    type Future = Ready<Result<Session, Error>>;
+    type Config = ();
+
+    #[inline]
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Ok(Session::get_session(&mut req.extensions_mut())))
+    }
+}
+
+/// How the session cookie is protected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CookieContentSecurity {
+    /// Cookie value is encrypted and authenticated; clients can neither read nor modify it.
+    Private,
+
+    /// Cookie value is authenticated; clients can read but not modify it.
+    Signed,
+}
+
+struct Inner<St> {
+    store: St,
+    key: Key,
+    cookie_name: String,
+    cookie_path: String,
+    cookie_domain: Option<String>,
+    cookie_secure: bool,
+    cookie_http_only: bool,
+    cookie_same_site: SameSite,
+    content_security: CookieContentSecurity,
+    ttl: Duration,
+    extend_ttl_on_every_request: bool,
+}
+
+/// Middleware providing [`Session`] support.
+///
+/// The session key is kept in a signed or private (encrypted) cookie, while the session state is
+/// kept by a [`SessionStore`]: server-side with [`MemorySessionStore`] or a custom store, or in
+/// the cookie itself with [`CookieSessionStore`].
+///
+/// Sessions expire after their TTL (1 day by default). The TTL is extended whenever the session
+/// state changes, or on every request if
+/// [`extend_ttl_on_every_request`](Self::extend_ttl_on_every_request) is enabled.
+///
+/// This middleware requires the `secure-cookies` feature.
+///
+/// # Examples
+/// ```
+/// use actix_web::{
+///     cookie::Key,
+///     middleware::{MemorySessionStore, Session, SessionMiddleware},
+///     web, App, Error, HttpResponse,
+/// };
+///
+/// async fn login(session: Session) -> Result<HttpResponse, Error> {
+///     // new session key after login prevents session fixation
+///     session.renew();
+///     session.insert("user_id", 42)?;
+///     Ok(HttpResponse::Ok().finish())
+/// }
+///
+/// // the key should be loaded from configuration so that sessions survive restarts
+/// let key = Key::generate();
+/// let store = MemorySessionStore::new();
+///
+/// let app = App::new()
+///     .wrap(SessionMiddleware::new(store.clone(), key.clone()).cookie_name("session"))
+///     .route("/login", web::post().to(login));
+/// ```
+pub struct SessionMiddleware<St> {
+    inner: Rc<Inner<St>>,
+}
+
+impl<St: SessionStore> SessionMiddleware<St> {
+    /// Constructs a `SessionMiddleware` using `store` and `key` to sign or encrypt cookies.
+    ///
+    /// Defaults to a private, secure, HTTP-only cookie named `id` with `SameSite=Lax`.
+    pub fn new(store: St, key: Key) -> Self {
+        SessionMiddleware {
+            inner: Rc::new(Inner {
+                store,
+                key,
+                cookie_name: "id".to_owned(),
+                cookie_path: "/".to_owned(),
+                cookie_domain: None,
+                cookie_secure: true,
+                cookie_http_only: true,
+                cookie_same_site: SameSite::Lax,
+                content_security: CookieContentSecurity::Private,
+                ttl: Duration::from_secs(24 * 60 * 60),
+                extend_ttl_on_every_request: false,
+            }),
+        }
+    }
+
+    fn inner_mut(&mut self) -> &mut Inner<St> {
+        Rc::get_mut(&mut self.inner).expect("Multiple copies exist")
+    }
+
+    /// Sets the name of the session cookie.
+    pub fn cookie_name(mut self, name: impl Into<String>) -> Self {
+        self.inner_mut().cookie_name = name.into();
+        self
+    }
+
+    /// Sets the `Path` attribute of the session cookie. Defaults to `/`.
+    pub fn cookie_path(mut self, path: impl Into<String>) -> Self {
+        self.inner_mut().cookie_path = path.into();
+        self
+    }
+
+    /// Sets the `Domain` attribute of the session cookie.
+    pub fn cookie_domain(mut self, domain: impl Into<String>) -> Self {
+        self.inner_mut().cookie_domain = Some(domain.into());
+        self
+    }
+
+    /// Sets whether the session cookie is only sent over HTTPS. Enabled by default.
+    pub fn cookie_secure(mut self, secure: bool) -> Self {
+        self.inner_mut().cookie_secure = secure;
+        self
+    }
+
+    /// Sets whether the session cookie is hidden from client scripts. Enabled by default.
+    pub fn cookie_http_only(mut self, http_only: bool) -> Self {
+        self.inner_mut().cookie_http_only = http_only;
+        self
+    }
+
+    /// Sets the `SameSite` attribute of the session cookie. Defaults to `Lax`.
+    pub fn cookie_same_site(mut self, same_site: SameSite) -> Self {
+        self.inner_mut().cookie_same_site = same_site;
+        self
+    }
+
+    /// Sets how the session cookie is protected. Defaults to
+    /// [`Private`](CookieContentSecurity::Private).
+    pub fn cookie_content_security(mut self, security: CookieContentSecurity) -> Self {
+        self.inner_mut().content_security = security;
+        self
+    }
+
+    /// Sets the session time-to-live, used for both the store and the cookie's `Max-Age`.
+    ///
+    /// TTLs too large to represent as an expiry time are treated as never expiring.
+    pub fn session_ttl(mut self, ttl: Duration) -> Self {
+        self.inner_mut().ttl = ttl;
+        self
+    }
+
+    /// Sets whether the session TTL is extended on every request instead of only when the
+    /// session state changes. Disabled by default.
+    pub fn extend_ttl_on_every_request(mut self, extend: bool) -> Self {
+        self.inner_mut().extend_ttl_on_every_request = extend;
+        self
+    }
+}
+
+impl<St> Inner<St> {
+    /// Reads and verifies the session key from the request cookie.
+    fn session_key(&self, req: &ServiceRequest) -> Option<String> {
+        let cookie = req.cookie(&self.cookie_name)?;
+
+        let mut jar = CookieJar::new();
+        jar.add_original(cookie);
+
+        let verified = match self.content_security {
+            CookieContentSecurity::Private => jar.private(&self.key).get(&self.cookie_name),
+            CookieContentSecurity::Signed => jar.signed(&self.key).get(&self.cookie_name),
+        };
+
+        verified.map(|cookie| cookie.value().to_owned())
+    }
+
+    fn base_cookie(&self, value: String) -> Cookie<'static> {
+        let mut cookie = Cookie::new(self.cookie_name.clone(), value);
+        cookie.set_path(self.cookie_path.clone());
+        cookie.set_secure(self.cookie_secure);
+        cookie.set_http_only(self.cookie_http_only);
+        cookie.set_same_site(self.cookie_same_site);
+
+        if let Some(ref domain) = self.cookie_domain {
+            cookie.set_domain(domain.clone());
+        }
+
+        cookie
+    }
+
+    fn set_cookie<B>(
+        &self,
+        res: &mut ServiceResponse<B>,
+        session_key: String,
+    ) -> Result<(), Error> {
+        let mut cookie = self.base_cookie(session_key);
+        let max_age = i64::try_from(self.ttl.as_secs()).unwrap_or(i64::MAX);
+        cookie.set_max_age(time::Duration::seconds(max_age));
+
+        let mut jar = CookieJar::new();
+        match self.content_security {
+            CookieContentSecurity::Private => jar.private_mut(&self.key).add(cookie),
+            CookieContentSecurity::Signed => jar.signed_mut(&self.key).add(cookie),
+        }
+
+        let cookie = jar.delta().next().unwrap();
+        res.response_mut().add_cookie(cookie)?;
+        Ok(())
+    }
+
+    fn remove_cookie<B>(&self, res: &mut ServiceResponse<B>) -> Result<(), Error> {
+        let mut cookie = self.base_cookie(String::new());
+        cookie.make_removal();
+        res.response_mut().add_cookie(&cookie)?;
+        Ok(())
+    }
+}
+
+impl<S, B, St> Transform<S, ServiceRequest> for SessionMiddleware<St>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+    St: SessionStore + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = InnerSessionMiddleware<S, St>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(InnerSessionMiddleware {
+            service: Rc::new(service),
+            inner: self.inner.clone(),
+        }))
+    }
+}
+
+#[doc(hidden)]
+pub struct InnerSessionMiddleware<S, St> {
+    service: Rc<S>,
+    inner: Rc<Inner<St>>,
+}
+
+impl<S, B, St> Service<ServiceRequest> for InnerSessionMiddleware<S, St>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+    St: SessionStore + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    actix_service::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let inner = Rc::clone(&self.inner);
+
+        Box::pin(async move {
+            let mut session_key = inner.session_key(&req);
+
+            let state = match session_key {
+                Some(ref key) => inner.store.load(key).await?,
+                None => None,
+            };
+
+            // unknown or expired keys are discarded so a new one is issued on change
+            let state = match state {
+                Some(state) => state,
+                None => {
+                    session_key = None;
+                    SessionState::new()
+                }
+            };
+
+            Session::set_session(&mut req.extensions_mut(), state);
+
+            let mut res = service.call(req).await?;
+            let (status, state) = Session::take_changes(&res);
+            let ttl = inner.ttl;
+
+            match (status, session_key) {
+                (SessionStatus::Changed, Some(key)) => {
+                    let key = inner.store.update(&key, state, ttl).await?;
+                    inner.set_cookie(&mut res, key)?;
+                }
+
+                (SessionStatus::Changed, None) | (SessionStatus::Renewed, None) => {
+                    let key = inner.store.save(state, ttl).await?;
+                    inner.set_cookie(&mut res, key)?;
+                }
+
+                (SessionStatus::Renewed, Some(key)) => {
+                    inner.store.delete(&key).await?;
+                    let key = inner.store.save(state, ttl).await?;
+                    inner.set_cookie(&mut res, key)?;
+                }
+
+                (SessionStatus::Purged, Some(key)) => {
+                    inner.store.delete(&key).await?;
+                    inner.remove_cookie(&mut res)?;
+                }
+
+                (SessionStatus::Unchanged, Some(key)) if inner.extend_ttl_on_every_request => {
+                    inner.store.update_ttl(&key, ttl).await?;
+                    inner.set_cookie(&mut res, key)?;
+                }
+
+                _ => {}
+            }
+
+            Ok(res)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        http::{header, StatusCode},
+        test::{self, TestRequest},
+        web, App, HttpResponse,
+    };
+
+    async fn counter(session: Session) -> Result<HttpResponse, Error> {
+        let count = session.get::<u32>("count")?.unwrap_or(0) + 1;
+        session.insert("count", count)?;
+        Ok(HttpResponse::Ok().body(count.to_string()))
+    }
+
+    async fn read(session: Session) -> Result<HttpResponse, Error> {
+        let count = session.get::<u32>("count")?.unwrap_or(0);
+        Ok(HttpResponse::Ok().body(count.to_string()))
+    }
+
+    async fn login(session: Session) -> HttpResponse {
+        session.renew();
+        HttpResponse::Ok().finish()
+    }
+
+    async fn logout(session: Session) -> HttpResponse {
+        session.purge();
+        HttpResponse::Ok().finish()
+    }
+
+    fn session_cookie<B>(res: &ServiceResponse<B>) -> Option<Cookie<'static>> {
+        res.response()
+            .cookies()
+            .find(|c| c.name() == "id")
+            .map(|c| c.into_owned())
+    }
+
+    macro_rules! app {
+        ($mw:expr) => {
+            test::init_service(
+                App::new()
+                    .wrap($mw)
+                    .route("/count", web::get().to(counter))
+                    .route("/read", web::get().to(read))
+                    .route("/login", web::get().to(login))
+                    .route("/logout", web::get().to(logout)),
+            )
+            .await
+        };
+    }
+
+    fn get(path: &str, cookie: &Cookie<'static>) -> crate::test::TestRequest {
+        TestRequest::with_uri(path).cookie(cookie.clone())
+    }
+
+    #[actix_rt::test]
+    async fn test_session_roundtrip() {
+        let srv = app!(SessionMiddleware::new(
+            MemorySessionStore::new(),
+            Key::generate()
+        ));
+
+        let res = test::call_service(&srv, TestRequest::with_uri("/count").to_request()).await;
+        let cookie = session_cookie(&res).unwrap();
+        assert!(cookie.secure().unwrap());
+        assert!(cookie.http_only().unwrap());
+        assert_eq!(cookie.max_age(), Some(time::Duration::days(1)));
+
+        let res = test::call_service(&srv, get("/count", &cookie).to_request()).await;
+        assert!(session_cookie(&res).is_some());
+        assert_eq!(test::read_body(res).await, "2");
+
+        // reads do not set the cookie again
+        let res = test::call_service(&srv, get("/read", &cookie).to_request()).await;
+        assert!(session_cookie(&res).is_none());
+        assert_eq!(test::read_body(res).await, "2");
+
+        // tampered cookies are ignored
+        let forged = Cookie::new("id", "forged");
+        let res = test::call_service(&srv, get("/read", &forged).to_request()).await;
+        assert_eq!(test::read_body(res).await, "0");
+    }
+
+    #[actix_rt::test]
+    async fn test_session_renew_and_purge() {
+        let store = MemorySessionStore::new();
+        let srv = app!(SessionMiddleware::new(store.clone(), Key::generate()));
+
+        let res = test::call_service(&srv, TestRequest::with_uri("/count").to_request()).await;
+        let cookie = session_cookie(&res).unwrap();
+
+        let res = test::call_service(&srv, get("/login", &cookie).to_request()).await;
+        let renewed = session_cookie(&res).unwrap();
+
+        // the old key is no longer valid, the state moved to the new key
+        let res = test::call_service(&srv, get("/read", &cookie).to_request()).await;
+        assert_eq!(test::read_body(res).await, "0");
+        let res = test::call_service(&srv, get("/read", &renewed).to_request()).await;
+        assert_eq!(test::read_body(res).await, "1");
+
+        let res = test::call_service(&srv, get("/logout", &renewed).to_request()).await;
+        let removal = session_cookie(&res).unwrap();
+        assert_eq!(removal.value(), "");
+        assert_eq!(removal.max_age(), Some(time::Duration::zero()));
+
+        let res = test::call_service(&srv, get("/read", &renewed).to_request()).await;
+        assert_eq!(test::read_body(res).await, "0");
+    }
+
+    #[actix_rt::test]
+    async fn test_cookie_store_signed() {
+        let srv = app!(SessionMiddleware::new(CookieSessionStore, Key::generate())
+            .cookie_content_security(CookieContentSecurity::Signed)
+            .cookie_secure(false)
+            .session_ttl(Duration::from_secs(60)));
+
+        let res = test::call_service(&srv, TestRequest::with_uri("/count").to_request()).await;
+        let cookie = session_cookie(&res).unwrap();
+        assert!(cookie.value().contains("count"));
+        assert_eq!(cookie.max_age(), Some(time::Duration::seconds(60)));
+
+        let res = test::call_service(&srv, get("/count", &cookie).to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(test::read_body(res).await, "2");
+    }
+
+    #[actix_rt::test]
+    async fn test_extend_ttl_on_every_request() {
+        let srv = app!(
+            SessionMiddleware::new(MemorySessionStore::new(), Key::generate())
+                .extend_ttl_on_every_request(true)
+        );
+
+        let res = test::call_service(&srv, TestRequest::with_uri("/count").to_request()).await;
+        let cookie = session_cookie(&res).unwrap();
+
+        let res = test::call_service(&srv, get("/read", &cookie).to_request()).await;
+        let extended = session_cookie(&res).unwrap();
+        let res = test::call_service(&srv, get("/read", &extended).to_request()).await;
+        assert_eq!(test::read_body(res).await, "1");
+
+        // no session, nothing to extend
+        let res = test::call_service(&srv, TestRequest::with_uri("/read").to_request()).await;
+        assert!(!res.headers().contains_key(header::SET_COOKIE));
+    }
+
+    #[actix_rt::test]
+    async fn test_session_without_middleware() {
+        let srv = test::init_service(App::new().route("/count", web::get().to(counter))).await;
+
+        let res = test::call_service(&srv, TestRequest::with_uri("/count").to_request()).await;
+        assert!(!res.headers().contains_key(header::SET_COOKIE));
+        assert_eq!(test::read_body(res).await, "1");
+    }
+}
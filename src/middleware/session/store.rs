@@ -0,0 +1,338 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use actix_utils::future::ready;
+use futures_core::future::LocalBoxFuture;
+use rand::{distributions::Alphanumeric, rngs::OsRng, Rng as _};
+
+use crate::{error, middleware::sweep::Sweeper, Error};
+
+/// Key-value data attached to a session.
+///
+/// Values are stored as JSON-serialized strings.
+pub type SessionState = HashMap<String, String>;
+
+/// Storage backend for session state used by [`SessionMiddleware`](super::SessionMiddleware).
+///
+/// A store maps an opaque session key, which is sent to the client in the session cookie, to
+/// the session state. Stores decide how keys are generated: server-side stores use random
+/// identifiers while [`CookieSessionStore`] uses the serialized state itself.
+pub trait SessionStore {
+    /// Loads the state for `session_key`.
+    ///
+    /// Returns `None` if the key is unknown or the session has expired.
+    fn load(
+        &self,
+        session_key: &str,
+    ) -> LocalBoxFuture<'static, Result<Option<SessionState>, Error>>;
+
+    /// Persists a new session and returns its key.
+    fn save(
+        &self,
+        state: SessionState,
+        ttl: Duration,
+    ) -> LocalBoxFuture<'static, Result<String, Error>>;
+
+    /// Replaces the state of an existing session and returns its (possibly new) key.
+    fn update(
+        &self,
+        session_key: &str,
+        state: SessionState,
+        ttl: Duration,
+    ) -> LocalBoxFuture<'static, Result<String, Error>>;
+
+    /// Extends the expiry of an existing session without changing its state.
+    fn update_ttl(
+        &self,
+        session_key: &str,
+        ttl: Duration,
+    ) -> LocalBoxFuture<'static, Result<(), Error>>;
+
+    /// Deletes the session.
+    fn delete(&self, session_key: &str) -> LocalBoxFuture<'static, Result<(), Error>>;
+}
+
+/// Session store that keeps the whole session state in the session cookie.
+///
+/// No server-side storage is needed, but the state is limited to roughly 4KB and the session
+/// cannot be invalidated server-side before the cookie expires: a client replaying an old cookie
+/// restores the old state. Combine with private (encrypted) cookies to keep the state
+/// confidential.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CookieSessionStore;
+
+/// Upper bound for the serialized state, leaving room for cookie attributes and encryption.
+const COOKIE_STATE_LIMIT: usize = 4064;
+
+impl CookieSessionStore {
+    fn serialize(state: &SessionState) -> Result<String, Error> {
+        let state = serde_json::to_string(state)?;
+
+        if state.len() > COOKIE_STATE_LIMIT {
+            return Err(error::ErrorInternalServerError(
+                "Session state is too large to be stored in a cookie",
+            ));
+        }
+
+        Ok(state)
+    }
+}
+
+impl SessionStore for CookieSessionStore {
+    fn load(
+        &self,
+        session_key: &str,
+    ) -> LocalBoxFuture<'static, Result<Option<SessionState>, Error>> {
+        Box::pin(ready(Ok(serde_json::from_str(session_key).ok())))
+    }
+
+    fn save(
+        &self,
+        state: SessionState,
+        _ttl: Duration,
+    ) -> LocalBoxFuture<'static, Result<String, Error>> {
+        Box::pin(ready(Self::serialize(&state)))
+    }
+
+    fn update(
+        &self,
+        _session_key: &str,
+        state: SessionState,
+        _ttl: Duration,
+    ) -> LocalBoxFuture<'static, Result<String, Error>> {
+        Box::pin(ready(Self::serialize(&state)))
+    }
+
+    fn update_ttl(
+        &self,
+        _session_key: &str,
+        _ttl: Duration,
+    ) -> LocalBoxFuture<'static, Result<(), Error>> {
+        Box::pin(ready(Ok(())))
+    }
+
+    fn delete(&self, _session_key: &str) -> LocalBoxFuture<'static, Result<(), Error>> {
+        Box::pin(ready(Ok(())))
+    }
+}
+
+/// In-memory server-side session store.
+///
+/// Session keys are 64 random alphanumeric characters. Sessions live in the process and are lost
+/// when it exits. Cloned handles see the same sessions, so a store constructed once before
+/// `HttpServer::new` lets any worker load a session saved by another.
+#[derive(Clone, Default)]
+pub struct MemorySessionStore {
+    inner: Arc<Mutex<MemoryInner>>,
+}
+
+#[derive(Default)]
+struct MemoryInner {
+    sessions: HashMap<String, (SessionState, Instant)>,
+
+    /// Drops expired sessions.
+    sweeper: Sweeper,
+}
+
+impl MemorySessionStore {
+    /// Constructs an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn generate_key() -> String {
+        OsRng
+            .sample_iter(&Alphanumeric)
+            .take(64)
+            .map(char::from)
+            .collect()
+    }
+
+    fn insert(&self, session_key: String, state: SessionState, ttl: Duration) -> String {
+        let now = Instant::now();
+        let mut inner = self.inner.lock().unwrap();
+
+        inner
+            .sessions
+            .insert(session_key.clone(), (state, expires_at(now, ttl)));
+
+        let MemoryInner { sessions, sweeper } = &mut *inner;
+        sweeper.sweep(sessions, |_, (_, expires)| *expires > now);
+
+        session_key
+    }
+}
+
+/// Expiry of a session saved at `now`. TTLs too large to add to `now` never expire in practice.
+fn expires_at(now: Instant, ttl: Duration) -> Instant {
+    const FAR_FUTURE: Duration = Duration::from_secs(100 * 365 * 24 * 60 * 60);
+
+    now.checked_add(ttl).unwrap_or_else(|| now + FAR_FUTURE)
+}
+
+impl SessionStore for MemorySessionStore {
+    fn load(
+        &self,
+        session_key: &str,
+    ) -> LocalBoxFuture<'static, Result<Option<SessionState>, Error>> {
+        let mut inner = self.inner.lock().unwrap();
+
+        let state = match inner.sessions.get(session_key) {
+            Some((state, expires)) if *expires > Instant::now() => Some(state.clone()),
+            Some(_) => {
+                inner.sessions.remove(session_key);
+                None
+            }
+            None => None,
+        };
+
+        Box::pin(ready(Ok(state)))
+    }
+
+    fn save(
+        &self,
+        state: SessionState,
+        ttl: Duration,
+    ) -> LocalBoxFuture<'static, Result<String, Error>> {
+        let key = self.insert(Self::generate_key(), state, ttl);
+        Box::pin(ready(Ok(key)))
+    }
+
+    fn update(
+        &self,
+        session_key: &str,
+        state: SessionState,
+        ttl: Duration,
+    ) -> LocalBoxFuture<'static, Result<String, Error>> {
+        let exists = {
+            let mut inner = self.inner.lock().unwrap();
+
+            match inner.sessions.get(session_key) {
+                Some((_, expires)) if *expires > Instant::now() => true,
+                Some(_) => {
+                    inner.sessions.remove(session_key);
+                    false
+                }
+                None => false,
+            }
+        };
+
+        // a session that expired mid-request gets a fresh key
+        let key = if exists {
+            session_key.to_owned()
+        } else {
+            Self::generate_key()
+        };
+
+        Box::pin(ready(Ok(self.insert(key, state, ttl))))
+    }
+
+    fn update_ttl(
+        &self,
+        session_key: &str,
+        ttl: Duration,
+    ) -> LocalBoxFuture<'static, Result<(), Error>> {
+        if let Some((_, expires)) = self.inner.lock().unwrap().sessions.get_mut(session_key) {
+            *expires = expires_at(Instant::now(), ttl);
+        }
+
+        Box::pin(ready(Ok(())))
+    }
+
+    fn delete(&self, session_key: &str) -> LocalBoxFuture<'static, Result<(), Error>> {
+        self.inner.lock().unwrap().sessions.remove(session_key);
+        Box::pin(ready(Ok(())))
+    }
+}
+
+impl fmt::Debug for MemorySessionStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemorySessionStore").finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(key: &str, value: &str) -> SessionState {
+        let mut state = SessionState::new();
+        state.insert(key.to_owned(), value.to_owned());
+        state
+    }
+
+    #[actix_rt::test]
+    async fn test_memory_store() {
+        let store = MemorySessionStore::new();
+        let ttl = Duration::from_secs(60);
+
+        let key = store.save(state("a", "1"), ttl).await.unwrap();
+        assert_eq!(key.len(), 64);
+        assert_eq!(store.load(&key).await.unwrap(), Some(state("a", "1")));
+
+        let key2 = store.update(&key, state("a", "2"), ttl).await.unwrap();
+        assert_eq!(key, key2);
+        assert_eq!(store.load(&key).await.unwrap(), Some(state("a", "2")));
+
+        store.delete(&key).await.unwrap();
+        assert_eq!(store.load(&key).await.unwrap(), None);
+
+        let key = store
+            .save(state("a", "1"), Duration::from_secs(0))
+            .await
+            .unwrap();
+        assert_eq!(store.load(&key).await.unwrap(), None);
+    }
+
+    #[actix_rt::test]
+    async fn test_memory_store_update_expired() {
+        let store = MemorySessionStore::new();
+
+        let key = store
+            .save(state("a", "1"), Duration::from_secs(0))
+            .await
+            .unwrap();
+
+        let key2 = store
+            .update(&key, state("a", "2"), Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_ne!(key, key2);
+        assert_eq!(store.load(&key).await.unwrap(), None);
+        assert_eq!(store.load(&key2).await.unwrap(), Some(state("a", "2")));
+    }
+
+    #[actix_rt::test]
+    async fn test_memory_store_max_ttl() {
+        let store = MemorySessionStore::new();
+
+        let key = store
+            .save(state("a", "1"), Duration::from_secs(u64::MAX))
+            .await
+            .unwrap();
+        assert_eq!(store.load(&key).await.unwrap(), Some(state("a", "1")));
+
+        store
+            .update_ttl(&key, Duration::from_secs(u64::MAX))
+            .await
+            .unwrap();
+        assert_eq!(store.load(&key).await.unwrap(), Some(state("a", "1")));
+    }
+
+    #[actix_rt::test]
+    async fn test_cookie_store() {
+        let store = CookieSessionStore;
+        let ttl = Duration::from_secs(60);
+
+        let key = store.save(state("a", "1"), ttl).await.unwrap();
+        assert_eq!(store.load(&key).await.unwrap(), Some(state("a", "1")));
+        assert_eq!(store.load("not json").await.unwrap(), None);
+
+        let large = state("a", &"x".repeat(COOKIE_STATE_LIMIT));
+        assert!(store.save(large, ttl).await.is_err());
+    }
+}
@@ -0,0 +1,56 @@
+//! Bulk removal of expired entries from the maps of in-memory stores.
+
+use std::{collections::HashMap, hash::Hash};
+
+/// Sweeps a map once it has doubled in size since the previous sweep.
+///
+/// Keeps the cost of dropping expired entries amortized over insertions, without a background
+/// task or a sweep on every request.
+#[derive(Debug, Default)]
+pub(crate) struct Sweeper {
+    /// Map size after the last sweep.
+    swept_len: usize,
+}
+
+impl Sweeper {
+    /// Smallest map size at which sweeps start.
+    const MIN_LEN: usize = 1024;
+
+    /// Retains the entries for which `keep` returns true, if the map has grown enough.
+    pub(crate) fn sweep<K, V, F>(&mut self, map: &mut HashMap<K, V>, keep: F)
+    where
+        K: Eq + Hash,
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        if map.len() > (self.swept_len * 2).max(Self::MIN_LEN) {
+            map.retain(keep);
+            self.swept_len = map.len();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sweep_after_doubling() {
+        let mut sweeper = Sweeper::default();
+        let mut map = (0..Sweeper::MIN_LEN)
+            .map(|i| (i, i))
+            .collect::<HashMap<_, _>>();
+
+        sweeper.sweep(&mut map, |_, _| false);
+        assert_eq!(map.len(), Sweeper::MIN_LEN);
+
+        map.insert(Sweeper::MIN_LEN, 0);
+        sweeper.sweep(&mut map, |k, _| k % 2 == 0);
+        assert_eq!(sweeper.swept_len, Sweeper::MIN_LEN / 2 + 1);
+        assert_eq!(map.len(), sweeper.swept_len);
+
+        // not doubled yet
+        map.insert(usize::MAX, 0);
+        sweeper.sweep(&mut map, |_, _| false);
+        assert_eq!(map.len(), Sweeper::MIN_LEN / 2 + 2);
+    }
+}
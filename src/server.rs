@@ -8,7 +8,10 @@ use std::{
     sync::{Arc, Mutex},
 };
 
-use actix_http::{body::MessageBody, Extensions, HttpService, KeepAlive, Request, Response};
+use actix_http::{
+    body::MessageBody, ConnectionStats, Extensions, H2Settings, HeadLimits, HttpService,
+    KeepAlive, Request, Response, ShutdownSignal,
+};
 use actix_server::{Server, ServerBuilder};
 use actix_service::{
     map_config, IntoServiceFactory, Service, ServiceFactory, ServiceFactoryExt as _,
@@ -19,7 +22,7 @@ use actix_tls::accept::openssl::{AlpnError, SslAcceptor, SslAcceptorBuilder};
 #[cfg(feature = "rustls")]
 use actix_tls::accept::rustls::ServerConfig as RustlsServerConfig;
 
-use crate::{config::AppConfig, Error};
+use crate::{config::AppConfig, info::TrustedProxies, Error};
 
 struct Socket {
     scheme: &'static str,
@@ -31,6 +34,13 @@ struct Config {
     keep_alive: KeepAlive,
     client_timeout: u64,
     client_shutdown: u64,
+    connection_stats: Option<ConnectionStats>,
+    sendfile: bool,
+    head_limits: HeadLimits,
+    h2_settings: H2Settings,
+    shutdown_signal: Option<ShutdownSignal>,
+    h2c_upgrade: bool,
+    trusted_proxies: TrustedProxies,
 }
 
 /// An HTTP Server.
@@ -94,6 +104,13 @@ where
                 keep_alive: KeepAlive::Timeout(5),
                 client_timeout: 5000,
                 client_shutdown: 5000,
+                connection_stats: None,
+                sendfile: false,
+                head_limits: HeadLimits::default(),
+                h2_settings: H2Settings::default(),
+                shutdown_signal: None,
+                h2c_upgrade: false,
+                trusted_proxies: TrustedProxies::default(),
             })),
             backlog: 1024,
             sockets: Vec::new(),
@@ -221,6 +238,176 @@ where
         self
     }
 
+    /// Set counters to be updated as connections are opened, closed and reused.
+    ///
+    /// The counters are shared by all workers and listeners of this server.
+    pub fn connection_stats(self, stats: ConnectionStats) -> Self {
+        self.config.lock().unwrap().connection_stats = Some(stats);
+        self
+    }
+
+    /// Enable sending file bodies with `sendfile(2)` on plaintext HTTP/1.1 connections.
+    ///
+    /// Files served by `actix-files` are then written to the socket by the kernel, without being
+    /// copied through user space. Only supported on Linux; ignored on other platforms and on TLS
+    /// and Unix socket listeners.
+    ///
+    /// By default sendfile is disabled.
+    pub fn sendfile(self, enabled: bool) -> Self {
+        self.config.lock().unwrap().sendfile = enabled;
+        self
+    }
+
+    /// Set the maximum number of headers accepted in a request.
+    ///
+    /// Requests with more headers are answered with 431 (Request Header Fields Too Large).
+    ///
+    /// By default up to 96 headers are accepted.
+    pub fn max_headers(self, num: usize) -> Self {
+        {
+            let mut c = self.config.lock().unwrap();
+            c.head_limits = c.head_limits.max_headers(num);
+        }
+        self
+    }
+
+    /// Set the maximum size in bytes of a request head, including the request line.
+    ///
+    /// Requests with a larger head are answered with 431 (Request Header Fields Too Large).
+    ///
+    /// By default the limit is 128KiB.
+    pub fn max_header_size(self, bytes: usize) -> Self {
+        {
+            let mut c = self.config.lock().unwrap();
+            c.head_limits = c.head_limits.max_header_size(bytes);
+        }
+        self
+    }
+
+    /// Set the maximum length in bytes of a request URI.
+    ///
+    /// Requests with a longer URI are answered with 414 (URI Too Long).
+    ///
+    /// By default the limit is 128KiB.
+    pub fn max_uri_length(self, bytes: usize) -> Self {
+        {
+            let mut c = self.config.lock().unwrap();
+            c.head_limits = c.head_limits.max_uri_length(bytes);
+        }
+        self
+    }
+
+    /// Set the maximum number of concurrent streams a client may open on an HTTP/2 connection.
+    ///
+    /// By default the number of streams is not limited.
+    pub fn h2_max_concurrent_streams(self, max: u32) -> Self {
+        {
+            let mut c = self.config.lock().unwrap();
+            c.h2_settings = c.h2_settings.max_concurrent_streams(max);
+        }
+        self
+    }
+
+    /// Set the initial window size (in octets) for HTTP/2 stream-level flow control for received
+    /// data.
+    ///
+    /// The default value is 65,535.
+    ///
+    /// # Panics
+    /// Panics if `size` is larger than 2,147,483,647.
+    pub fn h2_initial_window_size(self, size: u32) -> Self {
+        {
+            let mut c = self.config.lock().unwrap();
+            c.h2_settings = c.h2_settings.initial_window_size(size);
+        }
+        self
+    }
+
+    /// Set the initial window size (in octets) for HTTP/2 connection-level flow control for
+    /// received data.
+    ///
+    /// The default value is 65,535.
+    ///
+    /// # Panics
+    /// Panics if `size` is larger than 2,147,483,647.
+    pub fn h2_initial_connection_window_size(self, size: u32) -> Self {
+        {
+            let mut c = self.config.lock().unwrap();
+            c.h2_settings = c.h2_settings.initial_connection_window_size(size);
+        }
+        self
+    }
+
+    /// Set the largest HTTP/2 frame payload (in octets) the server is willing to receive.
+    ///
+    /// By default the max frame size is 16,384.
+    ///
+    /// # Panics
+    /// Panics if `size` is not between 16,384 and 16,777,215.
+    pub fn h2_max_frame_size(self, size: u32) -> Self {
+        {
+            let mut c = self.config.lock().unwrap();
+            c.h2_settings = c.h2_settings.max_frame_size(size);
+        }
+        self
+    }
+
+    /// Set the maximum size (in octets) of the header list of a received HTTP/2 request.
+    ///
+    /// By default the limit is 16MiB.
+    pub fn h2_max_header_list_size(self, size: u32) -> Self {
+        {
+            let mut c = self.config.lock().unwrap();
+            c.h2_settings = c.h2_settings.max_header_list_size(size);
+        }
+        self
+    }
+
+    /// Set the interval in milliseconds of HTTP/2 keep-alive pings.
+    ///
+    /// Connections which have not answered a ping by the time the next one is due are closed.
+    /// To disable pings set value to 0.
+    ///
+    /// By default keep-alive pings are disabled.
+    pub fn h2_keep_alive_interval(self, val: u64) -> Self {
+        {
+            let mut c = self.config.lock().unwrap();
+            c.h2_settings = c.h2_settings.keep_alive_interval(val);
+        }
+        self
+    }
+
+    /// Set a signal for shutting down HTTP/2 connections gracefully.
+    ///
+    /// Trigger it with [`ShutdownSignal::shutdown`] before stopping the server; connections then
+    /// send GOAWAY and close once their in-flight streams have completed.
+    pub fn shutdown_signal(self, signal: ShutdownSignal) -> Self {
+        self.config.lock().unwrap().shutdown_signal = Some(signal);
+        self
+    }
+
+    /// Enable switching plaintext HTTP/1.1 connections to HTTP/2 with `Upgrade: h2c`.
+    ///
+    /// Cleartext HTTP/2 with prior knowledge is always accepted on plaintext listeners; this also
+    /// lets HTTP/1.1 clients upgrade. Requests with a body are still served over HTTP/1.1. Ignored
+    /// on TLS listeners.
+    ///
+    /// By default h2c upgrades are disabled.
+    pub fn h2c_upgrade(self, enabled: bool) -> Self {
+        self.config.lock().unwrap().h2c_upgrade = enabled;
+        self
+    }
+
+    /// Set the proxies whose forwarding headers are honoured by
+    /// [ConnectionInfo](super::dev::ConnectionInfo).
+    ///
+    /// By default, forwarding headers from all peers are trusted. Apps can override this setting
+    /// with [`App::trusted_proxies`](crate::App::trusted_proxies).
+    pub fn trusted_proxies(self, proxies: TrustedProxies) -> Self {
+        self.config.lock().unwrap().trusted_proxies = proxies;
+        self
+    }
+
     /// Set server host name.
     ///
     /// Host name is used by application router as a hostname for url generation.
@@ -291,13 +478,26 @@ where
                 .listen(format!("actix-web-service-{}", addr), lst, move || {
                     let c = cfg.lock().unwrap();
                     let host = c.host.clone().unwrap_or_else(|| format!("{}", addr));
+                    let proxies = c.trusted_proxies.clone();
 
                     let mut svc = HttpService::build()
                         .keep_alive(c.keep_alive)
                         .client_timeout(c.client_timeout)
                         .client_disconnect(c.client_shutdown)
+                        .sendfile(c.sendfile)
+                        .head_limits(c.head_limits)
+                        .h2_settings(c.h2_settings)
+                        .h2c_upgrade(c.h2c_upgrade)
                         .local_addr(addr);
 
+                    if let Some(stats) = c.connection_stats.clone() {
+                        svc = svc.connection_stats(stats);
+                    }
+
+                    if let Some(signal) = c.shutdown_signal.clone() {
+                        svc = svc.shutdown_signal(signal);
+                    }
+
                     if let Some(handler) = on_connect_fn.clone() {
                         svc = svc.on_connect_ext(move |io: &_, ext: _| {
                             (handler)(io as &dyn Any, ext)
@@ -309,7 +509,7 @@ where
                         .map_err(|err| err.into().error_response());
 
                     svc.finish(map_config(fac, move |_| {
-                        AppConfig::new(false, host.clone(), addr)
+                        AppConfig::new(false, host.clone(), addr, proxies.clone())
                     }))
                     .tcp()
                 })?;
@@ -349,13 +549,25 @@ where
                 .listen(format!("actix-web-service-{}", addr), lst, move || {
                     let c = cfg.lock().unwrap();
                     let host = c.host.clone().unwrap_or_else(|| format!("{}", addr));
+                    let proxies = c.trusted_proxies.clone();
 
-                    let svc = HttpService::build()
+                    let mut svc = HttpService::build()
                         .keep_alive(c.keep_alive)
                         .client_timeout(c.client_timeout)
                         .client_disconnect(c.client_shutdown)
+                        .sendfile(c.sendfile)
+                        .head_limits(c.head_limits)
+                        .h2_settings(c.h2_settings)
                         .local_addr(addr);
 
+                    if let Some(stats) = c.connection_stats.clone() {
+                        svc = svc.connection_stats(stats);
+                    }
+
+                    if let Some(signal) = c.shutdown_signal.clone() {
+                        svc = svc.shutdown_signal(signal);
+                    }
+
                     let svc = if let Some(handler) = on_connect_fn.clone() {
                         svc.on_connect_ext(move |io: &_, ext: _| {
                             (&*handler)(io as &dyn Any, ext)
@@ -369,7 +581,7 @@ where
                         .map_err(|err| err.into().error_response());
 
                     svc.finish(map_config(fac, move |_| {
-                        AppConfig::new(true, host.clone(), addr)
+                        AppConfig::new(true, host.clone(), addr, proxies.clone())
                     }))
                     .openssl(acceptor.clone())
                 })?;
@@ -410,11 +622,23 @@ where
                 .listen(format!("actix-web-service-{}", addr), lst, move || {
                     let c = cfg.lock().unwrap();
                     let host = c.host.clone().unwrap_or_else(|| format!("{}", addr));
+                    let proxies = c.trusted_proxies.clone();
 
-                    let svc = HttpService::build()
+                    let mut svc = HttpService::build()
                         .keep_alive(c.keep_alive)
                         .client_timeout(c.client_timeout)
-                        .client_disconnect(c.client_shutdown);
+                        .client_disconnect(c.client_shutdown)
+                        .sendfile(c.sendfile)
+                        .head_limits(c.head_limits)
+                        .h2_settings(c.h2_settings);
+
+                    if let Some(stats) = c.connection_stats.clone() {
+                        svc = svc.connection_stats(stats);
+                    }
+
+                    if let Some(signal) = c.shutdown_signal.clone() {
+                        svc = svc.shutdown_signal(signal);
+                    }
 
                     let svc = if let Some(handler) = on_connect_fn.clone() {
                         svc.on_connect_ext(move |io: &_, ext: _| (handler)(io as &dyn Any, ext))
@@ -427,7 +651,7 @@ where
                         .map_err(|err| err.into().error_response());
 
                     svc.finish(map_config(fac, move |_| {
-                        AppConfig::new(true, host.clone(), addr)
+                        AppConfig::new(true, host.clone(), addr, proxies.clone())
                     }))
                     .rustls(config.clone())
                 })?;
@@ -535,13 +759,26 @@ where
                 false,
                 c.host.clone().unwrap_or_else(|| format!("{}", socket_addr)),
                 socket_addr,
+                c.trusted_proxies.clone(),
             );
 
             fn_service(|io: UnixStream| async { Ok((io, Protocol::Http1, None)) }).and_then({
                 let mut svc = HttpService::build()
                     .keep_alive(c.keep_alive)
                     .client_timeout(c.client_timeout)
-                    .client_disconnect(c.client_shutdown);
+                    .client_disconnect(c.client_shutdown)
+                    .sendfile(c.sendfile)
+                    .head_limits(c.head_limits)
+                    .h2_settings(c.h2_settings)
+                    .h2c_upgrade(c.h2c_upgrade);
+
+                if let Some(stats) = c.connection_stats.clone() {
+                    svc = svc.connection_stats(stats);
+                }
+
+                if let Some(signal) = c.shutdown_signal.clone() {
+                    svc = svc.shutdown_signal(signal);
+                }
 
                 if let Some(handler) = on_connect_fn.clone() {
                     svc = svc
@@ -587,19 +824,32 @@ where
                     false,
                     c.host.clone().unwrap_or_else(|| format!("{}", socket_addr)),
                     socket_addr,
+                    c.trusted_proxies.clone(),
                 );
 
                 let fac = factory()
                     .into_factory()
                     .map_err(|err| err.into().error_response());
 
-                fn_service(|io: UnixStream| async { Ok((io, Protocol::Http1, None)) }).and_then(
-                    HttpService::build()
-                        .keep_alive(c.keep_alive)
-                        .client_timeout(c.client_timeout)
-                        .client_disconnect(c.client_shutdown)
-                        .finish(map_config(fac, move |_| config.clone())),
-                )
+                let mut svc = HttpService::build()
+                    .keep_alive(c.keep_alive)
+                    .client_timeout(c.client_timeout)
+                    .client_disconnect(c.client_shutdown)
+                    .sendfile(c.sendfile)
+                    .head_limits(c.head_limits)
+                    .h2_settings(c.h2_settings)
+                    .h2c_upgrade(c.h2c_upgrade);
+
+                if let Some(stats) = c.connection_stats.clone() {
+                    svc = svc.connection_stats(stats);
+                }
+
+                if let Some(signal) = c.shutdown_signal.clone() {
+                    svc = svc.shutdown_signal(signal);
+                }
+
+                fn_service(|io: UnixStream| async { Ok((io, Protocol::Http1, None)) })
+                    .and_then(svc.finish(map_config(fac, move |_| config.clone())))
             },
         )?;
 
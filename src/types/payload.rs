@@ -9,15 +9,17 @@ use std::{
 };
 
 use actix_http::error::PayloadError;
-use actix_utils::future::{ready, Either, Ready};
+use actix_utils::future::{poll_fn, ready, Either, Ready};
 use bytes::{Bytes, BytesMut};
 use encoding_rs::{Encoding, UTF_8};
 use futures_core::{ready, stream::Stream};
 use mime::Mime;
 
 use crate::{
-    dev, error::ErrorBadRequest, http::header, web, Error, FromRequest, HttpMessage,
-    HttpRequest,
+    dev,
+    error::ErrorBadRequest,
+    http::{header, HeaderMap},
+    web, Error, FromRequest, HttpMessage, HttpRequest,
 };
 
 /// Extract a request's raw payload stream.
@@ -50,6 +52,45 @@ impl Payload {
     pub fn into_inner(self) -> crate::dev::Payload {
         self.0
     }
+
+    /// Attempt to pull out the trailer fields sent after the request body.
+    ///
+    /// Should only be called after the payload stream has yielded `None`.
+    pub fn poll_trailers(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, PayloadError>> {
+        self.0.poll_trailers(cx)
+    }
+
+    /// Wait for the trailer fields sent after the request body.
+    ///
+    /// Resolves to `None` if the client did not send any trailers. Should only be called after
+    /// the payload stream has yielded `None`.
+    ///
+    /// # Examples
+    /// ```
+    /// use futures_util::stream::StreamExt as _;
+    /// use actix_web::{post, web};
+    ///
+    /// #[post("/")]
+    /// async fn index(mut body: web::Payload) -> actix_web::Result<String> {
+    ///     let mut len = 0;
+    ///     while let Some(item) = body.next().await {
+    ///         len += item?.len();
+    ///     }
+    ///
+    ///     let checksum = body
+    ///         .trailers()
+    ///         .await?
+    ///         .and_then(|trailers| trailers.get("x-checksum").cloned());
+    ///
+    ///     Ok(format!("received {} bytes, checksum {:?}", len, checksum))
+    /// }
+    /// ```
+    pub async fn trailers(&mut self) -> Result<Option<HeaderMap>, PayloadError> {
+        poll_fn(|cx| self.poll_trailers(cx)).await
+    }
 }
 
 impl Stream for Payload {
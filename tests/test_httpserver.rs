@@ -147,3 +147,90 @@ async fn test_start_ssl() {
     thread::sleep(Duration::from_millis(100));
     let _ = sys.stop();
 }
+
+#[test]
+#[should_panic(expected = "HTTP/2 max frame size")]
+fn test_h2_max_frame_size_out_of_range() {
+    let _ = actix_web::HttpServer::new(actix_web::App::new).h2_max_frame_size(1024);
+}
+
+#[test]
+#[should_panic(expected = "HTTP/2 window size")]
+fn test_h2_window_size_out_of_range() {
+    let _ = actix_web::HttpServer::new(actix_web::App::new).h2_initial_window_size(1 << 31);
+}
+
+#[test]
+#[should_panic(expected = "HTTP/2 window size")]
+fn test_h2_connection_window_size_out_of_range() {
+    let _ = actix_web::HttpServer::new(actix_web::App::new)
+        .h2_initial_connection_window_size(u32::MAX);
+}
+
+#[cfg(unix)]
+#[actix_rt::test]
+async fn test_uds_h2_settings() {
+    use std::{
+        io::{Read as _, Write as _},
+        os::unix::net::UnixStream,
+    };
+
+    let path = std::env::temp_dir().join(format!("actix-web-h2-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let (tx, rx) = mpsc::channel();
+
+    let srv_path = path.clone();
+    thread::spawn(move || {
+        let sys = actix_rt::System::new();
+
+        sys.block_on(async {
+            let srv = HttpServer::new(|| {
+                App::new().service(web::resource("/").to(|| HttpResponse::Ok().body("test")))
+            })
+            .workers(1)
+            .h2_max_concurrent_streams(7)
+            .system_exit()
+            .disable_signals()
+            .bind_uds(srv_path)
+            .unwrap()
+            .run();
+
+            let _ = tx.send((srv, actix_rt::System::current()));
+        });
+
+        let _ = sys.run();
+    });
+    let (srv, sys) = rx.recv().unwrap();
+
+    // HTTP/2 with prior knowledge: preface followed by an empty SETTINGS frame
+    let mut stream = UnixStream::connect(&path).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream
+        .write_all(b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n\0\0\0\x04\0\0\0\0\0")
+        .unwrap();
+
+    // the server's SETTINGS frame comes first
+    let mut head = [0; 9];
+    stream.read_exact(&mut head).unwrap();
+    assert_eq!(head[3], 0x4);
+
+    let len = u32::from_be_bytes([0, head[0], head[1], head[2]]) as usize;
+    let mut payload = vec![0; len];
+    stream.read_exact(&mut payload).unwrap();
+
+    // SETTINGS_MAX_CONCURRENT_STREAMS
+    let max_streams = payload
+        .chunks(6)
+        .find(|setting| setting[..2] == [0, 0x3])
+        .map(|setting| u32::from_be_bytes([setting[2], setting[3], setting[4], setting[5]]));
+    assert_eq!(max_streams, Some(7));
+
+    // stop
+    srv.stop(false).await;
+
+    thread::sleep(Duration::from_millis(100));
+    sys.stop();
+    let _ = std::fs::remove_file(&path);
+}